@@ -1,12 +1,39 @@
 use anyhow::Result;
-use chrono::Local;
-use ollama_rs::{generation::completion::request::GenerationRequest, models::ModelOptions, Ollama};
+use ollama_rs::Ollama;
 use ratatui::widgets::ListState;
-use serde::{Deserialize, Serialize};
-use std::{fs, path::PathBuf, sync::Arc};
-use sysinfo::System;
-use tokio::sync::Mutex;
-use tokio_stream::StreamExt;
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use sysinfo::Pid;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::attempts::AttemptDiff;
+use crate::backend::{ChatBackend, OllamaBackend};
+use crate::chat::{ChatSession, Conversation};
+use crate::config::{self, unreadable, AppConfig, ConfigBundle, ConfigWatcher, Density, McpServerConfig, PromptDraft, APP_CONFIG_FILE, INPUT_HEIGHT_RANGE, MODEL_CONFIG_FILE};
+use crate::disk::DiskWriter;
+use crate::error::{AppError, Recovery, Retry};
+use crate::events::{AppEvent, Events};
+use crate::git;
+use crate::images::InlineImages;
+use crate::keymap::{Key, Keymap, KEYMAP_FILE};
+use crate::knowledge::{self, KnowledgeBase};
+use crate::logging::FrameTimes;
+use crate::macros::Macros;
+use crate::mcp::{self, McpServer, McpStatus};
+use crate::models::{ConfigField, ModelConfig};
+use crate::monitor::{LoadedModel, Metrics, Sampler};
+use crate::text::CodeBlock;
+use crate::theme::{Background, Theme};
+use crate::toast::{EventLog, Severity, Toasts};
+use crate::tools::{self, Tool, ToolCall, ToolRegistry};
+use crate::usage::{UsageStats, USAGE_FILE};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum AppMode {
@@ -16,135 +43,349 @@ pub enum AppMode {
     SystemMonitor,
     ChatHistory,
     ModelConfig,
+    HostSelection,
+    PromptLibrary,
+    EventLog,
+    McpServers,
+    Stats,
 }
 
+/// Parts of the layout that can be hidden to save rows.
 #[derive(Debug, Clone, Copy, PartialEq)]
-pub enum ConfigField {
-    Temperature,
-    TopP,
-    TopK,
-    RepeatPenalty,
-    ContextWindow,
-    SystemPrompt,
+pub enum Panel {
+    TitleBar,
+    StatusBar,
+    Input,
+    Sidebar,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-pub struct ChatSession {
-    pub timestamp: String,
-    pub model: String,
-    pub messages: Vec<(String, String)>,
+/// An action that only runs once the user answers `y` to a confirmation popup.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PendingAction {
+    ExportConfig(PathBuf),
+    /// Writes the current conversation to a standalone HTML page.
+    ExportHtml(PathBuf),
+    ImportConfig(PathBuf),
+    ResetConfig,
+    /// Runs a tool the model asked for; declining sends the refusal back to the model.
+    ToolCall { conversation_id: usize, call: ToolCall },
+    /// Ends a process from the system monitor; `force` sends SIGKILL rather than SIGTERM.
+    SignalProcess { pid: Pid, name: String, started: u64, force: bool },
+    /// Runs a `!` command; with `attach`, its output goes along with the next message.
+    ShellCommand { conversation_id: usize, command: String, attach: bool },
+    /// Runs commands and puts their output in the input, in place of their `{{$cmd ...}}`
+    /// placeholders or after what is there.
+    InsertCommandOutput { conversation_id: usize, commands: Vec<String> },
+    /// Commits the staged changes with a message the model wrote.
+    GitCommit(String),
+    /// Runs code block `number` of the last reply; its output goes along with the next message.
+    RunCode { conversation_id: usize, number: usize, block: CodeBlock },
+    /// Sends prompt `index` again, dropping the messages after it.
+    Regenerate { conversation_id: usize, index: usize },
+    /// Closes a tab whose messages are not saved yet.
+    CloseConversation(usize),
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct ModelConfig {
-    pub temperature: f32,
-    pub top_p: f32,
-    pub top_k: u32,
-    pub repeat_penalty: f32,
-    pub num_ctx: u64,
-    pub system_prompt: String,
+pub struct Confirmation {
+    pub message: String,
+    pub action: PendingAction,
 }
 
-impl Default for ModelConfig {
-    fn default() -> Self {
-        Self {
-            temperature: 0.8,
-            top_p: 0.9,
-            top_k: 40,
-            repeat_penalty: 1.1,
-            num_ctx: 2048,
-            system_prompt: String::from("You are a helpful AI assistant."),
-        }
-    }
+/// An error the user can do something about, shown in a popup with the ways out.
+pub struct Failure {
+    pub message: String,
+    pub error: AppError,
+    pub retry: Option<Retry>,
+    pub recoveries: Vec<Recovery>,
 }
 
+/// Model used until one is picked from the list.
+const DEFAULT_MODEL: &str = "llama2:latest";
+
+/// Everything the TUI shows and does. Each feature adds its methods in its own module: `chat`,
+/// `models`, `history`, `monitor`, `config`, and `input`.
 pub struct App {
     pub mode: AppMode,
-    pub input: String,
-    pub messages: Vec<(String, String)>, // (role, content)
-    pub current_model: String,
+    /// Open chats, one per tab.
+    pub conversations: Vec<Conversation>,
+    pub active_conversation: usize,
+    pub(crate) next_conversation_id: usize,
+    /// Id of the conversation shown beside the active one in split view.
+    pub split_conversation: Option<usize>,
     pub available_models: Vec<String>,
+    /// Whether the model list is being fetched from the backend.
+    pub listing_models: bool,
+    /// The model being pulled, while the download runs in the background.
+    pub downloading: Option<String>,
     pub model_list_state: ListState,
     pub download_input: String,
     pub status_message: String,
-    pub ollama: Ollama,
-    pub scroll_offset: usize,
-    pub is_thinking: bool,
+    /// Most recent error, kept after the status message moves on.
+    pub last_error: Option<String>,
+    /// Whether the terminal window has focus; `None` until the terminal first reports it,
+    /// which some never do.
+    pub terminal_focused: Option<bool>,
+    /// A reply finished since the last key press; the terminal title says so until then.
+    pub reply_unseen: bool,
+    pub toasts: Toasts,
+    pub event_log: EventLog,
+    pub log_list_state: ListState,
+    /// The active host's model provider.
+    pub backend: Arc<dyn ChatBackend>,
+    pub tools: ToolRegistry,
+    /// Enabled MCP servers, with the config each was connected with.
+    pub mcp_connections: BTreeMap<String, (McpServerConfig, McpStatus)>,
+    pub mcp_list_state: ListState,
+    /// The indexed directory that questions draw passages from, when retrieval is on.
+    pub knowledge: Option<Arc<KnowledgeBase>>,
+    /// Replies, tokens, and waiting time per day and model, across sessions.
+    pub usage: UsageStats,
+    /// A `/kb index` is running.
+    pub indexing: bool,
     pub thinking_frame: usize,
-    pub sys_info: System,
-    pub cpu_usage: f32,
-    pub memory_usage: u64,
-    pub memory_total: u64,
-    pub gpu_info: Option<String>,
+    /// Something visible changed since the last frame was drawn.
+    pub dirty: bool,
+    /// The system monitor's latest readings, taken on the sampler's thread.
+    pub metrics: Metrics,
+    pub sampler: Sampler,
+    /// Models the active host has in memory; `None` until it answers, or if it can't.
+    pub loaded_models: Option<Vec<LoadedModel>>,
+    pub(crate) loaded_models_checked: Option<Instant>,
     pub chat_history: Vec<ChatSession>,
     pub history_list_state: ListState,
     pub chat_dir: PathBuf,
     pub selected_text: Option<String>,
-    pub process_scroll: usize,
+    /// Highlighted row of the process table, an index into `visible_processes()`.
+    pub process_selected: usize,
+    /// Text typed in the system monitor; only processes whose name contains it are listed.
+    pub process_filter: String,
+    /// List only the Ollama server, its runners, and their children.
+    pub ollama_only: bool,
     pub model_config: ModelConfig,
     pub config_field: ConfigField,
     pub config_input: String,
+    pub config_error: Option<String>,
     pub config_dir: PathBuf,
     pub vim_mode: bool,
     pub vim_insert: bool,
-    pub pending_g: bool,
+    /// Text typed after `:` in vim normal mode, while the command line is open.
+    pub command_line: Option<String>,
+    /// Keys typed so far towards a multi-key binding such as `g m`.
+    pub pending_keys: Vec<Key>,
+    /// Macro registers and the recording in progress.
+    pub macros: Macros,
+    pub keymap: Keymap,
+    /// The terminal speaks the kitty keyboard protocol, so `Shift+Enter` and the like can be told
+    /// apart from their plain keys.
+    pub keyboard_enhanced: bool,
+    /// Print the active conversation to stdout after quitting: `--print-on-exit` or `:hardcopy`.
+    pub print_on_exit: bool,
+    pub show_help: bool,
+    pub help_scroll: u16,
+    /// The debug overlay with recent log lines and frame timing.
+    pub show_debug: bool,
+    /// The `:stats` popup for the active conversation.
+    pub show_session_stats: bool,
+    /// The quick-actions menu on the selected message is open, with this entry highlighted.
+    pub message_menu: Option<usize>,
+    /// The template picker shown on a new chat is open, with this entry highlighted; entry 0
+    /// is a blank chat.
+    pub template_picker: Option<usize>,
+    /// A regenerated reply compared with an earlier attempt at it.
+    pub attempt_diff: Option<AttemptDiff>,
+    pub frame_times: FrameTimes,
+    /// j/k and Enter act on the session sidebar instead of the chat.
+    pub sidebar_focus: bool,
+    pub app_config: AppConfig,
+    pub host_list_state: ListState,
+    pub config_watcher: Option<ConfigWatcher>,
+    pub prompt_list_state: ListState,
+    pub prompt_draft: Option<PromptDraft>,
+    pub confirmation: Option<Confirmation>,
+    /// Questions that came up while another was open, asked in turn once it is answered.
+    pub queued_confirmations: VecDeque<Confirmation>,
+    pub failure: Option<Failure>,
+    pub theme: Theme,
+    /// Terminal background detected at startup, used when the theme is `auto`.
+    pub background: Option<Background>,
+    /// Drawn during render, which only borrows the app, so it sits behind a `RefCell`.
+    pub images: RefCell<InlineImages>,
+    /// Where background tasks send their results; see `handle_event`.
+    pub events: Events,
+    /// The receiving end, until `run_app` takes it.
+    inbox: Option<UnboundedReceiver<AppEvent>>,
+    /// Saves chats and settings off the event loop.
+    pub disk: DiskWriter,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl App {
+    /// The app with its files in `~/.ollama_tui`, talking to the configured host.
     pub fn new() -> Self {
-        let ollama = Ollama::default();
-        let mut sys_info = System::new_all();
-        sys_info.refresh_all();
+        Self::open(&config::base_dir(), None)
+    }
+
+    /// The app with its files in `base_dir`, talking to `backend` whatever host is configured;
+    /// for tests, which use `MockBackend` and a temporary directory.
+    pub fn with_backend(base_dir: &Path, backend: Arc<dyn ChatBackend>) -> Self {
+        Self::open(base_dir, Some(backend))
+    }
 
+    fn open(base_dir: &Path, backend: Option<Arc<dyn ChatBackend>>) -> Self {
+        let (events, inbox) = Events::channel();
         // Create directories
-        let base_dir = dirs::home_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join(".ollama_tui");
         let chat_dir = base_dir.join("chats");
-        let config_dir = base_dir.clone();
+        let config_dir = base_dir.to_path_buf();
 
         fs::create_dir_all(&chat_dir).ok();
         fs::create_dir_all(&config_dir).ok();
 
-        // Load config or use default
-        let config_path = config_dir.join("model_config.json");
-        let model_config = if let Ok(content) = fs::read_to_string(&config_path) {
-            serde_json::from_str(&content).unwrap_or_default()
-        } else {
+        // Load config or use default; a file that can't be read is reported, not silently replaced
+        let mut load_errors = Vec::new();
+        let model_config = ModelConfig::load(&config_dir).unwrap_or_else(|e| {
+            load_errors.push(unreadable(&config_dir, MODEL_CONFIG_FILE, &e));
             ModelConfig::default()
+        });
+        let config_watcher = ConfigWatcher::new(&config_dir).ok();
+        let knowledge = KnowledgeBase::load(&config_dir).unwrap_or_else(|e| {
+            load_errors.push(format!("Ignoring {}: {:#}", knowledge::KNOWLEDGE_FILE, e));
+            None
+        });
+
+        let usage = UsageStats::load(&config_dir).unwrap_or_else(|e| {
+            load_errors.push(unreadable(&config_dir, USAGE_FILE, &e));
+            UsageStats::default()
+        });
+
+        let app_config = AppConfig::load(&config_dir).unwrap_or_else(|e| {
+            load_errors.push(unreadable(&config_dir, APP_CONFIG_FILE, &e));
+            AppConfig::default()
+        });
+        let theme = Theme::resolve(app_config.theme, None);
+        let keymap = Keymap::load(&config_dir).unwrap_or_else(|e| {
+            load_errors.push(format!("Ignoring {}: {:#}", KEYMAP_FILE, e));
+            Keymap::default()
+        });
+        let (backend, status_message): (Arc<dyn ChatBackend>, _) = match backend.map_or_else(|| app_config.active().client(), Ok) {
+            Ok(backend) => (backend, String::from("Ready. Press F1 for help")),
+            Err(e) => (Arc::new(OllamaBackend::new(Ollama::default())), format!("Invalid host '{}': {:#}", app_config.active_host, e)),
         };
 
-        Self {
+        let mut app = Self {
             mode: AppMode::Chat,
-            input: String::new(),
-            messages: Vec::new(),
-            current_model: String::from("llama2:latest"),
+            conversations: vec![Conversation::new(0, app_config.model.clone().unwrap_or_else(|| DEFAULT_MODEL.to_string()))],
+            active_conversation: 0,
+            next_conversation_id: 1,
+            split_conversation: None,
             available_models: Vec::new(),
+            listing_models: false,
+            downloading: None,
             model_list_state: ListState::default(),
             download_input: String::new(),
-            status_message: String::from("Ready. Press F1 for help"),
-            ollama,
-            scroll_offset: 0,
-            is_thinking: false,
+            status_message,
+            last_error: None,
+            terminal_focused: None,
+            reply_unseen: false,
+            toasts: Toasts::default(),
+            event_log: EventLog::default(),
+            log_list_state: ListState::default(),
+            backend,
+            tools: ToolRegistry::default(),
+            mcp_connections: BTreeMap::new(),
+            mcp_list_state: ListState::default(),
+            knowledge: knowledge.map(Arc::new),
+            usage,
+            indexing: false,
             thinking_frame: 0,
-            sys_info,
-            cpu_usage: 0.0,
-            memory_usage: 0,
-            memory_total: 0,
-            gpu_info: None,
+            dirty: true,
+            metrics: Metrics::default(),
+            sampler: Sampler::spawn(),
+            loaded_models: None,
+            loaded_models_checked: None,
             chat_history: Vec::new(),
             history_list_state: ListState::default(),
             chat_dir,
             selected_text: None,
-            process_scroll: 0,
+            process_selected: 0,
+            process_filter: String::new(),
+            ollama_only: false,
             model_config,
             config_field: ConfigField::Temperature,
             config_input: String::new(),
+            config_error: None,
             config_dir,
             vim_mode: true,
             vim_insert: true,
-            pending_g: false,
+            command_line: None,
+            pending_keys: Vec::new(),
+            macros: Macros::default(),
+            keymap,
+            keyboard_enhanced: false,
+            print_on_exit: false,
+            show_help: false,
+            help_scroll: 0,
+            show_debug: false,
+            show_session_stats: false,
+            message_menu: None,
+            template_picker: None,
+            attempt_diff: None,
+            frame_times: FrameTimes::default(),
+            sidebar_focus: false,
+            app_config,
+            host_list_state: ListState::default(),
+            config_watcher,
+            prompt_list_state: ListState::default(),
+            prompt_draft: None,
+            confirmation: None,
+            queued_confirmations: VecDeque::new(),
+            failure: None,
+            theme,
+            background: None,
+            images: RefCell::new(InlineImages::default()),
+            disk: DiskWriter::spawn(events.clone()),
+            events,
+            inbox: Some(inbox),
+        };
+        if app.app_config.show_sidebar {
+            app.load_chat_history();
         }
+        for error in load_errors {
+            app.set_error(error);
+        }
+        for warning in app.app_config.template_warnings() {
+            app.notify(Severity::Warning, warning);
+        }
+        app
+    }
+
+    /// The receiving end of `events`, for the loop that owns the app; `None` once taken.
+    pub fn take_inbox(&mut self) -> Option<UnboundedReceiver<AppEvent>> {
+        self.inbox.take()
+    }
+
+    /// Applies what a background task sent.
+    pub fn handle_event(&mut self, event: AppEvent) {
+        self.dirty = true;
+        match event {
+            AppEvent::Chunk { conversation_id, message_index, chunk } => self.append_chunk(conversation_id, message_index, chunk),
+            AppEvent::StreamEnded { conversation_id, message_index, error } => self.finish_stream(conversation_id, message_index, error),
+            AppEvent::Update(update) => update(self),
+        }
+    }
+
+    /// Records the detected terminal background and re-resolves an `auto` theme against it.
+    pub fn set_background(&mut self, background: Option<Background>) {
+        self.background = background;
+        self.refresh_theme();
+    }
+
+    pub fn refresh_theme(&mut self) {
+        self.theme = Theme::resolve(self.app_config.theme, self.background);
     }
 
     pub fn get_thinking_spinner(&self) -> &str {
@@ -152,300 +393,305 @@ impl App {
         frames[self.thinking_frame % frames.len()]
     }
 
+    /// How long the event loop waits for input before the next tick: the configured tick rate
+    /// while something animates, the slower idle rate otherwise.
+    pub fn tick_rate(&self) -> Duration {
+        let active = self.mode == AppMode::SystemMonitor || self.conversations.iter().any(|c| c.is_thinking);
+        let ms = if active { self.app_config.tick_rate_ms } else { self.app_config.idle_tick_rate_ms.max(self.app_config.tick_rate_ms) };
+        Duration::from_millis(ms.max(1))
+    }
+
     pub fn update_thinking_animation(&mut self) {
-        if self.is_thinking {
+        // No spinner is drawn in screen-reader mode, so there is nothing to redraw
+        if self.conversations.iter().any(|c| c.is_thinking) && !self.app_config.screen_reader {
             self.thinking_frame += 1;
+            self.dirty = true;
         }
     }
 
-    pub fn update_system_info(&mut self) {
-        self.sys_info.refresh_all();
-
-        // Calculate average CPU usage
-        let cpus = self.sys_info.cpus();
-        self.cpu_usage = if !cpus.is_empty() {
-            cpus.iter().map(|cpu| cpu.cpu_usage()).sum::<f32>() / cpus.len() as f32
-        } else {
-            0.0
-        };
-
-        self.memory_usage = self.sys_info.used_memory();
-        self.memory_total = self.sys_info.total_memory();
-
-        // Try to get GPU info using nvidia-smi
-        if let Ok(output) = std::process::Command::new("nvidia-smi")
-            .args(&[
-                "--query-gpu=utilization.gpu,memory.used,memory.total,temperature.gpu",
-                "--format=csv,noheader,nounits",
-            ])
-            .output()
-        {
-            if output.status.success() {
-                self.gpu_info = Some(String::from_utf8_lossy(&output.stdout).to_string());
+    /// Stops replies still streaming, saves chats with unsaved messages (unless `save_on_quit`
+    /// is off) and the settings, and waits until all of it is on disk.
+    pub async fn shutdown(&mut self) {
+        tracing::info!("quitting");
+        for conversation in &mut self.conversations {
+            conversation.stop_reply();
+        }
+        if self.app_config.save_on_quit {
+            for index in 0..self.conversations.len() {
+                if self.conversations[index].has_unsaved_messages() {
+                    self.save_chat(index);
+                }
             }
         }
+        self.save_config();
+        self.save_app_config();
+        self.disk.flush().await;
     }
 
-    pub fn save_current_chat(&mut self) -> Result<()> {
-        if self.messages.is_empty() {
-            return Ok(());
+    pub fn confirm(&mut self, message: String, action: PendingAction) {
+        // Replacing an open question would lose its action, and a tool call would wait forever
+        let confirmation = Confirmation { message, action };
+        match self.confirmation {
+            Some(_) => self.queued_confirmations.push_back(confirmation),
+            None => self.confirmation = Some(confirmation),
         }
-
-        let session = ChatSession {
-            timestamp: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
-            model: self.current_model.clone(),
-            messages: self.messages.clone(),
-        };
-
-        let filename = format!("chat_{}.json", Local::now().format("%Y%m%d_%H%M%S"));
-        let path = self.chat_dir.join(filename);
-        let json = serde_json::to_string_pretty(&session)?;
-        fs::write(path, json)?;
-
-        self.status_message = "Chat saved successfully".to_string();
-        Ok(())
     }
 
-    pub fn load_chat_history(&mut self) -> Result<()> {
-        self.chat_history.clear();
-
-        if let Ok(entries) = fs::read_dir(&self.chat_dir) {
-            for entry in entries.flatten() {
-                if let Ok(content) = fs::read_to_string(entry.path()) {
-                    if let Ok(session) = serde_json::from_str::<ChatSession>(&content) {
-                        self.chat_history.push(session);
-                    }
+    /// Resolves the open confirmation popup, running its action when `accepted`.
+    pub fn answer_confirmation(&mut self, accepted: bool) {
+        if let Some(confirmation) = self.confirmation.take() {
+            // The next question waiting comes before any the action itself raises
+            self.confirmation = self.queued_confirmations.pop_front();
+            // A tool call continues the reply either way
+            if let PendingAction::ToolCall { conversation_id, call } = confirmation.action {
+                match self.tools.get(&call.name) {
+                    Some(tool) if accepted => self.spawn_tool(conversation_id, tool, call),
+                    _ => self.finish_tool_call(conversation_id, "The user declined to run this tool.".to_string()),
                 }
+            } else if accepted {
+                self.run_pending_action(confirmation.action);
+            } else {
+                self.status_message = "Cancelled".to_string();
             }
         }
-
-        // Sort by timestamp (newest first)
-        self.chat_history
-            .sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
-        Ok(())
     }
 
-    pub fn load_selected_chat(&mut self) -> Result<()> {
-        if let Some(selected) = self.history_list_state.selected() {
-            if let Some(session) = self.chat_history.get(selected) {
-                self.messages = session.messages.clone();
-                self.current_model = session.model.clone();
-                self.status_message = format!("Loaded chat from {}", session.timestamp);
-                self.switch_mode(AppMode::Chat);
+    pub(crate) fn run_pending_action(&mut self, action: PendingAction) {
+        let result = match action {
+            PendingAction::ExportConfig(path) => {
+                let bundle = ConfigBundle::new(self.app_config.clone(), self.model_config.clone());
+                bundle.write(&path).map(|()| format!("Config exported to {}", path.display()))
             }
+            PendingAction::ExportHtml(path) => self.export_html(&path),
+            PendingAction::ImportConfig(path) => ConfigBundle::read(&path)
+                .and_then(|bundle| self.replace_config(bundle.app, bundle.model))
+                .map(|()| format!("Config imported from {}", path.display())),
+            PendingAction::ResetConfig => self
+                .replace_config(AppConfig::default(), ModelConfig::default())
+                .map(|()| "All settings reset to defaults".to_string()),
+            PendingAction::SignalProcess { pid, name, started, force } => self.signal_process(pid, &name, started, force),
+            PendingAction::GitCommit(message) => git::commit(&message).map(|summary| format!("Committed: {}", summary)),
+            PendingAction::InsertCommandOutput { conversation_id, commands } => return self.insert_command_output(conversation_id, commands),
+            PendingAction::ShellCommand { conversation_id, command, attach } => {
+                let heading = format!("$ {}", command);
+                return self.spawn_command(conversation_id, heading, attach, move || tools::run_shell_command(&command));
+            }
+            PendingAction::RunCode { conversation_id, number, block } => {
+                let heading = format!("{} · code block {}", block.language, number);
+                return self.spawn_command(conversation_id, heading, true, move || tools::run_code(&block.language, &block.code));
+            }
+            PendingAction::Regenerate { conversation_id, index } => return self.regenerate_from(conversation_id, index),
+            PendingAction::CloseConversation(id) => return self.close_conversation_now(id),
+            PendingAction::ToolCall { .. } => return,
+        };
+        match result {
+            Ok(message) => self.notify(Severity::Success, message),
+            Err(e) => self.set_error(format!("Error: {:#}", e)),
         }
-        Ok(())
     }
 
-    pub fn clear_chat(&mut self) {
-        self.messages.clear();
-        self.scroll_offset = 0;
-        self.status_message = "Chat cleared".to_string();
+    /// Pops up a transient toast; the status bar is left for persistent state.
+    pub fn notify(&mut self, severity: Severity, message: String) {
+        self.dirty = true;
+        if self.app_config.screen_reader {
+            self.status_message = message.clone();
+        }
+        self.event_log.push(severity, message.clone());
+        self.toasts.push(severity, message);
     }
 
-    pub fn copy_to_clipboard(&mut self) {
-        if let Some(text) = &self.selected_text {
-            if let Ok(mut clipboard) = arboard::Clipboard::new() {
-                if clipboard.set_text(text.clone()).is_ok() {
-                    self.status_message = "Copied to clipboard".to_string();
-                } else {
-                    self.status_message = "Failed to copy".to_string();
-                }
-            }
+    /// Shows an error toast and remembers it for the `last_error` status segment.
+    pub fn set_error(&mut self, message: String) {
+        self.notify(Severity::Error, message.clone());
+        self.last_error = Some(message);
+    }
+
+    /// Reports `error` under `context`; one with a way out, such as retrying `retry` or switching
+    /// host, also opens the error popup offering it.
+    pub fn fail(&mut self, context: &str, error: AppError, retry: Option<Retry>) {
+        let message = format!("{}: {}", context, error);
+        self.set_error(message.clone());
+        let recoveries = error.recoveries(retry.is_some());
+        if !recoveries.is_empty() {
+            self.failure = Some(Failure { message, error, retry, recoveries });
         }
     }
 
-    pub fn select_last_message(&mut self) {
-        if let Some((_, content)) = self.messages.last() {
-            self.selected_text = Some(content.clone());
-            self.status_message = "Message selected. Press Ctrl+Y to copy".to_string();
+    /// Runs `retry` again, reporting a second failure the same way.
+    pub fn retry(&mut self, retry: Retry) {
+        match retry {
+            Retry::Reply(conversation_id) => self.retry_reply(conversation_id),
+            Retry::ListModels => self.fetch_models_then(|app, result| match result {
+                Ok(()) => app.notify(Severity::Success, format!("Connected to {}", app.app_config.active().name)),
+                Err(e) => app.fail("Cannot list models", e, Some(Retry::ListModels)),
+            }),
         }
     }
 
-    pub fn save_config(&mut self) -> Result<()> {
-        let config_path = self.config_dir.join("model_config.json");
-        let json = serde_json::to_string_pretty(&self.model_config)?;
-        fs::write(config_path, json)?;
-        self.status_message = "Configuration saved".to_string();
-        Ok(())
+    pub fn switch_mode(&mut self, mode: AppMode) {
+        self.mode = mode;
+        if mode == AppMode::ModelSelection {
+            self.model_list_state.select(Some(0));
+        }
+        if mode == AppMode::PromptLibrary {
+            self.prompt_draft = None;
+            self.prompt_list_state.select(if self.app_config.system_prompts.is_empty() { None } else { Some(0) });
+        }
+        if mode == AppMode::HostSelection {
+            self.host_list_state.select(Some(self.app_config.active_index()));
+        }
+        if mode == AppMode::EventLog {
+            self.log_list_state.select(self.event_log.len().checked_sub(1));
+        }
+        if mode == AppMode::McpServers {
+            self.mcp_list_state.select(if self.app_config.mcp_servers.is_empty() { None } else { Some(0) });
+        }
     }
 
-    pub fn update_config_field(&mut self, value: String) {
-        match self.config_field {
-            ConfigField::Temperature => {
-                if let Ok(val) = value.parse::<f32>() {
-                    self.model_config.temperature = val.clamp(0.0, 2.0);
-                }
-            }
-            ConfigField::TopP => {
-                if let Ok(val) = value.parse::<f32>() {
-                    self.model_config.top_p = val.clamp(0.0, 1.0);
-                }
-            }
-            ConfigField::TopK => {
-                if let Ok(val) = value.parse::<u32>() {
-                    self.model_config.top_k = val.max(1);
-                }
-            }
-            ConfigField::RepeatPenalty => {
-                if let Ok(val) = value.parse::<f32>() {
-                    self.model_config.repeat_penalty = val.clamp(0.0, 2.0);
-                }
-            }
-            ConfigField::ContextWindow => {
-                if let Ok(val) = value.parse::<u64>() {
-                    self.model_config.num_ctx = val.clamp(512, 32768);
-                }
-            }
-            ConfigField::SystemPrompt => {
-                self.model_config.system_prompt = value;
-            }
+    /// Title for the terminal window or tmux pane: whether a reply is generating or has just
+    /// finished, and the active model.
+    pub fn window_title(&self) -> String {
+        let model = &self.conversation().model;
+        if self.conversations.iter().any(|c| c.is_thinking) {
+            format!("● generating… · {}", model)
+        } else if self.reply_unseen {
+            format!("✓ done · {}", model)
+        } else {
+            format!("ollama-tui · {}", model)
         }
     }
 
-    pub fn next_config_field(&mut self) {
-        self.config_field = match self.config_field {
-            ConfigField::Temperature => ConfigField::TopP,
-            ConfigField::TopP => ConfigField::TopK,
-            ConfigField::TopK => ConfigField::RepeatPenalty,
-            ConfigField::RepeatPenalty => ConfigField::ContextWindow,
-            ConfigField::ContextWindow => ConfigField::SystemPrompt,
-            ConfigField::SystemPrompt => ConfigField::Temperature,
+    pub fn toggle_panel(&mut self, panel: Panel) {
+        let (shown, name) = match panel {
+            Panel::TitleBar => (&mut self.app_config.show_title_bar, "Title bar"),
+            Panel::StatusBar => (&mut self.app_config.show_status_bar, "Status bar"),
+            Panel::Input => (&mut self.app_config.show_input, "Input pane"),
+            Panel::Sidebar => (&mut self.app_config.show_sidebar, "Sidebar"),
         };
+        *shown = !*shown;
+        self.status_message = format!("{} {}", name, if *shown { "shown" } else { "hidden" });
+        if panel == Panel::Sidebar {
+            if self.app_config.show_sidebar {
+                self.load_chat_history();
+            } else {
+                self.sidebar_focus = false;
+            }
+        }
+        self.save_app_config();
     }
 
-    pub fn prev_config_field(&mut self) {
-        self.config_field = match self.config_field {
-            ConfigField::Temperature => ConfigField::SystemPrompt,
-            ConfigField::TopP => ConfigField::Temperature,
-            ConfigField::TopK => ConfigField::TopP,
-            ConfigField::RepeatPenalty => ConfigField::TopK,
-            ConfigField::ContextWindow => ConfigField::RepeatPenalty,
-            ConfigField::SystemPrompt => ConfigField::ContextWindow,
-        };
+    pub fn toggle_message_numbers(&mut self) {
+        self.app_config.number_messages = !self.app_config.number_messages;
+        self.status_message = format!("Message numbers {}", if self.app_config.number_messages { "shown" } else { "hidden" });
+        self.save_app_config();
     }
 
-    pub fn get_current_config_value(&self) -> String {
-        match self.config_field {
-            ConfigField::Temperature => self.model_config.temperature.to_string(),
-            ConfigField::TopP => self.model_config.top_p.to_string(),
-            ConfigField::TopK => self.model_config.top_k.to_string(),
-            ConfigField::RepeatPenalty => self.model_config.repeat_penalty.to_string(),
-            ConfigField::ContextWindow => self.model_config.num_ctx.to_string(),
-            ConfigField::SystemPrompt => self.model_config.system_prompt.clone(),
-        }
+    pub fn toggle_system_prompt(&mut self) {
+        self.app_config.expand_system_prompt = !self.app_config.expand_system_prompt;
+        self.status_message = format!("System prompt {}", if self.app_config.expand_system_prompt { "expanded" } else { "collapsed" });
+        self.save_app_config();
     }
 
-    pub fn switch_mode(&mut self, mode: AppMode) {
-        self.mode = mode;
-        if mode == AppMode::ModelSelection {
-            self.model_list_state.select(Some(0));
-        }
+    pub fn toggle_density(&mut self) {
+        self.app_config.density = match self.app_config.density {
+            Density::Comfortable => Density::Compact,
+            Density::Compact => Density::Comfortable,
+        };
+        self.status_message = format!("{:?} layout", self.app_config.density);
+        self.save_app_config();
     }
 
-    pub async fn fetch_models(&mut self) -> Result<()> {
-        let models = self.ollama.list_local_models().await?;
-        self.available_models = models.iter().map(|m| m.name.clone()).collect();
-        Ok(())
+    pub fn resize_input(&mut self, delta: i16) {
+        let (min, max) = INPUT_HEIGHT_RANGE;
+        self.app_config.input_height = self.app_config.input_height.saturating_add_signed(delta).clamp(min, max);
+        self.status_message = format!("Input height: {} rows", self.app_config.input_height);
+        self.save_app_config();
     }
 
-    pub async fn download_model(&mut self, model_name: String) -> Result<()> {
-        self.status_message = format!("Downloading model: {}", model_name);
-        self.ollama.pull_model(model_name.clone(), false).await?;
-        self.status_message = format!("Model {} downloaded successfully", model_name);
-        self.fetch_models().await?;
-        Ok(())
+    /// Connects enabled MCP servers that aren't yet, and drops any disabled, removed, or
+    /// reconfigured since. Cheap when nothing changed, so it runs every tick.
+    pub fn sync_mcp_servers(&mut self) {
+        let wanted: Vec<McpServerConfig> = self.app_config.mcp_servers.iter().filter(|server| server.enabled).cloned().collect();
+        let stale: Vec<String> = self
+            .mcp_connections
+            .iter()
+            .filter(|(_, (config, _))| !wanted.contains(config))
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in stale {
+            self.mcp_connections.remove(&name);
+            self.tools.remove_server(&name);
+            self.dirty = true;
+        }
+        for config in wanted {
+            if self.mcp_connections.contains_key(&config.name) {
+                continue;
+            }
+            self.mcp_connections.insert(config.name.clone(), (config.clone(), McpStatus::Connecting));
+            self.dirty = true;
+            let events = self.events.clone();
+            tokio::spawn(async move {
+                let (connect, runtime) = (config.clone(), Handle::current());
+                let result = tokio::task::spawn_blocking(move || mcp::tools(McpServer::connect(&connect, runtime)?))
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|result| result);
+                events.update(move |app| app.finish_mcp_connection(config, result));
+            });
+        }
     }
 
-    pub fn start_message_stream(&mut self, shared_app: Arc<Mutex<App>>) {
-        if self.input.trim().is_empty() {
+    fn finish_mcp_connection(&mut self, config: McpServerConfig, result: Result<(Vec<Arc<dyn Tool>>, usize)>) {
+        // Disabled or changed while connecting
+        if self.mcp_connections.get(&config.name).map(|(current, _)| current) != Some(&config) {
             return;
         }
-
-        let user_message = self.input.clone();
-        self.messages
-            .push(("user".to_string(), user_message.clone()));
-        self.input.clear();
-
-        // Start thinking animation
-        self.is_thinking = true;
-        self.thinking_frame = 0;
-        self.messages.push(("assistant".to_string(), String::new()));
-
-        let model = self.current_model.clone();
-        let ollama = self.ollama.clone();
-        let config = self.model_config.clone();
-
-        // Spawn the streaming task in the background
-        tokio::spawn(async move {
-            let message_index = {
-                let app = shared_app.lock().await;
-                app.messages.len() - 1
-            };
-
-            // Build request with config parameters using ModelOptions
-            let options = ModelOptions::default()
-                .temperature(config.temperature)
-                .top_p(config.top_p)
-                .top_k(config.top_k)
-                .repeat_penalty(config.repeat_penalty)
-                .num_ctx(config.num_ctx);
-
-            let mut request = GenerationRequest::new(model, user_message).options(options);
-
-            // Add system prompt if not empty
-            if !config.system_prompt.is_empty() {
-                request = request.system(config.system_prompt);
+        let status = match result {
+            Ok((tools, resources)) => {
+                // One of the tools is the resource reader
+                let status = McpStatus::Connected { tools: tools.len() - usize::from(resources > 0), resources };
+                self.tools.set_server_tools(&config.name, tools);
+                status
             }
-
-            match ollama.generate_stream(request).await {
-                Ok(mut stream) => {
-                    while let Some(responses) = stream.next().await {
-                        match responses {
-                            Ok(response_chunks) => {
-                                for response in response_chunks {
-                                    // Append each token to the message as it arrives
-                                    let mut app = shared_app.lock().await;
-                                    if let Some((_, content)) = app.messages.get_mut(message_index)
-                                    {
-                                        content.push_str(&response.response);
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                let mut app = shared_app.lock().await;
-                                app.status_message = format!("Stream error: {}", e);
-                                break;
-                            }
-                        }
-                    }
-                    let mut app = shared_app.lock().await;
-                    app.status_message = "Ready".to_string();
-                    app.is_thinking = false;
-                }
-                Err(e) => {
-                    let mut app = shared_app.lock().await;
-                    // Remove the empty thinking message on error
-                    app.messages.pop();
-                    app.status_message = format!("Error: {}", e);
-                    app.is_thinking = false;
-                }
+            Err(e) => {
+                self.notify(Severity::Warning, format!("MCP server '{}' failed: {:#}", config.name, e));
+                McpStatus::Failed(format!("{:#}", e))
             }
-        });
+        };
+        self.mcp_connections.insert(config.name.clone(), (config, status));
     }
 
-    pub fn scroll_up(&mut self) {
-        if self.scroll_offset > 0 {
-            self.scroll_offset -= 1;
-        }
-    }
-    pub fn scroll_down(&mut self) {
-        self.scroll_offset += 1;
+    /// Turns the highlighted MCP server on or off; `sync_mcp_servers` does the connecting.
+    pub fn toggle_selected_mcp_server(&mut self) {
+        let Some(server) = self.mcp_list_state.selected().and_then(|i| self.app_config.mcp_servers.get_mut(i)) else { return };
+        server.enabled = !server.enabled;
+        self.status_message = format!("MCP server '{}' {}", server.name, if server.enabled { "enabled" } else { "disabled" });
+        self.save_app_config();
     }
-    pub fn scroll_top(&mut self) {
-        self.scroll_offset = 0;
-    }
-    pub fn scroll_bottom(&mut self) {
-        self.scroll_offset = u16::MAX as usize;
+
+    /// Moves the highlight in the current mode's list, staying within its bounds.
+    pub fn move_selection(&mut self, down: bool) {
+        let (state, len) = match self.mode {
+            AppMode::Chat if self.sidebar_focus => (&mut self.history_list_state, self.chat_history.len()),
+            AppMode::ModelSelection => (&mut self.model_list_state, self.available_models.len()),
+            AppMode::ChatHistory => (&mut self.history_list_state, self.chat_history.len()),
+            AppMode::HostSelection => (&mut self.host_list_state, self.app_config.hosts.len()),
+            AppMode::PromptLibrary => (&mut self.prompt_list_state, self.app_config.system_prompts.len()),
+            AppMode::EventLog => (&mut self.log_list_state, self.event_log.len()),
+            AppMode::McpServers => (&mut self.mcp_list_state, self.app_config.mcp_servers.len()),
+            AppMode::SystemMonitor => {
+                let last = self.visible_processes().len().saturating_sub(1);
+                self.process_selected = if down { (self.process_selected + 1).min(last) } else { self.process_selected.saturating_sub(1) };
+                return;
+            }
+            _ => return,
+        };
+        if let Some(selected) = state.selected() {
+            if down && selected < len.saturating_sub(1) {
+                state.select(Some(selected + 1));
+            } else if !down && selected > 0 {
+                state.select(Some(selected - 1));
+            }
+        }
     }
 }
@@ -0,0 +1,227 @@
+use serde::{Deserialize, Serialize};
+
+use crate::app::App;
+use crate::chat::Message;
+use crate::ui::ChatLayout;
+
+/// An earlier reply to the same prompt, kept when it was regenerated.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Attempt {
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+impl Attempt {
+    pub fn of(message: &Message) -> Self {
+        Self { content: message.content.clone(), model: message.model.clone() }
+    }
+}
+
+/// Whether a stretch of text is in both attempts compared, or only one of them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    Same,
+    Removed,
+    Added,
+}
+
+/// The changes from `old` to `new`, word by word; whitespace runs count as words so the
+/// pieces put back together give either text.
+pub fn word_diff(old: &str, new: &str) -> Vec<(Change, String)> {
+    let old = words(old);
+    let new = words(new);
+    // Only the middle, between what both start and end with, needs the full comparison
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (a, b) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    // Longest common subsequence lengths of every pair of tails
+    let mut lengths = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if a[i] == b[j] { lengths[i + 1][j + 1] + 1 } else { lengths[i + 1][j].max(lengths[i][j + 1]) };
+        }
+    }
+
+    let mut changes: Vec<(Change, String)> = Vec::new();
+    let mut push = |change: Change, word: &str| match changes.last_mut() {
+        Some((last, text)) if *last == change => text.push_str(word),
+        _ => changes.push((change, word.to_string())),
+    };
+    old[..prefix].iter().for_each(|word| push(Change::Same, word));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            push(Change::Same, a[i]);
+            (i, j) = (i + 1, j + 1);
+        } else if i < a.len() && (j == b.len() || lengths[i + 1][j] >= lengths[i][j + 1]) {
+            // What was taken out goes before what replaced it, as in `git diff`
+            push(Change::Removed, a[i]);
+            i += 1;
+        } else {
+            push(Change::Added, b[j]);
+            j += 1;
+        }
+    }
+    old[old.len() - suffix..].iter().for_each(|word| push(Change::Same, word));
+    changes
+}
+
+/// `text` split into runs of whitespace and runs of everything else.
+fn words(text: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = 0;
+    let mut space = None;
+    for (i, c) in text.char_indices() {
+        if space.is_some_and(|space| space != c.is_whitespace()) {
+            words.push(&text[start..i]);
+            start = i;
+        }
+        space = Some(c.is_whitespace());
+    }
+    if start < text.len() {
+        words.push(&text[start..]);
+    }
+    words
+}
+
+/// Every version of `reply`, oldest first, with the one shown in its place among them.
+pub fn versions(reply: &Message) -> Vec<Attempt> {
+    let mut versions = reply.attempts.clone();
+    versions.insert(shown_version(reply), Attempt::of(reply));
+    versions
+}
+
+/// Where the version shown comes among all the versions of `reply`.
+pub fn shown_version(reply: &Message) -> usize {
+    reply.attempts.len().saturating_sub(reply.later)
+}
+
+/// The open comparison of a regenerated reply with an earlier attempt at it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttemptDiff {
+    /// The reply in the active conversation.
+    pub message: usize,
+    /// Version `older` of the reply is compared with the one after it.
+    pub older: usize,
+    pub scroll: u16,
+}
+
+impl App {
+    /// Compares the selected reply, or the last one, with the attempt before it.
+    pub fn open_attempt_diff(&mut self) {
+        let Some(message) = self.target_reply() else {
+            self.set_error("No reply to compare".to_string());
+            return;
+        };
+        let reply = &self.conversation().messages[message];
+        match reply.attempts.len() {
+            0 => self.set_error("This reply has not been regenerated, so there is nothing to compare it with".to_string()),
+            count => {
+                let older = shown_version(reply).saturating_sub(1).min(count - 1);
+                self.attempt_diff = Some(AttemptDiff { message, older, scroll: 0 });
+            }
+        }
+    }
+
+    /// Compares the attempts one earlier or one later than those shown.
+    pub fn move_attempt_diff(&mut self, later: bool) {
+        let Some(diff) = self.attempt_diff.as_ref() else { return };
+        let count = self.conversation().messages.get(diff.message).map_or(0, |message| message.attempts.len());
+        let older = if later { (diff.older + 1).min(count.saturating_sub(1)) } else { diff.older.saturating_sub(1) };
+        if let Some(diff) = self.attempt_diff.as_mut() {
+            if diff.older != older {
+                *diff = AttemptDiff { older, scroll: 0, ..*diff };
+            }
+        }
+    }
+
+    /// Every version of reply `message` of the active conversation, oldest first.
+    pub fn reply_versions(&self, message: usize) -> Vec<Attempt> {
+        self.conversation().messages.get(message).map(versions).unwrap_or_default()
+    }
+
+    /// Shows the version of the last reply before or after the one shown, which is then the
+    /// one the conversation continues from.
+    pub fn cycle_reply(&mut self, later: bool) {
+        let conversation = self.conversation_mut();
+        if conversation.is_thinking {
+            self.set_error("Wait for the reply to finish first".to_string());
+            return;
+        }
+        let index = conversation.messages.len().saturating_sub(1);
+        let Some(reply) = conversation.messages.last_mut().filter(|m| m.role == "assistant") else {
+            self.set_error("Only the last reply can be switched for another version".to_string());
+            return;
+        };
+        if reply.attempts.is_empty() {
+            self.set_error("This reply has no other versions; regenerate it or use /sample".to_string());
+            return;
+        }
+        let mut versions = versions(reply);
+        let shown = shown_version(reply);
+        let target = if later { Some(shown + 1) } else { shown.checked_sub(1) };
+        let Some(target) = target.filter(|&target| target < versions.len()) else {
+            self.status_message = format!("This is the {} version", if later { "latest" } else { "first" });
+            return;
+        };
+        let chosen = versions.remove(target);
+        reply.later = versions.len() - target;
+        reply.attempts = versions;
+        reply.content = chosen.content;
+        reply.model = chosen.model;
+        reply.folded.clear();
+        reply.expanded = false;
+        reply.json_errors.clear();
+        conversation.saved_messages = conversation.saved_messages.min(index);
+        *conversation.layout.borrow_mut() = ChatLayout::default();
+        let (id, total) = (conversation.id, conversation.messages[index].attempts.len() + 1);
+        self.check_json_reply(id, index);
+        self.status_message = format!("Showing version {} of {}; the conversation continues from it", target + 1, total);
+    }
+
+    /// Asks for `count` replies to the last prompt in place of the one there, one after
+    /// another, keeping every one of them to choose from with `<` and `>`.
+    pub fn sample_replies(&mut self, count: usize) {
+        let conversation = self.conversation_mut();
+        if conversation.is_thinking {
+            self.set_error("Wait for the reply to finish first".to_string());
+            return;
+        }
+        let Some(prompt) = conversation.messages.iter().rposition(Message::is_user) else {
+            self.set_error("There is no prompt to sample replies to".to_string());
+            return;
+        };
+        conversation.samples_left = count.saturating_sub(1);
+        self.resend_prompt(self.active_conversation, prompt);
+        self.status_message = format!("Sampling {} replies to the last prompt", count);
+    }
+
+    /// Sends prompt `index` of the conversation at `position` again in place of everything
+    /// after it, keeping the reply it had as an earlier attempt at the new one. The draft in the
+    /// input and what is attached to it are left alone.
+    pub(crate) fn resend_prompt(&mut self, position: usize, index: usize) {
+        let conversation = &mut self.conversations[position];
+        if conversation.is_thinking || !conversation.messages.get(index).is_some_and(Message::is_user) {
+            return;
+        }
+        let attempts = match conversation.messages.get(index + 1).filter(|m| m.role == "assistant" && !m.content.is_empty()) {
+            Some(reply) => versions(reply),
+            None => Vec::new(),
+        };
+        let prompt = conversation.messages.remove(index);
+        conversation.messages.truncate(index);
+        conversation.selected_message = None;
+        conversation.saved_messages = conversation.saved_messages.min(index);
+        // The prompt already holds the command output that went with it
+        let images = std::mem::replace(&mut conversation.pending_images, prompt.images);
+        let output = std::mem::take(&mut conversation.pending_output);
+        self.send_prompt(position, prompt.content);
+        let conversation = &mut self.conversations[position];
+        (conversation.pending_images, conversation.pending_output) = (images, output);
+        if let Some(reply) = conversation.messages.last_mut() {
+            reply.attempts = attempts;
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use anyhow::Result;
+use serde_json::Value;
+use std::future::Future;
+use std::pin::Pin;
+use tokio_stream::{Stream, StreamExt};
+
+use crate::chat::Message;
+use crate::models::ModelConfig;
+use crate::tools::{ToolCall, ToolSpec};
+
+mod anthropic;
+mod mock;
+mod ollama;
+
+pub use anthropic::AnthropicBackend;
+pub use mock::MockBackend;
+pub use ollama::OllamaBackend;
+
+/// A boxed future, which keeps `ChatBackend` usable as a trait object.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+/// The pieces of a reply, in the order they arrive.
+pub type ChunkStream = Pin<Box<dyn Stream<Item = Result<Chunk>> + Send>>;
+
+/// One prompt for a model, with the sampling parameters and system prompt to use.
+#[derive(Debug, Clone)]
+pub struct ChatRequest {
+    pub model: String,
+    pub prompt: String,
+    /// Base64-encoded images sent along with the prompt.
+    pub images: Vec<String>,
+    pub config: ModelConfig,
+    /// Tools the model may call; only sent to backends that support them.
+    pub tools: Vec<ToolSpec>,
+    /// Earlier user messages and replies, oldest first, sent ahead of the prompt.
+    pub history: Vec<Message>,
+    /// Tool calls and their results since the prompt, when continuing after running tools.
+    pub turns: Vec<Message>,
+    /// Constrains the reply to JSON.
+    pub format: Option<ReplyFormat>,
+    /// `prompt` is already in the model's format and is sent as it is, without its template or
+    /// the system prompt.
+    pub raw: bool,
+    /// Code after the gap the reply fills, for fill-in-the-middle; `prompt` is the code before it.
+    pub suffix: Option<String>,
+}
+
+/// The shape a reply has to take.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplyFormat {
+    /// Any JSON value.
+    Json,
+    /// JSON following this schema.
+    Schema(Value),
+}
+
+/// Part of a streamed reply.
+#[derive(Debug, Clone, Default)]
+pub struct Chunk {
+    pub text: String,
+    /// Tokens in `text`; a backend may batch several into one chunk.
+    pub tokens: u64,
+    /// Set on the final chunk when the backend reports token counts.
+    pub usage: Option<Usage>,
+    pub tool_calls: Vec<ToolCall>,
+}
+
+impl Chunk {
+    /// Appends `next`, so several chunks can reach the app as one.
+    pub fn merge(&mut self, next: Chunk) {
+        self.text.push_str(&next.text);
+        self.tokens += next.tokens;
+        self.usage = next.usage.or(self.usage);
+        self.tool_calls.extend(next.tool_calls);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub reply_tokens: u64,
+}
+
+/// A model provider. `App` and the UI only go through this trait, so supporting another
+/// provider means one more implementation.
+pub trait ChatBackend: Send + Sync {
+    /// Whether `ChatRequest::tools` is honoured.
+    fn supports_tools(&self) -> bool {
+        false
+    }
+
+    /// Names of the models that can be chatted with.
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>>>;
+
+    /// Sends `request` and streams back the reply.
+    fn stream_chat(&self, request: ChatRequest) -> BoxFuture<'_, Result<ChunkStream>>;
+
+    /// Downloads a model, for providers that run models locally.
+    fn pull_model(&self, model: String) -> BoxFuture<'_, Result<()>>;
+
+    /// One embedding vector per input text.
+    fn embeddings(&self, model: String, input: Vec<String>) -> BoxFuture<'_, Result<Vec<Vec<f32>>>>;
+}
+
+/// Sends `request` and waits for the whole reply, for answers that aren't shown as they stream.
+pub async fn collect(backend: &dyn ChatBackend, request: ChatRequest) -> Result<String> {
+    let mut stream = backend.stream_chat(request).await?;
+    let mut reply = String::new();
+    while let Some(chunk) = stream.next().await {
+        reply.push_str(&chunk?.text);
+    }
+    Ok(reply)
+}
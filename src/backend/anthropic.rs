@@ -0,0 +1,189 @@
+use anyhow::{anyhow, bail, Context, Result};
+use reqwest::{
+    header::{HeaderMap, HeaderValue, CONTENT_TYPE},
+    Client, RequestBuilder, Response,
+};
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use super::{BoxFuture, ChatBackend, ChatRequest, Chunk, ChunkStream, ReplyFormat, Usage};
+use crate::images;
+
+/// Read when a host has no `api_key` of its own.
+const API_KEY_ENV: &str = "ANTHROPIC_API_KEY";
+const API_VERSION: &str = "2023-06-01";
+/// The Messages API requires a cap on the reply length.
+const MAX_TOKENS: u64 = 4096;
+
+/// Claude models through the Anthropic Messages API.
+pub struct AnthropicBackend {
+    client: Client,
+    url: String,
+    headers: HeaderMap,
+}
+
+impl AnthropicBackend {
+    /// `headers` are the host's extra headers; the key and API version are added here.
+    pub fn new(client: Client, url: &str, api_key: Option<&str>, mut headers: HeaderMap) -> Result<Self> {
+        let key = match api_key {
+            Some(key) => key.to_string(),
+            None => std::env::var(API_KEY_ENV).map_err(|_| anyhow!("no api_key on the host and {} is not set", API_KEY_ENV))?,
+        };
+        headers.insert("x-api-key", HeaderValue::from_str(&key).context("Invalid api_key")?);
+        headers.insert("anthropic-version", HeaderValue::from_static(API_VERSION));
+        Ok(Self { client, url: url.trim_end_matches('/').to_string(), headers })
+    }
+
+    /// Sends a request, turning an error response into its `error.message`.
+    async fn send(&self, request: RequestBuilder) -> Result<Response> {
+        let response = request.headers(self.headers.clone()).send().await?;
+        if response.status().is_success() {
+            return Ok(response);
+        }
+        let status = response.status();
+        let body: Value = serde_json::from_str(&response.text().await.unwrap_or_default()).unwrap_or_default();
+        match body["error"]["message"].as_str() {
+            Some(message) => bail!("{} ({})", message, status),
+            None => bail!("Anthropic API returned {}", status),
+        }
+    }
+}
+
+impl ChatBackend for AnthropicBackend {
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>>> {
+        Box::pin(async move {
+            let response = self.send(self.client.get(format!("{}/v1/models?limit=100", self.url))).await?;
+            let body: Value = serde_json::from_slice(&response.bytes().await?)?;
+            let models = body["data"].as_array().context("Unexpected model list")?;
+            Ok(models.iter().filter_map(|model| Some(model["id"].as_str()?.to_string())).collect())
+        })
+    }
+
+    fn stream_chat(&self, request: ChatRequest) -> BoxFuture<'_, Result<ChunkStream>> {
+        Box::pin(async move {
+            if request.raw {
+                bail!("Raw prompts need an Ollama model; the Anthropic API always applies its own format");
+            }
+            if request.suffix.is_some() {
+                bail!("Fill-in-the-middle needs an Ollama code model; the Anthropic API has no suffix");
+            }
+            let mut content = request
+                .images
+                .iter()
+                .map(|data| {
+                    let media_type = images::mime_type(data).context("Attached image is not a supported format")?;
+                    Ok(json!({ "type": "image", "source": { "type": "base64", "media_type": media_type, "data": data } }))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            content.push(json!({ "type": "text", "text": request.prompt }));
+            let mut messages: Vec<Value> = request
+                .history
+                .iter()
+                .map(|earlier| json!({ "role": if earlier.is_user() { "user" } else { "assistant" }, "content": earlier.content }))
+                .collect();
+            messages.push(json!({ "role": "user", "content": content }));
+            let config = &request.config;
+            // Anthropic's temperature tops out at 1; top_p is left out as newer models reject
+            // it alongside temperature
+            let mut body = json!({
+                "model": request.model,
+                "max_tokens": MAX_TOKENS,
+                "stream": true,
+                "temperature": config.temperature.clamp(0.0, 1.0),
+                "messages": messages,
+            });
+            // There is no JSON mode in the API, so the model is told instead
+            let system = match &request.format {
+                Some(format) => format!("{}\n\n{}", config.system_prompt, json_instruction(format)).trim_start().to_string(),
+                None => config.system_prompt.clone(),
+            };
+            if !system.is_empty() {
+                body["system"] = json!(system);
+            }
+            if config.top_k > 0 {
+                body["top_k"] = json!(config.top_k);
+            }
+            let post = self.client.post(format!("{}/v1/messages", self.url)).header(CONTENT_TYPE, "application/json").body(body.to_string());
+            let response = self.send(post).await?;
+
+            let (sender, receiver) = mpsc::channel(64);
+            tokio::spawn(read_events(response, sender));
+            let chunks: ChunkStream = Box::pin(ReceiverStream::new(receiver));
+            Ok(chunks)
+        })
+    }
+
+    fn pull_model(&self, _model: String) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async { bail!("Claude models run on Anthropic's servers; there is nothing to download") })
+    }
+
+    fn embeddings(&self, _model: String, _input: Vec<String>) -> BoxFuture<'_, Result<Vec<Vec<f32>>>> {
+        Box::pin(async { bail!("Anthropic has no embeddings API") })
+    }
+}
+
+/// What the system prompt asks for in place of Ollama's JSON mode.
+fn json_instruction(format: &ReplyFormat) -> String {
+    let only = "Reply with a single JSON value and nothing else: no prose and no code fences.";
+    match format {
+        ReplyFormat::Json => only.to_string(),
+        ReplyFormat::Schema(schema) => format!("{} It must follow this JSON schema:\n{}", only, schema),
+    }
+}
+
+/// Reads the server-sent events of a streaming reply and forwards them as chunks, until the
+/// reply ends or the receiver goes away.
+async fn read_events(mut response: Response, sender: mpsc::Sender<Result<Chunk>>) {
+    let mut buffer = Vec::new();
+    let mut prompt_tokens = 0;
+    loop {
+        let bytes = match response.chunk().await {
+            Ok(Some(bytes)) => bytes,
+            Ok(None) => return,
+            Err(e) => {
+                let _ = sender.send(Err(e.into())).await;
+                return;
+            }
+        };
+        buffer.extend_from_slice(&bytes);
+        // Split on whole lines only, so a character cut between reads stays intact
+        while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let Some(data) = line.trim_end().strip_prefix("data:") else { continue };
+            let chunk = match event(data.trim(), &mut prompt_tokens) {
+                Ok(None) => continue,
+                Ok(Some(chunk)) => Ok(chunk),
+                Err(e) => Err(e),
+            };
+            let failed = chunk.is_err();
+            if sender.send(chunk).await.is_err() || failed {
+                return;
+            }
+        }
+    }
+}
+
+/// One event's `data` as a chunk, or `None` for events that carry no text or usage.
+fn event(data: &str, prompt_tokens: &mut u64) -> Result<Option<Chunk>> {
+    let event: Value = serde_json::from_str(data)?;
+    Ok(match event["type"].as_str() {
+        Some("message_start") => {
+            *prompt_tokens = event["message"]["usage"]["input_tokens"].as_u64().unwrap_or(0);
+            None
+        }
+        Some("content_block_delta") => {
+            let text = event["delta"]["text"].as_str().unwrap_or_default().to_string();
+            // Deltas don't carry token counts; about four characters make a token
+            let tokens = (text.chars().count() as u64).div_ceil(4);
+            Some(Chunk { text, tokens, ..Chunk::default() })
+        }
+        Some("message_delta") => {
+            let reply_tokens = event["usage"]["output_tokens"].as_u64().unwrap_or(0);
+            Some(Chunk { usage: Some(Usage { prompt_tokens: *prompt_tokens, reply_tokens }), ..Chunk::default() })
+        }
+        Some("error") => bail!("{}", event["error"]["message"].as_str().unwrap_or("Anthropic stream error")),
+        _ => None,
+    })
+}
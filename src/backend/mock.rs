@@ -0,0 +1,93 @@
+use anyhow::{anyhow, Result};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use super::{BoxFuture, ChatBackend, ChatRequest, Chunk, ChunkStream, Usage};
+
+/// What one `stream_chat` call does.
+enum Step {
+    Reply(Vec<Chunk>),
+    Fail(String),
+}
+
+/// A backend that plays back a script instead of calling a server, so the app can be driven
+/// end to end in tests. Each `stream_chat` takes the next scripted step; requests and pulls
+/// are kept for checking afterwards.
+#[derive(Default)]
+pub struct MockBackend {
+    models: Mutex<Vec<String>>,
+    script: Mutex<VecDeque<Step>>,
+    requests: Mutex<Vec<ChatRequest>>,
+    pulled: Mutex<Vec<String>>,
+}
+
+impl MockBackend {
+    pub fn new(models: &[&str]) -> Self {
+        Self { models: Mutex::new(models.iter().map(|m| m.to_string()).collect()), ..Self::default() }
+    }
+
+    /// Queues a reply streamed as `chunks`, each counted as one token.
+    pub fn reply(self, chunks: &[&str]) -> Self {
+        let chunks = chunks.iter().map(|text| Chunk { text: text.to_string(), tokens: 1, ..Chunk::default() }).collect();
+        self.script.lock().unwrap().push_back(Step::Reply(chunks));
+        self
+    }
+
+    /// Has the reply queued last report token counts on its final chunk, as Ollama does.
+    pub fn usage(self, prompt_tokens: u64, reply_tokens: u64) -> Self {
+        if let Some(Step::Reply(chunks)) = self.script.lock().unwrap().back_mut() {
+            if let Some(last) = chunks.last_mut() {
+                last.usage = Some(Usage { prompt_tokens, reply_tokens });
+            }
+        }
+        self
+    }
+
+    /// Queues a request that fails with `message`, as a server error would.
+    pub fn fail(self, message: &str) -> Self {
+        self.script.lock().unwrap().push_back(Step::Fail(message.to_string()));
+        self
+    }
+
+    /// Every request sent so far, oldest first.
+    pub fn requests(&self) -> Vec<ChatRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Models downloaded so far; each is added to the model list too.
+    pub fn pulled(&self) -> Vec<String> {
+        self.pulled.lock().unwrap().clone()
+    }
+}
+
+impl ChatBackend for MockBackend {
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>>> {
+        Box::pin(async move { Ok(self.models.lock().unwrap().clone()) })
+    }
+
+    fn stream_chat(&self, request: ChatRequest) -> BoxFuture<'_, Result<ChunkStream>> {
+        Box::pin(async move {
+            self.requests.lock().unwrap().push(request);
+            match self.script.lock().unwrap().pop_front() {
+                Some(Step::Reply(chunks)) => {
+                    let stream: ChunkStream = Box::pin(tokio_stream::iter(chunks.into_iter().map(Ok)));
+                    Ok(stream)
+                }
+                Some(Step::Fail(message)) => Err(anyhow!(message)),
+                None => Err(anyhow!("No scripted reply left")),
+            }
+        })
+    }
+
+    fn pull_model(&self, model: String) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            self.models.lock().unwrap().push(model.clone());
+            self.pulled.lock().unwrap().push(model);
+            Ok(())
+        })
+    }
+
+    fn embeddings(&self, _model: String, input: Vec<String>) -> BoxFuture<'_, Result<Vec<Vec<f32>>>> {
+        Box::pin(async move { Ok(input.iter().map(|text| vec![text.len() as f32, 1.0]).collect()) })
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::{anyhow, Result};
+use ollama_rs::{
+    generation::{
+        chat::{request::ChatMessageRequest, ChatMessage, ChatMessageResponse},
+        completion::{request::GenerationRequest, GenerationResponse},
+        embeddings::request::{EmbeddingsInput, GenerateEmbeddingsRequest},
+        images::Image,
+        parameters::{FormatType, JsonStructure},
+        tools::{ToolCallFunction, ToolInfo},
+    },
+    Ollama,
+};
+use serde_json::json;
+use tokio_stream::StreamExt;
+
+use super::{BoxFuture, ChatBackend, ChatRequest, Chunk, ChunkStream, ReplyFormat, Usage};
+use crate::tools::{ToolCall, ToolSpec};
+
+/// An Ollama server, local or behind a proxy.
+pub struct OllamaBackend {
+    client: Ollama,
+}
+
+impl OllamaBackend {
+    pub fn new(client: Ollama) -> Self {
+        Self { client }
+    }
+}
+
+impl ChatBackend for OllamaBackend {
+    fn supports_tools(&self) -> bool {
+        true
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>>> {
+        Box::pin(async move { Ok(self.client.list_local_models().await?.into_iter().map(|m| m.name).collect()) })
+    }
+
+    fn stream_chat(&self, request: ChatRequest) -> BoxFuture<'_, Result<ChunkStream>> {
+        // Tools and earlier turns need the chat endpoint; a lone, raw, or fill-in prompt keeps
+        // using generate
+        let lone = request.raw || request.suffix.is_some() || (request.tools.is_empty() && request.turns.is_empty() && request.history.is_empty());
+        if !lone {
+            return self.stream_messages(request);
+        }
+        Box::pin(async move {
+            let mut generation = GenerationRequest::new(request.model, request.prompt)
+                .options(request.config.to_options())
+                .images(request.images.into_iter().map(Image::from_base64).collect());
+            if let Some(suffix) = request.suffix {
+                generation = generation.suffix(suffix);
+            }
+            if request.raw {
+                generation = generation.raw(true);
+            } else if !request.config.system_prompt.is_empty() {
+                generation = generation.system(request.config.system_prompt);
+            }
+            if let Some(format) = request.format.as_ref().map(format_type).transpose()? {
+                generation = generation.format(format);
+            }
+            let stream = self.client.generate_stream(generation).await?;
+            let chunks: ChunkStream = Box::pin(stream.map(|batch| Ok(merge(batch?))));
+            Ok(chunks)
+        })
+    }
+
+    fn pull_model(&self, model: String) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            self.client.pull_model(model, false).await?;
+            Ok(())
+        })
+    }
+
+    fn embeddings(&self, model: String, input: Vec<String>) -> BoxFuture<'_, Result<Vec<Vec<f32>>>> {
+        Box::pin(async move {
+            let request = GenerateEmbeddingsRequest::new(model, EmbeddingsInput::Multiple(input));
+            Ok(self.client.generate_embeddings(request).await?.embeddings)
+        })
+    }
+}
+
+impl OllamaBackend {
+    fn stream_messages(&self, request: ChatRequest) -> BoxFuture<'_, Result<ChunkStream>> {
+        Box::pin(async move {
+            let mut messages = Vec::new();
+            if !request.config.system_prompt.is_empty() {
+                messages.push(ChatMessage::system(request.config.system_prompt.clone()));
+            }
+            for earlier in request.history {
+                messages.push(if earlier.is_user() { ChatMessage::user(earlier.content) } else { ChatMessage::assistant(earlier.content) });
+            }
+            messages.push(ChatMessage::user(request.prompt).with_images(request.images.into_iter().map(Image::from_base64).collect()));
+            for turn in request.turns {
+                let mut message = if turn.is_tool_result() { ChatMessage::tool(turn.content) } else { ChatMessage::assistant(turn.content) };
+                message.tool_calls = turn
+                    .tool_calls
+                    .into_iter()
+                    .map(|call| ollama_rs::generation::tools::ToolCall { function: ToolCallFunction { name: call.name, arguments: call.arguments } })
+                    .collect();
+                messages.push(message);
+            }
+            let tools = request.tools.iter().map(tool_info).collect::<Result<Vec<_>>>()?;
+            let mut chat = ChatMessageRequest::new(request.model, messages).options(request.config.to_options()).tools(tools);
+            if let Some(format) = request.format.as_ref().map(format_type).transpose()? {
+                chat = chat.format(format);
+            }
+            let stream = self.client.send_chat_messages_stream(chat).await?;
+            let chunks: ChunkStream = Box::pin(stream.map(|response| response.map(chat_chunk).map_err(|()| anyhow!("Stream error"))));
+            Ok(chunks)
+        })
+    }
+}
+
+fn tool_info(spec: &ToolSpec) -> Result<ToolInfo> {
+    let info = json!({
+        "type": "function",
+        "function": { "name": spec.name, "description": spec.description, "parameters": spec.parameters },
+    });
+    Ok(serde_json::from_value(info)?)
+}
+
+/// Ollama's `format` for a reply format; schemas need Ollama 0.5 or later.
+fn format_type(format: &ReplyFormat) -> Result<FormatType> {
+    Ok(match format {
+        ReplyFormat::Json => FormatType::Json,
+        ReplyFormat::Schema(schema) => {
+            let schema: JsonStructure = serde_json::from_value(schema.clone()).map_err(|e| anyhow!("Not a usable JSON schema: {}", e))?;
+            FormatType::StructuredJson(Box::new(schema))
+        }
+    })
+}
+
+fn chat_chunk(response: ChatMessageResponse) -> Chunk {
+    let text = response.message.content;
+    Chunk {
+        tokens: u64::from(!text.is_empty()),
+        text,
+        usage: response.final_data.map(|data| Usage { prompt_tokens: data.prompt_eval_count, reply_tokens: data.eval_count }),
+        tool_calls: response.message.tool_calls.into_iter().map(|call| ToolCall { name: call.function.name, arguments: call.function.arguments }).collect(),
+    }
+}
+
+/// Folds the responses Ollama delivered together into one chunk. Each carries one token.
+fn merge(batch: Vec<GenerationResponse>) -> Chunk {
+    let mut chunk = Chunk::default();
+    for response in batch {
+        if !response.response.is_empty() {
+            chunk.tokens += 1;
+        }
+        chunk.text.push_str(&response.response);
+        if response.done {
+            chunk.usage = Some(Usage { prompt_tokens: response.prompt_eval_count.unwrap_or(0), reply_tokens: response.eval_count.unwrap_or(0) });
+        }
+    }
+    chunk
+}
@@ -0,0 +1,199 @@
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+use tokio_stream::StreamExt;
+
+use crate::backend::{ChatBackend, ChatRequest};
+use crate::models::ModelConfig;
+use crate::usage::format_duration;
+
+/// How one model answered one prompt.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct BatchResult {
+    pub model: String,
+    pub prompt: String,
+    pub reply: String,
+    /// Why the reply failed; whatever arrived before it is in `reply`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub seconds: f64,
+    pub prompt_tokens: u64,
+    /// As the server reports them, or counted from the stream when it doesn't.
+    pub reply_tokens: u64,
+}
+
+impl BatchResult {
+    pub fn tokens_per_second(&self) -> f64 {
+        if self.seconds > 0.0 { self.reply_tokens as f64 / self.seconds } else { 0.0 }
+    }
+}
+
+/// Every model's reply to every prompt, as written by `llama-term batch`.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct BatchReport {
+    /// When the run started, local time.
+    pub started: String,
+    pub models: Vec<String>,
+    pub prompts: Vec<String>,
+    /// Grouped by model, in the order the prompts were given.
+    pub results: Vec<BatchResult>,
+}
+
+impl BatchReport {
+    pub fn failed(&self) -> usize {
+        self.results.iter().filter(|result| result.error.is_some()).count()
+    }
+
+    /// Writes the report as Markdown for a `.md` path and as JSON otherwise.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let markdown = path.extension().is_some_and(|ext| ext == "md" || ext == "markdown");
+        let contents = if markdown { self.to_markdown() } else { serde_json::to_string_pretty(self)? };
+        fs::write(path, contents).with_context(|| format!("Cannot write {}", path.display()))
+    }
+
+    /// A table of totals per model, then each prompt with every model's reply under it.
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("# Batch run, {}\n\n", self.started);
+        out.push_str(&format!("{} prompt(s) against {}\n\n", self.prompts.len(), self.models.join(", ")));
+        out.push_str("| Model | Replies | Failed | Time | Reply tokens | Tokens/s |\n|---|---:|---:|---:|---:|---:|\n");
+        for model in &self.models {
+            let results: Vec<&BatchResult> = self.results.iter().filter(|result| &result.model == model).collect();
+            let failed = results.iter().filter(|result| result.error.is_some()).count();
+            let seconds: f64 = results.iter().map(|result| result.seconds).sum();
+            let tokens: u64 = results.iter().map(|result| result.reply_tokens).sum();
+            let rate = if seconds > 0.0 { tokens as f64 / seconds } else { 0.0 };
+            out.push_str(&format!(
+                "| {} | {} | {} | {} | {} | {:.1} |\n",
+                model,
+                results.len() - failed,
+                failed,
+                format_duration(seconds),
+                tokens,
+                rate
+            ));
+        }
+        for (i, prompt) in self.prompts.iter().enumerate() {
+            out.push_str(&format!("\n## Prompt {}\n\n", i + 1));
+            for line in prompt.lines() {
+                out.push_str(format!("> {}", line).trim_end());
+                out.push('\n');
+            }
+            // Each model answered every prompt in order
+            for result in self.results.iter().skip(i).step_by(self.prompts.len()) {
+                out.push_str(&format!(
+                    "\n### {} ({:.1}s, {} tokens, {:.1} tokens/s)\n\n",
+                    result.model,
+                    result.seconds,
+                    result.reply_tokens,
+                    result.tokens_per_second()
+                ));
+                if let Some(error) = &result.error {
+                    out.push_str(&format!("**Failed:** {}\n\n", error));
+                }
+                if !result.reply.trim().is_empty() {
+                    out.push_str(result.reply.trim());
+                    out.push('\n');
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Prompts from a file: a JSON array of strings for a `.json` file, otherwise one prompt per
+/// line, skipping blank lines and lines starting with `#`.
+pub fn load_prompts(path: &Path) -> Result<Vec<String>> {
+    let contents = fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    let prompts: Vec<String> = if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str(&contents).with_context(|| format!("{} is not a JSON array of prompts", path.display()))?
+    } else {
+        contents.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')).map(String::from).collect()
+    };
+    if prompts.iter().all(|prompt| prompt.trim().is_empty()) {
+        bail!("{} has no prompts", path.display());
+    }
+    Ok(prompts)
+}
+
+/// Sends each prompt to each model in turn with `config`, one request at a time so the timings
+/// don't compete, and calls `progress` after every reply. All of one model's prompts run
+/// together, so a local server loads each model once.
+pub async fn run(
+    backend: &dyn ChatBackend,
+    models: &[String],
+    prompts: &[String],
+    config: &ModelConfig,
+    mut progress: impl FnMut(&BatchResult),
+) -> BatchReport {
+    let mut report = BatchReport {
+        started: Local::now().format("%Y-%m-%d %H:%M").to_string(),
+        models: models.to_vec(),
+        prompts: prompts.to_vec(),
+        results: Vec::new(),
+    };
+    for model in models {
+        for prompt in prompts {
+            let result = ask(backend, request(model, prompt, config.clone())).await;
+            progress(&result);
+            report.results.push(result);
+        }
+    }
+    report
+}
+
+/// `prompt` on its own, without history, tools, or attachments.
+pub(crate) fn request(model: &str, prompt: &str, config: ModelConfig) -> ChatRequest {
+    ChatRequest {
+        model: model.to_string(),
+        prompt: prompt.to_string(),
+        images: Vec::new(),
+        config,
+        tools: Vec::new(),
+        history: Vec::new(),
+        turns: Vec::new(),
+        format: None,
+        raw: false,
+        suffix: None,
+    }
+}
+
+/// Streams the reply to `request`, timing it and counting its tokens.
+pub(crate) async fn ask(backend: &dyn ChatBackend, request: ChatRequest) -> BatchResult {
+    let mut result = BatchResult {
+        model: request.model.clone(),
+        prompt: request.prompt.clone(),
+        reply: String::new(),
+        error: None,
+        seconds: 0.0,
+        prompt_tokens: 0,
+        reply_tokens: 0,
+    };
+    let started = Instant::now();
+    let mut counted = 0;
+    let mut usage = None;
+    match backend.stream_chat(request).await {
+        Ok(mut stream) => {
+            while let Some(chunk) = stream.next().await {
+                match chunk {
+                    Ok(chunk) => {
+                        result.reply.push_str(&chunk.text);
+                        counted += chunk.tokens;
+                        usage = chunk.usage.or(usage);
+                    }
+                    Err(e) => {
+                        result.error = Some(format!("{:#}", e));
+                        break;
+                    }
+                }
+            }
+        }
+        Err(e) => result.error = Some(format!("{:#}", e)),
+    }
+    result.seconds = started.elapsed().as_secs_f64();
+    result.prompt_tokens = usage.map_or(0, |usage| usage.prompt_tokens);
+    result.reply_tokens = usage.map_or(counted, |usage| usage.reply_tokens);
+    result
+}
@@ -0,0 +1,1352 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::task::AbortHandle;
+use tokio_stream::StreamExt;
+
+use crate::app::{App, PendingAction};
+use crate::attempts::Attempt;
+use crate::backend::{self, ChatBackend, ChatRequest, Chunk, ReplyFormat};
+use crate::commands::CommitAction;
+use crate::config::{AutoCopy, Preset, ReplyAlert};
+use crate::desktop;
+use crate::error::{AppError, Retry};
+use crate::events::{AppEvent, Events};
+use crate::fim::Fill;
+use crate::git::{self, Diff};
+use crate::knowledge::{self, KnowledgeBase};
+use crate::migrate::Versioned;
+use crate::models::ModelConfig;
+use crate::raw::{self, RawTemplate};
+use crate::text;
+use crate::toast::Severity;
+use crate::tools::{self, Tool, ToolCall, ToolSpec};
+use crate::ui::ChatLayout;
+use crate::usage::UsageTotals;
+use crate::web;
+
+/// One turn of the conversation.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Message {
+    /// `user`, `assistant`, `tool` for a tool's output, `command` for what a `!` command or
+    /// a code block run from a reply printed, or `summary` for messages compressed into one
+    pub role: String,
+    pub content: String,
+    /// Model that wrote an assistant reply, so labels stay right after switching models mid-chat.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Attached images, base64-encoded.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<String>,
+    /// Tools an assistant reply asked to run.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// Knowledge base passages given to the model with the question, as `path:line`, in the
+    /// order it was told to cite them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
+    /// A reply asked for as JSON with `/json`; shown pretty-printed and highlighted.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub json: bool,
+    /// Why a JSON reply doesn't parse or doesn't follow the schema it was asked for.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub json_errors: Vec<String>,
+    /// A long reply shown whole rather than cut short; not saved.
+    #[serde(skip)]
+    pub expanded: bool,
+    /// Code blocks, counting from 0, shown as a one-line summary; not saved.
+    #[serde(skip)]
+    pub folded: Vec<usize>,
+    /// The other replies to the same prompt, kept when it was regenerated, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attempts: Vec<Attempt>,
+    /// How many of `attempts` came after the version shown.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub later: usize,
+    /// The code around the gap a fill-in-the-middle reply fills, shown with it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fill: Option<Fill>,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+impl Message {
+    pub fn user(content: String, images: Vec<String>) -> Self {
+        Self { role: "user".to_string(), content, images, ..Self::default() }
+    }
+
+    pub fn assistant(model: &str) -> Self {
+        Self { role: "assistant".to_string(), model: Some(model.to_string()), ..Self::default() }
+    }
+
+    pub fn tool_result(output: String) -> Self {
+        Self { role: "tool".to_string(), content: output, ..Self::default() }
+    }
+
+    /// What a `!` command or code block printed, under a heading saying what ran.
+    pub fn command_output(heading: &str, output: String) -> Self {
+        Self { role: "command".to_string(), content: format!("{}\n{}", heading, output.trim_end()), ..Self::default() }
+    }
+
+    pub fn is_user(&self) -> bool {
+        self.role == "user"
+    }
+
+    pub fn is_tool_result(&self) -> bool {
+        self.role == "tool"
+    }
+
+    pub fn is_command_output(&self) -> bool {
+        self.role == "command"
+    }
+
+    /// Stands in for the oldest messages of a conversation once they were summarized to fit
+    /// the context window.
+    pub fn summary(content: String) -> Self {
+        Self { role: "summary".to_string(), content, ..Self::default() }
+    }
+
+    pub fn is_summary(&self) -> bool {
+        self.role == "summary"
+    }
+
+    /// Output and summaries, shown collapsed unless expanded with `z o`.
+    pub fn is_output(&self) -> bool {
+        self.is_tool_result() || self.is_command_output() || self.is_summary()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ChatSession {
+    /// Format version; see `migrate`.
+    #[serde(default)]
+    pub version: u32,
+    pub timestamp: String,
+    pub model: String,
+    pub messages: Vec<Message>,
+    #[serde(default)]
+    pub overrides: SessionOverrides,
+}
+
+impl Versioned for ChatSession {
+    const VERSION: u32 = 1;
+
+    fn upgrade(version: u32, value: &mut Value) -> Result<()> {
+        if version == 0 {
+            // Messages were `[role, content]` pairs before they recorded their model, and
+            // replies without a model came from the session's
+            let model = value["model"].clone();
+            if let Some(messages) = value["messages"].as_array_mut() {
+                for message in messages {
+                    if let Some([role, content]) = message.as_array().map(Vec::as_slice) {
+                        *message = json!({ "role": role, "content": content });
+                    }
+                    if message["role"] == "assistant" && message.get("model").is_none_or(Value::is_null) {
+                        message["model"] = model.clone();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parameters set with `/temp`, `/system`, and `/persona` that apply to the current conversation only.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SessionOverrides {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// Library entry the system prompt was picked from, shown in the title bar.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt_name: Option<String>,
+    /// Sampling parameters of the persona in use, applied before `temperature`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampling: Option<Preset>,
+    /// Persona the overrides came from, shown in the title bar.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persona: Option<String>,
+}
+
+impl SessionOverrides {
+    pub fn is_empty(&self) -> bool {
+        self.temperature.is_none() && self.system_prompt.is_none() && self.sampling.is_none()
+    }
+
+    pub fn apply(&self, config: &ModelConfig) -> ModelConfig {
+        let mut config = config.clone();
+        if let Some(sampling) = &self.sampling {
+            sampling.apply_to(&mut config);
+        }
+        if let Some(temperature) = self.temperature {
+            config.temperature = temperature;
+        }
+        if let Some(system_prompt) = &self.system_prompt {
+            config.system_prompt = system_prompt.clone();
+        }
+        config
+    }
+}
+
+/// A row of the chat view, for picking the message shown there.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ViewRow {
+    Top,
+    Middle,
+    Bottom,
+}
+
+/// One open chat: its transcript, model, view position and any reply still streaming in.
+pub struct Conversation {
+    /// Stable while tabs open and close, so a stream keeps writing to the tab that started it.
+    pub id: usize,
+    pub messages: Vec<Message>,
+    pub model: String,
+    pub overrides: SessionOverrides,
+    pub input: String,
+    /// Images queued with `/image`, sent with the next message.
+    pub pending_images: Vec<String>,
+    /// Output of `!!` commands and code blocks, added to the next message.
+    pub pending_output: Vec<String>,
+    /// Prompts sent while a reply was streaming, sent in order as each reply finishes.
+    pub queued: VecDeque<String>,
+    /// Set with `/json`: replies are asked for as JSON, optionally following a schema.
+    pub reply_format: Option<ReplyFormat>,
+    /// Set with `/raw`: prompts are filled into this template and sent without the model's own.
+    pub raw_template: Option<RawTemplate>,
+    /// Set with `/fim`: each prompt is split at `{{fill}}` and the reply is what goes there.
+    pub fim: bool,
+    /// Show command and tool output in full rather than its first few lines.
+    pub expand_output: bool,
+    pub scroll_offset: usize,
+    /// Keep the chat pinned to the bottom while a reply streams; scrolling up detaches it.
+    pub follow_output: bool,
+    /// Tokens arrived while detached from the bottom.
+    pub unseen_output: bool,
+    /// Largest useful `scroll_offset`, recorded by the renderer each frame.
+    pub max_scroll: Cell<usize>,
+    /// Rows the chat pane showed last frame, also recorded by the renderer.
+    pub viewport_height: Cell<usize>,
+    /// First rendered line of each message, for jumping to one by number.
+    pub message_lines: RefCell<Vec<usize>>,
+    /// Lines laid out in earlier frames, reused while the messages and pane stay the same.
+    pub layout: RefCell<ChatLayout>,
+    /// Message picked with `Ctrl+S` or `Ctrl+Up`/`Ctrl+Down`, which the quick-actions menu acts on.
+    pub selected_message: Option<usize>,
+    pub is_thinking: bool,
+    /// The task streaming the reply, so it can be stopped.
+    pub stream: Option<AbortHandle>,
+    /// Whether the reply now streaming answers a plain prompt, which can be sent again if it fails.
+    pub resendable: bool,
+    /// When the reply now streaming was requested.
+    pub stream_started: Option<Instant>,
+    /// Chunks received for the reply now streaming; Ollama sends one token per chunk.
+    pub stream_tokens: u64,
+    /// Tool calls from the last reply still waiting to run.
+    pub pending_tool_calls: VecDeque<ToolCall>,
+    /// Prompt and reply tokens processed so far, as reported by Ollama.
+    pub tokens: u64,
+    /// Tokens in the context after the last reply.
+    pub context_tokens: u64,
+    /// Replies finished since the conversation was opened, and the tokens and time they took.
+    pub usage: UsageTotals,
+    /// When the first prompt since the conversation was opened was sent.
+    pub started: Option<Instant>,
+    /// Messages in the chat history as of the last save or load.
+    pub saved_messages: usize,
+    /// The oldest messages are being summarized in the background.
+    pub compressing: bool,
+    /// Replies still to ask for after this one, for `/sample`.
+    pub samples_left: usize,
+}
+
+impl Conversation {
+    pub fn new(id: usize, model: String) -> Self {
+        Self {
+            id,
+            messages: Vec::new(),
+            model,
+            overrides: SessionOverrides::default(),
+            input: String::new(),
+            pending_images: Vec::new(),
+            pending_output: Vec::new(),
+            queued: VecDeque::new(),
+            reply_format: None,
+            raw_template: None,
+            fim: false,
+            expand_output: false,
+            scroll_offset: 0,
+            follow_output: true,
+            unseen_output: false,
+            max_scroll: Cell::new(0),
+            viewport_height: Cell::new(0),
+            message_lines: RefCell::new(Vec::new()),
+            layout: RefCell::default(),
+            is_thinking: false,
+            stream: None,
+            resendable: false,
+            stream_started: None,
+            stream_tokens: 0,
+            pending_tool_calls: VecDeque::new(),
+            tokens: 0,
+            context_tokens: 0,
+            saved_messages: 0,
+            compressing: false,
+            samples_left: 0,
+            usage: UsageTotals::default(),
+            started: None,
+            selected_message: None,
+        }
+    }
+
+    /// Tab label: the start of the first message, or the model for an empty chat.
+    pub fn title(&self) -> String {
+        match self.messages.first() {
+            Some(message) => text::truncate(message.content.lines().next().unwrap_or(""), 20),
+            None => self.model.clone(),
+        }
+    }
+
+    /// What the model is sent ahead of a prompt at `end`: what was said before it, without tool
+    /// traffic or command output, and any summary as a message of its own. Images only go with
+    /// the message they were attached to.
+    pub fn history(&self, end: usize) -> Vec<Message> {
+        self.messages[..end]
+            .iter()
+            .filter_map(|message| match message.role.as_str() {
+                "user" => Some(Message::user(message.content.clone(), Vec::new())),
+                "assistant" if message.tool_calls.is_empty() && !message.content.is_empty() => Some(message.clone()),
+                "summary" => Some(Message::user(format!("Summary of our conversation so far:\n{}", message.content), Vec::new())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Tokens this conversation occupies in the model's context: Ollama's count after the last
+    /// reply, or roughly four characters per token for text it hasn't counted yet.
+    pub fn estimated_tokens(&self, system_prompt: &str) -> u64 {
+        let chars = system_prompt.len() + self.messages.iter().map(|m| m.content.len()).sum::<usize>();
+        self.context_tokens.max((chars / CHARS_PER_TOKEN) as u64 + self.draft_tokens())
+    }
+
+    /// Rough tokens the message being written takes once sent, attached images and command
+    /// output included.
+    pub fn draft_tokens(&self) -> u64 {
+        // Each output goes in a fenced block of its own
+        let chars = self.input.len() + self.pending_output.iter().map(|output| output.len() + 10).sum::<usize>();
+        (chars / CHARS_PER_TOKEN) as u64 + self.pending_images.len() as u64 * IMAGE_TOKENS
+    }
+
+    /// Live progress of the streaming reply, e.g. `3.2s · 148 tokens · 46 tok/s`.
+    pub fn stream_stats(&self) -> Option<String> {
+        let elapsed = self.stream_started?.elapsed().as_secs_f64();
+        let mut stats = format!("{:.1}s · {} tokens", elapsed, self.stream_tokens);
+        if self.stream_tokens > 0 && elapsed > 0.0 {
+            stats.push_str(&format!(" · {:.0} tok/s", self.stream_tokens as f64 / elapsed));
+        }
+        Some(stats)
+    }
+
+    pub fn clear(&mut self) {
+        // A reply still streaming would write into messages that are gone
+        self.stop_reply();
+        self.messages.clear();
+        self.overrides = SessionOverrides::default();
+        self.pending_images.clear();
+        self.pending_output.clear();
+        self.queued.clear();
+        self.scroll_offset = 0;
+        self.follow_output = true;
+        self.unseen_output = false;
+        self.tokens = 0;
+        self.context_tokens = 0;
+        self.saved_messages = 0;
+        self.usage = UsageTotals::default();
+        self.started = None;
+        self.selected_message = None;
+    }
+
+    /// Whether the chat has messages the history doesn't have yet.
+    pub fn has_unsaved_messages(&self) -> bool {
+        !self.messages.is_empty() && self.messages.len() != self.saved_messages
+    }
+
+    /// Stops the reply in progress, keeping whatever part of it arrived.
+    pub fn stop_reply(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            stream.abort();
+        }
+        if !self.is_thinking {
+            return;
+        }
+        self.is_thinking = false;
+        self.stream_started = None;
+        self.pending_tool_calls.clear();
+        if self.messages.last().is_some_and(|m| m.role == "assistant" && m.content.is_empty() && m.tool_calls.is_empty()) {
+            self.messages.pop();
+        }
+    }
+
+    pub fn scroll_up(&mut self, rows: usize) {
+        if self.follow_output {
+            self.follow_output = false;
+            self.scroll_offset = self.max_scroll.get();
+        }
+        self.scroll_offset = self.scroll_offset.saturating_sub(rows);
+    }
+    pub fn scroll_down(&mut self, rows: usize) {
+        if self.follow_output {
+            return;
+        }
+        self.scroll_offset += rows;
+        if self.scroll_offset >= self.max_scroll.get() {
+            self.scroll_bottom();
+        }
+    }
+    /// Scrolls so message `number` (counting from 1) is at the top of the view.
+    pub fn jump_to_message(&mut self, number: usize) -> bool {
+        let line = number.checked_sub(1).and_then(|i| self.message_lines.borrow().get(i).copied());
+        let Some(line) = line else { return false };
+        self.follow_output = false;
+        self.scroll_offset = line;
+        if line >= self.max_scroll.get() {
+            self.scroll_bottom();
+        }
+        true
+    }
+    /// The first line in view, as of the last frame.
+    pub fn top_line(&self) -> usize {
+        if self.follow_output { self.max_scroll.get() } else { self.scroll_offset.min(self.max_scroll.get()) }
+    }
+    /// Scrolls the start of the next message to the top of the view, or the previous one's
+    /// when `forward` is false, like `}` and `{` between paragraphs in vim.
+    pub fn jump_to_boundary(&mut self, forward: bool) {
+        let top = self.top_line();
+        let starts = self.message_lines.borrow();
+        let line = match forward {
+            true => starts.iter().copied().find(|&start| start > top),
+            false => starts.iter().copied().rev().find(|&start| start < top),
+        };
+        drop(starts);
+        match line {
+            Some(line) => {
+                self.follow_output = false;
+                self.scroll_offset = line;
+                if line >= self.max_scroll.get() {
+                    self.scroll_bottom();
+                }
+            }
+            None if forward => self.scroll_bottom(),
+            None => self.scroll_top(),
+        }
+    }
+    /// The message shown on a row of the view: its top, middle, or bottom, as `H`, `M`, and `L`
+    /// pick in vim.
+    pub fn message_in_view(&self, row: ViewRow) -> Option<usize> {
+        let top = self.top_line();
+        let line = match row {
+            ViewRow::Top => top,
+            ViewRow::Middle => top + (self.page_height() - 1) / 2,
+            ViewRow::Bottom => top + self.page_height() - 1,
+        };
+        self.message_lines.borrow().partition_point(|&start| start <= line).checked_sub(1)
+    }
+    /// Rows of chat visible in the last frame, for page-wise scrolling.
+    pub fn page_height(&self) -> usize {
+        self.viewport_height.get().max(1)
+    }
+    pub fn scroll_top(&mut self) {
+        self.follow_output = false;
+        self.scroll_offset = 0;
+    }
+    pub fn scroll_bottom(&mut self) {
+        self.follow_output = true;
+        self.unseen_output = false;
+        self.scroll_offset = self.max_scroll.get();
+    }
+}
+
+/// Replies in a row that may call tools before the app stops sending results back.
+const MAX_TOOL_ROUNDS: usize = 8;
+
+/// Rough characters per token, for sizing text against the context window.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Rough tokens an attached image takes; vision models vary widely.
+const IMAGE_TOKENS: u64 = 768;
+
+/// Smallest piece of a page or diff sent with `/url` or `/review`, however small the context window.
+const MIN_CONTENT_PART: usize = 2000;
+
+/// Parts of a long page `/url` reads before giving up on the rest.
+const MAX_URL_PARTS: usize = 8;
+
+/// Share of the context window a conversation may fill before its oldest messages are summarized.
+const COMPRESS_AT: f64 = 0.8;
+/// Latest messages always kept word for word when compressing.
+const KEEP_RECENT: usize = 4;
+/// What the model is asked to do with the messages being compressed.
+const SUMMARY_TASK: &str = "Summarize the conversation below so the summary can replace it in a chat that continues. Keep facts, decisions, names, numbers, code identifiers, and open questions; leave out pleasantries. Write concise notes.";
+/// Words from which a finished reply's length is shown in the status bar.
+const LONG_REPLY_WORDS: usize = 150;
+/// How long a stream gathers chunks before handing them to the app in one event.
+const STREAM_BATCH: Duration = Duration::from_millis(30);
+
+impl App {
+    pub fn conversation(&self) -> &Conversation {
+        &self.conversations[self.active_conversation]
+    }
+
+    pub fn conversation_mut(&mut self) -> &mut Conversation {
+        &mut self.conversations[self.active_conversation]
+    }
+
+    /// Opens an empty tab using the current tab's model and switches to it.
+    pub fn new_conversation(&mut self) {
+        let conversation = Conversation::new(self.next_conversation_id, self.conversation().model.clone());
+        self.next_conversation_id += 1;
+        self.conversations.push(conversation);
+        self.active_conversation = self.conversations.len() - 1;
+        self.status_message = format!("Tab {} opened", self.conversations.len());
+    }
+
+    pub fn cycle_conversation(&mut self, forward: bool) {
+        let count = self.conversations.len();
+        self.active_conversation = if forward { (self.active_conversation + 1) % count } else { (self.active_conversation + count - 1) % count };
+        self.status_message = format!("Tab {}/{}", self.active_conversation + 1, count);
+    }
+
+    /// Closes the current tab, asking first if it has messages the history doesn't have yet;
+    /// the last tab is cleared instead.
+    pub fn close_conversation(&mut self) {
+        let conversation = self.conversation();
+        if conversation.has_unsaved_messages() {
+            let id = conversation.id;
+            self.confirm("Close this tab? Its messages are not saved to the history".to_string(), PendingAction::CloseConversation(id));
+            return;
+        }
+        self.close_conversation_now(self.conversation().id);
+    }
+
+    /// Closes the tab with `id` without asking, stopping any reply it is waiting on.
+    pub(crate) fn close_conversation_now(&mut self, id: usize) {
+        let Some(position) = self.conversations.iter().position(|c| c.id == id) else { return };
+        if self.conversations.len() == 1 {
+            self.conversations[position].clear();
+            self.status_message = "Chat cleared".to_string();
+            return;
+        }
+        let mut closed = self.conversations.remove(position);
+        closed.stop_reply();
+        if position < self.active_conversation {
+            self.active_conversation -= 1;
+        }
+        self.active_conversation = self.active_conversation.min(self.conversations.len() - 1);
+        // Closing either side of a split leaves the other one focused on its own
+        if let Some(other) = self.split_conversation.take() {
+            if other != closed.id {
+                self.active_conversation = self.conversations.iter().position(|c| c.id == other).unwrap_or(self.active_conversation);
+            }
+        }
+        self.status_message = "Tab closed".to_string();
+    }
+
+    /// Index of the conversation in the other split pane, if split view is on.
+    pub fn split_index(&self) -> Option<usize> {
+        let id = self.split_conversation?;
+        self.conversations.iter().position(|c| c.id == id && c.id != self.conversation().id)
+    }
+
+    /// Shows the next tab (or a new one) beside the current conversation, or closes the split.
+    pub fn toggle_split(&mut self) {
+        if self.split_conversation.take().is_some() {
+            self.status_message = "Split closed".to_string();
+            return;
+        }
+        let current = self.active_conversation;
+        if self.conversations.len() == 1 {
+            self.new_conversation();
+            self.active_conversation = current;
+        }
+        let other = (current + 1) % self.conversations.len();
+        self.split_conversation = Some(self.conversations[other].id);
+        self.status_message = "Split view: Alt+w switches pane".to_string();
+    }
+
+    pub fn switch_pane(&mut self) {
+        let Some(other) = self.split_index() else { return };
+        self.split_conversation = Some(self.conversation().id);
+        self.active_conversation = other;
+    }
+
+    pub fn clear_chat(&mut self) {
+        self.conversation_mut().clear();
+        self.status_message = "Chat cleared".to_string();
+    }
+
+    pub fn select_last_message(&mut self) {
+        if let Some(last) = self.conversation().messages.len().checked_sub(1) {
+            self.select_message(last);
+        }
+    }
+
+    /// Estimated tokens used by the active conversation and the context window they must fit in.
+    pub fn context_usage(&self) -> (u64, u64) {
+        let config = self.effective_config();
+        (self.conversation().estimated_tokens(&config.system_prompt), config.num_ctx.max(1))
+    }
+
+    /// Estimated tokens the message being written takes with the system prompt, and the context
+    /// window, which it must fit in by itself.
+    pub fn draft_usage(&self) -> (u64, u64) {
+        let config = self.effective_config();
+        ((config.system_prompt.len() / CHARS_PER_TOKEN) as u64 + self.conversation().draft_tokens(), config.num_ctx.max(1))
+    }
+
+    /// Sends the input, or queues it while the conversation is busy with a reply.
+    pub fn start_message_stream(&mut self) {
+        let conversation = self.conversation_mut();
+        let busy = conversation.is_thinking || conversation.compressing;
+        if conversation.input.trim().is_empty() {
+            // An empty Enter resumes a queue left waiting by a failed or stopped reply
+            if !busy {
+                let id = conversation.id;
+                self.send_queued(id);
+            }
+            return;
+        }
+        let prompt = std::mem::take(&mut conversation.input);
+        if busy {
+            conversation.queued.push_back(prompt);
+            let waiting = conversation.queued.len();
+            self.status_message = format!("Queued; sent when the reply finishes ({} waiting)", waiting);
+            return;
+        }
+        self.send_prompt(self.active_conversation, prompt);
+    }
+
+    /// Sends the first queued prompt of a conversation that is not busy with a reply.
+    fn send_queued(&mut self, conversation_id: usize) {
+        let Some(position) = self.conversations.iter().position(|c| c.id == conversation_id) else { return };
+        let conversation = &mut self.conversations[position];
+        if conversation.is_thinking || conversation.compressing {
+            return;
+        }
+        // Samples asked for with `/sample` come before queued prompts
+        if conversation.samples_left > 0 {
+            conversation.samples_left -= 1;
+            if let Some(prompt) = conversation.messages.iter().rposition(Message::is_user) {
+                self.resend_prompt(position, prompt);
+                return;
+            }
+        }
+        if let Some(prompt) = conversation.queued.pop_front() {
+            self.send_prompt(position, prompt);
+        }
+    }
+
+    /// Adds `user_message`, with the attachments waiting in the conversation at `position`,
+    /// to that conversation and streams the reply into it.
+    pub(crate) fn send_prompt(&mut self, position: usize, mut user_message: String) {
+        let config = self.conversations[position].overrides.apply(&self.model_config);
+        let (backend, events) = (self.backend.clone(), self.events.clone());
+        let tools = self.offered_tools();
+        let knowledge = self.knowledge.clone().filter(|_| self.app_config.knowledge_enabled);
+        let conversation = &mut self.conversations[position];
+        let history = conversation.history(conversation.messages.len());
+        let images = std::mem::take(&mut conversation.pending_images);
+        for output in std::mem::take(&mut conversation.pending_output) {
+            user_message.push_str(&format!("\n\n```\n{}\n```", output));
+        }
+        conversation.messages.push(Message::user(user_message.clone(), images.clone()));
+        conversation.started.get_or_insert_with(Instant::now);
+        conversation.scroll_bottom();
+
+        // Start thinking animation
+        conversation.is_thinking = true;
+        conversation.resendable = true;
+        conversation.stream_started = Some(Instant::now());
+        conversation.stream_tokens = 0;
+        conversation.messages.push(Message { json: conversation.reply_format.is_some(), ..Message::assistant(&conversation.model) });
+
+        let conversation_id = conversation.id;
+        let message_index = conversation.messages.len() - 1;
+        let model = conversation.model.clone();
+        self.thinking_frame = 0;
+
+        // Spawn the streaming task in the background
+        let mut request = ChatRequest { model, prompt: user_message, images, config, tools, history, turns: Vec::new(), format: conversation.reply_format.clone(), raw: false, suffix: None };
+        // A raw or fill-in prompt goes exactly as written, with nothing added to it
+        let knowledge = if conversation.fim {
+            let fill = Fill::split(&request.prompt);
+            request.prompt = fill.prefix.clone();
+            (request.suffix, request.format) = (Some(fill.suffix.clone()), None);
+            (request.tools, request.history) = (Vec::new(), Vec::new());
+            conversation.messages[message_index] = Message { fill: Some(fill), ..Message::assistant(&conversation.model) };
+            None
+        } else if let Some(template) = &conversation.raw_template {
+            let template = template.text(&self.app_config, &conversation.model);
+            request.prompt = raw::render(template, &request.config.system_prompt, &request.history, &request.prompt);
+            (request.tools, request.history, request.raw) = (Vec::new(), Vec::new(), true);
+            None
+        } else {
+            knowledge
+        };
+        let task = match knowledge {
+            Some(knowledge) => tokio::spawn(answer_with_knowledge(events, backend, conversation_id, message_index, request, knowledge)),
+            None => tokio::spawn(stream_reply(events, backend, conversation_id, message_index, request)),
+        };
+        self.conversations[position].stream = Some(task.abort_handle());
+    }
+
+    /// Builds a knowledge base from `path` in the background, replacing the current one and
+    /// turning retrieval on once it's saved.
+    pub(crate) fn index_knowledge(&mut self, path: PathBuf) {
+        if self.indexing {
+            self.set_error("Already indexing; wait for it to finish".to_string());
+            return;
+        }
+        self.indexing = true;
+        self.status_message = format!("Indexing {}", path.display());
+        let (backend, events) = (self.backend.clone(), self.events.clone());
+        let (model, config_dir) = (self.app_config.embedding_model.clone(), self.config_dir.clone());
+        tokio::spawn(async move {
+            let progress_events = events.clone();
+            let progress = move |done: usize, total: usize| {
+                progress_events.update(move |app| app.status_message = format!("Indexing: {} of {} passages embedded", done, total));
+                async {}
+            };
+            let result = match KnowledgeBase::build(backend.as_ref(), &path, &model, progress).await {
+                Ok(base) => base.save(&config_dir).map(|()| base),
+                Err(e) => Err(e),
+            };
+            events.update(move |app| {
+                app.indexing = false;
+                match result {
+                    Ok(base) => {
+                        app.notify(Severity::Success, format!("Indexed {} passages from {}; retrieval is on", base.passages.len(), base.root.display()));
+                        app.knowledge = Some(Arc::new(base));
+                        app.app_config.knowledge_enabled = true;
+                        app.save_app_config();
+                    }
+                    Err(e) => app.set_error(format!("Indexing failed: {:#}", e)),
+                }
+            });
+        });
+    }
+
+    /// Fetches a page for `/url` and streams the model's summary of it, or its answer to
+    /// `question`. The transcript shows the request and the source, not the page itself.
+    pub(crate) fn ask_about_url(&mut self, url: String, question: Option<String>) {
+        let task = match &question {
+            Some(question) => format!("Answer this question using the web page below: {}", question),
+            None => "Summarize the web page below.".to_string(),
+        };
+        let shown = question.unwrap_or_else(|| "Summarize this page".to_string());
+        let Some((conversation_id, message_index, request)) = self.begin_reply(format!("{}\n\nSource: {}", shown, url), task) else { return };
+        self.status_message = format!("Fetching {}", url);
+        let task = tokio::spawn(answer_from_url(self.events.clone(), self.backend.clone(), conversation_id, message_index, request, url));
+        self.conversation_mut().stream = Some(task.abort_handle());
+    }
+
+    /// Reads the git diff for `/commitmsg` or `/review` and streams the model's answer to `task`
+    /// about it. The transcript shows `shown` and the repository, not the diff.
+    pub(crate) fn ask_about_diff(&mut self, diff: Diff, shown: String, task: String) {
+        let root = match git::repo_root() {
+            Ok(root) => root,
+            Err(e) => return self.set_error(format!("{:#}", e)),
+        };
+        let Some((conversation_id, message_index, request)) = self.begin_reply(format!("{}\n\nRepository: {}", shown, root.display()), task) else { return };
+        self.status_message = "Reading the diff".to_string();
+        let task = tokio::spawn(answer_about_diff(self.events.clone(), self.backend.clone(), conversation_id, message_index, request, diff));
+        self.conversation_mut().stream = Some(task.abort_handle());
+    }
+
+    /// Starts a reply to a request whose prompt is put together in the background: `shown` goes
+    /// in the transcript, and the returned request carries `task` as its prompt for now. `None`
+    /// while a reply is already streaming.
+    fn begin_reply(&mut self, shown: String, task: String) -> Option<(usize, usize, ChatRequest)> {
+        if self.conversation().is_thinking {
+            self.set_error("Wait for the current reply to finish".to_string());
+            return None;
+        }
+        let config = self.effective_config();
+        let conversation = self.conversation_mut();
+        conversation.messages.push(Message::user(shown, Vec::new()));
+        conversation.scroll_bottom();
+        conversation.is_thinking = true;
+        conversation.resendable = false;
+        conversation.stream_started = Some(Instant::now());
+        conversation.stream_tokens = 0;
+        conversation.messages.push(Message::assistant(&conversation.model));
+
+        let conversation_id = conversation.id;
+        let message_index = conversation.messages.len() - 1;
+        let model = conversation.model.clone();
+        self.thinking_frame = 0;
+
+        // No tools: continuing after a call would resend the short transcript message, not the
+        // prompt. No history either: the page or diff needs the room
+        let request = ChatRequest { model, prompt: task, images: Vec::new(), config, tools: Vec::new(), history: Vec::new(), turns: Vec::new(), format: None, raw: false, suffix: None };
+        Some((conversation_id, message_index, request))
+    }
+
+    /// Copies the commit message from the last reply, or asks before committing with it. A
+    /// message in a code block is taken from the block.
+    pub(crate) fn use_commit_message(&mut self, action: CommitAction) {
+        let Some(reply) = self.conversation().messages.iter().rev().find(|m| m.role == "assistant" && !m.content.is_empty()) else {
+            self.set_error("No commit message yet; write one with /commitmsg".to_string());
+            return;
+        };
+        let message = match text::code_blocks(&reply.content).into_iter().next() {
+            Some(block) => block.code.trim().to_string(),
+            None => reply.content.trim().to_string(),
+        };
+        match action {
+            CommitAction::Copy => {
+                self.selected_text = Some(message);
+                self.copy_to_clipboard();
+            }
+            CommitAction::Commit => {
+                let subject = message.lines().next().unwrap_or("").to_string();
+                self.confirm(format!("Commit the staged changes with this message?\n{}", subject), PendingAction::GitCommit(message));
+            }
+        }
+    }
+
+    /// Rings the bell or shows a desktop notification, as configured, for a slow reply that
+    /// finished while the terminal was in the background.
+    fn alert_reply_finished(&mut self, model: &str, elapsed: Duration, reply: &str) {
+        let alert = self.app_config.reply_alert;
+        if alert == ReplyAlert::Off || self.terminal_focused == Some(true) || elapsed.as_secs() < self.app_config.reply_alert_after_secs {
+            return;
+        }
+        if matches!(alert, ReplyAlert::Bell | ReplyAlert::Both) {
+            desktop::bell();
+        }
+        if matches!(alert, ReplyAlert::Desktop | ReplyAlert::Both) {
+            let title = format!("{} replied in {:.0}s", model, elapsed.as_secs_f64());
+            let body = text::truncate(reply.lines().find(|line| !line.trim().is_empty()).unwrap_or(""), 120);
+            let events = self.events.clone();
+            tokio::spawn(async move {
+                if let Ok(Err(e)) = tokio::task::spawn_blocking(move || desktop::notify(&title, &body)).await {
+                    events.update(move |app| app.set_error(format!("Desktop notification failed: {:#}", e)));
+                }
+            });
+        }
+    }
+
+    /// Puts a finished reply, or its last code block, on the clipboard when `auto_copy` asks to.
+    fn auto_copy_reply(&mut self, reply: &str) {
+        let setting = self.app_config.auto_copy;
+        let Some(copied) = setting.text(reply) else { return };
+        match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(copied)) {
+            Ok(()) => {
+                let what = if setting == AutoCopy::LastCodeBlock { "the reply's last code block" } else { "the reply" };
+                self.status_message = format!("{}; copied {} to the clipboard", self.status_message, what);
+            }
+            Err(e) => self.set_error(format!("Cannot copy the reply to the clipboard: {}", e)),
+        }
+    }
+
+    /// Adds a piece of a streaming reply to its message.
+    pub(crate) fn append_chunk(&mut self, conversation_id: usize, message_index: usize, chunk: Chunk) {
+        let Some(conversation) = self.conversation_by_id(conversation_id) else { return };
+        if let Some(message) = conversation.messages.get_mut(message_index) {
+            message.content.push_str(&chunk.text);
+            message.tool_calls.extend(chunk.tool_calls);
+        }
+        conversation.stream_tokens += chunk.tokens;
+        if !conversation.follow_output {
+            conversation.unseen_output = true;
+        }
+        if let Some(usage) = chunk.usage {
+            let used = usage.prompt_tokens + usage.reply_tokens;
+            conversation.tokens += used;
+            conversation.context_tokens = used;
+            self.record_tokens(conversation_id, usage);
+        }
+    }
+
+    /// Wraps up a finished stream: runs the tools the reply asked for, or settles the reply.
+    pub(crate) fn finish_stream(&mut self, conversation_id: usize, message_index: usize, error: Option<AppError>) {
+        let Some(conversation) = self.conversation_by_id(conversation_id) else { return };
+        conversation.stream = None;
+        let elapsed = conversation.stream_started.take().map_or(Duration::ZERO, |started| started.elapsed());
+        let calls = conversation.messages.get(message_index).map(|message| message.tool_calls.clone()).unwrap_or_default();
+        // Each reply that calls tools is one round since the prompt
+        let prompt_index = conversation.messages.iter().rposition(Message::is_user).unwrap_or(0);
+        let rounds = conversation.messages[prompt_index..].iter().filter(|m| !m.tool_calls.is_empty()).count();
+        if error.is_none() && !calls.is_empty() && rounds <= MAX_TOOL_ROUNDS {
+            conversation.pending_tool_calls = calls.into();
+            self.run_next_tool(conversation_id);
+            return;
+        }
+        conversation.is_thinking = false;
+        let (model, tokens) = (conversation.model.clone(), conversation.tokens);
+        let retry = conversation.resendable.then_some(Retry::Reply(conversation_id));
+        let reply = conversation.messages.get(message_index).map(|message| message.content.clone()).unwrap_or_default();
+        match error {
+            Some(error) => self.fail("The reply stopped", error, retry),
+            None if !calls.is_empty() => self.set_error(format!("Stopped after {} rounds of tool calls", MAX_TOOL_ROUNDS)),
+            None => {
+                let length = text::Length::of(&reply);
+                self.status_message = match length.words >= LONG_REPLY_WORDS {
+                    true => format!("Ready. The reply is {}", length),
+                    false => "Ready".to_string(),
+                };
+                self.auto_copy_reply(&reply);
+                self.check_json_reply(conversation_id, message_index);
+                tracing::debug!(conversation_id, elapsed_ms = elapsed.as_millis() as u64, chars = reply.len(), "reply finished");
+                self.event_log.push(Severity::Info, format!("Reply from {} finished ({} tokens in conversation)", model, tokens));
+                self.reply_unseen = true;
+                self.record_reply(conversation_id, elapsed);
+                self.alert_reply_finished(&model, elapsed, &reply);
+                self.compress_if_needed(conversation_id);
+                self.send_queued(conversation_id);
+            }
+        }
+        if let Some(conversation) = self.conversation_by_id(conversation_id).filter(|c| !c.is_thinking && !c.compressing && !c.queued.is_empty()) {
+            let waiting = conversation.queued.len();
+            self.status_message = format!("{} queued prompt(s) waiting; press Enter to send the next", waiting);
+        }
+    }
+
+    /// Once a conversation nears its context window, summarizes its oldest messages in the
+    /// background and puts the summary in their place, rather than leaving the server to drop
+    /// them unseen. The latest exchanges are kept as they are.
+    fn compress_if_needed(&mut self, conversation_id: usize) {
+        if !self.app_config.compress_context {
+            return;
+        }
+        let (backend, events, model_config) = (self.backend.clone(), self.events.clone(), self.model_config.clone());
+        let Some(conversation) = self.conversation_by_id(conversation_id) else { return };
+        let config = conversation.overrides.apply(&model_config);
+        let used = conversation.estimated_tokens(&config.system_prompt);
+        if conversation.compressing || conversation.is_thinking || (used as f64) < config.num_ctx as f64 * COMPRESS_AT {
+            return;
+        }
+        // Cut just before a prompt, so a question and its reply stay together
+        let limit = conversation.messages.len().saturating_sub(KEEP_RECENT);
+        let cut = (1..=limit).rev().find(|&i| conversation.messages[i].is_user()).unwrap_or(0);
+        if cut < 2 {
+            return;
+        }
+        let older = conversation.messages[..cut].to_vec();
+        let transcript: Vec<String> = older
+            .iter()
+            .filter_map(|message| match message.role.as_str() {
+                "user" => Some(format!("User: {}", message.content)),
+                "assistant" if !message.content.is_empty() => Some(format!("Assistant: {}", message.content)),
+                "summary" => Some(format!("Summary of what came before: {}", message.content)),
+                _ => None,
+            })
+            .collect();
+        let request = ChatRequest {
+            model: conversation.model.clone(),
+            prompt: format!("{}\n\n---\n{}\n---", SUMMARY_TASK, transcript.join("\n\n")),
+            images: Vec::new(),
+            // The summary is for the app, not in the persona the system prompt sets up
+            config: ModelConfig { system_prompt: String::new(), ..config },
+            tools: Vec::new(),
+            history: Vec::new(),
+            turns: Vec::new(),
+            format: None,
+            raw: false,
+            suffix: None,
+        };
+        conversation.compressing = true;
+        self.status_message = "Summarizing earlier messages to free up context".to_string();
+        tracing::info!(conversation_id, messages = cut, tokens = used, "compressing context");
+        tokio::spawn(async move {
+            let result = backend::collect(backend.as_ref(), request).await;
+            events.update(move |app| {
+                app.finish_compression(conversation_id, older, result);
+                app.send_queued(conversation_id);
+            });
+        });
+    }
+
+    /// Puts a finished summary in place of the messages it covers, unless the chat was cleared
+    /// or replaced in the meantime.
+    fn finish_compression(&mut self, conversation_id: usize, older: Vec<Message>, result: Result<String>) {
+        self.dirty = true;
+        let Some(conversation) = self.conversation_by_id(conversation_id) else { return };
+        conversation.compressing = false;
+        if !conversation.messages.starts_with(&older) {
+            return;
+        }
+        let summary = match result {
+            Ok(summary) if !summary.trim().is_empty() => summary.trim().to_string(),
+            Ok(_) => return self.notify(Severity::Warning, "Cannot summarize earlier messages: the model returned nothing".to_string()),
+            Err(e) => return self.notify(Severity::Warning, format!("Cannot summarize earlier messages: {:#}", e)),
+        };
+        let Some(conversation) = self.conversation_by_id(conversation_id) else { return };
+        conversation.messages.splice(..older.len(), [Message::summary(summary)]);
+        conversation.selected_message = None;
+        // Ollama's count covered the messages just replaced; estimate until the next reply
+        conversation.context_tokens = 0;
+        conversation.saved_messages = 0;
+        *conversation.layout.borrow_mut() = ChatLayout::default();
+        self.status_message = "Ready".to_string();
+        self.notify(Severity::Info, format!("Summarized {} earlier messages to stay within the context window", older.len()));
+    }
+
+    /// Ends a reply that failed before any of it arrived, removing the empty message. A reply
+    /// already stopped, cleared, or closed is left alone.
+    pub(crate) fn abandon_reply(&mut self, conversation_id: usize, error: AppError) {
+        let Some(conversation) = self.conversation_by_id(conversation_id) else { return };
+        if !conversation.is_thinking {
+            return;
+        }
+        conversation.stream = None;
+        conversation.stop_reply();
+        let retry = conversation.resendable.then_some(Retry::Reply(conversation_id));
+        self.dirty = true;
+        self.fail("No reply", error, retry);
+    }
+
+    /// Tools to send with a request: none unless enabled and the backend can use them.
+    fn offered_tools(&self) -> Vec<ToolSpec> {
+        if self.app_config.tools_enabled && self.backend.supports_tools() { self.tools.specs() } else { Vec::new() }
+    }
+
+    /// Works through the tool calls the last reply asked for, one at a time, then sends the
+    /// results back so the model can carry on.
+    fn run_next_tool(&mut self, conversation_id: usize) {
+        let Some(conversation) = self.conversation_by_id(conversation_id) else { return };
+        let Some(call) = conversation.pending_tool_calls.pop_front() else {
+            self.continue_after_tools(conversation_id);
+            return;
+        };
+        match self.tools.get(&call.name) {
+            None => self.finish_tool_call(conversation_id, format!("Error: there is no tool named '{}'", call.name)),
+            Some(tool) if tool.needs_approval() => self.confirm(format!("Let the model run {}?", call.summary()), PendingAction::ToolCall { conversation_id, call }),
+            Some(tool) => self.spawn_tool(conversation_id, tool, call),
+        }
+    }
+
+    /// Runs an approved call off the UI thread; commands may take a while.
+    pub(crate) fn spawn_tool(&mut self, conversation_id: usize, tool: Arc<dyn Tool>, call: ToolCall) {
+        self.status_message = format!("Running {}", call.summary());
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            let output = tokio::task::spawn_blocking(move || tools::run(tool.as_ref(), &call.arguments)).await.unwrap_or_else(|e| format!("Error: {}", e));
+            events.update(move |app| app.finish_tool_call(conversation_id, output));
+        });
+    }
+
+    pub(crate) fn finish_tool_call(&mut self, conversation_id: usize, output: String) {
+        self.dirty = true;
+        let Some(conversation) = self.conversation_by_id(conversation_id) else { return };
+        conversation.messages.push(Message::tool_result(output));
+        self.run_next_tool(conversation_id);
+    }
+
+    /// Sends the prompt again with the tool calls and results that followed it.
+    fn continue_after_tools(&mut self, conversation_id: usize) {
+        let (backend, events) = (self.backend.clone(), self.events.clone());
+        let tools = self.offered_tools();
+        let model_config = self.model_config.clone();
+        let Some(conversation) = self.conversation_by_id(conversation_id) else { return };
+        let Some(prompt_index) = conversation.messages.iter().rposition(Message::is_user) else { return };
+        let prompt = &conversation.messages[prompt_index];
+        let request = ChatRequest {
+            model: conversation.model.clone(),
+            prompt: prompt.content.clone(),
+            images: prompt.images.clone(),
+            config: conversation.overrides.apply(&model_config),
+            tools,
+            history: conversation.history(prompt_index),
+            // `!` commands that finished mid-reply aren't part of the exchange with the model
+            turns: conversation.messages[prompt_index + 1..].iter().filter(|m| !m.is_command_output()).cloned().collect(),
+            format: conversation.reply_format.clone(),
+            raw: false,
+            suffix: None,
+        };
+        conversation.stream_started = Some(Instant::now());
+        conversation.stream_tokens = 0;
+        conversation.resendable = false;
+        conversation.messages.push(Message { json: conversation.reply_format.is_some(), ..Message::assistant(&conversation.model) });
+        let message_index = conversation.messages.len() - 1;
+        conversation.stream = Some(tokio::spawn(stream_reply(events, backend, conversation_id, message_index, request)).abort_handle());
+        self.status_message = "Sending tool results".to_string();
+    }
+
+    /// The tab a background stream belongs to, if it is still open.
+    pub(crate) fn conversation_by_id(&mut self, id: usize) -> Option<&mut Conversation> {
+        self.conversations.iter_mut().find(|c| c.id == id)
+    }
+
+    /// Puts the last prompt of a conversation whose reply failed back in its input and sends
+    /// it again, dropping whatever part of the reply arrived.
+    pub(crate) fn retry_reply(&mut self, conversation_id: usize) {
+        let Some(index) = self.conversations.iter().position(|c| c.id == conversation_id) else { return };
+        self.active_conversation = index;
+        let conversation = self.conversation_mut();
+        if conversation.is_thinking {
+            return;
+        }
+        if conversation.messages.last().is_some_and(|m| m.role == "assistant" && m.tool_calls.is_empty()) {
+            conversation.messages.pop();
+        }
+        if !conversation.messages.last().is_some_and(Message::is_user) {
+            return;
+        }
+        let Some(prompt) = conversation.messages.pop() else { return };
+        conversation.input = prompt.content;
+        conversation.pending_images = prompt.images;
+        self.start_message_stream();
+    }
+
+    pub fn toggle_output(&mut self) {
+        let conversation = self.conversation_mut();
+        conversation.expand_output = !conversation.expand_output;
+        self.status_message = format!("Command and tool output {}", if self.conversation().expand_output { "expanded" } else { "collapsed" });
+    }
+
+    /// Expands the selected long reply, or the last one, or cuts it short again.
+    pub fn toggle_reply(&mut self) {
+        let Some(message) = self.target_reply().map(|index| &mut self.conversation_mut().messages[index]) else {
+            self.status_message = "No reply to expand".to_string();
+            return;
+        };
+        message.expanded = !message.expanded;
+        self.status_message = format!("Reply {}", if message.expanded { "expanded" } else { "collapsed" });
+    }
+
+    /// Folds code block `number` (from 1) of the selected reply, or the last one, or unfolds it.
+    /// Without a number every block is folded, or unfolded when all of them already are.
+    pub fn toggle_code_fold(&mut self, number: Option<usize>) {
+        let Some(message) = self.target_reply().map(|index| &mut self.conversation_mut().messages[index]) else {
+            self.status_message = "No reply to fold".to_string();
+            return;
+        };
+        let count = text::code_blocks(&message.content).len();
+        let status = match number {
+            _ if count == 0 => Err("The reply has no code blocks".to_string()),
+            Some(number) if number == 0 || number > count => Err(format!("The reply has {} code block(s)", count)),
+            Some(number) => match message.folded.iter().position(|&block| block == number - 1) {
+                Some(i) => {
+                    message.folded.remove(i);
+                    Ok(format!("Unfolded code block {}", number))
+                }
+                None => {
+                    message.folded.push(number - 1);
+                    Ok(format!("Folded code block {}", number))
+                }
+            },
+            None if message.folded.len() < count => {
+                message.folded = (0..count).collect();
+                Ok(format!("Folded {} code block(s)", count))
+            }
+            None => {
+                message.folded.clear();
+                Ok(format!("Unfolded {} code block(s)", count))
+            }
+        };
+        match status {
+            Ok(status) => self.status_message = status,
+            Err(e) => self.set_error(e),
+        }
+    }
+
+    /// The selected message if it is a reply, otherwise the last reply.
+    pub(crate) fn target_reply(&self) -> Option<usize> {
+        let conversation = self.conversation();
+        let index = conversation.selected_message.filter(|&i| conversation.messages.get(i).is_some_and(|m| m.role == "assistant"));
+        index.or_else(|| conversation.messages.iter().rposition(|m| m.role == "assistant"))
+    }
+
+    /// The name shown before a message, from the configured display names.
+    pub fn message_label(&self, message: &Message) -> String {
+        if message.is_user() {
+            return self.app_config.user_name.clone();
+        }
+        if message.is_tool_result() {
+            return "Tool result".to_string();
+        }
+        if message.is_command_output() {
+            return "Command".to_string();
+        }
+        if message.is_summary() {
+            return "Earlier messages (summarized)".to_string();
+        }
+        let model = message.model.as_deref().unwrap_or(&self.conversation().model);
+        match (&self.app_config.assistant_name, self.app_config.show_model_in_label) {
+            (Some(name), true) => format!("{} ({})", name, model),
+            (Some(name), false) => name.clone(),
+            (None, true) => model.to_string(),
+            // Short name: `llama3.2:latest` -> `llama3.2`
+            (None, false) => model.split(':').next().unwrap_or(model).to_string(),
+        }
+    }
+
+    /// The active conversation as Markdown: a heading naming who wrote each message, then the
+    /// message. Command and tool output is fenced as it is.
+    pub fn transcript(&self) -> String {
+        let mut out = String::new();
+        for message in &self.conversation().messages {
+            out.push_str(&format!("## {}\n\n", self.message_label(message)));
+            match message.is_output() {
+                true => out.push_str(&format!("```\n{}\n```\n", message.content.trim_end())),
+                false if !message.content.trim().is_empty() => out.push_str(&format!("{}\n", message.content.trim_end())),
+                false => {}
+            }
+            for call in &message.tool_calls {
+                out.push_str(&format!("\nTool call: `{}`\n", call.summary()));
+            }
+            if !message.sources.is_empty() {
+                out.push_str(&format!("\nSources: {}\n", message.sources.join(", ")));
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Streams a reply for `message_index` of the conversation to the app as events; the app
+/// appends each chunk and settles the reply when the stream ends.
+async fn stream_reply(events: Events, backend: Arc<dyn ChatBackend>, conversation_id: usize, message_index: usize, request: ChatRequest) {
+    let model = request.model.clone();
+    tracing::info!(model = %model, conversation_id, turns = request.turns.len(), tools = request.tools.len(), "requesting reply");
+    let mut stream = match backend.stream_chat(request).await {
+        Ok(stream) => stream,
+        Err(e) => return abandon(&events, conversation_id, AppError::from_backend(e, &model)),
+    };
+    let mut batches = 0;
+    // Fast models send a token at a time; gather them so the app takes one event per batch
+    let mut batch = Chunk::default();
+    let mut flush_at = None;
+    let mut error = None;
+    loop {
+        let next = match flush_at {
+            Some(at) => match tokio::time::timeout_at(at, stream.next()).await {
+                Ok(next) => next,
+                Err(_) => {
+                    flush_at = None;
+                    batches += 1;
+                    if !events.send(AppEvent::Chunk { conversation_id, message_index, chunk: std::mem::take(&mut batch) }) {
+                        return;
+                    }
+                    continue;
+                }
+            },
+            None => stream.next().await,
+        };
+        match next {
+            Some(Ok(chunk)) => {
+                batch.merge(chunk);
+                flush_at.get_or_insert_with(|| tokio::time::Instant::now() + STREAM_BATCH);
+            }
+            Some(Err(e)) => {
+                error = Some(AppError::from_backend(e, &model));
+                break;
+            }
+            None => break,
+        }
+    }
+    if flush_at.is_some() {
+        batches += 1;
+        events.send(AppEvent::Chunk { conversation_id, message_index, chunk: batch });
+    }
+    tracing::debug!(conversation_id, batches, failed = error.is_some(), "reply stream ended");
+    events.send(AppEvent::StreamEnded { conversation_id, message_index, error });
+}
+
+/// Fetches `url` for `/url` and streams the answer to the task in `request.prompt`. A page too
+/// long for the context window is read in parts first, each condensed to notes for the task.
+async fn answer_from_url(events: Events, backend: Arc<dyn ChatBackend>, conversation_id: usize, message_index: usize, mut request: ChatRequest, url: String) {
+    let task = std::mem::take(&mut request.prompt);
+    let prompt = async {
+        let page = web::fetch(&url).await?;
+        if page.text.trim().is_empty() {
+            return Err(anyhow!("{} has no readable text", url));
+        }
+        let source = match &page.title {
+            Some(title) => format!("{} ({})", title, url),
+            None => url.clone(),
+        };
+        // Half the context for the page leaves room for the instructions and the reply
+        let budget = (request.config.num_ctx as usize * CHARS_PER_TOKEN / 2).max(MIN_CONTENT_PART);
+        let mut parts = web::chunks(&page.text, budget);
+        let truncated = parts.len() > MAX_URL_PARTS;
+        parts.truncate(MAX_URL_PARTS);
+        let content = if parts.len() == 1 {
+            parts.remove(0)
+        } else {
+            let mut notes = Vec::new();
+            for (i, part) in parts.iter().enumerate() {
+                let status = format!("Reading part {} of {} of {}", i + 1, parts.len(), url);
+                events.update(move |app| app.status_message = status);
+                let prompt = format!("{}\n\nThis is part {} of {} of the page {}. Write concise notes on everything in it that bears on that task.\n\n---\n{}\n---", task, i + 1, parts.len(), source, part);
+                let part_notes = backend::collect(backend.as_ref(), ChatRequest { prompt, ..request.clone() }).await?;
+                notes.push(format!("Notes on part {}:\n{}", i + 1, part_notes.trim()));
+            }
+            notes.join("\n\n")
+        };
+        let cut = if truncated { "\n\n(The rest of the page was too long to read.)" } else { "" };
+        Ok(format!("{}\n\nSource: {}\n\n---\n{}{}\n---", task, source, content, cut))
+    }
+    .await;
+    match prompt {
+        Ok(prompt) => {
+            let status = format!("Reading {}", url);
+            events.update(move |app| app.status_message = status);
+            stream_reply(events, backend, conversation_id, message_index, ChatRequest { prompt, ..request }).await;
+        }
+        Err(e) => abandon(&events, conversation_id, AppError::Other(e.context(format!("Cannot read {}", url)))),
+    }
+}
+
+/// Puts the diff after the task, cut short if it would crowd the reply out of the context
+/// window, and streams the reply.
+async fn answer_about_diff(events: Events, backend: Arc<dyn ChatBackend>, conversation_id: usize, message_index: usize, request: ChatRequest, kind: Diff) {
+    let diff = match tokio::task::spawn_blocking(move || git::diff(kind)).await {
+        Ok(Ok(diff)) => diff,
+        Ok(Err(e)) => return abandon(&events, conversation_id, AppError::Other(e.context("Cannot read the diff"))),
+        Err(e) => return abandon(&events, conversation_id, AppError::Other(anyhow!("Cannot read the diff: {}", e))),
+    };
+    // Half the context for the diff leaves room for the instructions and the reply
+    let budget = (request.config.num_ctx as usize * CHARS_PER_TOKEN / 2).max(MIN_CONTENT_PART);
+    let mut parts = web::chunks(&diff, budget);
+    let cut = if parts.len() > 1 { "\n(The rest of the diff was too long to include.)" } else { "" };
+    let diff = if parts.is_empty() { String::new() } else { parts.swap_remove(0) };
+    let prompt = format!("{}\n\n```diff\n{}\n```{}", request.prompt, diff, cut);
+    events.update(|app| app.status_message = "Reading the diff".to_string());
+    stream_reply(events, backend, conversation_id, message_index, ChatRequest { prompt, ..request }).await;
+}
+
+/// Puts the knowledge base passages closest to the question in front of it, records them as
+/// the reply's sources, and streams the reply.
+async fn answer_with_knowledge(events: Events, backend: Arc<dyn ChatBackend>, conversation_id: usize, message_index: usize, mut request: ChatRequest, knowledge: Arc<KnowledgeBase>) {
+    let query = match backend.embeddings(knowledge.model.clone(), vec![request.prompt.clone()]).await {
+        Ok(mut vectors) if !vectors.is_empty() => vectors.swap_remove(0),
+        Ok(_) => return abandon(&events, conversation_id, AppError::Other(anyhow!("Knowledge base lookup returned nothing"))),
+        Err(e) => return abandon(&events, conversation_id, AppError::from_backend(e.context("Knowledge base lookup failed"), &knowledge.model)),
+    };
+    let passages = knowledge.search(&query, knowledge::TOP_K);
+    request.prompt = knowledge::augment(&request.prompt, &passages);
+    let sources: Vec<String> = passages.iter().map(|p| p.citation()).collect();
+    events.update(move |app| {
+        if let Some(message) = app.conversation_by_id(conversation_id).and_then(|c| c.messages.get_mut(message_index)) {
+            message.sources = sources;
+        }
+    });
+    stream_reply(events, backend, conversation_id, message_index, request).await;
+}
+
+/// Has the app drop a reply that failed before any of it arrived.
+fn abandon(events: &Events, conversation_id: usize, error: AppError) {
+    events.update(move |app| app.abandon_reply(conversation_id, error));
+}
@@ -0,0 +1,258 @@
+use anyhow::{bail, Result};
+use std::fmt;
+use std::io::{self, ErrorKind, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use tokio_stream::StreamExt;
+
+use crate::app::App;
+use crate::backend::ChatRequest;
+use crate::batch;
+use crate::models::ConfigField;
+use crate::sweep;
+
+pub const USAGE: &str = "Usage: llama-term [--prompt <text>] [--oneshot] [--model <name>] [--print-on-exit]
+       llama-term ask [--model <name>] <question>
+       llama-term batch [--model <name>]... [--output <file>] <prompts file>
+       llama-term sweep (--temperature <values> | --top-p <values>) [--model <name>]
+                        [--output <file>] <prompt>
+
+Commands:
+  ask <question>       Print the answer to stdout and exit, without opening the interface
+  batch <file>         Send every prompt in the file (one per line, or a .json array) to
+                       each --model in turn (a comma-separated list works too) and report
+                       the replies with their timings and token counts
+  sweep <prompt>       Send the prompt once for each value of --temperature or --top-p
+                       (comma-separated, e.g. 0.2,0.7,1.2), the other settings as saved,
+                       and compare the replies in a grid with their timings
+
+Options:
+  -p, --prompt <text>  Send this message as soon as the app starts
+      --oneshot        Print the reply to stdout and exit instead of opening the interface
+      --print-on-exit  Print the conversation as Markdown to stdout after quitting; the
+                       interface is drawn on the terminal even when stdout is redirected
+  -m, --model <name>   Use this model instead of the saved one
+  -o, --output <file>  Write the batch or sweep report here, as Markdown for .md and
+                       JSON otherwise, instead of printing it as Markdown
+  -t, --temperature <values>, --top-p <values>
+                       The values of the setting for sweep to try
+  -h, --help           Show this help
+  -V, --version        Show the version
+
+Input piped on stdin is attached to the message, e.g.
+  git diff | llama-term --prompt \"review this\"
+  git diff | llama-term ask \"write a commit message\"
+
+Exit status is 0 on success, 1 if the reply (or any batch or sweep reply) failed, and 2
+for a usage error.";
+
+/// A command line that can't be acted on; reported with the usage text and exit status 2.
+#[derive(Debug)]
+pub struct UsageError(pub String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+/// What the command line asked for.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Args {
+    pub prompt: Option<String>,
+    pub oneshot: bool,
+    /// Print the conversation to stdout after the interface closes.
+    pub print_on_exit: bool,
+    /// Set by `ask`: answer without the interface. The question words are in `prompt`.
+    pub ask: bool,
+    pub model: Option<String>,
+    /// Set by `batch`: the file of prompts to run.
+    pub batch: Option<PathBuf>,
+    /// Every `--model` given, in order, for `batch` to run against.
+    pub models: Vec<String>,
+    pub output: Option<PathBuf>,
+    /// Set by `sweep`: the setting to vary, and its values to try. The prompt is in `prompt`.
+    pub sweep: Option<ConfigField>,
+    pub sweep_values: Vec<f64>,
+    pub help: bool,
+    pub version: bool,
+}
+
+impl Args {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Args, UsageError> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter().peekable();
+        let command = args.next_if(|arg| arg == "ask" || arg == "batch" || arg == "sweep");
+        parsed.ask = command.as_deref() == Some("ask");
+        let batch = command.as_deref() == Some("batch");
+        let sweep = command.as_deref() == Some("sweep");
+        let mut words = Vec::new();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().ok_or_else(|| UsageError(format!("{} needs a value", name)));
+            match arg.as_str() {
+                "-p" | "--prompt" if command.is_none() => parsed.prompt = Some(value(&arg)?),
+                "-m" | "--model" => parsed.add_model(value(&arg)?),
+                "-o" | "--output" if batch || sweep => parsed.output = Some(value(&arg)?.into()),
+                "-t" | "--temperature" | "--top-p" if sweep => {
+                    let setting = if arg == "--top-p" { ConfigField::TopP } else { ConfigField::Temperature };
+                    if parsed.sweep.is_some_and(|swept| swept != setting) {
+                        return Err(UsageError("sweep varies one setting at a time".to_string()));
+                    }
+                    parsed.sweep = Some(setting);
+                    parsed.sweep_values = sweep::parse_values(setting, &value(&arg)?).map_err(|e| UsageError(e.to_string()))?;
+                }
+                "--oneshot" if command.is_none() => parsed.oneshot = true,
+                "--print-on-exit" if command.is_none() => parsed.print_on_exit = true,
+                "-h" | "--help" => parsed.help = true,
+                "-V" | "--version" => parsed.version = true,
+                _ if (parsed.ask || sweep) && !arg.starts_with('-') => words.push(arg),
+                _ if batch && parsed.batch.is_none() && !arg.starts_with('-') => parsed.batch = Some(arg.into()),
+                _ => match (arg.strip_prefix("--prompt="), arg.strip_prefix("--model=")) {
+                    (Some(prompt), _) if command.is_none() => parsed.prompt = Some(prompt.to_string()),
+                    (_, Some(model)) => parsed.add_model(model.to_string()),
+                    _ => return Err(UsageError(format!("Unknown argument '{}'", arg))),
+                },
+            }
+        }
+        // `ask why is the sky blue` works without quotes
+        if !words.is_empty() {
+            parsed.prompt = Some(words.join(" "));
+        }
+        if batch && parsed.batch.is_none() && !parsed.help {
+            return Err(UsageError("batch needs a file of prompts".to_string()));
+        }
+        if sweep && parsed.sweep.is_none() && !parsed.help {
+            return Err(UsageError("sweep needs the values to try, e.g. --temperature 0.2,0.7,1.2".to_string()));
+        }
+        Ok(parsed)
+    }
+
+    /// Records a `--model`, which may list several separated by commas.
+    fn add_model(&mut self, value: String) {
+        self.models.extend(value.split(',').map(str::trim).filter(|model| !model.is_empty()).map(String::from));
+        self.model = self.models.last().cloned();
+    }
+
+    /// Whether to print a reply and exit rather than open the interface.
+    pub fn headless(&self) -> bool {
+        self.ask || self.oneshot
+    }
+}
+
+/// Everything piped in on stdin, or `None` when stdin is the terminal. It has to be read
+/// before raw mode, after which the terminal is read through `/dev/tty`.
+pub fn piped_stdin() -> Result<Option<String>> {
+    if io::stdin().is_terminal() {
+        return Ok(None);
+    }
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+    Ok(if input.trim().is_empty() { None } else { Some(input) })
+}
+
+/// The message to start with: the prompt, followed by the piped input in a code block.
+pub fn initial_message(prompt: Option<&str>, piped: Option<&str>) -> Option<String> {
+    let piped = piped.map(|input| format!("```\n{}\n```", input.trim_end()));
+    match (prompt, piped) {
+        (Some(prompt), Some(piped)) => Some(format!("{}\n\n{}", prompt, piped)),
+        (Some(prompt), None) => Some(prompt.to_string()),
+        (None, piped) => piped,
+    }
+}
+
+/// Sends `message` with the app's host, model, and settings, and prints the reply to stdout
+/// as it streams. A reader that stops early, such as `head`, ends it quietly.
+pub async fn oneshot(app: &App, message: String) -> Result<()> {
+    match print_reply(app, message).await {
+        Err(e) if e.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == ErrorKind::BrokenPipe) => Ok(()),
+        result => result,
+    }
+}
+
+async fn print_reply(app: &App, message: String) -> Result<()> {
+    let request = ChatRequest {
+        model: app.conversation().model.clone(),
+        prompt: message,
+        images: Vec::new(),
+        config: app.effective_config(),
+        tools: Vec::new(),
+        history: Vec::new(),
+        turns: Vec::new(),
+        format: None,
+        raw: false,
+        suffix: None,
+    };
+    let mut stream = app.backend.stream_chat(request).await?;
+    let mut stdout = io::stdout();
+    let mut ends_with_newline = true;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        if chunk.text.is_empty() {
+            continue;
+        }
+        stdout.write_all(chunk.text.as_bytes())?;
+        stdout.flush()?;
+        ends_with_newline = chunk.text.ends_with('\n');
+    }
+    if !ends_with_newline {
+        writeln!(stdout)?;
+    }
+    Ok(())
+}
+
+/// Runs every prompt in `path` against each of `models`, or the saved model when none are
+/// given, with progress on stderr. The report goes to `output`, or to stdout as Markdown.
+pub async fn batch(app: &App, path: &Path, models: &[String], output: Option<&Path>) -> Result<()> {
+    let prompts = batch::load_prompts(path)?;
+    let models = if models.is_empty() { vec![app.conversation().model.clone()] } else { models.to_vec() };
+    let total = prompts.len() * models.len();
+    let mut done = 0;
+    let report = batch::run(app.backend.as_ref(), &models, &prompts, &app.effective_config(), |result| {
+        done += 1;
+        let outcome = match &result.error {
+            Some(error) => format!("failed: {}", error),
+            None => format!("{:.1}s, {} tokens", result.seconds, result.reply_tokens),
+        };
+        eprintln!("[{}/{}] {}: {}", done, total, result.model, outcome);
+    })
+    .await;
+    match output {
+        Some(output) => {
+            report.save(output)?;
+            eprintln!("Report written to {}", output.display());
+        }
+        None => print!("{}", report.to_markdown()),
+    }
+    if report.failed() > 0 {
+        bail!("{} of {} replies failed", report.failed(), total);
+    }
+    Ok(())
+}
+
+/// Sends `prompt` to `model`, or the saved model, once for each of `values` of `setting`, with
+/// progress on stderr. The report goes to `output`, or to stdout as Markdown.
+pub async fn sweep(app: &App, prompt: &str, model: Option<&str>, setting: ConfigField, values: &[f64], output: Option<&Path>) -> Result<()> {
+    let model = model.map_or_else(|| app.conversation().model.clone(), String::from);
+    let mut done = 0;
+    let report = sweep::run(app.backend.as_ref(), &model, prompt, &app.effective_config(), setting, values, |sweep| {
+        done += 1;
+        let outcome = match &sweep.result.error {
+            Some(error) => format!("failed: {}", error),
+            None => format!("{:.1}s, {} tokens", sweep.result.seconds, sweep.result.reply_tokens),
+        };
+        eprintln!("[{}/{}] {} {}: {}", done, values.len(), setting.label(), sweep.value, outcome);
+    })
+    .await;
+    match output {
+        Some(output) => {
+            report.save(output)?;
+            eprintln!("Report written to {}", output.display());
+        }
+        None => print!("{}", report.to_markdown()),
+    }
+    if report.failed() > 0 {
+        bail!("{} of {} replies failed", report.failed(), values.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,258 @@
+use anyhow::{anyhow, bail, Result};
+use std::path::PathBuf;
+
+use crate::config::{expand_path, MIN_CHAT_WIDTH};
+use crate::raw;
+
+/// A slash command typed into the chat input, e.g. `/temp 0.2`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Override the temperature for the current session; no argument resets it.
+    Temp(Option<f32>),
+    /// Override the system prompt for the current session; no argument resets it.
+    System(Option<String>),
+    /// Put `/system` and the system prompt in force into the input, to edit and send.
+    EditSystem,
+    /// Give the current conversation a persona; `off` drops it and no argument lists them.
+    Persona(Option<String>),
+    /// Start the current conversation from a template; no argument lists them.
+    Template(Option<String>),
+    /// Apply a named sampling preset; no argument lists the available presets.
+    Preset(Option<String>),
+    /// Limit the chat column to this many characters; no argument uses the full width.
+    Width(Option<u16>),
+    /// Scroll to message N, counting from 1; typed as `:N` or `/jump N`.
+    Jump(usize),
+    /// Attach an image to the next message; no argument drops the attached images.
+    Image(Option<PathBuf>),
+    /// Turn tool calling on or off; no argument lists the tools.
+    Tools(Option<bool>),
+    /// Ask for replies as JSON; no argument turns it on or off.
+    Json(Option<JsonCommand>),
+    /// Send prompts through a template of our own with `raw`; no argument turns it on or off.
+    Raw(Option<RawCommand>),
+    /// Have the model fill in the gap at `{{fill}}` in each prompt; no argument turns it on or off.
+    Fim(Option<FimCommand>),
+    /// Show the knowledge base, or change it as described by `KnowledgeCommand`.
+    Knowledge(Option<KnowledgeCommand>),
+    /// Fetch a web page and have the model summarize it, or answer the question that follows the link.
+    Url { url: String, question: Option<String> },
+    /// Run code block N of the last reply, counting from 1; no argument runs the last one
+    /// that can be run.
+    Run(Option<usize>),
+    /// Fold or unfold code block N of the selected (or last) reply; no argument folds or
+    /// unfolds all of them.
+    Fold(Option<usize>),
+    /// Compare the selected (or last) regenerated reply with the attempt before it.
+    Compare,
+    /// Ask for N replies to the last prompt, 2 by default, to choose from with `<` and `>`.
+    Sample(usize),
+    /// Run a shell command and add its output to the input, to send along with a question.
+    RunInsert(String),
+    /// Have the model write a commit message for the staged changes, or act on the one it wrote.
+    CommitMessage(Option<CommitAction>),
+    /// Have the model review the changes since the last commit, paying attention to the
+    /// optional focus.
+    Review(Option<String>),
+    /// Write the current conversation to a standalone HTML page; no argument names the file
+    /// after the time.
+    Export(Option<PathBuf>),
+    /// Print the conversation to stdout after quitting, as `--print-on-exit` does.
+    Hardcopy,
+    /// Write the app and model config to a single file.
+    ExportConfig(PathBuf),
+    /// Replace the app and model config with the contents of an exported file.
+    ImportConfig(PathBuf),
+    /// Restore every setting to its default.
+    ResetConfig,
+    /// Show statistics for the current conversation.
+    Stats,
+    /// Drop the prompts waiting to be sent after the current reply.
+    ClearQueue,
+}
+
+/// What `/commitmsg` does with the commit message in the last reply.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommitAction {
+    Copy,
+    /// Commit the staged changes with it.
+    Commit,
+}
+
+/// What `/json` does.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonCommand {
+    Enable(bool),
+    /// Ask for replies following the JSON schema in this file, and check them against it.
+    Schema(PathBuf),
+}
+
+/// What `/raw` does.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RawCommand {
+    Enable(bool),
+    /// Turn raw mode on with this template rather than the one in the config.
+    Template(String),
+}
+
+/// What `/fim` does.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FimCommand {
+    Enable(bool),
+    /// Put a file in the input with the gap at lines `start` to `end`, or before line `start`
+    /// when `end` is less.
+    Region { path: PathBuf, start: usize, end: usize },
+}
+
+/// What `/kb` does to the knowledge base.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KnowledgeCommand {
+    /// Index a directory, replacing the current knowledge base.
+    Index(PathBuf),
+    /// Turn retrieval on or off.
+    Enable(bool),
+    Clear,
+}
+
+impl Command {
+    pub fn parse(input: &str) -> Result<Command> {
+        let input = input.trim();
+        let input = input.strip_prefix('/').unwrap_or(input);
+        let (name, arg) = match input.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, arg.trim()),
+            None => (input, ""),
+        };
+        let arg = if arg.is_empty() { None } else { Some(arg) };
+
+        match name {
+            "temp" | "temperature" => {
+                let value = arg
+                    .map(|a| a.parse::<f32>().map_err(|_| anyhow!("/temp expects a number, got '{}'", a)))
+                    .transpose()?;
+                Ok(Command::Temp(value.map(|v| v.clamp(0.0, 2.0))))
+            }
+            "system" if arg == Some("edit") => Ok(Command::EditSystem),
+            "system" => Ok(Command::System(arg.map(String::from))),
+            "preset" => Ok(Command::Preset(arg.map(String::from))),
+            "persona" => Ok(Command::Persona(arg.map(String::from))),
+            "template" => Ok(Command::Template(arg.map(String::from))),
+            "width" => {
+                let value = arg
+                    .map(|a| a.parse::<u16>().map_err(|_| anyhow!("/width expects a column count, got '{}'", a)))
+                    .transpose()?;
+                Ok(Command::Width(value.map(|v| v.max(MIN_CHAT_WIDTH))))
+            }
+            "image" | "img" => Ok(Command::Image(arg.map(expand_path))),
+            "tools" => match arg {
+                None => Ok(Command::Tools(None)),
+                Some("on") => Ok(Command::Tools(Some(true))),
+                Some("off") => Ok(Command::Tools(Some(false))),
+                Some(other) => bail!("/tools expects on or off, got '{}'", other),
+            },
+            "json" => match arg.map(|a| a.split_once(char::is_whitespace).map_or((a, ""), |(sub, rest)| (sub, rest.trim()))) {
+                None => Ok(Command::Json(None)),
+                Some(("on", "")) => Ok(Command::Json(Some(JsonCommand::Enable(true)))),
+                Some(("off", "")) => Ok(Command::Json(Some(JsonCommand::Enable(false)))),
+                Some(("schema", path)) if !path.is_empty() => Ok(Command::Json(Some(JsonCommand::Schema(expand_path(path))))),
+                _ => bail!("Usage: /json | /json on | /json off | /json schema <file>"),
+            },
+            "raw" => match arg {
+                None => Ok(Command::Raw(None)),
+                Some("on") => Ok(Command::Raw(Some(RawCommand::Enable(true)))),
+                Some("off") => Ok(Command::Raw(Some(RawCommand::Enable(false)))),
+                Some(template) => Ok(Command::Raw(Some(RawCommand::Template(raw::parse_template(template)?)))),
+            },
+            "fim" => match arg {
+                None => Ok(Command::Fim(None)),
+                Some("on") => Ok(Command::Fim(Some(FimCommand::Enable(true)))),
+                Some("off") => Ok(Command::Fim(Some(FimCommand::Enable(false)))),
+                Some(region) => {
+                    let usage = || anyhow!("Usage: /fim | /fim on | /fim off | /fim <file>:<line> | /fim <file>:<first>-<last>");
+                    let (path, lines) = region.rsplit_once(':').ok_or_else(usage)?;
+                    let (start, end) = match lines.split_once('-') {
+                        Some((start, end)) => (start.trim().parse().map_err(|_| usage())?, end.trim().parse().map_err(|_| usage())?),
+                        None => {
+                            let line: usize = lines.trim().parse().map_err(|_| usage())?;
+                            (line, line.saturating_sub(1))
+                        }
+                    };
+                    if start == 0 || (lines.contains('-') && end < start) {
+                        bail!("/fim lines count from 1, first to last");
+                    }
+                    Ok(Command::Fim(Some(FimCommand::Region { path: expand_path(path.trim()), start, end })))
+                }
+            },
+            "kb" | "knowledge" => {
+                let (sub, path) = match arg.and_then(|a| a.split_once(char::is_whitespace)) {
+                    Some((sub, path)) => (Some(sub), Some(path.trim())),
+                    None => (arg, None),
+                };
+                match (sub, path) {
+                    (None, _) => Ok(Command::Knowledge(None)),
+                    (Some("index"), Some(path)) => Ok(Command::Knowledge(Some(KnowledgeCommand::Index(expand_path(path))))),
+                    (Some("on"), None) => Ok(Command::Knowledge(Some(KnowledgeCommand::Enable(true)))),
+                    (Some("off"), None) => Ok(Command::Knowledge(Some(KnowledgeCommand::Enable(false)))),
+                    (Some("clear"), None) => Ok(Command::Knowledge(Some(KnowledgeCommand::Clear))),
+                    _ => bail!("Usage: /kb index <directory> | /kb on | /kb off | /kb clear"),
+                }
+            }
+            "url" => {
+                let arg = arg.ok_or_else(|| anyhow!("Usage: /url <link> [question]"))?;
+                let (link, question) = match arg.split_once(char::is_whitespace) {
+                    Some((link, question)) => (link, Some(question.trim().to_string())),
+                    None => (arg, None),
+                };
+                let url = if link.contains("://") { link.to_string() } else { format!("https://{}", link) };
+                if !url.starts_with("http://") && !url.starts_with("https://") {
+                    bail!("/url only fetches http and https links");
+                }
+                Ok(Command::Url { url, question })
+            }
+            "run" => {
+                let number = arg.map(|n| n.parse::<usize>().map_err(|_| anyhow!("/run expects a code block number, got '{}'", n))).transpose()?;
+                Ok(Command::Run(number))
+            }
+            "fold" => {
+                let number = arg.map(|n| n.parse::<usize>().map_err(|_| anyhow!("/fold expects a code block number, got '{}'", n))).transpose()?;
+                Ok(Command::Fold(number))
+            }
+            "compare" => Ok(Command::Compare),
+            "sample" => {
+                let count = arg.map(|n| n.parse::<usize>().ok().filter(|n| (1..=10).contains(n)).ok_or_else(|| anyhow!("/sample expects a number of replies from 1 to 10, got '{}'", n))).transpose()?;
+                Ok(Command::Sample(count.unwrap_or(2)))
+            }
+            "run-insert" => Ok(Command::RunInsert(arg.ok_or_else(|| anyhow!("Usage: /run-insert <command>"))?.to_string())),
+            "commitmsg" => match arg {
+                None => Ok(Command::CommitMessage(None)),
+                Some("copy") => Ok(Command::CommitMessage(Some(CommitAction::Copy))),
+                Some("commit") => Ok(Command::CommitMessage(Some(CommitAction::Commit))),
+                Some(other) => bail!("/commitmsg expects copy or commit, got '{}'", other),
+            },
+            "review" => Ok(Command::Review(arg.map(String::from))),
+            "stats" => Ok(Command::Stats),
+            "export" => Ok(Command::Export(arg.map(expand_path))),
+            "hardcopy" | "ha" => Ok(Command::Hardcopy),
+            "queue" if arg == Some("clear") => Ok(Command::ClearQueue),
+            "queue" => bail!("Usage: /queue clear"),
+            "jump" => {
+                let number = arg.ok_or_else(|| anyhow!("Usage: /jump <message number>"))?;
+                Ok(Command::Jump(number.parse().map_err(|_| anyhow!("/jump expects a message number, got '{}'", number))?))
+            }
+            "config" => {
+                let (sub, path) = match arg.and_then(|a| a.split_once(char::is_whitespace)) {
+                    Some((sub, path)) => (sub, Some(path.trim())),
+                    None => (arg.unwrap_or(""), None),
+                };
+                match (sub, path) {
+                    ("export", Some(path)) => Ok(Command::ExportConfig(expand_path(path))),
+                    ("import", Some(path)) => Ok(Command::ImportConfig(expand_path(path))),
+                    ("reset", None) => Ok(Command::ResetConfig),
+                    _ => bail!("Usage: /config export <file> | /config import <file> | /config reset"),
+                }
+            }
+            _ if arg.is_none() && name.parse::<usize>().is_ok() => Ok(Command::Jump(name.parse()?)),
+            "" => bail!("Empty command"),
+            _ => bail!("Unknown command: /{}", name),
+        }
+    }
+}
@@ -0,0 +1,961 @@
+use anyhow::{Context, Result};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use ollama_rs::{
+    headers::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION},
+    Ollama,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc},
+};
+
+use crate::app::{App, AppMode};
+use crate::backend::{AnthropicBackend, ChatBackend, OllamaBackend};
+use crate::chat::SessionOverrides;
+use crate::error::Retry;
+use crate::keymap::{Keymap, KEYMAP_FILE};
+use crate::migrate::{self, Versioned};
+use crate::models::ModelConfig;
+use crate::raw;
+use crate::text;
+use crate::toast::Severity;
+use crate::theme::ThemeName;
+
+pub const APP_CONFIG_FILE: &str = "config.json";
+pub const MODEL_CONFIG_FILE: &str = "model_config.json";
+/// Narrowest chat column `chat_width` may ask for.
+pub const MIN_CHAT_WIDTH: u16 = 20;
+/// Input pane height range in rows, borders included.
+pub const INPUT_HEIGHT_RANGE: (u16, u16) = (3, 20);
+
+/// Where settings, chats, and logs are kept: `~/.ollama_tui`.
+pub fn base_dir() -> PathBuf {
+    dirs::home_dir().unwrap_or_else(|| PathBuf::from(".")).join(".ollama_tui")
+}
+
+/// Which API a host speaks.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendKind {
+    #[default]
+    Ollama,
+    /// The Anthropic Messages API, for Claude models.
+    Anthropic,
+}
+
+impl BackendKind {
+    fn is_ollama(&self) -> bool {
+        *self == BackendKind::Ollama
+    }
+}
+
+/// A named Ollama endpoint, e.g. `workstation = http://10.0.0.5:11434`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HostConfig {
+    pub name: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "BackendKind::is_ollama")]
+    pub kind: BackendKind,
+    /// Sent as `Authorization: Bearer <key>` for hosts behind an authenticating proxy, or as
+    /// `x-api-key` to Anthropic, which falls back to `ANTHROPIC_API_KEY`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    /// Extra HTTP headers attached to every request to this host.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    /// Skip certificate verification, for `https://` hosts with self-signed certificates.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub accept_invalid_certs: bool,
+    /// PEM bundle of additional root certificates to trust for this host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<PathBuf>,
+}
+
+impl HostConfig {
+    pub fn new(name: &str, url: &str) -> Self {
+        Self { name: name.to_string(), url: url.to_string(), kind: BackendKind::Ollama, api_key: None, headers: BTreeMap::new(), accept_invalid_certs: false, ca_cert: None }
+    }
+
+    pub fn header_map(&self) -> Result<HeaderMap> {
+        let mut map = HeaderMap::new();
+        if let Some(key) = &self.api_key {
+            let value = HeaderValue::from_str(&format!("Bearer {}", key)).context("Invalid api_key")?;
+            map.insert(AUTHORIZATION, value);
+        }
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes()).with_context(|| format!("Invalid header name: {}", name))?;
+            let value = HeaderValue::from_str(value).with_context(|| format!("Invalid value for header {}", name))?;
+            map.insert(name, value);
+        }
+        Ok(map)
+    }
+
+    fn http_client(&self) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder().danger_accept_invalid_certs(self.accept_invalid_certs);
+        if let Some(path) = &self.ca_cert {
+            let pem = fs::read(path).with_context(|| format!("Cannot read CA bundle {}", path.display()))?;
+            for cert in reqwest::Certificate::from_pem_bundle(&pem).context("Invalid CA bundle")? {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        Ok(builder.build()?)
+    }
+
+    /// A GET request for an API path the Ollama client doesn't cover, such as `api/ps`.
+    pub fn get(&self, path: &str) -> Result<reqwest::RequestBuilder> {
+        let url = format!("{}/{}", self.url.trim_end_matches('/'), path);
+        Ok(self.http_client()?.get(url).headers(self.header_map()?))
+    }
+
+    pub fn client(&self) -> Result<Arc<dyn ChatBackend>> {
+        if self.kind == BackendKind::Anthropic {
+            // The key goes in `x-api-key` rather than a bearer token
+            let mut headers = self.header_map()?;
+            headers.remove(AUTHORIZATION);
+            return Ok(Arc::new(AnthropicBackend::new(self.http_client()?, &self.url, self.api_key.as_deref(), headers)?));
+        }
+        let url = reqwest::Url::parse(&self.url).with_context(|| format!("Invalid host URL: {}", self.url))?;
+        let client = Ollama::builder()
+            .url(url)
+            .reqwest_client(self.http_client()?)
+            .request_headers(self.header_map()?)
+            .build();
+        Ok(Arc::new(OllamaBackend::new(client)))
+    }
+}
+
+/// A named set of sampling parameters. The system prompt and context size are left untouched when applied.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Preset {
+    pub name: String,
+    pub temperature: f32,
+    pub top_p: f32,
+    pub top_k: u32,
+    pub repeat_penalty: f32,
+    pub min_p: f32,
+    pub tfs_z: f32,
+    pub frequency_penalty: f32,
+    pub presence_penalty: f32,
+    pub mirostat: u8,
+    pub mirostat_eta: f32,
+    pub mirostat_tau: f32,
+}
+
+impl Default for Preset {
+    fn default() -> Self {
+        Self::from_config("custom", &ModelConfig::default())
+    }
+}
+
+impl Preset {
+    pub fn from_config(name: &str, config: &ModelConfig) -> Self {
+        Self {
+            name: name.to_string(),
+            temperature: config.temperature,
+            top_p: config.top_p,
+            top_k: config.top_k,
+            repeat_penalty: config.repeat_penalty,
+            min_p: config.min_p,
+            tfs_z: config.tfs_z,
+            frequency_penalty: config.frequency_penalty,
+            presence_penalty: config.presence_penalty,
+            mirostat: config.mirostat,
+            mirostat_eta: config.mirostat_eta,
+            mirostat_tau: config.mirostat_tau,
+        }
+    }
+
+    pub fn apply_to(&self, config: &mut ModelConfig) {
+        config.temperature = self.temperature;
+        config.top_p = self.top_p;
+        config.top_k = self.top_k;
+        config.repeat_penalty = self.repeat_penalty;
+        config.min_p = self.min_p;
+        config.tfs_z = self.tfs_z;
+        config.frequency_penalty = self.frequency_penalty;
+        config.presence_penalty = self.presence_penalty;
+        config.mirostat = self.mirostat;
+        config.mirostat_eta = self.mirostat_eta;
+        config.mirostat_tau = self.mirostat_tau;
+        config.preset = Some(self.name.clone());
+    }
+
+    pub fn builtin() -> Vec<Preset> {
+        vec![
+            Preset { name: "precise".into(), temperature: 0.2, top_p: 0.8, top_k: 20, min_p: 0.05, ..Preset::default() },
+            Preset { name: "balanced".into(), ..Preset::default() },
+            Preset { name: "creative".into(), temperature: 1.2, top_p: 0.95, top_k: 80, repeat_penalty: 1.05, ..Preset::default() },
+            Preset { name: "coding".into(), temperature: 0.1, top_p: 0.9, top_k: 40, repeat_penalty: 1.0, min_p: 0.05, ..Preset::default() },
+        ]
+    }
+}
+
+/// An entry in the system prompt library.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NamedPrompt {
+    pub name: String,
+    pub prompt: String,
+}
+
+impl NamedPrompt {
+    pub fn new(name: &str, prompt: &str) -> Self {
+        Self { name: name.to_string(), prompt: prompt.to_string() }
+    }
+
+    pub fn builtin() -> Vec<NamedPrompt> {
+        vec![
+            NamedPrompt::new("Rust expert", "You are an expert Rust engineer. Prefer idiomatic, safe code, explain ownership and lifetime issues clearly, and point out relevant crates."),
+            NamedPrompt::new("Terse answers", "Answer as briefly as possible. No preamble, no restating the question, no closing remarks."),
+            NamedPrompt::new("Translator", "Translate the user's message. If it is in English, translate it to French; otherwise translate it to English. Output only the translation."),
+        ]
+    }
+}
+
+/// A named assistant: the model, system prompt, and sampling parameters a conversation
+/// switches to together with `/persona`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Persona {
+    pub name: String,
+    /// Model the conversation switches to; unset keeps its model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub system_prompt: String,
+    /// Preset whose sampling parameters are used; unset keeps the model config's.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    /// Replaces the preset's temperature.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+}
+
+impl Persona {
+    pub fn builtin() -> Vec<Persona> {
+        vec![
+            Persona {
+                name: "Rust reviewer".into(),
+                system_prompt: "You are a senior Rust engineer reviewing code. Point out bugs, unsound or non-idiomatic code, and missing error handling, most serious first, and suggest concrete fixes.".into(),
+                preset: Some("coding".into()),
+                ..Persona::default()
+            },
+            Persona {
+                name: "Creative writer".into(),
+                system_prompt: "You are an imaginative writer. Favour vivid, concrete detail and varied rhythm, and avoid cliches.".into(),
+                preset: Some("creative".into()),
+                ..Persona::default()
+            },
+        ]
+    }
+}
+
+/// A way to start a conversation for a common task: a system prompt, a first message to fill
+/// in, and the sampling parameters that suit it. Offered when a new chat opens.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Template {
+    pub name: String,
+    /// One line shown in the picker.
+    pub description: String,
+    pub system_prompt: String,
+    /// Put into the input, for the rest to be filled in.
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    /// Replaces the preset's temperature.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+}
+
+impl Template {
+    pub fn builtin() -> Vec<Template> {
+        vec![
+            Template {
+                name: "Debugging".into(),
+                description: "Track down a bug from its symptoms".into(),
+                system_prompt: "You are a patient debugging partner. Ask for missing details, reason about likely causes before proposing fixes, and say how to confirm each one.".into(),
+                message: "What I expected:\n\nWhat happens instead (with any error output):\n\nWhat I've tried:\n".into(),
+                preset: Some("precise".into()),
+                ..Template::default()
+            },
+            Template {
+                name: "Summarizing".into(),
+                description: "Boil a text down to its main points".into(),
+                system_prompt: "You summarize text faithfully and concisely. Keep names, numbers, and decisions; leave out filler and never add facts.".into(),
+                message: "Summarize the following in five bullet points:\n\n".into(),
+                preset: Some("balanced".into()),
+                temperature: Some(0.3),
+            },
+            Template {
+                name: "Translating".into(),
+                description: "Translate while keeping tone and formatting".into(),
+                system_prompt: "You are a professional translator. Keep the meaning, tone, and formatting of the original, and output only the translation.".into(),
+                message: "Translate into French:\n\n".into(),
+                preset: Some("precise".into()),
+                ..Template::default()
+            },
+        ]
+    }
+}
+
+/// How tightly the chat transcript is laid out.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Density {
+    /// Labels on their own line, indented text, a blank line between messages.
+    #[default]
+    Comfortable,
+    /// Labels inline with the text and no spacing, for small terminals.
+    Compact,
+}
+
+/// How a reply that finished while the terminal was in the background is announced.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplyAlert {
+    #[default]
+    Off,
+    /// The terminal bell, which most terminals show on the window or tab.
+    Bell,
+    /// A desktop notification with the start of the reply.
+    Desktop,
+    Both,
+}
+
+/// What of each finished reply is put on the clipboard without asking.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoCopy {
+    #[default]
+    Off,
+    /// The whole reply, markdown and all.
+    Reply,
+    /// The reply's last fenced code block, without the fence; replies without one are left alone.
+    LastCodeBlock,
+}
+
+impl AutoCopy {
+    /// The text copied for `reply`, if any.
+    pub fn text(self, reply: &str) -> Option<String> {
+        match self {
+            AutoCopy::Off => None,
+            AutoCopy::Reply => Some(reply.trim().to_string()).filter(|reply| !reply.is_empty()),
+            AutoCopy::LastCodeBlock => text::code_blocks(reply).pop().map(|block| block.code),
+        }
+    }
+}
+
+/// A piece of the bottom status line; `status_line` in `config.json` lists them in display order.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusSegment {
+    /// The latest status message, e.g. "Chat saved successfully"
+    Message,
+    Model,
+    Host,
+    Mode,
+    /// Tokens processed in the current conversation
+    Tokens,
+    /// Tokens in the model's context after the last reply, against `num_ctx`
+    Context,
+    Clock,
+    LastError,
+}
+
+/// An MCP server from `config.json`: either a `command` to run over stdio or an HTTP `url`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct McpServerConfig {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Extra environment variables for a stdio server.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl McpServerConfig {
+    /// `stdio: <command>` or `http: <url>`, for the server list.
+    pub fn describe(&self) -> String {
+        match (&self.command, &self.url) {
+            (Some(command), _) => format!("stdio: {} {}", command, self.args.join(" ")).trim_end().to_string(),
+            (None, Some(url)) => format!("http: {}", url),
+            (None, None) => "no command or url".to_string(),
+        }
+    }
+}
+
+/// Application-level settings stored in `~/.ollama_tui/config.json`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct AppConfig {
+    /// Format version; see `migrate`.
+    pub version: u32,
+    pub hosts: Vec<HostConfig>,
+    pub active_host: String,
+    /// Model new conversations and `ask` use; updated whenever one is picked from the list.
+    pub model: Option<String>,
+    pub presets: Vec<Preset>,
+    pub system_prompts: Vec<NamedPrompt>,
+    /// Bundles of model, system prompt, and sampling preset, applied with `/persona`.
+    pub personas: Vec<Persona>,
+    /// Conversation starters offered when a new chat opens, and applied with `/template`.
+    pub templates: Vec<Template>,
+    /// Offer the templates each time a new tab opens.
+    pub pick_template_on_new_chat: bool,
+    pub theme: ThemeName,
+    /// Maximum width of the chat column in characters; it is centered on wider terminals.
+    pub chat_width: Option<u16>,
+    /// Label shown before your messages.
+    pub user_name: String,
+    /// Label shown before replies; defaults to the short name of the model that wrote them.
+    pub assistant_name: Option<String>,
+    /// Include the full model name (with tag) in reply labels.
+    pub show_model_in_label: bool,
+    /// Prefix each message with its number, as used by `:N`.
+    pub number_messages: bool,
+    pub density: Density,
+    /// Replies longer than this many lines show only their start until expanded; 0 never cuts
+    /// them short.
+    pub collapse_reply_lines: usize,
+    /// Show the whole system prompt above the chat, not just its first line.
+    pub expand_system_prompt: bool,
+    pub show_title_bar: bool,
+    /// Title bar text with `{model}`-style placeholders; unset keeps the built-in title.
+    pub title_template: Option<String>,
+    /// What `/raw` fills each exchange into: `{system}`, `{prompt}`, and `{response}` stand for
+    /// the system prompt, a prompt, and its reply.
+    pub raw_template: String,
+    /// Templates for particular models, used instead of `raw_template`, e.g. for a GGUF that
+    /// ships with a broken one. A name without a tag covers every tag of the model.
+    pub model_templates: BTreeMap<String, String>,
+    pub show_status_bar: bool,
+    pub show_input: bool,
+    /// List of saved sessions to the left of the chat.
+    pub show_sidebar: bool,
+    /// Rows taken by the chat input pane, borders included.
+    pub input_height: u16,
+    pub status_line: Vec<StatusSegment>,
+    /// Milliseconds between ticks while a reply streams or the system monitor is open.
+    pub tick_rate_ms: u64,
+    /// Slower tick used when nothing is animating; key presses still wake the app at once.
+    pub idle_tick_rate_ms: u64,
+    /// Plain output for screen readers: no spinner, borders, gauges, or toasts.
+    pub screen_reader: bool,
+    /// Offer the built-in tools to models that support tool calling.
+    pub tools_enabled: bool,
+    /// MCP servers whose tools are offered alongside the built-in ones.
+    pub mcp_servers: Vec<McpServerConfig>,
+    /// Add passages from the knowledge base to each question.
+    pub knowledge_enabled: bool,
+    /// Ollama model used to index the knowledge base.
+    pub embedding_model: String,
+    pub reply_alert: ReplyAlert,
+    /// Copy each finished reply, or its last code block, to the clipboard.
+    pub auto_copy: AutoCopy,
+    /// Show the model and whether a reply is generating in the terminal (and tmux pane) title.
+    pub terminal_title: bool,
+    /// Replies quicker than this many seconds are never announced.
+    pub reply_alert_after_secs: u64,
+    /// Save chats with unsaved messages to the history when quitting.
+    pub save_on_quit: bool,
+    /// Summarize the oldest messages once a conversation nears its context window.
+    pub compress_context: bool,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            version: Self::VERSION,
+            hosts: vec![HostConfig::new("local", "http://127.0.0.1:11434")],
+            active_host: String::from("local"),
+            model: None,
+            presets: Preset::builtin(),
+            system_prompts: NamedPrompt::builtin(),
+            personas: Persona::builtin(),
+            templates: Template::builtin(),
+            pick_template_on_new_chat: true,
+            theme: ThemeName::Auto,
+            chat_width: None,
+            user_name: String::from("You"),
+            assistant_name: None,
+            show_model_in_label: false,
+            number_messages: false,
+            expand_system_prompt: false,
+            density: Density::Comfortable,
+            collapse_reply_lines: 40,
+            show_title_bar: true,
+            title_template: None,
+            raw_template: String::from(raw::DEFAULT_TEMPLATE),
+            model_templates: BTreeMap::new(),
+            tools_enabled: false,
+            show_status_bar: true,
+            show_input: true,
+            show_sidebar: false,
+            input_height: INPUT_HEIGHT_RANGE.0,
+            status_line: vec![StatusSegment::Message, StatusSegment::Tokens, StatusSegment::LastError, StatusSegment::Clock],
+            tick_rate_ms: 100,
+            idle_tick_rate_ms: 1000,
+            screen_reader: false,
+            mcp_servers: Vec::new(),
+            knowledge_enabled: false,
+            embedding_model: String::from("nomic-embed-text"),
+            reply_alert: ReplyAlert::Off,
+            auto_copy: AutoCopy::Off,
+            terminal_title: true,
+            reply_alert_after_secs: 10,
+            save_on_quit: true,
+            compress_context: true,
+        }
+    }
+}
+
+impl Versioned for AppConfig {
+    const VERSION: u32 = 1;
+
+    fn upgrade(_version: u32, _value: &mut Value) -> Result<()> {
+        // Version 1 only added the version field
+        Ok(())
+    }
+}
+
+impl AppConfig {
+    pub fn load(config_dir: &Path) -> Result<Self> {
+        let path = config_dir.join(APP_CONFIG_FILE);
+        if !path.exists() {
+            // Write the defaults out so hosts and presets are discoverable and editable
+            let config = AppConfig::default();
+            config.save(config_dir).ok();
+            return Ok(config);
+        }
+        let mut config: AppConfig = migrate::load(&path)?;
+        if config.hosts.is_empty() {
+            config.hosts = AppConfig::default().hosts;
+        }
+        Ok(config)
+    }
+
+    pub fn save(&self, config_dir: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(config_dir.join(APP_CONFIG_FILE), json)?;
+        Ok(())
+    }
+
+    /// The host generation and model listing currently point at, falling back to the first entry.
+    pub fn active(&self) -> &HostConfig {
+        self.hosts
+            .iter()
+            .find(|h| h.name == self.active_host)
+            .unwrap_or(&self.hosts[0])
+    }
+
+    pub fn preset(&self, name: &str) -> Option<&Preset> {
+        self.presets.iter().find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn persona(&self, name: &str) -> Option<&Persona> {
+        self.personas.iter().find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn template(&self, name: &str) -> Option<&Template> {
+        self.templates.iter().find(|t| t.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The template `model_templates` has for `model`, by its full name or its name without
+    /// the tag. A colon before a `/` is a registry port, not a tag. Entries without `{prompt}`
+    /// are skipped.
+    pub fn model_template(&self, model: &str) -> Option<&str> {
+        let name = model.rsplit_once(':').filter(|(_, tag)| !tag.contains('/')).map_or(model, |(name, _)| name);
+        [model, name]
+            .into_iter()
+            .find_map(|key| self.model_templates.get(key).filter(|template| template.contains("{prompt}")))
+            .map(String::as_str)
+    }
+
+    /// What raw mode fills in for `model` unless given a template of its own; the default
+    /// stands in for a `raw_template` without `{prompt}`.
+    pub fn raw_template_for(&self, model: &str) -> &str {
+        let general = Some(self.raw_template.as_str()).filter(|template| template.contains("{prompt}"));
+        self.model_template(model).or(general).unwrap_or(raw::DEFAULT_TEMPLATE)
+    }
+
+    /// Raw templates with nowhere to put the prompt, which would send every message as the
+    /// same text; they are ignored rather than failing the whole config.
+    pub fn template_warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if !self.raw_template.contains("{prompt}") {
+            warnings.push(format!("Ignoring raw_template, which has no {{prompt}} placeholder; using {}", raw::DEFAULT_TEMPLATE));
+        }
+        for (model, template) in &self.model_templates {
+            if !template.contains("{prompt}") {
+                warnings.push(format!("Ignoring the template for {} in model_templates, which has no {{prompt}} placeholder", model));
+            }
+        }
+        warnings
+    }
+
+    pub fn active_index(&self) -> usize {
+        self.hosts.iter().position(|h| h.name == self.active_host).unwrap_or(0)
+    }
+}
+
+/// Everything needed to reproduce this setup on another machine, written by `/config export`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ConfigBundle {
+    /// Format version; see `migrate`.
+    #[serde(default)]
+    pub version: u32,
+    pub app: AppConfig,
+    pub model: ModelConfig,
+}
+
+impl Versioned for ConfigBundle {
+    const VERSION: u32 = 1;
+
+    fn upgrade(version: u32, value: &mut Value) -> Result<()> {
+        if version == 0 {
+            migrate::upgrade::<AppConfig>(&mut value["app"])?;
+            migrate::upgrade::<ModelConfig>(&mut value["model"])?;
+        }
+        Ok(())
+    }
+}
+
+impl ConfigBundle {
+    pub fn new(app: AppConfig, model: ModelConfig) -> Self {
+        Self { version: Self::VERSION, app, model }
+    }
+
+    /// Reads an export, upgrading one written by an older version; the file itself is left as it is.
+    pub fn read(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+        let (bundle, _) = migrate::parse(&content).with_context(|| format!("{} is not a config export", path.display()))?;
+        Ok(bundle)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?).with_context(|| format!("Cannot write {}", path.display()))
+    }
+}
+
+/// Expands a leading `~/` to the home directory.
+pub fn expand_path(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Watches the config directory so edits made in another terminal apply without a restart.
+pub struct ConfigWatcher {
+    _watcher: RecommendedWatcher,
+    rx: mpsc::Receiver<String>,
+}
+
+impl ConfigWatcher {
+    pub fn new(config_dir: &Path) -> Result<Self> {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            if let Ok(event) = res {
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    for path in event.paths {
+                        if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+                            let _ = tx.send(name.to_string());
+                        }
+                    }
+                }
+            }
+        })?;
+        watcher.watch(config_dir, RecursiveMode::NonRecursive)?;
+        Ok(Self { _watcher: watcher, rx })
+    }
+
+    /// File names in the config directory that changed since the last call, without duplicates.
+    pub fn changed_files(&self) -> Vec<String> {
+        let mut changed: Vec<String> = self.rx.try_iter().collect();
+        changed.sort();
+        changed.dedup();
+        changed
+    }
+}
+
+/// An add or edit in progress in the prompt library, typed as `Name: prompt text`.
+pub struct PromptDraft {
+    pub index: Option<usize>,
+    pub input: String,
+}
+
+impl App {
+    /// Re-reads any config file edited outside the app since the last call.
+    pub fn reload_changed_configs(&mut self) {
+        let Some(watcher) = &self.config_watcher else { return };
+        for file in watcher.changed_files() {
+            // A save still queued is about to overwrite the file, so what is there now is stale
+            if self.disk.is_busy() && (file == MODEL_CONFIG_FILE || file == APP_CONFIG_FILE) {
+                continue;
+            }
+            match file.as_str() {
+                MODEL_CONFIG_FILE => match ModelConfig::load(&self.config_dir) {
+                    Ok(config) if config == self.model_config => {}
+                    Ok(config) => {
+                        self.model_config = config;
+                        if self.mode == AppMode::ModelConfig {
+                            self.config_input = self.get_current_config_value();
+                        }
+                        self.notify(Severity::Info, format!("Reloaded {}", MODEL_CONFIG_FILE));
+                    }
+                    Err(e) => self.notify(Severity::Warning, format!("Not reloading {}: {}", MODEL_CONFIG_FILE, e)),
+                },
+                APP_CONFIG_FILE => {
+                    match migrate::load::<AppConfig>(&self.config_dir.join(APP_CONFIG_FILE)) {
+                        Ok(config) if config == self.app_config => {}
+                        Ok(config) if !config.hosts.is_empty() => {
+                            if config.active() != self.app_config.active() {
+                                match config.active().client() {
+                                    Ok(backend) => self.backend = backend,
+                                    Err(e) => self.set_error(format!("Invalid host '{}': {:#}", config.active_host, e)),
+                                }
+                            }
+                            self.app_config = config;
+                            self.refresh_theme();
+                            self.notify(Severity::Info, format!("Reloaded {}", APP_CONFIG_FILE));
+                            for warning in self.app_config.template_warnings() {
+                                self.notify(Severity::Warning, warning);
+                            }
+                        }
+                        Ok(_) => self.notify(Severity::Warning, format!("Not reloading {}: no hosts configured", APP_CONFIG_FILE)),
+                        Err(e) => self.notify(Severity::Warning, format!("Not reloading {}: {}", APP_CONFIG_FILE, e)),
+                    }
+                }
+                KEYMAP_FILE => match Keymap::load(&self.config_dir) {
+                    Ok(keymap) if keymap == self.keymap => {}
+                    Ok(keymap) => {
+                        self.keymap = keymap;
+                        self.notify(Severity::Info, format!("Reloaded {}", KEYMAP_FILE));
+                    }
+                    Err(e) => self.notify(Severity::Warning, format!("Not reloading {}: {:#}", KEYMAP_FILE, e)),
+                },
+                _ => {}
+            }
+        }
+    }
+
+    /// Writes the app config in the background; only a failure is reported.
+    pub fn save_app_config(&mut self) {
+        let json = match serde_json::to_string_pretty(&self.app_config) {
+            Ok(json) => json,
+            Err(e) => return self.set_error(format!("Cannot save settings: {}", e)),
+        };
+        self.disk.write(self.config_dir.join(APP_CONFIG_FILE), json, |app, result| {
+            if let Err(e) = result {
+                app.set_error(format!("Cannot save settings: {}", e));
+            }
+        });
+    }
+
+    pub(crate) fn replace_config(&mut self, app_config: AppConfig, model_config: ModelConfig) -> Result<()> {
+        if app_config.hosts.is_empty() {
+            anyhow::bail!("config has no hosts");
+        }
+        self.backend = app_config.active().client()?;
+        self.app_config = app_config;
+        self.model_config = model_config;
+        self.refresh_theme();
+        self.save_app_config();
+        self.save_config();
+        Ok(())
+    }
+
+    /// Copies a named preset's sampling parameters into the global model config.
+    pub fn apply_preset(&mut self, name: &str) -> Result<()> {
+        let preset = self.app_config.preset(name).cloned().ok_or_else(|| anyhow::anyhow!("Unknown preset: {}", name))?;
+        preset.apply_to(&mut self.model_config);
+        Ok(())
+    }
+
+    /// Gives the current conversation a persona's model, system prompt, and sampling
+    /// parameters, replacing its other session overrides. The global config is untouched.
+    pub fn apply_persona(&mut self, name: &str) -> Result<()> {
+        let persona = self.app_config.persona(name).cloned().ok_or_else(|| anyhow::anyhow!("Unknown persona: {}", name))?;
+        let sampling = match &persona.preset {
+            Some(preset) => Some(
+                self.app_config
+                    .preset(preset)
+                    .cloned()
+                    .ok_or_else(|| anyhow::anyhow!("Persona '{}' uses the unknown preset '{}'", persona.name, preset))?,
+            ),
+            None => None,
+        };
+        let conversation = self.conversation_mut();
+        if let Some(model) = persona.model {
+            conversation.model = model;
+        }
+        conversation.overrides = SessionOverrides {
+            temperature: persona.temperature,
+            system_prompt: Some(persona.system_prompt),
+            system_prompt_name: None,
+            sampling,
+            persona: Some(persona.name),
+        };
+        Ok(())
+    }
+
+    /// Switches the current conversation to the persona after the one it uses, and back to
+    /// none after the last.
+    pub fn next_persona(&mut self) {
+        let personas = &self.app_config.personas;
+        let current = self.conversation().overrides.persona.as_ref().and_then(|name| personas.iter().position(|p| &p.name == name));
+        let next = current.map_or(0, |i| i + 1);
+        match personas.get(next).map(|p| p.name.clone()) {
+            Some(name) => match self.apply_persona(&name) {
+                Ok(()) => self.status_message = format!("Persona: {}", name),
+                Err(e) => self.set_error(e.to_string()),
+            },
+            None => self.clear_persona(),
+        }
+    }
+
+    /// Drops the persona and every other session override of the current conversation; its
+    /// model stays.
+    pub fn clear_persona(&mut self) {
+        self.conversation_mut().overrides = SessionOverrides::default();
+        self.status_message = "No persona: using the model config".to_string();
+    }
+
+    /// Starts the current conversation from a template: its system prompt and sampling
+    /// parameters for this conversation, and its first message in the input to fill in.
+    pub fn apply_template(&mut self, name: &str) -> Result<()> {
+        let template = self.app_config.template(name).cloned().ok_or_else(|| anyhow::anyhow!("Unknown template: {}", name))?;
+        let sampling = match &template.preset {
+            Some(preset) => Some(
+                self.app_config
+                    .preset(preset)
+                    .cloned()
+                    .ok_or_else(|| anyhow::anyhow!("Template '{}' uses the unknown preset '{}'", template.name, preset))?,
+            ),
+            None => None,
+        };
+        let conversation = self.conversation_mut();
+        conversation.overrides = SessionOverrides {
+            temperature: template.temperature,
+            system_prompt: Some(template.system_prompt),
+            system_prompt_name: Some(template.name.clone()),
+            sampling,
+            persona: None,
+        };
+        conversation.input = template.message;
+        self.vim_insert = true;
+        self.status_message = format!("Started from the '{}' template; fill in the message and press Enter", template.name);
+        Ok(())
+    }
+
+    /// Offers the templates for the conversation just opened, if there are any and the
+    /// picker is on.
+    pub fn open_template_picker(&mut self) {
+        if self.app_config.pick_template_on_new_chat && !self.app_config.templates.is_empty() {
+            self.template_picker = Some(0);
+        }
+    }
+
+    pub fn move_template_selection(&mut self, down: bool) {
+        let last = self.app_config.templates.len();
+        if let Some(item) = self.template_picker.as_mut() {
+            *item = if down { (*item + 1).min(last) } else { item.saturating_sub(1) };
+        }
+    }
+
+    /// Applies the highlighted template and closes the picker; the first entry is a blank chat.
+    pub fn choose_template(&mut self) {
+        let Some(item) = self.template_picker.take() else { return };
+        let Some(name) = item.checked_sub(1).and_then(|i| self.app_config.templates.get(i)).map(|t| t.name.clone()) else { return };
+        if let Err(e) = self.apply_template(&name) {
+            self.set_error(e.to_string());
+        }
+    }
+
+    pub fn switch_host(&mut self, index: usize) -> Result<()> {
+        let Some(host) = self.app_config.hosts.get(index).cloned() else { return Ok(()) };
+        self.backend = host.client()?;
+        self.app_config.active_host = host.name.clone();
+        self.save_app_config();
+        self.available_models.clear();
+        self.fetch_models_then(move |app, result| match result {
+            Ok(()) => app.notify(Severity::Success, format!("Connected to {} ({})", host.name, host.url)),
+            Err(e) => app.fail(&format!("Switched to {}", host.name), e, Some(Retry::ListModels)),
+        });
+        Ok(())
+    }
+
+    /// Makes the highlighted library prompt the system prompt for the current session.
+    pub fn use_selected_prompt(&mut self) {
+        let Some(entry) = self.prompt_list_state.selected().and_then(|i| self.app_config.system_prompts.get(i)).cloned() else { return };
+        self.conversation_mut().overrides.system_prompt = Some(entry.prompt);
+        self.conversation_mut().overrides.system_prompt_name = Some(entry.name.clone());
+        self.conversation_mut().overrides.persona = None;
+        self.status_message = format!("Using system prompt '{}' for this session", entry.name);
+        self.switch_mode(AppMode::Chat);
+    }
+
+    pub fn start_prompt_draft(&mut self, edit: bool) {
+        let selected = self.prompt_list_state.selected();
+        self.prompt_draft = match selected.and_then(|i| self.app_config.system_prompts.get(i)) {
+            Some(entry) if edit => Some(PromptDraft { index: selected, input: format!("{}: {}", entry.name, entry.prompt) }),
+            _ => Some(PromptDraft { index: None, input: String::new() }),
+        };
+    }
+
+    pub fn commit_prompt_draft(&mut self) -> Result<()> {
+        let Some(draft) = self.prompt_draft.take() else { return Ok(()) };
+        let Some((name, prompt)) = draft.input.split_once(':').map(|(n, p)| (n.trim(), p.trim())).filter(|(n, p)| !n.is_empty() && !p.is_empty()) else {
+            self.notify(Severity::Warning, "Use the form 'Name: prompt text'".to_string());
+            self.prompt_draft = Some(draft);
+            return Ok(());
+        };
+        let entry = NamedPrompt::new(name, prompt);
+        match draft.index {
+            Some(index) if index < self.app_config.system_prompts.len() => self.app_config.system_prompts[index] = entry,
+            _ => {
+                self.app_config.system_prompts.push(entry);
+                self.prompt_list_state.select(Some(self.app_config.system_prompts.len() - 1));
+            }
+        }
+        self.save_app_config();
+        self.notify(Severity::Success, format!("Saved system prompt '{}'", name));
+        Ok(())
+    }
+
+    pub fn delete_selected_prompt(&mut self) -> Result<()> {
+        let Some(index) = self.prompt_list_state.selected() else { return Ok(()) };
+        if index >= self.app_config.system_prompts.len() {
+            return Ok(());
+        }
+        let removed = self.app_config.system_prompts.remove(index);
+        let len = self.app_config.system_prompts.len();
+        self.prompt_list_state.select(if len == 0 { None } else { Some(index.min(len - 1)) });
+        self.save_app_config();
+        self.notify(Severity::Success, format!("Deleted system prompt '{}'", removed.name));
+        Ok(())
+    }
+}
+
+/// Reports a settings file in `config_dir` that could not be read. Defaults will be saved over
+/// it, so a copy is kept first.
+pub(crate) fn unreadable(config_dir: &Path, file: &str, error: &anyhow::Error) -> String {
+    match migrate::back_up(&config_dir.join(file)) {
+        Ok(backup) => format!("Cannot read {}, using defaults (old file kept as {}): {:#}", file, backup.display(), error),
+        Err(_) => format!("Cannot read {}, using defaults: {:#}", file, error),
+    }
+}
@@ -0,0 +1,35 @@
+use anyhow::{bail, Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::terminal;
+
+/// Rings the terminal bell; most terminals flag the window or tab when it's in the background.
+pub fn bell() {
+    let mut screen = terminal::screen();
+    let _ = screen.write_all(b"\x07").and_then(|()| screen.flush());
+}
+
+/// Shows a desktop notification with the platform's own tool: `notify-send` on Linux and the
+/// BSDs, `osascript` on macOS.
+pub fn notify(title: &str, body: &str) -> Result<()> {
+    #[cfg(target_os = "macos")]
+    let mut command = {
+        let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut command = Command::new("osascript");
+        command.args(["-e", &format!("display notification {} with title {}", quote(body), quote(title))]);
+        command
+    };
+    #[cfg(not(target_os = "macos"))]
+    let mut command = {
+        let mut command = Command::new("notify-send");
+        command.args(["--app-name", "ollama-tui", title, body]);
+        command
+    };
+    // Neither tool should ever write over the interface
+    let status = command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).status().context("cannot run the notification tool")?;
+    if !status.success() {
+        bail!("the notification tool exited with {}", status);
+    }
+    Ok(())
+}
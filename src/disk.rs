@@ -0,0 +1,64 @@
+use std::io;
+use std::path::PathBuf;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::task::JoinHandle;
+
+use crate::app::App;
+use crate::events::Events;
+
+type Done = Box<dyn FnOnce(&mut App, io::Result<()>) + Send>;
+
+struct Job {
+    path: PathBuf,
+    contents: String,
+    done: Done,
+}
+
+/// Writes files on a background task, so a slow disk or network home never stalls the UI.
+/// Writes happen one at a time in the order queued, so the last save of a file always wins.
+pub struct DiskWriter {
+    jobs: Option<UnboundedSender<Job>>,
+    task: Option<JoinHandle<()>>,
+    /// Writes queued whose outcome the app has not seen yet.
+    pending: usize,
+}
+
+impl DiskWriter {
+    /// Starts the writing task; each write's outcome comes back to the app through `events`.
+    pub fn spawn(events: Events) -> Self {
+        let (jobs, mut queue) = mpsc::unbounded_channel::<Job>();
+        let task = tokio::spawn(async move {
+            while let Some(job) = queue.recv().await {
+                let result = tokio::fs::write(&job.path, job.contents).await;
+                tracing::debug!(path = %job.path.display(), ok = result.is_ok(), "write finished");
+                events.update(move |app| {
+                    app.disk.pending -= 1;
+                    (job.done)(app, result);
+                });
+            }
+        });
+        Self { jobs: Some(jobs), task: Some(task), pending: 0 }
+    }
+
+    /// Queues `contents` for `path`; `done` runs on the app once the write finishes or fails.
+    pub fn write(&mut self, path: PathBuf, contents: String, done: impl FnOnce(&mut App, io::Result<()>) + Send + 'static) {
+        if let Some(jobs) = &self.jobs {
+            if jobs.send(Job { path, contents, done: Box::new(done) }).is_ok() {
+                self.pending += 1;
+            }
+        }
+    }
+
+    /// Whether files may still change from writes already queued.
+    pub fn is_busy(&self) -> bool {
+        self.pending > 0
+    }
+
+    /// Waits for every queued write to finish. Nothing more is written afterwards.
+    pub async fn flush(&mut self) {
+        self.jobs = None;
+        if let Some(task) = self.task.take() {
+            let _ = task.await;
+        }
+    }
+}
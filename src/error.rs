@@ -0,0 +1,102 @@
+use std::fmt;
+use std::io;
+
+/// What went wrong, sorted by what the user can do about it. Each variant decides the ways
+/// out offered by the error popup; see `recoveries`.
+#[derive(Debug)]
+pub enum AppError {
+    /// Nothing answered at the host's address, or it stopped answering.
+    Unreachable(String),
+    /// The host answered but does not have this model.
+    ModelMissing(String),
+    /// A file could not be read or written.
+    Io(io::Error),
+    /// A reply or file was not in the expected format.
+    Parse(String),
+    /// Anything else, such as an error message from the model server.
+    Other(anyhow::Error),
+}
+
+/// A way out of an error, offered on the error popup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    Retry,
+    OpenHosts,
+    OpenModels,
+    PullModel,
+}
+
+/// What `Recovery::Retry` does again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Retry {
+    /// Sends the last prompt of the conversation again.
+    Reply(usize),
+    ListModels,
+}
+
+impl AppError {
+    /// Sorts an error from a chat backend working with `model`.
+    pub fn from_backend(error: anyhow::Error, model: &str) -> Self {
+        for cause in error.chain() {
+            if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+                if e.is_connect() || e.is_timeout() {
+                    return AppError::Unreachable(error.root_cause().to_string());
+                }
+            }
+            if cause.downcast_ref::<serde_json::Error>().is_some() {
+                return AppError::Parse(format!("{:#}", error));
+            }
+        }
+        // Ollama says `model "x" not found, try pulling it first`; Anthropic answers 404
+        let message = format!("{:#}", error).to_lowercase();
+        if message.contains("model") && message.contains("not found") {
+            return AppError::ModelMissing(model.to_string());
+        }
+        AppError::Other(error)
+    }
+
+    /// The ways out of this error; `Retry` only when there is something to retry.
+    pub fn recoveries(&self, can_retry: bool) -> Vec<Recovery> {
+        let retry = can_retry.then_some(Recovery::Retry);
+        match self {
+            AppError::Unreachable(_) => retry.into_iter().chain([Recovery::OpenHosts]).collect(),
+            AppError::ModelMissing(_) => vec![Recovery::PullModel, Recovery::OpenModels],
+            AppError::Io(_) | AppError::Parse(_) | AppError::Other(_) => retry.into_iter().collect(),
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Unreachable(detail) => write!(f, "The model server is not answering: {}", detail),
+            AppError::ModelMissing(model) => write!(f, "Model '{}' is not installed on this host", model),
+            AppError::Io(e) => write!(f, "{}", e),
+            AppError::Parse(detail) => write!(f, "Unexpected response: {}", detail),
+            AppError::Other(e) => write!(f, "{:#}", e),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<io::Error> for AppError {
+    fn from(error: io::Error) -> Self {
+        AppError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(error: serde_json::Error) -> Self {
+        AppError::Parse(error.to_string())
+    }
+}
+
+impl From<anyhow::Error> for AppError {
+    fn from(error: anyhow::Error) -> Self {
+        match error.downcast::<io::Error>() {
+            Ok(e) => AppError::Io(e),
+            Err(error) => AppError::Other(error),
+        }
+    }
+}
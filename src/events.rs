@@ -0,0 +1,36 @@
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::app::App;
+use crate::backend::Chunk;
+use crate::error::AppError;
+
+/// Something for the loop that owns `App` to act on. Background tasks never touch the app
+/// themselves; they send one of these and carry on.
+pub enum AppEvent {
+    /// More of a streaming reply, for message `message_index` of the conversation.
+    Chunk { conversation_id: usize, message_index: usize, chunk: Chunk },
+    /// A reply stream ended, cut short if `error` is set.
+    StreamEnded { conversation_id: usize, message_index: usize, error: Option<AppError> },
+    /// Any other result from a background task, applied to the app when its turn comes.
+    Update(Box<dyn FnOnce(&mut App) + Send>),
+}
+
+/// The sending half of the app's event channel, cloned into every background task.
+#[derive(Clone)]
+pub struct Events(UnboundedSender<AppEvent>);
+
+impl Events {
+    pub fn channel() -> (Events, UnboundedReceiver<AppEvent>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (Events(sender), receiver)
+    }
+
+    /// Queues `event`. Returns `false` once the app has quit, so a task can stop early.
+    pub fn send(&self, event: AppEvent) -> bool {
+        self.0.send(event).is_ok()
+    }
+
+    pub fn update(&self, update: impl FnOnce(&mut App) + Send + 'static) -> bool {
+        self.send(AppEvent::Update(Box::new(update)))
+    }
+}
@@ -0,0 +1,76 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::app::App;
+
+/// Marks the gap in a prompt sent in fill-in-the-middle mode.
+pub const FILL_MARKER: &str = "{{fill}}";
+
+/// The code on either side of the gap a fill-in-the-middle reply was written for.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Fill {
+    pub prefix: String,
+    pub suffix: String,
+}
+
+impl Fill {
+    /// `prompt` split at its first `{{fill}}`; without one, all of it comes before the gap.
+    pub fn split(prompt: &str) -> Self {
+        let (prefix, suffix) = prompt.split_once(FILL_MARKER).unwrap_or((prompt, ""));
+        Self { prefix: prefix.to_string(), suffix: suffix.to_string() }
+    }
+
+    /// The code with `middle` put in the gap.
+    pub fn stitch(&self, middle: &str) -> String {
+        format!("{}{}{}", self.prefix, middle, self.suffix)
+    }
+}
+
+/// The text of the file at `path` with `{{fill}}` in place of lines `start` to `end`, counting
+/// from 1. An `end` before `start` replaces nothing and puts the gap at the start of line `start`.
+pub fn load_region(path: &Path, start: usize, end: usize) -> Result<String> {
+    let text = fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    if start == 0 || start > lines.len() + 1 || end > lines.len() {
+        let region = if end >= start { format!("lines {}-{}", start, end) } else { format!("line {}", start) };
+        bail!("{} has {} lines, so {} cannot be filled in", path.display(), lines.len(), region);
+    }
+    Ok(format!("{}{}{}", lines[..start - 1].concat(), FILL_MARKER, lines[end.max(start - 1)..].concat()))
+}
+
+impl App {
+    /// Turns fill-in-the-middle mode on or off for the current conversation.
+    pub fn toggle_fim_mode(&mut self) {
+        let enable = !self.conversation().fim;
+        self.set_fim_mode(enable);
+    }
+
+    /// Has the model write the code that goes at `{{fill}}` in each prompt; it takes the place
+    /// of raw mode, which cannot pass code after the gap.
+    pub fn set_fim_mode(&mut self, enable: bool) {
+        let conversation = self.conversation_mut();
+        conversation.fim = enable;
+        if enable {
+            conversation.raw_template = None;
+        }
+        self.status_message = match enable {
+            true => format!("Fill-in-the-middle on: the model writes what goes at {} in each prompt, for code models such as qwen2.5-coder", FILL_MARKER),
+            false => "Fill-in-the-middle off".to_string(),
+        };
+    }
+
+    /// Puts the file at `path` in the input with the gap at lines `start` to `end`, to check
+    /// and send in fill-in-the-middle mode.
+    pub fn load_fim_region(&mut self, path: &Path, start: usize, end: usize) -> Result<()> {
+        let text = load_region(path, start, end)?;
+        self.set_fim_mode(true);
+        self.conversation_mut().input = text;
+        self.status_message = match end >= start {
+            true => format!("Loaded {} with lines {}-{} to fill in; press Enter to send", path.display(), start, end),
+            false => format!("Loaded {} with a gap at line {}; press Enter to send", path.display(), start),
+        };
+        Ok(())
+    }
+}
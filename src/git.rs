@@ -0,0 +1,72 @@
+use anyhow::{bail, Context, Result};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Which changes `/commitmsg` and `/review` look at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Diff {
+    /// What `git commit` would record.
+    Staged,
+    /// Everything changed since the last commit, staged or not.
+    Working,
+}
+
+/// Top directory of the repository the app was started in.
+pub fn repo_root() -> Result<PathBuf> {
+    let root = git(&["rev-parse", "--show-toplevel"]).context("not in a git repository")?;
+    Ok(PathBuf::from(root.trim()))
+}
+
+/// A summary of the changed files followed by the full diff, or an error when nothing changed.
+pub fn diff(kind: Diff) -> Result<String> {
+    let range: &[&str] = match kind {
+        Diff::Staged => &["--staged"],
+        // A repository without commits has no HEAD to compare with
+        Diff::Working if git(&["rev-parse", "--verify", "--quiet", "HEAD"]).is_ok() => &["HEAD"],
+        Diff::Working => &["--staged"],
+    };
+    let diff = git(&[&["diff", "--no-color", "--no-ext-diff"], range].concat())?;
+    if diff.trim().is_empty() {
+        bail!(match kind {
+            Diff::Staged => "nothing is staged; add changes with git add first",
+            Diff::Working => "there are no changes since the last commit",
+        });
+    }
+    let stat = git(&[&["diff", "--no-color", "--stat"], range].concat())?;
+    Ok(format!("{}\n{}", stat.trim_end(), diff))
+}
+
+/// Commits the staged changes with `message`, returning git's summary of the new commit.
+pub fn commit(message: &str) -> Result<String> {
+    let mut child = Command::new("git")
+        .args(["commit", "--file", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("cannot run git")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(message.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!("{}", failure(&output.stdout, &output.stderr));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).lines().next().unwrap_or("").to_string())
+}
+
+fn git(args: &[&str]) -> Result<String> {
+    let output = Command::new("git").args(args).output().context("cannot run git")?;
+    if !output.status.success() {
+        bail!("{}", failure(&output.stdout, &output.stderr));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// What git said about a failed command; hooks and some checks report on stdout.
+fn failure(stdout: &[u8], stderr: &[u8]) -> String {
+    let stderr = String::from_utf8_lossy(stderr);
+    let message = if stderr.trim().is_empty() { String::from_utf8_lossy(stdout) } else { stderr };
+    message.trim().to_string()
+}
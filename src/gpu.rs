@@ -0,0 +1,266 @@
+use std::fs;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// One GPU's readings for the system monitor. Fields a vendor tool doesn't report are `None`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GpuStats {
+    pub name: String,
+    /// Busy percentage, 0–100.
+    pub utilization: Option<f32>,
+    /// Megabytes of VRAM in use and in total.
+    pub memory_used: Option<u64>,
+    pub memory_total: Option<u64>,
+    /// Degrees Celsius.
+    pub temperature: Option<f32>,
+    /// How hard the system is pressed for unified memory, 0–100; Apple Silicon only.
+    pub memory_pressure: Option<f32>,
+}
+
+/// A source of GPU readings for one vendor.
+pub trait GpuProvider: Send {
+    /// Current readings; empty when this vendor's GPUs or tools aren't present.
+    fn sample(&self) -> Vec<GpuStats>;
+}
+
+pub struct Nvidia;
+pub struct Amd;
+pub struct Intel;
+#[cfg(target_os = "macos")]
+pub struct Apple;
+
+/// Every provider for this platform, discrete GPUs first.
+pub fn providers() -> Vec<Box<dyn GpuProvider>> {
+    #[cfg(target_os = "macos")]
+    return vec![Box::new(Apple)];
+    #[cfg(not(target_os = "macos"))]
+    vec![Box::new(Nvidia), Box::new(Amd), Box::new(Intel)]
+}
+
+impl GpuProvider for Nvidia {
+    fn sample(&self) -> Vec<GpuStats> {
+        nvidia()
+    }
+}
+
+impl GpuProvider for Amd {
+    fn sample(&self) -> Vec<GpuStats> {
+        let gpus = amd_sysfs();
+        if gpus.is_empty() { rocm_smi() } else { gpus }
+    }
+}
+
+impl GpuProvider for Intel {
+    /// Cards come from sysfs; utilization needs `intel_gpu_top`, which usually wants root.
+    fn sample(&self) -> Vec<GpuStats> {
+        let cards = drm_cards(INTEL_VENDOR_ID);
+        let utilization = if cards.is_empty() { None } else { intel_gpu_top_busy() };
+        cards
+            .iter()
+            .map(|(card, device)| GpuStats {
+                name: format!("Intel GPU ({})", card),
+                utilization,
+                temperature: hwmon_temperature(&device.join("hwmon")),
+                ..GpuStats::default()
+            })
+            .collect()
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl GpuProvider for Apple {
+    fn sample(&self) -> Vec<GpuStats> {
+        apple().into_iter().collect()
+    }
+}
+
+fn nvidia() -> Vec<GpuStats> {
+    let Ok(output) = Command::new("nvidia-smi")
+        .args([
+            "--query-gpu=name,utilization.gpu,memory.used,memory.total,temperature.gpu",
+            "--format=csv,noheader,nounits",
+        ])
+        .output()
+    else {
+        return Vec::new();
+    };
+    if !output.status.success() {
+        return Vec::new();
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split(',').map(str::trim).collect();
+            if parts.len() < 5 {
+                return None;
+            }
+            Some(GpuStats {
+                name: parts[0].to_string(),
+                utilization: parts[1].parse().ok(),
+                memory_used: parts[2].parse().ok(),
+                memory_total: parts[3].parse().ok(),
+                temperature: parts[4].parse().ok(),
+                memory_pressure: None,
+            })
+        })
+        .collect()
+}
+
+const AMD_VENDOR_ID: &str = "0x1002";
+const INTEL_VENDOR_ID: &str = "0x8086";
+
+/// `(card name, device directory)` of each DRM card from the given PCI vendor.
+fn drm_cards(vendor: &str) -> Vec<(String, PathBuf)> {
+    let Ok(entries) = fs::read_dir("/sys/class/drm") else { return Vec::new() };
+    let mut cards: Vec<_> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            // `card0`, not connectors such as `card0-DP-1`
+            (name.starts_with("card") && !name.contains('-')).then(|| (name, entry.path().join("device")))
+        })
+        .filter(|(_, device)| read_trimmed(&device.join("vendor")).as_deref() == Some(vendor))
+        .collect();
+    cards.sort();
+    cards
+}
+
+/// AMD cards through the amdgpu sysfs files.
+fn amd_sysfs() -> Vec<GpuStats> {
+    drm_cards(AMD_VENDOR_ID)
+        .iter()
+        .map(|(card, device)| {
+            let bytes_to_mb = |file: &str| read_trimmed(&device.join(file))?.parse::<u64>().ok().map(|b| b / 1024 / 1024);
+            GpuStats {
+                name: format!("AMD GPU ({})", card),
+                utilization: read_trimmed(&device.join("gpu_busy_percent")).and_then(|v| v.parse().ok()),
+                memory_used: bytes_to_mb("mem_info_vram_used"),
+                memory_total: bytes_to_mb("mem_info_vram_total"),
+                temperature: hwmon_temperature(&device.join("hwmon")),
+                memory_pressure: None,
+            }
+        })
+        .collect()
+}
+
+/// Busiest engine from the first sample `intel_gpu_top -J` prints. It streams a JSON array
+/// forever, so the output is read up to the end of the first object and the tool is killed.
+fn intel_gpu_top_busy() -> Option<f32> {
+    let mut child = Command::new("intel_gpu_top")
+        .args(["-J", "-s", "100"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let sample = first_json_object(child.stdout.take()?);
+    let _ = child.kill();
+    let _ = child.wait();
+    let sample: serde_json::Value = serde_json::from_str(&sample?).ok()?;
+    sample
+        .get("engines")?
+        .as_object()?
+        .values()
+        .filter_map(|engine| engine.get("busy")?.as_f64())
+        .reduce(f64::max)
+        .map(|busy| busy as f32)
+}
+
+/// Text of the first complete `{...}` object in a stream, skipping braces inside strings.
+fn first_json_object(stream: impl Read) -> Option<String> {
+    let mut object = String::new();
+    let (mut depth, mut in_string, mut escaped) = (0usize, false, false);
+    for byte in BufReader::new(stream).bytes() {
+        let c = byte.ok()? as char;
+        if depth == 0 && c != '{' {
+            continue;
+        }
+        object.push(c);
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '{' if !in_string => depth += 1,
+            '}' if !in_string => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(object);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// First `temp1_input` under a device's hwmon directory, reported in millidegrees.
+fn hwmon_temperature(hwmon: &Path) -> Option<f32> {
+    fs::read_dir(hwmon)
+        .ok()?
+        .flatten()
+        .find_map(|entry| read_trimmed(&entry.path().join("temp1_input"))?.parse::<f32>().ok())
+        .map(|millidegrees| millidegrees / 1000.0)
+}
+
+fn rocm_smi() -> Vec<GpuStats> {
+    let Ok(output) = Command::new("rocm-smi").args(["--showuse", "--showtemp", "--showmeminfo", "vram", "--json"]).output() else {
+        return Vec::new();
+    };
+    if !output.status.success() {
+        return Vec::new();
+    }
+    let Ok(serde_json::Value::Object(cards)) = serde_json::from_slice(&output.stdout) else { return Vec::new() };
+    cards
+        .iter()
+        .filter(|(card, _)| card.starts_with("card"))
+        .map(|(card, fields)| {
+            // Key names vary between rocm-smi releases, so match on the stable part
+            let field = |needle: &str| -> Option<f64> {
+                fields.as_object()?.iter().find(|(key, _)| key.contains(needle))?.1.as_str()?.trim().parse().ok()
+            };
+            GpuStats {
+                name: format!("AMD GPU ({})", card),
+                utilization: field("GPU use").map(|v| v as f32),
+                memory_used: field("VRAM Total Used Memory").map(|b| b as u64 / 1024 / 1024),
+                memory_total: field("VRAM Total Memory").map(|b| b as u64 / 1024 / 1024),
+                temperature: field("Temperature").map(|v| v as f32),
+                memory_pressure: None,
+            }
+        })
+        .collect()
+}
+
+/// Apple Silicon GPU from the IOAccelerator registry entry, which needs no root unlike
+/// `powermetrics`. Memory is unified, so the total is the machine's RAM.
+#[cfg(target_os = "macos")]
+fn apple() -> Option<GpuStats> {
+    let output = Command::new("ioreg").args(["-r", "-d", "1", "-w", "0", "-c", "IOAccelerator"]).output().ok()?;
+    let registry = String::from_utf8_lossy(&output.stdout);
+    let sysctl = |name: &str| -> Option<u64> {
+        let output = Command::new("sysctl").args(["-n", name]).output().ok()?;
+        String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+    };
+    Some(GpuStats {
+        name: ioreg_value(&registry, "model").map(|m| m.trim_matches('"').to_string()).unwrap_or_else(|| "Apple GPU".to_string()),
+        utilization: ioreg_value(&registry, "Device Utilization %")?.parse().ok(),
+        memory_used: ioreg_value(&registry, "In use system memory").and_then(|v| v.parse::<u64>().ok()).map(|b| b / 1024 / 1024),
+        memory_total: sysctl("hw.memsize").map(|b| b / 1024 / 1024),
+        temperature: None,
+        // The kernel reports the share of memory still available
+        memory_pressure: sysctl("kern.memorystatus_level").map(|free| 100.0 - free as f32),
+    })
+}
+
+/// The value after `"key"=` or `"key" = ` in `ioreg` output, up to the next `,`, `}` or newline.
+#[cfg(target_os = "macos")]
+fn ioreg_value<'a>(registry: &'a str, key: &str) -> Option<&'a str> {
+    let quoted = format!("\"{}\"", key);
+    let rest = registry[registry.find(&quoted)? + quoted.len()..].trim_start().strip_prefix('=')?.trim_start();
+    let end = rest.find([',', '}', '\n']).unwrap_or(rest.len());
+    Some(rest[..end].trim())
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
@@ -0,0 +1,109 @@
+use chrono::Local;
+use std::fs;
+use std::path::Path;
+
+use crate::app::{App, AppMode, Panel};
+use crate::chat::ChatSession;
+use crate::migrate::{self, Versioned};
+use crate::toast::Severity;
+
+impl App {
+    /// Writes the current chat to the history in the background.
+    pub fn save_current_chat(&mut self) {
+        self.save_chat(self.active_conversation);
+    }
+
+    /// Writes the chat in tab `index` to the history in the background.
+    pub(crate) fn save_chat(&mut self, index: usize) {
+        let conversation = &self.conversations[index];
+        if conversation.messages.is_empty() {
+            return;
+        }
+
+        let session = ChatSession {
+            version: ChatSession::VERSION,
+            timestamp: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            model: conversation.model.clone(),
+            messages: conversation.messages.clone(),
+            overrides: conversation.overrides.clone(),
+        };
+
+        // The tab id keeps tabs saved in the same second, as when quitting, apart
+        let filename = format!("chat_{}_{}.json", Local::now().format("%Y%m%d_%H%M%S"), conversation.id);
+        let path = self.chat_dir.join(filename);
+        let (conversation_id, count) = (conversation.id, conversation.messages.len());
+        let json = match serde_json::to_string_pretty(&session) {
+            Ok(json) => json,
+            Err(e) => return self.set_error(format!("Cannot save chat: {}", e)),
+        };
+        self.disk.write(path, json, move |app, result| match result {
+            Ok(()) => {
+                if let Some(conversation) = app.conversation_by_id(conversation_id) {
+                    conversation.saved_messages = count;
+                }
+                app.notify(Severity::Success, "Chat saved".to_string());
+                if app.app_config.show_sidebar {
+                    app.load_chat_history();
+                }
+            }
+            Err(e) => app.set_error(format!("Cannot save chat: {}", e)),
+        });
+    }
+
+    /// Re-reads saved sessions in the background for the history list and sidebar, keeping a
+    /// valid highlight.
+    pub fn load_chat_history(&mut self) {
+        let (chat_dir, events) = (self.chat_dir.clone(), self.events.clone());
+        tokio::spawn(async move {
+            let Ok(history) = tokio::task::spawn_blocking(move || read_chat_history(&chat_dir)).await else { return };
+            events.update(move |app| {
+                app.chat_history = history;
+                let selected = app.history_list_state.selected().unwrap_or(0);
+                app.history_list_state.select(if app.chat_history.is_empty() { None } else { Some(selected.min(app.chat_history.len() - 1)) });
+            });
+        });
+    }
+
+    pub fn toggle_sidebar_focus(&mut self) {
+        if !self.app_config.show_sidebar {
+            self.toggle_panel(Panel::Sidebar);
+        }
+        self.sidebar_focus = !self.sidebar_focus;
+    }
+
+    pub fn load_selected_chat(&mut self) {
+        let Some(session) = self.history_list_state.selected().and_then(|i| self.chat_history.get(i)).cloned() else { return };
+        // Don't pull the transcript out from under a reply that is still streaming
+        if self.conversation().is_thinking {
+            self.new_conversation();
+        }
+        let conversation = self.conversation_mut();
+        conversation.clear();
+        conversation.messages = session.messages;
+        conversation.model = session.model;
+        conversation.saved_messages = conversation.messages.len();
+        conversation.overrides = session.overrides;
+        self.status_message = format!("Loaded chat from {}", session.timestamp);
+        self.switch_mode(AppMode::Chat);
+    }
+}
+
+/// Saved sessions in `chat_dir`, newest first; unreadable files are skipped.
+fn read_chat_history(chat_dir: &Path) -> Vec<ChatSession> {
+    let mut history: Vec<ChatSession> = fs::read_dir(chat_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+        .filter_map(|path| match migrate::load(&path) {
+            Ok(session) => Some(session),
+            Err(e) => {
+                tracing::warn!(path = %path.display(), "skipping saved chat: {:#}", e);
+                None
+            }
+        })
+        .collect();
+    history.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+    history
+}
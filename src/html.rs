@@ -0,0 +1,368 @@
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use std::fs;
+use std::path::Path;
+
+use crate::app::App;
+use crate::chat::Message;
+use crate::structured;
+
+/// Words shown as keywords in code blocks, across the languages models write most. There is no
+/// grammar behind it; it only has to make code easier to scan.
+const KEYWORDS: &[&str] = &[
+    "and", "as", "async", "await", "break", "case", "catch", "class", "const", "continue", "crate", "def", "default", "defer", "del", "do", "done",
+    "dyn", "elif", "else", "enum", "esac", "except", "export", "extends", "false", "False", "fi", "finally", "fn", "for", "from", "func", "function",
+    "go", "if", "impl", "import", "in", "interface", "is", "lambda", "let", "loop", "match", "mod", "move", "mut", "new", "nil", "None", "not", "null",
+    "or", "package", "pass", "private", "protected", "pub", "public", "raise", "ref", "return", "self", "Self", "static", "struct", "super", "switch",
+    "then", "this", "throw", "trait", "true", "True", "try", "type", "typeof", "undefined", "unsafe", "use", "var", "void", "where", "while",
+    "with", "yield",
+];
+
+/// Styles for the exported page, following the reader's light or dark preference.
+const STYLE: &str = "
+:root { --bg: #fdfdfc; --fg: #1f2328; --muted: #6e7781; --user: #0969da; --assistant: #1a7f37; --output: #8c959f; --code-bg: #f3f4f6; --kw: #cf222e; --str: #0a3069; --num: #0550ae; --com: #6e7781; }
+@media (prefers-color-scheme: dark) {
+  :root { --bg: #0d1117; --fg: #e6edf3; --muted: #8d96a0; --user: #4493f8; --assistant: #3fb950; --output: #6e7681; --code-bg: #161b22; --kw: #ff7b72; --str: #a5d6ff; --num: #79c0ff; --com: #8b949e; }
+}
+body { background: var(--bg); color: var(--fg); font: 16px/1.6 system-ui, sans-serif; max-width: 52rem; margin: 2rem auto; padding: 0 1rem; }
+header p, .sources, .tool-call { color: var(--muted); font-size: 0.9em; }
+section { border-left: 4px solid var(--assistant); padding: 0.25rem 1rem; margin: 1.5rem 0; }
+section.user { border-color: var(--user); }
+section.output, section.summary { border-color: var(--output); }
+h2 { font-size: 0.95em; margin: 0.25rem 0; }
+section.user h2 { color: var(--user); }
+section.assistant h2 { color: var(--assistant); }
+section.output h2, section.summary h2 { color: var(--muted); }
+pre { background: var(--code-bg); padding: 0.75rem 1rem; border-radius: 6px; overflow-x: auto; }
+code { font: 0.9em ui-monospace, SFMono-Regular, Menlo, monospace; }
+:not(pre) > code { background: var(--code-bg); padding: 0.1em 0.3em; border-radius: 4px; }
+blockquote { border-left: 3px solid var(--output); margin-left: 0; padding-left: 1rem; color: var(--muted); }
+table { border-collapse: collapse; }
+th, td { border: 1px solid var(--output); padding: 0.25rem 0.6rem; }
+img { max-width: 100%; border-radius: 6px; }
+.json-error { color: var(--kw); }
+.kw { color: var(--kw); } .str { color: var(--str); } .num { color: var(--num); } .com { color: var(--com); font-style: italic; }
+";
+
+/// The conversation as one HTML page with its styles inline, for reading without the app.
+/// `label` names whoever wrote each message.
+pub fn page(title: &str, model: &str, messages: &[Message], label: impl Fn(&Message) -> String) -> String {
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n<header>\n<h1>{}</h1>\n<p>{} · exported {}</p>\n</header>\n",
+        escape(title),
+        STYLE,
+        escape(title),
+        escape(model),
+        Local::now().format("%Y-%m-%d %H:%M")
+    );
+    for message in messages {
+        let class = match message.role.as_str() {
+            "user" => "user",
+            "summary" => "summary",
+            _ if message.is_output() => "output",
+            _ => "assistant",
+        };
+        out.push_str(&format!("<section class=\"{}\">\n<h2>{}</h2>\n", class, escape(&label(message))));
+        let pretty = message.json.then(|| serde_json::from_str::<serde_json::Value>(structured::reply_json(&message.content)).ok()).flatten();
+        match pretty {
+            Some(value) => out.push_str(&code_block(&serde_json::to_string_pretty(&value).unwrap_or_default(), "json")),
+            None if message.is_output() => out.push_str(&code_block(&message.content, "")),
+            None => out.push_str(&markdown(&message.content)),
+        }
+        for call in &message.tool_calls {
+            out.push_str(&format!("<p class=\"tool-call\">Tool call: <code>{}</code></p>\n", escape(&call.summary())));
+        }
+        for error in &message.json_errors {
+            out.push_str(&format!("<p class=\"json-error\">✗ {}</p>\n", escape(error)));
+        }
+        if !message.sources.is_empty() {
+            let sources: Vec<String> = message.sources.iter().enumerate().map(|(i, source)| format!("[{}] {}", i + 1, escape(source))).collect();
+            out.push_str(&format!("<p class=\"sources\">Sources: {}</p>\n", sources.join(" · ")));
+        }
+        for data in &message.images {
+            out.push_str(&format!("<img alt=\"Attached image\" src=\"data:{};base64,{}\">\n", image_type(data), data));
+        }
+        out.push_str("</section>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Markdown as HTML: headings, paragraphs, lists, quotes, tables, rules, and fenced code, with
+/// inline code, emphasis, and links. Raw HTML in the text is shown as text.
+pub fn markdown(text: &str) -> String {
+    let mut out = String::new();
+    let lines: Vec<&str> = text.lines().collect();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim();
+        if let Some(fence) = trimmed.strip_prefix("```") {
+            let end = lines[i + 1..].iter().position(|line| line.trim_start().starts_with("```")).map_or(lines.len(), |end| i + 1 + end);
+            out.push_str(&code_block(&lines[i + 1..end].join("\n"), fence.trim()));
+            i = end + 1;
+        } else if trimmed.is_empty() {
+            i += 1;
+        } else if let Some((level, heading)) = heading(trimmed) {
+            // h1 and h2 are the page's own
+            let level = (level + 2).min(6);
+            out.push_str(&format!("<h{}>{}</h{}>\n", level, inline(heading), level));
+            i += 1;
+        } else if matches!(trimmed, "---" | "***" | "___") {
+            out.push_str("<hr>\n");
+            i += 1;
+        } else if trimmed.starts_with('>') {
+            let end = block_end(&lines, i, |line| line.trim_start().starts_with('>'));
+            let quoted: Vec<&str> = lines[i..end].iter().map(|line| line.trim_start()[1..].strip_prefix(' ').unwrap_or(&line.trim_start()[1..])).collect();
+            out.push_str(&format!("<blockquote>\n{}</blockquote>\n", markdown(&quoted.join("\n"))));
+            i = end;
+        } else if trimmed.starts_with('|') && lines.get(i + 1).is_some_and(|next| is_table_rule(next)) {
+            let end = block_end(&lines, i, |line| line.trim_start().starts_with('|'));
+            out.push_str(&table(&lines[i..end]));
+            i = end;
+        } else if let Some((ordered, _)) = list_item(line) {
+            // Items run until a blank line or a line that is neither an item nor indented under one
+            let end = block_end(&lines, i, |line| list_item(line).is_some() || line.starts_with([' ', '\t']));
+            let tag = if ordered { "ol" } else { "ul" };
+            out.push_str(&format!("<{}>\n", tag));
+            let mut items: Vec<String> = Vec::new();
+            for line in &lines[i..end] {
+                match list_item(line) {
+                    Some((_, item)) => items.push(item.to_string()),
+                    None => {
+                        if let Some(last) = items.last_mut() {
+                            last.push(' ');
+                            last.push_str(line.trim());
+                        }
+                    }
+                }
+            }
+            for item in items {
+                out.push_str(&format!("<li>{}</li>\n", inline(&item)));
+            }
+            out.push_str(&format!("</{}>\n", tag));
+            i = end;
+        } else {
+            let end = block_end(&lines, i, |line| {
+                let trimmed = line.trim();
+                !trimmed.starts_with("```") && heading(trimmed).is_none() && !trimmed.starts_with('>') && list_item(line).is_none()
+            });
+            let paragraph: Vec<&str> = lines[i..end].iter().map(|line| line.trim()).collect();
+            out.push_str(&format!("<p>{}</p>\n", inline(&paragraph.join("\n")).replace('\n', "<br>\n")));
+            i = end;
+        }
+    }
+    out
+}
+
+/// `code` in a `<pre>` block, its keywords, strings, numbers, and comments marked for colouring.
+pub fn code_block(code: &str, language: &str) -> String {
+    let class = language.split(|c: char| c.is_whitespace() || c == ',').next().unwrap_or("");
+    let class = if class.is_empty() { String::new() } else { format!(" class=\"language-{}\"", escape(class)) };
+    format!("<pre><code{}>{}</code></pre>\n", class, highlight(code, language))
+}
+
+/// Marks up `code` token by token. Comments are `//` and `/* */` in C-like languages and `#`
+/// in scripting ones; `'` only quotes strings outside Rust, where it starts lifetimes. Code
+/// without a language is only escaped.
+pub fn highlight(code: &str, language: &str) -> String {
+    let language = language.to_ascii_lowercase();
+    if language.is_empty() || language == "text" {
+        return escape(code);
+    }
+    let hash_comments = matches!(language.as_str(), "python" | "py" | "sh" | "bash" | "zsh" | "shell" | "ruby" | "rb" | "yaml" | "yml" | "toml" | "perl" | "r" | "make" | "makefile" | "dockerfile" | "nix" | "elixir" | "powershell" | "ps1");
+    let dash_comments = matches!(language.as_str(), "sql" | "lua" | "haskell" | "hs");
+    let single_quotes = !matches!(language.as_str(), "rust" | "rs");
+    let chars: Vec<char> = code.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    let span = |out: &mut String, class: &str, text: &[char]| {
+        out.push_str(&format!("<span class=\"{}\">{}</span>", class, escape(&text.iter().collect::<String>())));
+    };
+    while i < chars.len() {
+        let c = chars[i];
+        let rest = &chars[i..];
+        let line_comment = if hash_comments { c == '#' } else if dash_comments { rest.starts_with(&['-', '-']) } else { rest.starts_with(&['/', '/']) };
+        if line_comment {
+            let end = rest.iter().position(|&c| c == '\n').map_or(chars.len(), |end| i + end);
+            span(&mut out, "com", &chars[i..end]);
+            i = end;
+        } else if !hash_comments && rest.starts_with(&['/', '*']) {
+            let end = (i + 2..chars.len().saturating_sub(1)).find(|&j| chars[j] == '*' && chars[j + 1] == '/').map_or(chars.len(), |end| end + 2);
+            span(&mut out, "com", &chars[i..end]);
+            i = end;
+        } else if c == '"' || c == '`' || (c == '\'' && single_quotes) {
+            let mut end = i + 1;
+            while end < chars.len() && chars[end] != c && chars[end] != '\n' {
+                end += if chars[end] == '\\' { 2 } else { 1 };
+            }
+            let end = (end + 1).min(chars.len());
+            span(&mut out, "str", &chars[i..end]);
+            i = end;
+        } else if c.is_ascii_digit() {
+            let end = rest.iter().position(|&c| !(c.is_ascii_alphanumeric() || c == '.' || c == '_')).map_or(chars.len(), |end| i + end);
+            span(&mut out, "num", &chars[i..end]);
+            i = end;
+        } else if c.is_alphabetic() || c == '_' {
+            let end = rest.iter().position(|&c| !(c.is_alphanumeric() || c == '_')).map_or(chars.len(), |end| i + end);
+            let word: String = chars[i..end].iter().collect();
+            if KEYWORDS.contains(&word.as_str()) {
+                span(&mut out, "kw", &chars[i..end]);
+            } else {
+                out.push_str(&escape(&word));
+            }
+            i = end;
+        } else {
+            out.push_str(&escape(&c.to_string()));
+            i += 1;
+        }
+    }
+    out
+}
+
+/// `text` with the characters HTML gives meaning to escaped.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Inline markdown: `code`, **bold**, *italic*, and [links](url).
+fn inline(text: &str) -> String {
+    // Code spans stand aside as numbered placeholders while the markers around them are
+    // replaced; escaping changes none of the marker characters
+    let mut spans = Vec::new();
+    let mut rest = String::new();
+    let parts: Vec<&str> = text.split('`').collect();
+    for (i, part) in parts.iter().enumerate() {
+        // A backtick without a partner is just a backtick
+        if i % 2 == 1 && i == parts.len() - 1 {
+            rest.push('`');
+            rest.push_str(&escape(part));
+        } else if i % 2 == 1 {
+            rest.push_str(&format!("\u{0}{}\u{0}", spans.len()));
+            spans.push(format!("<code>{}</code>", escape(part)));
+        } else {
+            rest.push_str(&escape(part));
+        }
+    }
+    let mut out = links(&emphasis(&emphasis(&rest, "**", "strong"), "*", "em"));
+    for (i, span) in spans.iter().enumerate() {
+        out = out.replacen(&format!("\u{0}{}\u{0}", i), span, 1);
+    }
+    out
+}
+
+/// Wraps text between pairs of `marker` in `tag`. A marker only opens before a non-space and
+/// closes after one, so `2 * 3 * 4` is left alone.
+fn emphasis(text: &str, marker: &str, tag: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(marker) {
+        let after = &rest[start + marker.len()..];
+        let close = after.find(marker).filter(|&end| end > 0 && !after.starts_with(' ') && !after[..end].ends_with(' '));
+        match close {
+            Some(end) => {
+                out.push_str(&format!("{}<{}>{}</{}>", &rest[..start], tag, &after[..end], tag));
+                rest = &after[end + marker.len()..];
+            }
+            None => {
+                out.push_str(&rest[..start + marker.len()]);
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn links(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('[') {
+        let link = rest[start..].find("](").and_then(|middle| {
+            let url_start = start + middle + 2;
+            rest[url_start..].find(')').map(|end| (start + middle, url_start, url_start + end))
+        });
+        match link {
+            // A script link would run in the reader's browser
+            Some((label_end, url_start, url_end)) if !rest[url_start..url_end].trim_start().to_ascii_lowercase().starts_with("javascript:") => {
+                out.push_str(&format!("{}<a href=\"{}\">{}</a>", &rest[..start], &rest[url_start..url_end], &rest[start + 1..label_end]));
+                rest = &rest[url_end + 1..];
+            }
+            _ => {
+                out.push_str(&rest[..=start]);
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let text = line[level..].strip_prefix(' ')?;
+    (1..=6).contains(&level).then_some((level, text))
+}
+
+/// Whether `line` starts a list item, whether the list is numbered, and the item's text.
+fn list_item(line: &str) -> Option<(bool, &str)> {
+    let trimmed = line.trim_start();
+    if let Some(item) = trimmed.strip_prefix("- ").or_else(|| trimmed.strip_prefix("* ")).or_else(|| trimmed.strip_prefix("+ ")) {
+        return Some((false, item));
+    }
+    let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+    let item = trimmed[digits..].strip_prefix(". ").or_else(|| trimmed[digits..].strip_prefix(") "))?;
+    (digits > 0).then_some((true, item))
+}
+
+/// The index of the first line from `start` on that is blank or fails `belongs`.
+fn block_end(lines: &[&str], start: usize, belongs: impl Fn(&str) -> bool) -> usize {
+    lines[start..].iter().position(|line| line.trim().is_empty() || !belongs(line)).map_or(lines.len(), |end| start + end.max(1))
+}
+
+fn is_table_rule(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with('|') && line.contains('-') && line.chars().all(|c| matches!(c, '|' | '-' | ':' | ' '))
+}
+
+fn table(lines: &[&str]) -> String {
+    let cells = |line: &str| -> Vec<String> { line.trim().trim_matches('|').split('|').map(|cell| inline(cell.trim())).collect() };
+    let mut out = String::from("<table>\n<thead><tr>");
+    for cell in cells(lines[0]) {
+        out.push_str(&format!("<th>{}</th>", cell));
+    }
+    out.push_str("</tr></thead>\n<tbody>\n");
+    for line in &lines[2..] {
+        out.push_str("<tr>");
+        for cell in cells(line) {
+            out.push_str(&format!("<td>{}</td>", cell));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</tbody>\n</table>\n");
+    out
+}
+
+/// The MIME type of a base64-encoded image, from the first bytes of its encoding.
+fn image_type(data: &str) -> &'static str {
+    match data.get(..4) {
+        Some("/9j/") => "image/jpeg",
+        Some("R0lG") => "image/gif",
+        Some("UklG") => "image/webp",
+        _ => "image/png",
+    }
+}
+
+impl App {
+    /// Writes the current conversation to `path` as a standalone HTML page.
+    pub fn export_html(&self, path: &Path) -> Result<String> {
+        let conversation = self.conversation();
+        if conversation.messages.is_empty() {
+            bail!("There is nothing to export yet");
+        }
+        let title = conversation.title();
+        let html = page(&title, &conversation.model, &conversation.messages, |message| self.message_label(message));
+        fs::write(path, html).with_context(|| format!("Cannot write {}", path.display()))?;
+        Ok(format!("Conversation exported to {}", path.display()))
+    }
+}
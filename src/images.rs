@@ -0,0 +1,107 @@
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ratatui::layout::Rect;
+use ratatui_image::{picker::{Picker, ProtocolType}, protocol::Protocol, Resize};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::Cursor;
+use std::path::Path;
+
+/// Rows an inline image occupies in the chat.
+pub const IMAGE_ROWS: u16 = 12;
+/// Widest an inline image is drawn, in columns.
+pub const IMAGE_COLS: u16 = 40;
+/// Rows of the placeholder box drawn where the terminal cannot show images.
+const PLACEHOLDER_ROWS: u16 = 3;
+
+/// Reads an image file and returns it base64-encoded, the form Ollama expects.
+pub fn encode_file(path: &Path) -> Result<String> {
+    let bytes = std::fs::read(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    image::guess_format(&bytes).map_err(|_| anyhow!("{} is not a supported image", path.display()))?;
+    Ok(STANDARD.encode(bytes))
+}
+
+/// MIME type of a base64-encoded image, for APIs that want it spelled out.
+pub fn mime_type(data: &str) -> Option<&'static str> {
+    let bytes = STANDARD.decode(data).ok()?;
+    Some(image::guess_format(&bytes).ok()?.to_mime_type())
+}
+
+/// Asks the terminal which graphics protocol it speaks; `None` means placeholders only.
+/// Writes to and reads from the tty, so call it before the event loop starts.
+#[cfg(unix)]
+pub fn detect_picker() -> Option<Picker> {
+    use std::io::IsTerminal;
+    // The graphics protocols are asked about and drawn through stdout
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    let mut picker = Picker::from_termios().ok()?;
+    match picker.guess_protocol() {
+        ProtocolType::Halfblocks => None,
+        _ => Some(picker),
+    }
+}
+
+#[cfg(not(unix))]
+pub fn detect_picker() -> Option<Picker> {
+    None
+}
+
+/// Images attached to messages, encoded for the terminal's graphics protocol on first draw.
+#[derive(Default)]
+pub struct InlineImages {
+    picker: Option<Picker>,
+    labels: HashMap<u64, String>,
+    /// Keyed by image and width; `None` records an image that failed to decode.
+    protocols: HashMap<(u64, u16), Option<Box<dyn Protocol>>>,
+}
+
+impl InlineImages {
+    pub fn set_picker(&mut self, picker: Option<Picker>) {
+        self.picker = picker;
+        self.protocols.clear();
+    }
+
+    /// Whether images are drawn, rather than shown as a placeholder box.
+    pub fn graphics(&self) -> bool {
+        self.picker.is_some()
+    }
+
+    /// Chat rows reserved for one image.
+    pub fn rows(&self) -> u16 {
+        if self.graphics() { IMAGE_ROWS } else { PLACEHOLDER_ROWS }
+    }
+
+    /// Short description for the placeholder, e.g. `image 640×480`.
+    pub fn label(&mut self, data: &str) -> &str {
+        self.labels.entry(key(data)).or_insert_with(|| {
+            let size = STANDARD.decode(data).ok().and_then(|bytes| {
+                image::ImageReader::new(Cursor::new(bytes)).with_guessed_format().ok()?.into_dimensions().ok()
+            });
+            match size {
+                Some((w, h)) => format!("image {}×{}", w, h),
+                None => "unreadable image".to_string(),
+            }
+        })
+    }
+
+    /// The image encoded to fit `width` columns, or `None` without graphics support.
+    pub fn protocol(&mut self, data: &str, width: u16) -> Option<&dyn Protocol> {
+        let picker = self.picker.as_mut()?;
+        self.protocols
+            .entry((key(data), width))
+            .or_insert_with(|| {
+                let bytes = STANDARD.decode(data).ok()?;
+                let image = image::load_from_memory(&bytes).ok()?;
+                picker.new_protocol(image, Rect::new(0, 0, width, IMAGE_ROWS), Resize::Fit(None)).ok()
+            })
+            .as_deref()
+    }
+}
+
+fn key(data: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
+}
@@ -0,0 +1,677 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::app::{App, AppMode, Failure, Panel, PendingAction};
+use crate::chat::{Message, ViewRow};
+use crate::commands::{Command, FimCommand, JsonCommand, KnowledgeCommand, RawCommand};
+use crate::error::{AppError, Retry};
+use crate::git::Diff;
+use crate::images;
+use crate::keymap::{Action, Key, KeyContext, Lookup};
+use crate::knowledge::KnowledgeBase;
+use crate::macros::{MacroPrompt, MacroStep};
+use crate::raw::RawTemplate;
+use crate::text;
+use crate::toast::Severity;
+use crate::tools;
+
+/// Lines of a code block shown when asking whether to run it.
+const CODE_PREVIEW_LINES: usize = 8;
+
+impl App {
+    /// Binding contexts for the current state, most specific first.
+    pub fn key_contexts(&self) -> Vec<KeyContext> {
+        if self.confirmation.is_some() {
+            return vec![KeyContext::Confirm];
+        }
+        if self.failure.is_some() {
+            return vec![KeyContext::Recovery, KeyContext::Global];
+        }
+        if self.show_help {
+            return vec![KeyContext::Help, KeyContext::Global];
+        }
+        if self.show_session_stats {
+            return vec![KeyContext::SessionStats, KeyContext::Global];
+        }
+        if self.message_menu.is_some() {
+            return vec![KeyContext::MessageMenu, KeyContext::Global];
+        }
+        if self.template_picker.is_some() {
+            return vec![KeyContext::TemplatePicker, KeyContext::Global];
+        }
+        if self.attempt_diff.is_some() {
+            return vec![KeyContext::AttemptDiff, KeyContext::Global];
+        }
+        if self.command_line.is_some() {
+            return vec![KeyContext::CommandLine, KeyContext::Global];
+        }
+        let mut contexts = match self.mode {
+            AppMode::Chat if self.sidebar_focus => vec![KeyContext::Sidebar, KeyContext::Chat],
+            AppMode::Chat if self.vim_mode && !self.vim_insert => vec![KeyContext::Normal, KeyContext::Chat],
+            AppMode::Chat => vec![KeyContext::Chat],
+            AppMode::ModelSelection => vec![KeyContext::ModelSelection],
+            AppMode::ModelDownload => vec![KeyContext::ModelDownload],
+            AppMode::SystemMonitor => vec![KeyContext::SystemMonitor],
+            AppMode::ChatHistory => vec![KeyContext::ChatHistory],
+            AppMode::ModelConfig => vec![KeyContext::ModelConfig],
+            AppMode::HostSelection => vec![KeyContext::HostSelection],
+            AppMode::PromptLibrary if self.prompt_draft.is_some() => vec![KeyContext::PromptEdit],
+            AppMode::PromptLibrary => vec![KeyContext::PromptLibrary],
+            AppMode::EventLog => vec![KeyContext::EventLog],
+            AppMode::McpServers => vec![KeyContext::McpServers],
+            AppMode::Stats => vec![KeyContext::Stats],
+        };
+        contexts.push(KeyContext::Global);
+        contexts
+    }
+
+    pub fn copy_to_clipboard(&mut self) {
+        if let Some(text) = &self.selected_text {
+            if let Ok(mut clipboard) = arboard::Clipboard::new() {
+                if clipboard.set_text(text.clone()).is_ok() {
+                    self.notify(Severity::Success, "Copied to clipboard".to_string());
+                } else {
+                    self.set_error("Failed to copy".to_string());
+                }
+            }
+        }
+    }
+
+    /// Appends the clipboard's text to the input, keeping its line breaks.
+    pub fn paste_from_clipboard(&mut self) {
+        match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get_text()) {
+            Ok(text) if !text.is_empty() => {
+                let text = text.replace("\r\n", "\n").replace('\r', "\n");
+                let lines = text.lines().count();
+                self.conversation_mut().input.push_str(&text);
+                if lines > 1 {
+                    self.status_message = format!("Pasted {} lines", lines);
+                }
+            }
+            Ok(_) => self.set_error("The clipboard has no text".to_string()),
+            Err(e) => self.set_error(format!("Cannot read the clipboard: {}", e)),
+        }
+    }
+
+    pub fn run_slash_command(&mut self) {
+        let input = std::mem::take(&mut self.conversation_mut().input);
+        match Command::parse(&input) {
+            Ok(command) => self.execute_command(command),
+            Err(e) => self.set_error(e.to_string()),
+        }
+    }
+
+    /// Asks before running what was typed after `!`; `!!` also attaches the output to the next
+    /// message.
+    pub fn run_shell_input(&mut self) {
+        let input = std::mem::take(&mut self.conversation_mut().input);
+        let (attach, command) = match input.strip_prefix("!!") {
+            Some(command) => (true, command.trim()),
+            None => (false, input.trim_start_matches('!').trim()),
+        };
+        if command.is_empty() {
+            self.set_error("Usage: !<command> to run it, or !!<command> to also send its output with your next message".to_string());
+            return;
+        }
+        let conversation_id = self.conversation().id;
+        let question = if attach { format!("Run `{}` and attach its output to your next message?", command) } else { format!("Run `{}`?", command) };
+        self.confirm(question, PendingAction::ShellCommand { conversation_id, command: command.to_string(), attach });
+    }
+
+    /// Asks before running the commands in the input's `{{$cmd ...}}` placeholders, whose
+    /// output takes their place before the prompt is sent.
+    pub fn run_prompt_commands(&mut self) {
+        let commands: Vec<String> = text::command_placeholders(&self.conversation().input).into_iter().map(String::from).collect();
+        self.confirm_command_output(commands);
+    }
+
+    fn confirm_command_output(&mut self, commands: Vec<String>) {
+        let listed: Vec<String> = commands.iter().map(|command| format!("`{}`", command)).collect();
+        let question = match commands.len() {
+            1 => format!("Run {} and put its output in the prompt?", listed[0]),
+            _ => format!("Run {} and put their output in the prompt?", listed.join(", ")),
+        };
+        let conversation_id = self.conversation().id;
+        self.confirm(question, PendingAction::InsertCommandOutput { conversation_id, commands });
+    }
+
+    /// Runs `commands` in the background and puts their output in the conversation's input,
+    /// leaving the prompt there to read over before it is sent.
+    pub(crate) fn insert_command_output(&mut self, conversation_id: usize, commands: Vec<String>) {
+        self.status_message = format!("Running {} command(s) for the prompt", commands.len());
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            let run = move || commands.into_iter().map(|command| (tools::command_output(&command), command)).collect::<Vec<_>>();
+            let outputs = tokio::task::spawn_blocking(run).await.unwrap_or_default();
+            events.update(move |app| {
+                let Some(conversation) = app.conversation_by_id(conversation_id) else { return };
+                for (output, command) in &outputs {
+                    conversation.input = text::insert_command_output(&conversation.input, command, output);
+                }
+                app.status_message = "Command output is in the prompt; press Enter to send it".to_string();
+            });
+        });
+    }
+
+    /// Asks before running code block `number` of the last reply, counting from 1, or the last
+    /// block that can be run when no number is given.
+    pub fn run_code_block(&mut self, number: Option<usize>) {
+        let Some(reply) = self.conversation().messages.iter().rev().find(|m| m.role == "assistant" && !m.content.is_empty()) else {
+            self.set_error("No reply to run code from".to_string());
+            return;
+        };
+        let blocks = text::code_blocks(&reply.content);
+        let found = match number {
+            Some(number) => blocks.get(number.wrapping_sub(1)).map(|block| (number, block)),
+            None => blocks.iter().enumerate().rev().find(|(_, block)| tools::can_run(&block.language)).map(|(i, block)| (i + 1, block)),
+        };
+        let Some((number, block)) = found else {
+            self.set_error(match number {
+                Some(_) => format!("The last reply has {} code block(s)", blocks.len()),
+                None => "The last reply has no sh, bash, zsh, python or rust code blocks".to_string(),
+            });
+            return;
+        };
+        if !tools::can_run(&block.language) {
+            let language = if block.language.is_empty() { "unlabelled" } else { block.language.as_str() };
+            self.set_error(format!("Can't run {} code; only sh, bash, zsh, python and rust blocks", language));
+            return;
+        }
+        let lines: Vec<&str> = block.code.lines().collect();
+        let mut question = format!("Run code block {} ({}, {} lines)?", number, block.language, lines.len());
+        for line in lines.iter().take(CODE_PREVIEW_LINES) {
+            question.push('\n');
+            question.push_str(line);
+        }
+        if lines.len() > CODE_PREVIEW_LINES {
+            question.push_str("\n…");
+        }
+        let (conversation_id, block) = (self.conversation().id, block.clone());
+        self.confirm(question, PendingAction::RunCode { conversation_id, number, block });
+    }
+
+    /// Runs an approved `!` command or code block off the UI thread and adds its output to the
+    /// chat under `heading`.
+    pub(crate) fn spawn_command<F>(&mut self, conversation_id: usize, heading: String, attach: bool, run: F)
+    where
+        F: FnOnce() -> String + Send + 'static,
+    {
+        self.status_message = format!("Running {}", heading);
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            let output = tokio::task::spawn_blocking(run).await.unwrap_or_else(|e| format!("Error: {}", e));
+            events.update(move |app| {
+                let Some(conversation) = app.conversation_by_id(conversation_id) else { return };
+                let message = Message::command_output(&heading, output);
+                if attach {
+                    conversation.pending_output.push(message.content.clone());
+                }
+                conversation.messages.push(message);
+                conversation.scroll_bottom();
+                app.status_message = if attach { format!("Ran {}; the output goes with your next message", heading) } else { format!("Ran {}", heading) };
+            });
+        });
+    }
+
+    /// Runs what was typed after `:`; it takes the same commands as `/`, plus a bare number.
+    pub fn run_command_line(&mut self) {
+        let Some(input) = self.command_line.take() else { return };
+        if input.trim().is_empty() {
+            return;
+        }
+        match Command::parse(&input) {
+            Ok(command) => self.execute_command(command),
+            Err(e) => self.set_error(e.to_string()),
+        }
+    }
+
+    pub fn execute_command(&mut self, command: Command) {
+        match command {
+            Command::Url { url, question } => self.ask_about_url(url, question),
+            Command::Run(number) => self.run_code_block(number),
+            Command::Fold(number) => self.toggle_code_fold(number),
+            Command::Compare => self.open_attempt_diff(),
+            Command::Sample(count) => self.sample_replies(count),
+            Command::CommitMessage(None) => self.ask_about_diff(
+                Diff::Staged,
+                "Write a commit message".to_string(),
+                "Write a commit message for the staged changes in the diff below. Follow the Conventional Commits format: a `type(scope): summary` subject line of at most 72 characters, then, if the reason for the change isn't obvious, a blank line and a short body saying what changed and why. Reply with only the commit message, in a single code block.".to_string(),
+            ),
+            Command::CommitMessage(Some(action)) => self.use_commit_message(action),
+            Command::Review(focus) => {
+                let mut task = "Review the changes in the diff below as an experienced code reviewer. Point out bugs, risky or breaking changes, missing error handling or tests, and unclear code, naming the file and line for each. Keep praise brief, and say so if the changes look ready.".to_string();
+                if let Some(focus) = &focus {
+                    task.push_str(&format!(" Pay particular attention to: {}", focus));
+                }
+                let shown = focus.map_or("Review the current changes".to_string(), |focus| format!("Review the current changes, focusing on {}", focus));
+                self.ask_about_diff(Diff::Working, shown, task);
+            }
+            Command::Json(None) => self.toggle_json_mode(),
+            Command::Json(Some(JsonCommand::Enable(enable))) => self.set_json_mode(enable),
+            Command::Json(Some(JsonCommand::Schema(path))) => {
+                if let Err(e) = self.load_json_schema(&path) {
+                    self.set_error(format!("{:#}", e));
+                }
+            }
+            Command::Raw(None) => self.toggle_raw_mode(),
+            Command::Raw(Some(RawCommand::Enable(enable))) => self.set_raw_mode(enable),
+            Command::Raw(Some(RawCommand::Template(template))) => self.set_raw_template(RawTemplate::Custom(template)),
+            Command::Fim(None) => self.toggle_fim_mode(),
+            Command::Fim(Some(FimCommand::Enable(enable))) => self.set_fim_mode(enable),
+            Command::Fim(Some(FimCommand::Region { path, start, end })) => {
+                if let Err(e) = self.load_fim_region(&path, start, end) {
+                    self.set_error(format!("{:#}", e));
+                }
+            }
+            Command::Knowledge(None) => {
+                self.status_message = match &self.knowledge {
+                    Some(base) => format!(
+                        "Knowledge base: {} passages from {} ({}), retrieval {}",
+                        base.passages.len(),
+                        base.root.display(),
+                        base.model,
+                        if self.app_config.knowledge_enabled { "on" } else { "off" }
+                    ),
+                    None => "No knowledge base; build one with /kb index <directory>".to_string(),
+                };
+            }
+            Command::Knowledge(Some(KnowledgeCommand::Index(path))) => self.index_knowledge(path),
+            Command::Knowledge(Some(KnowledgeCommand::Enable(_))) if self.knowledge.is_none() => {
+                self.set_error("No knowledge base; build one with /kb index <directory>".to_string());
+            }
+            Command::Knowledge(Some(KnowledgeCommand::Enable(enabled))) => {
+                self.app_config.knowledge_enabled = enabled;
+                self.save_app_config();
+                self.status_message = format!("Knowledge base retrieval {}", if enabled { "on" } else { "off" });
+            }
+            Command::Knowledge(Some(KnowledgeCommand::Clear)) => match KnowledgeBase::delete(&self.config_dir) {
+                Ok(()) => {
+                    self.knowledge = None;
+                    self.app_config.knowledge_enabled = false;
+                    self.save_app_config();
+                    self.status_message = "Knowledge base deleted".to_string();
+                }
+                Err(e) => self.set_error(format!("Cannot delete the knowledge base: {}", e)),
+            },
+            Command::Jump(number) => {
+                if self.conversation_mut().jump_to_message(number) {
+                    self.status_message = format!("Message {}", number);
+                } else {
+                    let count = self.conversation().messages.len();
+                    self.set_error(format!("No message {} (this chat has {})", number, count));
+                }
+            }
+            Command::Temp(Some(temperature)) => {
+                self.conversation_mut().overrides.temperature = Some(temperature);
+                self.status_message = format!("Session temperature set to {}", temperature);
+            }
+            Command::Temp(None) => {
+                self.conversation_mut().overrides.temperature = None;
+                self.status_message = format!("Session temperature reset to {}", self.model_config.temperature);
+            }
+            Command::System(Some(prompt)) => {
+                self.conversation_mut().overrides.system_prompt = Some(prompt);
+                self.conversation_mut().overrides.system_prompt_name = None;
+                self.conversation_mut().overrides.persona = None;
+                self.status_message = "Session system prompt set".to_string();
+            }
+            Command::EditSystem => {
+                let prompt = self.effective_config().system_prompt;
+                self.conversation_mut().input = format!("/system {}", prompt);
+                self.vim_insert = true;
+                self.status_message = "Edit the system prompt and press Enter to use it in this conversation".to_string();
+            }
+            Command::System(None) => {
+                self.conversation_mut().overrides.system_prompt = None;
+                self.conversation_mut().overrides.system_prompt_name = None;
+                self.status_message = "Session system prompt reset".to_string();
+            }
+            Command::Preset(Some(name)) => match self.apply_preset(&name) {
+                Ok(()) => {
+                    self.save_config();
+                    self.notify(Severity::Success, format!("Preset '{}' applied", name));
+                }
+                Err(e) => self.set_error(e.to_string()),
+            },
+            Command::Persona(Some(name)) if name.eq_ignore_ascii_case("off") => self.clear_persona(),
+            Command::Persona(Some(name)) => match self.apply_persona(&name) {
+                Ok(()) => {
+                    let persona = self.conversation().overrides.persona.clone().unwrap_or(name);
+                    self.notify(Severity::Success, format!("Persona '{}' in use for this conversation", persona));
+                }
+                Err(e) => self.set_error(e.to_string()),
+            },
+            Command::Persona(None) => {
+                let names: Vec<&str> = self.app_config.personas.iter().map(|p| p.name.as_str()).collect();
+                self.status_message = format!("Personas: {} (Alt+R cycles them)", names.join(", "));
+            }
+            Command::Template(Some(name)) => {
+                if let Err(e) = self.apply_template(&name) {
+                    self.set_error(e.to_string());
+                }
+            }
+            Command::Template(None) => {
+                let names: Vec<&str> = self.app_config.templates.iter().map(|t| t.name.as_str()).collect();
+                self.status_message = format!("Templates: {} (offered on Ctrl+T)", names.join(", "));
+            }
+            Command::Width(width) => {
+                self.app_config.chat_width = width;
+                self.save_app_config();
+                self.status_message = match width {
+                    Some(width) => format!("Chat width limited to {} columns", width),
+                    None => "Chat uses the full terminal width".to_string(),
+                };
+            }
+            Command::Image(Some(path)) => match images::encode_file(&path) {
+                Ok(data) => {
+                    let conversation = self.conversation_mut();
+                    conversation.pending_images.push(data);
+                    let count = conversation.pending_images.len();
+                    self.status_message = format!("{} image(s) attached to the next message", count);
+                }
+                Err(e) => self.set_error(e.to_string()),
+            },
+            Command::Image(None) => {
+                self.conversation_mut().pending_images.clear();
+                self.status_message = "Attached images removed".to_string();
+            }
+            Command::Export(path) => {
+                let path = path.unwrap_or_else(|| format!("chat-{}.html", chrono::Local::now().format("%Y%m%d-%H%M%S")).into());
+                if path.exists() {
+                    self.confirm(format!("Overwrite {}?", path.display()), PendingAction::ExportHtml(path));
+                } else {
+                    self.run_pending_action(PendingAction::ExportHtml(path));
+                }
+            }
+            Command::RunInsert(command) => self.confirm_command_output(vec![command]),
+            Command::Hardcopy => {
+                self.print_on_exit = true;
+                self.status_message = "The conversation open when you quit will be printed to stdout".to_string();
+            }
+            Command::ExportConfig(path) => {
+                if path.exists() {
+                    self.confirm(format!("Overwrite {}?", path.display()), PendingAction::ExportConfig(path));
+                } else {
+                    self.run_pending_action(PendingAction::ExportConfig(path));
+                }
+            }
+            Command::ImportConfig(path) => {
+                self.confirm(format!("Replace all settings with {}?", path.display()), PendingAction::ImportConfig(path));
+            }
+            Command::ResetConfig => {
+                self.confirm("Reset all settings (hosts, presets, prompts, model parameters) to defaults?".to_string(), PendingAction::ResetConfig);
+            }
+            Command::Stats => self.show_session_stats = true,
+            Command::ClearQueue => {
+                let dropped = std::mem::take(&mut self.conversation_mut().queued).len();
+                self.status_message = format!("Dropped {} queued prompt(s)", dropped);
+            }
+            Command::Preset(None) => {
+                let names: Vec<&str> = self.app_config.presets.iter().map(|p| p.name.as_str()).collect();
+                self.status_message = format!("Presets: {}", names.join(", "));
+            }
+            Command::Tools(Some(enabled)) => {
+                self.app_config.tools_enabled = enabled;
+                self.status_message = match (enabled, self.backend.supports_tools()) {
+                    (true, false) => "Tool calling on, but this host does not support it".to_string(),
+                    (true, true) => "Tool calling on".to_string(),
+                    (false, _) => "Tool calling off".to_string(),
+                };
+                self.save_app_config();
+            }
+            Command::Tools(None) => {
+                let names: Vec<String> = self.tools.specs().into_iter().map(|spec| spec.name).collect();
+                let state = if self.app_config.tools_enabled { "on" } else { "off" };
+                self.status_message = format!("Tools ({}): {}", state, names.join(", "));
+            }
+        }
+    }
+}
+
+/// Routes a key press through the keymap; keys with no binding go to the focused text input.
+/// Returns `false` when the app should quit.
+pub async fn handle_key(app: &mut App, event: KeyEvent) -> bool {
+    let key = Key::from(event);
+    tracing::trace!(key = %key, "key");
+    // The key after `q` or `@` names a macro register
+    if let Some(prompt) = app.macros.awaiting.take() {
+        return match (key.code, prompt) {
+            (KeyCode::Char(register), MacroPrompt::Record) => { app.start_macro_recording(register); true }
+            (KeyCode::Char(register), MacroPrompt::Replay) => replay_macro(app, register).await,
+            _ => { app.status_message = "Macro cancelled".into(); true }
+        };
+    }
+    let contexts = app.key_contexts();
+    app.pending_keys.push(key);
+    let keys = app.pending_keys.clone();
+    match app.keymap.lookup(&contexts, &keys) {
+        Lookup::Action(action) => {
+            app.pending_keys.clear();
+            return run_step(app, MacroStep::Action(action)).await;
+        }
+        Lookup::Pending => return true,
+        Lookup::None => app.pending_keys.clear(),
+    }
+
+    // A dead-end sequence such as `g x` retries its last key on its own
+    if keys.len() > 1 {
+        if let Lookup::Action(action) = app.keymap.lookup(&contexts, &[key]) {
+            return run_step(app, MacroStep::Action(action)).await;
+        }
+    }
+
+    run_step(app, MacroStep::Text(key)).await
+}
+
+/// Does one action or types one key, adding it to the macro being recorded.
+async fn run_step(app: &mut App, step: MacroStep) -> bool {
+    app.macros.record(step);
+    match step {
+        MacroStep::Action(action) => perform(app, action).await,
+        MacroStep::Text(key) => { insert_text(app, key); true }
+    }
+}
+
+/// Runs the steps recorded in `register` as if their keys were pressed again.
+async fn replay_macro(app: &mut App, register: char) -> bool {
+    let Some((register, steps)) = app.macros.steps(register) else {
+        app.set_error(match register {
+            '@' => "No macro has been replayed yet".to_string(),
+            _ => format!("Register @{} holds no macro", register),
+        });
+        return true;
+    };
+    for &step in &steps {
+        if !run_step(app, step).await {
+            return false;
+        }
+    }
+    app.status_message = format!("Replayed @{} ({} step(s))", register, steps.len());
+    true
+}
+
+fn insert_text(app: &mut App, key: Key) {
+    if key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) || app.confirmation.is_some() || app.failure.is_some() || app.show_help || app.show_session_stats || app.message_menu.is_some() || app.template_picker.is_some() || app.attempt_diff.is_some() || app.sidebar_focus {
+        return;
+    }
+    if let Some(command) = app.command_line.as_mut() {
+        match key.code {
+            KeyCode::Char(c) => command.push(c),
+            // Backspace on an empty command line closes it, as in vim
+            KeyCode::Backspace if command.is_empty() => app.command_line = None,
+            KeyCode::Backspace => { command.pop(); }
+            _ => {}
+        }
+        return;
+    }
+    let target = match app.mode {
+        AppMode::Chat if !app.vim_mode || app.vim_insert => &mut app.conversation_mut().input,
+        AppMode::ModelDownload => &mut app.download_input,
+        AppMode::SystemMonitor => { app.process_selected = 0; &mut app.process_filter }
+        AppMode::ModelConfig => { app.config_error = None; &mut app.config_input }
+        AppMode::PromptLibrary => match app.prompt_draft.as_mut() { Some(draft) => &mut draft.input, None => return },
+        _ => return,
+    };
+    match key.code {
+        KeyCode::Char(c) => target.push(c),
+        KeyCode::Backspace => { target.pop(); }
+        _ => {}
+    }
+}
+
+async fn perform(app: &mut App, action: Action) -> bool {
+    // Any key bound on the error popup closes it
+    let failure = app.failure.take();
+    match action {
+        Action::Quit => return false,
+        Action::ToggleHelp => { app.show_help = !app.show_help; app.help_scroll = 0; }
+        Action::ToggleDebug => { app.show_debug = !app.show_debug; }
+        Action::OpenModels => {
+            // The list opens once it arrives, unless something else was opened meanwhile
+            let mode = app.mode;
+            app.fetch_models_then(move |app, result| {
+                if let Err(e) = result {
+                    app.fail("Cannot list models", e, Some(Retry::ListModels));
+                }
+                if app.mode == mode {
+                    app.switch_mode(AppMode::ModelSelection);
+                }
+            });
+        }
+        Action::OpenDownload => { app.switch_mode(AppMode::ModelDownload); }
+        Action::OpenMonitor => { app.switch_mode(AppMode::SystemMonitor); }
+        Action::OpenHistory => { app.load_chat_history(); app.history_list_state.select(Some(0)); app.switch_mode(AppMode::ChatHistory); }
+        Action::OpenConfig => { app.config_input = app.get_current_config_value(); app.switch_mode(AppMode::ModelConfig); }
+        Action::OpenHosts => { app.switch_mode(AppMode::HostSelection); }
+        Action::OpenPrompts => { app.switch_mode(AppMode::PromptLibrary); }
+        Action::OpenEventLog => { app.switch_mode(AppMode::EventLog); }
+        Action::OpenMcpServers => { app.switch_mode(AppMode::McpServers); }
+        Action::OpenStats => { app.switch_mode(AppMode::Stats); }
+        Action::SaveChat => { app.save_current_chat(); }
+        Action::ClearChat => { app.clear_chat(); }
+        Action::SelectLastMessage => { app.select_last_message(); }
+        Action::SelectPrevMessage => { app.move_message_selection(false); }
+        Action::SelectNextMessage => { app.move_message_selection(true); }
+        Action::SelectTopMessage => { app.select_message_in_view(ViewRow::Top); }
+        Action::SelectMiddleMessage => { app.select_message_in_view(ViewRow::Middle); }
+        Action::SelectBottomMessage => { app.select_message_in_view(ViewRow::Bottom); }
+        Action::OpenMessageMenu => { app.open_message_menu(); }
+        Action::QuoteMessage => { app.quote_selected_message(); }
+        Action::CopySelection => { app.copy_to_clipboard(); }
+        Action::Paste => { app.paste_from_clipboard(); }
+        Action::Send => {
+            match app.conversation().input.chars().next() {
+                Some('/') => app.run_slash_command(),
+                Some('!') => app.run_shell_input(),
+                _ if !text::command_placeholders(&app.conversation().input).is_empty() => app.run_prompt_commands(),
+                _ => app.start_message_stream(),
+            }
+        }
+        Action::NewLine => { if !app.vim_mode || app.vim_insert { app.conversation_mut().input.push('\n'); } }
+        Action::ScrollUp if app.show_help => { app.help_scroll = app.help_scroll.saturating_sub(1); }
+        Action::ScrollDown if app.show_help => { app.help_scroll = app.help_scroll.saturating_add(1); }
+        Action::ScrollUp if app.attempt_diff.is_some() => { if let Some(diff) = app.attempt_diff.as_mut() { diff.scroll = diff.scroll.saturating_sub(1); } }
+        Action::ScrollDown if app.attempt_diff.is_some() => { if let Some(diff) = app.attempt_diff.as_mut() { diff.scroll = diff.scroll.saturating_add(1); } }
+        Action::ScrollUp => { app.conversation_mut().scroll_up(1); }
+        Action::ScrollDown => { app.conversation_mut().scroll_down(1); }
+        Action::HalfPageUp => { let rows = (app.conversation().page_height() / 2).max(1); app.conversation_mut().scroll_up(rows); }
+        Action::HalfPageDown => { let rows = (app.conversation().page_height() / 2).max(1); app.conversation_mut().scroll_down(rows); }
+        Action::PageUp => { let rows = app.conversation().page_height(); app.conversation_mut().scroll_up(rows); }
+        Action::PageDown => { let rows = app.conversation().page_height(); app.conversation_mut().scroll_down(rows); }
+        Action::ScrollTop => { app.conversation_mut().scroll_top(); }
+        Action::ScrollBottom => { app.conversation_mut().scroll_bottom(); }
+        Action::PrevMessageStart => { app.conversation_mut().jump_to_boundary(false); }
+        Action::NextMessageStart => { app.conversation_mut().jump_to_boundary(true); }
+        Action::NormalMode => { if app.vim_mode { app.vim_insert = false; app.status_message = "Normal mode".into(); } }
+        Action::InsertMode => { app.vim_insert = true; app.status_message = "Insert mode".into(); }
+        Action::Back if app.show_session_stats => { app.show_session_stats = false; }
+        Action::Back if app.message_menu.is_some() => { app.message_menu = None; }
+        Action::ListUp if app.message_menu.is_some() => { app.move_menu_selection(false); }
+        Action::ListDown if app.message_menu.is_some() => { app.move_menu_selection(true); }
+        Action::Select if app.message_menu.is_some() => { app.run_message_menu(); }
+        Action::Back if app.template_picker.is_some() => { app.template_picker = None; }
+        Action::ListUp if app.template_picker.is_some() => { app.move_template_selection(false); }
+        Action::ListDown if app.template_picker.is_some() => { app.move_template_selection(true); }
+        Action::Select if app.template_picker.is_some() => { app.choose_template(); }
+        Action::Back if app.attempt_diff.is_some() => { app.attempt_diff = None; }
+        Action::Back if app.command_line.is_some() => { app.command_line = None; }
+        Action::Select if app.command_line.is_some() => { app.run_command_line(); }
+        Action::Back if app.mode == AppMode::SystemMonitor && !app.process_filter.is_empty() => { app.process_filter.clear(); }
+        Action::Back => match app.mode {
+            AppMode::ModelDownload => { app.download_input.clear(); app.switch_mode(AppMode::Chat); }
+            AppMode::ModelConfig => { app.config_error = None; app.switch_mode(AppMode::Chat); }
+            AppMode::PromptLibrary if app.prompt_draft.is_some() => { app.prompt_draft = None; }
+            _ => { app.switch_mode(AppMode::Chat); }
+        },
+        Action::ListUp => { app.move_selection(false); }
+        Action::ListDown => { app.move_selection(true); }
+        Action::Select => match app.mode {
+            AppMode::ModelSelection => { if let Some(selected) = app.model_list_state.selected() { if let Some(model) = app.available_models.get(selected).cloned() { app.select_model(model); app.switch_mode(AppMode::Chat); } } }
+            AppMode::ModelDownload => { let model_name = std::mem::take(&mut app.download_input); app.download_model(model_name, None); app.switch_mode(AppMode::Chat); }
+            AppMode::ChatHistory => { app.load_selected_chat(); }
+            AppMode::Chat if app.sidebar_focus => { app.load_selected_chat(); }
+            AppMode::ModelConfig => { let value = app.config_input.clone(); if app.update_config_field(value) { app.save_config(); app.config_input = app.get_current_config_value(); } }
+            AppMode::HostSelection => {
+                if let Some(selected) = app.host_list_state.selected() {
+                    if let Err(e) = app.switch_host(selected) { app.set_error(format!("Error: {}", e)); }
+                    app.switch_mode(AppMode::Chat);
+                }
+            }
+            AppMode::PromptLibrary if app.prompt_draft.is_some() => { if let Err(e) = app.commit_prompt_draft() { app.set_error(format!("Error: {}", e)); } }
+            AppMode::PromptLibrary => { app.use_selected_prompt(); }
+            AppMode::McpServers => { app.toggle_selected_mcp_server(); }
+            _ => {}
+        },
+        Action::NewItem => { app.start_prompt_draft(false); }
+        Action::EditItem => { app.start_prompt_draft(true); }
+        Action::DeleteItem => { if let Err(e) = app.delete_selected_prompt() { app.set_error(format!("Error: {}", e)); } }
+        Action::PrevField => { app.prev_config_field(); app.config_input = app.get_current_config_value(); app.config_error = None; }
+        Action::NextField => { app.next_config_field(); app.config_input = app.get_current_config_value(); app.config_error = None; }
+        Action::Decrease => { app.adjust_config_field(-1.0); app.save_config(); }
+        Action::Increase => { app.adjust_config_field(1.0); app.save_config(); }
+        Action::ConfirmYes => { app.answer_confirmation(true); }
+        Action::ConfirmNo => { app.answer_confirmation(false); }
+        Action::Retry => { if let Some(retry) = failure.and_then(|f| f.retry) { app.retry(retry); } }
+        Action::PullModel => {
+            if let Some(Failure { error: AppError::ModelMissing(model), retry, .. }) = failure {
+                // With the model in place, what failed for want of it can go again
+                app.download_model(model, retry);
+            }
+        }
+        Action::DismissError => {}
+        Action::ToggleTitleBar => { app.toggle_panel(Panel::TitleBar); }
+        Action::ToggleStatusBar => { app.toggle_panel(Panel::StatusBar); }
+        Action::ToggleInput => { app.toggle_panel(Panel::Input); }
+        Action::GrowInput => { app.resize_input(1); }
+        Action::ShrinkInput => { app.resize_input(-1); }
+        Action::ToggleSidebar => { app.toggle_panel(Panel::Sidebar); }
+        Action::FocusSidebar => { app.toggle_sidebar_focus(); }
+        Action::NewTab => { app.new_conversation(); app.open_template_picker(); }
+        Action::NextTab => { app.cycle_conversation(true); }
+        Action::PrevTab => { app.cycle_conversation(false); }
+        Action::CloseTab => { app.close_conversation(); }
+        Action::ToggleSplit => { app.toggle_split(); }
+        Action::SwitchPane => { app.switch_pane(); }
+        Action::OpenCommandLine => { app.command_line = Some(String::new()); }
+        Action::ToggleMessageNumbers => { app.toggle_message_numbers(); }
+        Action::ToggleSystemPrompt => { app.toggle_system_prompt(); }
+        Action::NextPersona => { app.next_persona(); }
+        Action::ToggleJson => { app.toggle_json_mode(); }
+        Action::ToggleRaw => { app.toggle_raw_mode(); }
+        Action::ToggleDensity => { app.toggle_density(); }
+        Action::ToggleOutput => { app.toggle_output(); }
+        Action::ToggleReply => { app.toggle_reply(); }
+        Action::ToggleCodeFold => { app.toggle_code_fold(None); }
+        Action::CompareAttempts => { app.open_attempt_diff(); }
+        Action::PrevAttempt if app.attempt_diff.is_some() => { app.move_attempt_diff(false); }
+        Action::NextAttempt if app.attempt_diff.is_some() => { app.move_attempt_diff(true); }
+        Action::PrevAttempt => { app.cycle_reply(false); }
+        Action::NextAttempt => { app.cycle_reply(true); }
+        Action::RunCodeBlock => { app.run_code_block(None); }
+        Action::RecordMacro => { app.toggle_macro_recording(); }
+        Action::ReplayMacro => { app.macros.awaiting = Some(MacroPrompt::Replay); app.status_message = "Replay the macro in register: a-z, 0-9, or @ for the last".into(); }
+        Action::ToggleOllamaOnly => { app.toggle_ollama_only(); }
+        Action::TerminateProcess => { app.end_selected_process(false); }
+        Action::KillProcess => { app.end_selected_process(true); }
+    }
+    true
+}
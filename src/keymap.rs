@@ -0,0 +1,660 @@
+use anyhow::{bail, Context, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, path::Path};
+
+pub const KEYMAP_FILE: &str = "keymap.json";
+
+/// Everything a key can be bound to. Text typed into input boxes is not an action; keys
+/// without a binding fall through to the focused input.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Quit,
+    ToggleHelp,
+    ToggleDebug,
+    OpenModels,
+    OpenDownload,
+    OpenMonitor,
+    OpenHistory,
+    OpenConfig,
+    OpenHosts,
+    OpenPrompts,
+    SaveChat,
+    ClearChat,
+    SelectLastMessage,
+    SelectPrevMessage,
+    SelectNextMessage,
+    SelectTopMessage,
+    SelectMiddleMessage,
+    SelectBottomMessage,
+    OpenMessageMenu,
+    QuoteMessage,
+    CopySelection,
+    Paste,
+    Send,
+    NewLine,
+    ScrollUp,
+    ScrollDown,
+    ScrollTop,
+    ScrollBottom,
+    PrevMessageStart,
+    NextMessageStart,
+    HalfPageUp,
+    HalfPageDown,
+    PageUp,
+    PageDown,
+    NormalMode,
+    InsertMode,
+    Back,
+    ListUp,
+    ListDown,
+    Select,
+    NewItem,
+    EditItem,
+    DeleteItem,
+    PrevField,
+    NextField,
+    Decrease,
+    Increase,
+    ConfirmYes,
+    ConfirmNo,
+    Retry,
+    PullModel,
+    DismissError,
+    ToggleTitleBar,
+    ToggleStatusBar,
+    ToggleInput,
+    GrowInput,
+    ShrinkInput,
+    ToggleSidebar,
+    FocusSidebar,
+    NewTab,
+    NextTab,
+    PrevTab,
+    CloseTab,
+    ToggleSplit,
+    SwitchPane,
+    OpenEventLog,
+    OpenMcpServers,
+    OpenStats,
+    OpenCommandLine,
+    ToggleMessageNumbers,
+    ToggleSystemPrompt,
+    NextPersona,
+    ToggleJson,
+    ToggleRaw,
+    ToggleDensity,
+    ToggleOutput,
+    ToggleReply,
+    ToggleCodeFold,
+    CompareAttempts,
+    PrevAttempt,
+    NextAttempt,
+    RunCodeBlock,
+    RecordMacro,
+    ReplayMacro,
+    ToggleOllamaOnly,
+    TerminateProcess,
+    KillProcess,
+}
+
+impl Action {
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::ToggleHelp => "Show/hide this help",
+            Action::ToggleDebug => "Show/hide recent log lines and frame timing",
+            Action::OpenModels => "Select model",
+            Action::OpenDownload => "Download model",
+            Action::OpenMonitor => "System monitor",
+            Action::OpenHistory => "Chat history",
+            Action::OpenConfig => "Model configuration",
+            Action::OpenHosts => "Switch Ollama host",
+            Action::OpenPrompts => "System prompt library",
+            Action::SaveChat => "Save chat",
+            Action::ClearChat => "Clear chat",
+            Action::SelectLastMessage => "Select last message",
+            Action::SelectPrevMessage => "Select the previous message",
+            Action::SelectTopMessage => "Select the message at the top of the view",
+            Action::SelectMiddleMessage => "Select the message in the middle of the view",
+            Action::SelectBottomMessage => "Select the message at the bottom of the view",
+            Action::SelectNextMessage => "Select the next message",
+            Action::OpenMessageMenu => "Quick actions for the selected message",
+            Action::QuoteMessage => "Quote the selected message (or the last reply) into the input",
+            Action::CopySelection => "Copy selection to clipboard",
+            Action::Paste => "Paste clipboard into the input",
+            Action::Send => "Send message / run /command",
+            Action::NewLine => "Start a new line in the message",
+            Action::ScrollUp => "Scroll up",
+            Action::ScrollDown => "Scroll down",
+            Action::ScrollTop => "Scroll to top",
+            Action::ScrollBottom => "Scroll to bottom",
+            Action::PrevMessageStart => "Scroll to the start of the previous message",
+            Action::NextMessageStart => "Scroll to the start of the next message",
+            Action::HalfPageUp => "Scroll up half a page",
+            Action::HalfPageDown => "Scroll down half a page",
+            Action::PageUp => "Scroll up a page",
+            Action::PageDown => "Scroll down a page",
+            Action::NormalMode => "Vim normal mode",
+            Action::InsertMode => "Vim insert mode",
+            Action::Back => "Back / cancel",
+            Action::ListUp => "Previous item",
+            Action::ListDown => "Next item",
+            Action::Select => "Select / apply",
+            Action::NewItem => "New",
+            Action::EditItem => "Edit",
+            Action::DeleteItem => "Delete",
+            Action::PrevField => "Previous field",
+            Action::NextField => "Next field",
+            Action::Decrease => "Decrease value",
+            Action::Increase => "Increase value",
+            Action::ConfirmYes => "Confirm",
+            Action::ConfirmNo => "Cancel",
+            Action::Retry => "Retry what failed",
+            Action::PullModel => "Download the missing model",
+            Action::DismissError => "Close the error popup",
+            Action::ToggleTitleBar => "Show/hide title bar",
+            Action::ToggleStatusBar => "Show/hide status bar",
+            Action::ToggleInput => "Show/hide input pane",
+            Action::GrowInput => "Taller input pane",
+            Action::ShrinkInput => "Shorter input pane",
+            Action::ToggleSidebar => "Show/hide session sidebar",
+            Action::FocusSidebar => "Move focus between chat and sidebar",
+            Action::NewTab => "New conversation tab",
+            Action::NextTab => "Next tab",
+            Action::PrevTab => "Previous tab",
+            Action::CloseTab => "Close tab",
+            Action::ToggleSplit => "Split view with a second conversation",
+            Action::SwitchPane => "Focus the other split pane",
+            Action::OpenEventLog => "Event log",
+            Action::OpenMcpServers => "MCP servers",
+            Action::OpenStats => "Usage statistics",
+            Action::OpenCommandLine => "Command line (:N jumps to message N)",
+            Action::ToggleMessageNumbers => "Show/hide message numbers",
+            Action::ToggleSystemPrompt => "Expand/collapse the system prompt above the chat",
+            Action::NextPersona => "Switch the conversation to the next persona",
+            Action::ToggleJson => "Ask for replies as JSON, or stop",
+            Action::ToggleRaw => "Send prompts raw, through the raw template instead of the model's",
+            Action::ToggleDensity => "Compact/comfortable chat layout",
+            Action::ToggleOutput => "Expand/collapse command and tool output",
+            Action::ToggleReply => "Expand/collapse the selected (or last) long reply",
+            Action::ToggleCodeFold => "Fold/unfold the code blocks of the selected (or last) reply",
+            Action::CompareAttempts => "Compare the selected (or last) regenerated reply with the attempt before it",
+            Action::PrevAttempt => "Show the earlier version of the last reply (or compare earlier attempts)",
+            Action::NextAttempt => "Show the later version of the last reply (or compare later attempts)",
+            Action::RunCodeBlock => "Run the last code block in the reply",
+            Action::RecordMacro => "Record a macro into a register, or stop recording",
+            Action::ReplayMacro => "Replay the macro in a register (@@ repeats the last)",
+            Action::ToggleOllamaOnly => "Show only Ollama processes",
+            Action::TerminateProcess => "Terminate the selected process (SIGTERM)",
+            Action::KillProcess => "Kill the selected process (SIGKILL)",
+        }
+    }
+}
+
+/// Where a binding applies. Several contexts can be active at once (e.g. `Normal`, `Chat`, `Global`).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyContext {
+    Global,
+    Chat,
+    Normal,
+    ModelSelection,
+    ModelDownload,
+    SystemMonitor,
+    ChatHistory,
+    ModelConfig,
+    HostSelection,
+    PromptLibrary,
+    PromptEdit,
+    EventLog,
+    McpServers,
+    Stats,
+    Sidebar,
+    CommandLine,
+    Confirm,
+    Recovery,
+    Help,
+    SessionStats,
+    MessageMenu,
+    TemplatePicker,
+    AttemptDiff,
+}
+
+impl KeyContext {
+    pub const ALL: [KeyContext; 23] = [
+        KeyContext::Global,
+        KeyContext::Chat,
+        KeyContext::Normal,
+        KeyContext::ModelSelection,
+        KeyContext::ModelDownload,
+        KeyContext::SystemMonitor,
+        KeyContext::ChatHistory,
+        KeyContext::ModelConfig,
+        KeyContext::HostSelection,
+        KeyContext::PromptLibrary,
+        KeyContext::PromptEdit,
+        KeyContext::EventLog,
+        KeyContext::McpServers,
+        KeyContext::Stats,
+        KeyContext::Sidebar,
+        KeyContext::CommandLine,
+        KeyContext::Confirm,
+        KeyContext::Recovery,
+        KeyContext::Help,
+        KeyContext::SessionStats,
+        KeyContext::MessageMenu,
+        KeyContext::TemplatePicker,
+        KeyContext::AttemptDiff,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            KeyContext::Global => "Everywhere",
+            KeyContext::Chat => "Chat",
+            KeyContext::Normal => "Chat (vim normal mode)",
+            KeyContext::ModelSelection => "Model selection",
+            KeyContext::ModelDownload => "Model download",
+            KeyContext::SystemMonitor => "System monitor",
+            KeyContext::ChatHistory => "Chat history",
+            KeyContext::ModelConfig => "Model configuration",
+            KeyContext::HostSelection => "Host selection",
+            KeyContext::PromptLibrary => "System prompt library",
+            KeyContext::PromptEdit => "System prompt library (editing)",
+            KeyContext::EventLog => "Event log",
+            KeyContext::McpServers => "MCP servers",
+            KeyContext::Stats => "Usage statistics",
+            KeyContext::Sidebar => "Session sidebar (focused)",
+            KeyContext::CommandLine => "Command line",
+            KeyContext::Confirm => "Confirmation popup",
+            KeyContext::Recovery => "Error popup",
+            KeyContext::Help => "Help",
+            KeyContext::SessionStats => "Conversation statistics (:stats)",
+            KeyContext::MessageMenu => "Message quick actions",
+            KeyContext::TemplatePicker => "Template picker (new chat)",
+            KeyContext::AttemptDiff => "Comparing attempts at a reply",
+        }
+    }
+}
+
+/// A single key press, normalized so `G` and `Shift+G` compare equal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Self {
+        Key::new(event.code, event.modifiers)
+    }
+}
+
+impl Key {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let mut modifiers = modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        if matches!(code, KeyCode::Char(_)) {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Self { code, modifiers }
+    }
+
+    /// Parses one key such as `j`, `G`, `Ctrl+s`, `F2`, `Esc`, or `PageDown`.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = spec;
+        loop {
+            if let Some(r) = rest.strip_prefix("Ctrl+") {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix("Alt+") {
+                modifiers |= KeyModifiers::ALT;
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix("Shift+") {
+                modifiers |= KeyModifiers::SHIFT;
+                rest = r;
+            } else {
+                break;
+            }
+        }
+        let code = match rest {
+            "Esc" => KeyCode::Esc,
+            "Enter" => KeyCode::Enter,
+            "Tab" => KeyCode::Tab,
+            "BackTab" => KeyCode::BackTab,
+            "Backspace" => KeyCode::Backspace,
+            "Delete" => KeyCode::Delete,
+            "Space" => KeyCode::Char(' '),
+            "Up" => KeyCode::Up,
+            "Down" => KeyCode::Down,
+            "Left" => KeyCode::Left,
+            "Right" => KeyCode::Right,
+            "Home" => KeyCode::Home,
+            "End" => KeyCode::End,
+            "PageUp" => KeyCode::PageUp,
+            "PageDown" => KeyCode::PageDown,
+            f if f.len() > 1 && f.starts_with('F') && f[1..].parse::<u8>().is_ok() => KeyCode::F(f[1..].parse()?),
+            c if c.chars().count() == 1 => KeyCode::Char(c.chars().next().unwrap()),
+            _ => bail!("Unknown key: {}", spec),
+        };
+        Ok(Key::new(code, modifiers))
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            KeyCode::Esc => write!(f, "Esc"),
+            KeyCode::PageUp => write!(f, "PageUp"),
+            KeyCode::PageDown => write!(f, "PageDown"),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+/// Parses a space-separated key sequence such as `g m` or `Ctrl+s`.
+pub fn parse_sequence(spec: &str) -> Result<Vec<Key>> {
+    let keys = spec.split_whitespace().map(Key::parse).collect::<Result<Vec<_>>>()?;
+    if keys.is_empty() {
+        bail!("Empty key sequence");
+    }
+    Ok(keys)
+}
+
+pub fn format_sequence(keys: &[Key]) -> String {
+    keys.iter().map(Key::to_string).collect::<Vec<_>>().join(" ")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding {
+    pub context: KeyContext,
+    pub keys: Vec<Key>,
+    pub action: Action,
+}
+
+/// An entry in `~/.ollama_tui/keymap.json`, e.g. `{ "context": "normal", "keys": "g m", "action": "open_models" }`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BindingOverride {
+    pub context: KeyContext,
+    pub keys: String,
+    pub action: Action,
+}
+
+pub enum Lookup {
+    Action(Action),
+    /// The keys so far are the start of a longer binding.
+    Pending,
+    None,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    pub bindings: Vec<Binding>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        use Action::*;
+        use KeyContext::*;
+        let defaults: &[(KeyContext, &str, Action)] = &[
+            (Global, "Ctrl+c", Quit),
+            (Global, "F1", ToggleHelp),
+            (Global, "Ctrl+g", ToggleDebug),
+            (Chat, "Enter", Send),
+            (Chat, "Shift+Enter", NewLine),
+            (Chat, "Alt+Enter", NewLine),
+            (Chat, "Esc", NormalMode),
+            (Chat, "Up", ScrollUp),
+            (Chat, "Down", ScrollDown),
+            (Chat, "PageUp", PageUp),
+            (Chat, "PageDown", PageDown),
+            (Chat, "Ctrl+u", HalfPageUp),
+            (Chat, "Ctrl+d", HalfPageDown),
+            (Chat, "Ctrl+b", PageUp),
+            (Chat, "Ctrl+f", PageDown),
+            (Chat, "F2", OpenModels),
+            (Chat, "F3", OpenDownload),
+            (Chat, "F4", OpenMonitor),
+            (Chat, "F5", OpenHistory),
+            (Chat, "F6", SaveChat),
+            (Chat, "F7", ClearChat),
+            (Chat, "F8", OpenConfig),
+            (Chat, "F9", OpenHosts),
+            (Chat, "F10", OpenPrompts),
+            (Chat, "F11", OpenMcpServers),
+            (Chat, "F12", OpenEventLog),
+            (Chat, "Alt+u", OpenStats),
+            (Chat, "Ctrl+s", SelectLastMessage),
+            (Chat, "Ctrl+Up", SelectPrevMessage),
+            (Chat, "Ctrl+Down", SelectNextMessage),
+            (Chat, "Alt+m", OpenMessageMenu),
+            (Chat, "Alt+q", QuoteMessage),
+            (Chat, "Ctrl+y", CopySelection),
+            (Chat, "Ctrl+v", Paste),
+            (Chat, "Alt+t", ToggleTitleBar),
+            (Chat, "Alt+b", ToggleStatusBar),
+            (Chat, "Alt+i", ToggleInput),
+            (Chat, "Alt+Up", GrowInput),
+            (Chat, "Alt+Down", ShrinkInput),
+            (Chat, "Alt+s", ToggleSidebar),
+            (Chat, "Ctrl+t", NewTab),
+            (Chat, "Ctrl+Tab", NextTab),
+            (Chat, "Ctrl+w", CloseTab),
+            (Chat, "Alt+v", ToggleSplit),
+            (Chat, "Alt+w", SwitchPane),
+            (Chat, "Alt+n", ToggleMessageNumbers),
+            (Chat, "Alt+p", ToggleSystemPrompt),
+            (Chat, "Alt+r", NextPersona),
+            (Chat, "Alt+j", ToggleJson),
+            (Chat, "Alt+d", ToggleDensity),
+            (Chat, "Alt+o", ToggleOutput),
+            (Chat, "Alt+e", ToggleReply),
+            (Chat, "Alt+f", ToggleCodeFold),
+            (Chat, "Alt+c", CompareAttempts),
+            (Chat, "Alt+,", PrevAttempt),
+            (Chat, "Alt+.", NextAttempt),
+            (Chat, "Alt+x", RunCodeBlock),
+            (Normal, "i", InsertMode),
+            (Normal, "j", ScrollDown),
+            (Normal, "k", ScrollUp),
+            (Normal, "g g", ScrollTop),
+            (Normal, "G", ScrollBottom),
+            (Normal, "{", PrevMessageStart),
+            (Normal, "}", NextMessageStart),
+            (Normal, "H", SelectTopMessage),
+            (Normal, "M", SelectMiddleMessage),
+            (Normal, "L", SelectBottomMessage),
+            (Normal, "g m", OpenModels),
+            (Normal, "g d", OpenDownload),
+            (Normal, "g s", OpenMonitor),
+            (Normal, "g h", OpenHistory),
+            (Normal, "g c", OpenConfig),
+            (Normal, "g H", OpenHosts),
+            (Normal, "g p", OpenPrompts),
+            (Normal, "g l", OpenEventLog),
+            (Normal, "g M", OpenMcpServers),
+            (Normal, "g u", OpenStats),
+            (Normal, "w", SaveChat),
+            (Normal, "z t", ToggleTitleBar),
+            (Normal, "z b", ToggleStatusBar),
+            (Normal, "z i", ToggleInput),
+            (Normal, "+", GrowInput),
+            (Normal, "-", ShrinkInput),
+            (Normal, "z s", ToggleSidebar),
+            (Normal, "Tab", FocusSidebar),
+            (Normal, "g t", NextTab),
+            (Normal, "g T", PrevTab),
+            (Normal, "z v", ToggleSplit),
+            (Normal, "z w", SwitchPane),
+            (Normal, ":", OpenCommandLine),
+            (Normal, "z n", ToggleMessageNumbers),
+            (Normal, "z p", ToggleSystemPrompt),
+            (Normal, "g r", NextPersona),
+            (Normal, "z j", ToggleJson),
+            (Normal, "z r", ToggleRaw),
+            (Normal, "z d", ToggleDensity),
+            (Normal, "z o", ToggleOutput),
+            (Normal, "z a", ToggleReply),
+            (Normal, "z f", ToggleCodeFold),
+            (Normal, "g a", CompareAttempts),
+            (Normal, "p", Paste),
+            (Normal, "g q", QuoteMessage),
+            (Normal, "<", PrevAttempt),
+            (Normal, ">", NextAttempt),
+            (Normal, "g x", RunCodeBlock),
+            (Normal, "q", RecordMacro),
+            (Normal, "@", ReplayMacro),
+            (ModelSelection, "Up", ListUp),
+            (ModelSelection, "Down", ListDown),
+            (ModelSelection, "Enter", Select),
+            (ModelSelection, "Esc", Back),
+            (ModelDownload, "Enter", Select),
+            (ModelDownload, "Esc", Back),
+            (SystemMonitor, "Up", ListUp),
+            (SystemMonitor, "Down", ListDown),
+            (SystemMonitor, "Tab", ToggleOllamaOnly),
+            (SystemMonitor, "Delete", TerminateProcess),
+            (SystemMonitor, "Shift+Delete", KillProcess),
+            (SystemMonitor, "Esc", Back),
+            (ChatHistory, "Up", ListUp),
+            (ChatHistory, "Down", ListDown),
+            (ChatHistory, "Enter", Select),
+            (ChatHistory, "Esc", Back),
+            (ModelConfig, "Up", PrevField),
+            (ModelConfig, "Down", NextField),
+            (ModelConfig, "Tab", NextField),
+            (ModelConfig, "Left", Decrease),
+            (ModelConfig, "Right", Increase),
+            (ModelConfig, "Enter", Select),
+            (ModelConfig, "Esc", Back),
+            (HostSelection, "Up", ListUp),
+            (HostSelection, "Down", ListDown),
+            (HostSelection, "Enter", Select),
+            (HostSelection, "Esc", Back),
+            (PromptLibrary, "Up", ListUp),
+            (PromptLibrary, "Down", ListDown),
+            (PromptLibrary, "Enter", Select),
+            (PromptLibrary, "n", NewItem),
+            (PromptLibrary, "e", EditItem),
+            (PromptLibrary, "d", DeleteItem),
+            (PromptLibrary, "Esc", Back),
+            (EventLog, "Up", ListUp),
+            (EventLog, "Down", ListDown),
+            (EventLog, "k", ListUp),
+            (EventLog, "j", ListDown),
+            (EventLog, "Esc", Back),
+            (McpServers, "Up", ListUp),
+            (McpServers, "Down", ListDown),
+            (McpServers, "Enter", Select),
+            (McpServers, "Esc", Back),
+            (Stats, "Esc", Back),
+            (PromptEdit, "Enter", Select),
+            (PromptEdit, "Esc", Back),
+            (Sidebar, "k", ListUp),
+            (Sidebar, "j", ListDown),
+            (Sidebar, "Up", ListUp),
+            (Sidebar, "Down", ListDown),
+            (Sidebar, "Enter", Select),
+            (Sidebar, "Tab", FocusSidebar),
+            (Sidebar, "Esc", FocusSidebar),
+            (CommandLine, "Enter", Select),
+            (CommandLine, "Esc", Back),
+            (Confirm, "y", ConfirmYes),
+            (Confirm, "n", ConfirmNo),
+            (Confirm, "Esc", ConfirmNo),
+            (Recovery, "r", Retry),
+            (Recovery, "h", OpenHosts),
+            (Recovery, "m", OpenModels),
+            (Recovery, "p", PullModel),
+            (Recovery, "Esc", DismissError),
+            (Help, "Up", ScrollUp),
+            (Help, "Down", ScrollDown),
+            (Help, "k", ScrollUp),
+            (Help, "j", ScrollDown),
+            (Help, "Esc", ToggleHelp),
+            (Help, "q", ToggleHelp),
+            (SessionStats, "Esc", Back),
+            (SessionStats, "q", Back),
+            (SessionStats, "Enter", Back),
+            (MessageMenu, "Up", ListUp),
+            (MessageMenu, "Down", ListDown),
+            (MessageMenu, "k", ListUp),
+            (MessageMenu, "j", ListDown),
+            (MessageMenu, "Enter", Select),
+            (MessageMenu, "Esc", Back),
+            (MessageMenu, "q", Back),
+            (TemplatePicker, "Up", ListUp),
+            (TemplatePicker, "Down", ListDown),
+            (TemplatePicker, "k", ListUp),
+            (TemplatePicker, "j", ListDown),
+            (TemplatePicker, "Enter", Select),
+            (TemplatePicker, "Esc", Back),
+            (AttemptDiff, "Up", ScrollUp),
+            (AttemptDiff, "Down", ScrollDown),
+            (AttemptDiff, "k", ScrollUp),
+            (AttemptDiff, "j", ScrollDown),
+            (AttemptDiff, "Left", PrevAttempt),
+            (AttemptDiff, "Right", NextAttempt),
+            (AttemptDiff, "h", PrevAttempt),
+            (AttemptDiff, "l", NextAttempt),
+            (AttemptDiff, "Esc", Back),
+            (AttemptDiff, "q", Back),
+        ];
+        Keymap {
+            bindings: defaults
+                .iter()
+                .map(|(context, keys, action)| Binding { context: *context, keys: parse_sequence(keys).expect("valid built-in key binding"), action: *action })
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    /// The built-in bindings with any from `keymap.json` layered on top.
+    pub fn load(config_dir: &Path) -> Result<Self> {
+        let mut keymap = Keymap::default();
+        let path = config_dir.join(KEYMAP_FILE);
+        if !path.exists() {
+            return Ok(keymap);
+        }
+        let content = fs::read_to_string(&path)?;
+        let overrides: Vec<BindingOverride> = serde_json::from_str(&content).with_context(|| format!("Invalid {}", KEYMAP_FILE))?;
+        for entry in overrides {
+            let keys = parse_sequence(&entry.keys)?;
+            keymap.bindings.retain(|b| !(b.context == entry.context && b.keys == keys));
+            keymap.bindings.push(Binding { context: entry.context, keys, action: entry.action });
+        }
+        Ok(keymap)
+    }
+
+    /// Resolves the keys typed so far against the active contexts, earlier contexts winning.
+    pub fn lookup(&self, contexts: &[KeyContext], keys: &[Key]) -> Lookup {
+        for context in contexts {
+            if let Some(binding) = self.bindings.iter().find(|b| b.context == *context && b.keys == keys) {
+                return Lookup::Action(binding.action);
+            }
+        }
+        let is_prefix = self
+            .bindings
+            .iter()
+            .any(|b| contexts.contains(&b.context) && b.keys.len() > keys.len() && b.keys.starts_with(keys));
+        if is_prefix { Lookup::Pending } else { Lookup::None }
+    }
+
+    pub fn bindings_for(&self, context: KeyContext) -> impl Iterator<Item = &Binding> {
+        self.bindings.iter().filter(move |b| b.context == context)
+    }
+}
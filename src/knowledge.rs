@@ -0,0 +1,183 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+
+use crate::backend::ChatBackend;
+use crate::migrate::{self, Versioned};
+use crate::text;
+
+pub const KNOWLEDGE_FILE: &str = "knowledge.json";
+/// Passages retrieved for each question.
+pub const TOP_K: usize = 4;
+/// Longest passage, in characters; files are split between lines to stay under it.
+const PASSAGE_CHARS: usize = 1500;
+/// Files larger than this are skipped, as are files that aren't UTF-8 text.
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+/// Directories of build output and dependencies, never worth indexing.
+const SKIPPED_DIRS: [&str; 6] = ["target", "node_modules", "vendor", "dist", "build", "__pycache__"];
+/// Passages sent to the embedding model per request.
+const EMBED_BATCH: usize = 32;
+
+/// A directory indexed for retrieval: each passage with its embedding.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct KnowledgeBase {
+    /// Format version; see `migrate`.
+    #[serde(default)]
+    pub version: u32,
+    pub root: PathBuf,
+    /// Embedding model the index was built with; questions must use the same one.
+    pub model: String,
+    pub passages: Vec<Passage>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Passage {
+    /// Relative to the knowledge base root.
+    pub path: PathBuf,
+    /// First line of the passage, counting from 1.
+    pub line: usize,
+    pub text: String,
+    pub vector: Vec<f32>,
+}
+
+impl Passage {
+    /// `path:line`, as shown under replies that used the passage.
+    pub fn citation(&self) -> String {
+        format!("{}:{}", self.path.display(), self.line)
+    }
+}
+
+impl Versioned for KnowledgeBase {
+    const VERSION: u32 = 1;
+
+    fn upgrade(_version: u32, _value: &mut Value) -> Result<()> {
+        // Version 1 only added the version field
+        Ok(())
+    }
+}
+
+impl KnowledgeBase {
+    /// Splits the text files under `root` into passages and embeds them with `model`,
+    /// awaiting `progress(done, total)` after each batch.
+    pub async fn build<F, Fut>(backend: &dyn ChatBackend, root: &Path, model: &str, mut progress: F) -> Result<Self>
+    where
+        F: FnMut(usize, usize) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        let root = root.canonicalize().with_context(|| format!("cannot open {}", root.display()))?;
+        let walk_root = root.clone();
+        let drafts = tokio::task::spawn_blocking(move || {
+            let mut drafts = Vec::new();
+            collect_passages(&walk_root, &walk_root, &mut drafts);
+            drafts
+        })
+        .await?;
+        if drafts.is_empty() {
+            bail!("no text files under {}", root.display());
+        }
+        let total = drafts.len();
+        let mut passages = Vec::with_capacity(total);
+        for batch in drafts.chunks(EMBED_BATCH) {
+            let vectors = backend.embeddings(model.to_string(), batch.iter().map(|(_, _, text)| text.clone()).collect()).await?;
+            if vectors.len() != batch.len() {
+                bail!("{} returned {} embeddings for {} passages", model, vectors.len(), batch.len());
+            }
+            for ((path, line, text), vector) in batch.iter().cloned().zip(vectors) {
+                passages.push(Passage { path, line, text, vector });
+            }
+            progress(passages.len(), total).await;
+        }
+        Ok(Self { version: Self::VERSION, root, model: model.to_string(), passages })
+    }
+
+    /// The saved knowledge base, if one has been built.
+    pub fn load(config_dir: &Path) -> Result<Option<Self>> {
+        let path = config_dir.join(KNOWLEDGE_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(migrate::load(&path)?))
+    }
+
+    pub fn save(&self, config_dir: &Path) -> Result<()> {
+        fs::create_dir_all(config_dir)?;
+        fs::write(config_dir.join(KNOWLEDGE_FILE), serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn delete(config_dir: &Path) -> Result<()> {
+        let path = config_dir.join(KNOWLEDGE_FILE);
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// The `k` passages closest to `query` by cosine similarity, best first.
+    pub fn search(&self, query: &[f32], k: usize) -> Vec<&Passage> {
+        let mut scored: Vec<(f32, &Passage)> = self.passages.iter().map(|p| (cosine(query, &p.vector), p)).collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.into_iter().take(k).map(|(_, p)| p).collect()
+    }
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let denominator = norm(a) * norm(b);
+    if denominator == 0.0 { 0.0 } else { dot / denominator }
+}
+
+/// Adds `(relative path, first line, text)` for every passage of every text file under `dir`,
+/// skipping hidden entries and build directories.
+fn collect_passages(root: &Path, dir: &Path, drafts: &mut Vec<(PathBuf, usize, String)>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    let mut entries: Vec<_> = entries.flatten().collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Ok(kind) = entry.file_type() else { continue };
+        if name.starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        if kind.is_dir() {
+            if !SKIPPED_DIRS.contains(&name.as_str()) {
+                collect_passages(root, &path, drafts);
+            }
+            continue;
+        }
+        if !kind.is_file() || entry.metadata().map_or(true, |m| m.len() > MAX_FILE_BYTES) {
+            continue;
+        }
+        let Ok(content) = fs::read_to_string(&path) else { continue };
+        let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+        let (mut passage, mut first_line) = (String::new(), 1);
+        for (i, line) in content.lines().enumerate() {
+            if !passage.is_empty() && passage.len() + line.len() > PASSAGE_CHARS {
+                drafts.push((relative.clone(), first_line, std::mem::take(&mut passage)));
+                first_line = i + 1;
+            }
+            // Very long lines are cut rather than split, which is enough for retrieval
+            passage.push_str(&text::truncate(line, PASSAGE_CHARS));
+            passage.push('\n');
+        }
+        if !passage.trim().is_empty() {
+            drafts.push((relative, first_line, passage));
+        }
+    }
+}
+
+/// The question with the retrieved passages in front of it, numbered for citing.
+pub fn augment(question: &str, passages: &[&Passage]) -> String {
+    let mut prompt = String::from("Use the numbered excerpts below where they help answer the question, and cite the ones you use inline as [1], [2], and so on. If they don't cover it, say so and answer from what you know.\n\n");
+    for (i, passage) in passages.iter().enumerate() {
+        prompt.push_str(&format!("[{}] {}\n```\n{}```\n\n", i + 1, passage.citation(), passage.text));
+    }
+    prompt.push_str("Question: ");
+    prompt.push_str(question);
+    prompt
+}
@@ -1,136 +1,157 @@
+//! A terminal chat client for Ollama and Claude models. `run_app` runs an [`App`] on a terminal;
+//! the app can also be driven directly, as the tests do against `backend::MockBackend`.
+
 pub mod app;
+pub mod attempts;
+pub mod backend;
+pub mod batch;
+pub mod chat;
+pub mod cli;
+pub mod commands;
+pub mod config;
+pub mod desktop;
+pub mod disk;
+pub mod error;
+pub mod events;
+pub mod fim;
+pub mod git;
+pub mod gpu;
+pub mod history;
+pub mod html;
+pub mod images;
+pub mod input;
+pub mod keymap;
+pub mod knowledge;
+pub mod logging;
+pub mod macros;
+pub mod mcp;
+pub mod message_menu;
+pub mod migrate;
+pub mod models;
+pub mod monitor;
+pub mod raw;
+pub mod structured;
+pub mod sweep;
+pub mod terminal;
+pub mod text;
+pub mod theme;
+pub mod toast;
+pub mod tools;
 pub mod ui;
+pub mod usage;
+pub mod web;
 
-use anyhow::Result;
-use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use anyhow::{anyhow, Result};
+use crossterm::event::{self, Event, KeyEventKind};
+use crossterm::{execute, terminal::SetTitle};
 use ratatui::{Terminal, backend::Backend};
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::Mutex;
+use std::time::Instant;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
 
-use crate::app::{App, AppMode};
 use crate::ui::ui;
 
-pub async fn run_app<B: Backend>(
-    terminal: &mut Terminal<B>,
-    app_arc: Arc<Mutex<App>>,
-) -> Result<()> {
-    loop {
-        {
-            let app = app_arc.lock().await;
-            terminal.draw(|f| ui(f, &app))?;
-        }
+pub use app::{App, AppMode};
+pub use backend::ChatBackend;
+pub use chat::{ChatSession, Conversation, Message};
+pub use config::AppConfig;
+pub use models::ModelConfig;
 
-        {
-            let mut app = app_arc.lock().await;
+/// Runs `app` on `terminal` until the user quits, then stops replies and saves before returning.
+/// Returns the transcript to print once the terminal is back, when `print_on_exit` asks for one.
+pub async fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> Result<Option<String>> {
+    let mut inbox = app.take_inbox().ok_or_else(|| anyhow!("The app is already running"))?;
+    let mut input = spawn_input_reader();
+    // The clock segment changes once a minute with nothing else happening
+    let mut clock = String::new();
+    let mut window_title = String::new();
+    let mut last_tick: Option<Instant> = None;
+    let result = loop {
+        if last_tick.is_none_or(|tick| tick.elapsed() >= app.tick_rate()) {
+            last_tick = Some(Instant::now());
             app.update_thinking_animation();
-            if app.mode == AppMode::SystemMonitor {
-                app.update_system_info();
+            app.reload_changed_configs();
+            if app.toasts.expire() {
+                app.dirty = true;
+            }
+            if app.update_system_info() {
+                app.dirty = true;
+            }
+            app.refresh_loaded_models();
+            app.sync_mcp_servers();
+            // New log lines arrive without anything else changing
+            if app.show_debug {
+                app.dirty = true;
+            }
+            let now = chrono::Local::now().format("%H:%M").to_string();
+            if now != clock {
+                clock = now;
+                app.dirty = true;
             }
         }
+        // Redraw only when something on screen changed, so an idle app stays idle
+        if std::mem::take(&mut app.dirty) {
+            let started = Instant::now();
+            if let Err(e) = terminal.draw(|f| ui(f, &app)) {
+                break Err(e.into());
+            }
+            app.frame_times.record(started.elapsed());
+        }
+        let title = app.window_title();
+        if app.app_config.terminal_title && title != window_title {
+            if let Err(e) = execute!(terminal::screen(), SetTitle(&title)) {
+                break Err(e.into());
+            }
+            window_title = title;
+        }
 
-        if event::poll(Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                let mut app = app_arc.lock().await;
-
-                // Vim-like key handling pre-processing for Chat mode
-                if app.mode == AppMode::Chat && app.vim_mode {
-                    // Esc/i to switch modes
-                    if let KeyCode::Esc = key.code {
-                        app.vim_insert = false;
-                        app.pending_g = false;
-                        app.status_message = "Normal mode".into();
-                        continue;
-                    }
-                    if matches!(key.code, KeyCode::Char('i')) && key.modifiers.is_empty() && !app.vim_insert {
-                        app.vim_insert = true;
-                        app.status_message = "Insert mode".into();
-                        continue;
-                    }
-
-                    if !app.vim_insert {
-                        match key.code {
-                            KeyCode::Char('j') => { app.scroll_down(); continue; }
-                            KeyCode::Char('k') => { app.scroll_up(); continue; }
-                            KeyCode::Char('g') => {
-                                if app.pending_g { app.scroll_top(); app.pending_g = false; } else { app.pending_g = true; }
-                                continue;
-                            }
-                            KeyCode::Char('G') => { app.scroll_bottom(); continue; }
-                            // g-prefixed shortcuts for mode switching
-                            KeyCode::Char('m') if app.pending_g => { let _ = app.fetch_models().await; app.switch_mode(AppMode::ModelSelection); app.pending_g = false; continue; }
-                            KeyCode::Char('d') if app.pending_g => { app.switch_mode(AppMode::ModelDownload); app.pending_g = false; continue; }
-                            KeyCode::Char('s') if app.pending_g => { app.update_system_info(); app.switch_mode(AppMode::SystemMonitor); app.pending_g = false; continue; }
-                            KeyCode::Char('h') if app.pending_g => { let _ = app.load_chat_history(); app.history_list_state.select(Some(0)); app.switch_mode(AppMode::ChatHistory); app.pending_g = false; continue; }
-                            KeyCode::Char('c') if app.pending_g => { app.config_input = app.get_current_config_value(); app.switch_mode(AppMode::ModelConfig); app.pending_g = false; continue; }
-                            KeyCode::Char('w') => { let _ = app.save_current_chat(); continue; }
-                            _ => { app.pending_g = false; }
-                        }
-                    }
-                }
-
-                match app.mode {
-                    AppMode::Chat => match key.code {
-                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                            return Ok(());
-                        }
-                        KeyCode::F(1) => {
-                            app.status_message = "Vim: Esc/i modes | j/k scroll | gg top | G bottom | gm models | gd download | gs monitor | gh history | gc config | gw save | Enter send | Ctrl+C quit".to_string();
-                        }
-                        KeyCode::F(2) => { let _ = app.fetch_models().await; app.switch_mode(AppMode::ModelSelection); }
-                        KeyCode::F(3) => { app.switch_mode(AppMode::ModelDownload); }
-                        KeyCode::F(4) => { app.update_system_info(); app.switch_mode(AppMode::SystemMonitor); }
-                        KeyCode::F(5) => { let _ = app.load_chat_history(); app.history_list_state.select(Some(0)); app.switch_mode(AppMode::ChatHistory); }
-                        KeyCode::F(6) => { let _ = app.save_current_chat(); }
-                        KeyCode::F(7) => { app.clear_chat(); }
-                        KeyCode::F(8) => { app.config_input = app.get_current_config_value(); app.switch_mode(AppMode::ModelConfig); }
-                        KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => { app.select_last_message(); }
-                        KeyCode::Char('y') if key.modifiers.contains(KeyModifiers::CONTROL) => { app.copy_to_clipboard(); }
-                        KeyCode::Enter => { app.start_message_stream(Arc::clone(&app_arc)); }
-                        KeyCode::Char(c) => { app.input.push(c); }
-                        KeyCode::Backspace => { app.input.pop(); }
-                        KeyCode::Up => { app.scroll_up(); }
-                        KeyCode::Down => { app.scroll_down(); }
-                        _ => {}
-                    },
-                    AppMode::ModelSelection => match key.code {
-                        KeyCode::Esc => { app.switch_mode(AppMode::Chat); }
-                        KeyCode::Up => { if let Some(selected) = app.model_list_state.selected() { if selected > 0 { app.model_list_state.select(Some(selected - 1)); } } }
-                        KeyCode::Down => { if let Some(selected) = app.model_list_state.selected() { if selected < app.available_models.len().saturating_sub(1) { app.model_list_state.select(Some(selected + 1)); } } }
-                        KeyCode::Enter => { if let Some(selected) = app.model_list_state.selected() { if let Some(model) = app.available_models.get(selected).cloned() { app.current_model = model.clone(); app.status_message = format!("Model changed to: {}", model); app.switch_mode(AppMode::Chat); } } }
-                        _ => {}
-                    },
-                    AppMode::ModelDownload => match key.code {
-                        KeyCode::Esc => { app.download_input.clear(); app.switch_mode(AppMode::Chat); }
-                        KeyCode::Enter => { let model_name = app.download_input.clone(); app.download_input.clear(); let _ = app.download_model(model_name).await; app.switch_mode(AppMode::Chat); }
-                        KeyCode::Char(c) => { app.download_input.push(c); }
-                        KeyCode::Backspace => { app.download_input.pop(); }
-                        _ => {}
-                    },
-                    AppMode::SystemMonitor => match key.code {
-                        KeyCode::Esc => { app.switch_mode(AppMode::Chat); }
-                        KeyCode::Up => { if app.process_scroll > 0 { app.process_scroll -= 1; } }
-                        KeyCode::Down => { app.process_scroll += 1; }
-                        _ => {}
-                    },
-                    AppMode::ChatHistory => match key.code {
-                        KeyCode::Esc => { app.switch_mode(AppMode::Chat); }
-                        KeyCode::Up => { if let Some(selected) = app.history_list_state.selected() { if selected > 0 { app.history_list_state.select(Some(selected - 1)); } } }
-                        KeyCode::Down => { if let Some(selected) = app.history_list_state.selected() { if selected < app.chat_history.len().saturating_sub(1) { app.history_list_state.select(Some(selected + 1)); } } }
-                        KeyCode::Enter => { let _ = app.load_selected_chat(); }
-                        _ => {}
-                    },
-                    AppMode::ModelConfig => match key.code {
-                        KeyCode::Esc => { app.switch_mode(AppMode::Chat); }
-                        KeyCode::Up => { app.prev_config_field(); app.config_input = app.get_current_config_value(); }
-                        KeyCode::Down | KeyCode::Tab => { app.next_config_field(); app.config_input = app.get_current_config_value(); }
-                        KeyCode::Enter => { let value = app.config_input.clone(); app.update_config_field(value); let _ = app.save_config(); app.config_input.clear(); }
-                        KeyCode::Char(c) => { app.config_input.push(c); }
-                        KeyCode::Backspace => { app.config_input.pop(); }
-                        _ => {}
-                    },
+        let next_tick = last_tick.unwrap_or_else(Instant::now) + app.tick_rate();
+        tokio::select! {
+            Some(event) = inbox.recv() => {
+                app.handle_event(event);
+                // Take everything already queued before the next redraw
+                while let Ok(event) = inbox.try_recv() {
+                    app.handle_event(event);
                 }
             }
+            event = input.recv() => match event {
+                // Terminals with the kitty protocol (and Windows) report releases too
+                Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Release => {}
+                Some(Ok(Event::Key(key))) => {
+                    app.dirty = true;
+                    app.reply_unseen = false;
+                    if !input::handle_key(&mut app, key).await {
+                        break Ok(());
+                    }
+                }
+                Some(Ok(Event::Resize(..))) => app.dirty = true,
+                Some(Ok(Event::FocusGained)) => app.terminal_focused = Some(true),
+                Some(Ok(Event::FocusLost)) => app.terminal_focused = Some(false),
+                Some(Ok(_)) => {}
+                Some(Err(e)) => break Err(e.into()),
+                None => break Ok(()),
+            },
+            _ = tokio::time::sleep_until(next_tick.into()) => {}
         }
+    };
+    app.shutdown().await;
+    // Run what the writes reported, so a failed save is at least logged
+    while let Ok(event) = inbox.try_recv() {
+        app.handle_event(event);
     }
+    result.map(|()| app.print_on_exit.then(|| app.transcript()))
+}
+
+/// Reads terminal input on its own thread, so a key press is handled the moment it arrives.
+/// A plain thread rather than a blocking task: it sits in `read` for good, and the runtime
+/// would wait for a blocking task on shutdown.
+fn spawn_input_reader() -> UnboundedReceiver<std::io::Result<Event>> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    std::thread::spawn(move || loop {
+        let event = event::read();
+        let failed = event.is_err();
+        if sender.send(event).is_err() || failed {
+            break;
+        }
+    });
+    receiver
 }
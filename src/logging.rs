@@ -0,0 +1,123 @@
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::FormatTime;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter};
+
+pub const LOG_DIR: &str = "logs";
+/// Sets what is logged, e.g. `debug` or `llama_term=trace`; `info` for this app otherwise.
+pub const LOG_ENV: &str = "OLLAMA_TUI_LOG";
+const DEFAULT_FILTER: &str = "warn,llama_term=info";
+/// Days of log files kept.
+const MAX_LOG_FILES: usize = 7;
+const MAX_RECENT_LINES: usize = 200;
+const MAX_FRAME_TIMES: usize = 120;
+
+/// The newest log lines, for the debug overlay.
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Starts logging to a daily file in `<base_dir>/logs/` and to the debug overlay. Lines still
+/// queued for the file are written when the returned guard drops.
+pub fn init(base_dir: &Path) -> Result<WorkerGuard> {
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("ollama_tui")
+        .filename_suffix("log")
+        .max_log_files(MAX_LOG_FILES)
+        .build(base_dir.join(LOG_DIR))?;
+    let (file, guard) = tracing_appender::non_blocking(appender);
+    let filter = EnvFilter::try_from_env(LOG_ENV).unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_writer(file).with_ansi(false).with_timer(LocalTime("%Y-%m-%d %H:%M:%S%.3f")))
+        .with(fmt::layer().with_writer(RecentWriter).with_ansi(false).with_target(false).with_timer(LocalTime("%H:%M:%S%.3f")))
+        .try_init()?;
+    Ok(guard)
+}
+
+/// The newest log lines, oldest first.
+pub fn recent_lines() -> Vec<String> {
+    RECENT.lock().map(|lines| lines.iter().cloned().collect()).unwrap_or_default()
+}
+
+struct LocalTime(&'static str);
+
+impl FormatTime for LocalTime {
+    fn format_time(&self, w: &mut Writer<'_>) -> std::fmt::Result {
+        write!(w, "{}", chrono::Local::now().format(self.0))
+    }
+}
+
+/// Keeps each formatted event in `RECENT`; the formatter writes one whole event per call.
+struct RecentWriter;
+
+impl io::Write for RecentWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Ok(mut lines) = RECENT.lock() {
+            for line in String::from_utf8_lossy(buf).lines().filter(|line| !line.is_empty()) {
+                if lines.len() == MAX_RECENT_LINES {
+                    lines.pop_front();
+                }
+                lines.push_back(line.to_string());
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for RecentWriter {
+    type Writer = RecentWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RecentWriter
+    }
+}
+
+/// How long the latest frames took to draw.
+#[derive(Debug, Default)]
+pub struct FrameTimes {
+    recent: VecDeque<Duration>,
+}
+
+impl FrameTimes {
+    pub fn record(&mut self, elapsed: Duration) {
+        if self.recent.len() == MAX_FRAME_TIMES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(elapsed);
+    }
+
+    pub fn last(&self) -> Option<Duration> {
+        self.recent.back().copied()
+    }
+
+    pub fn average(&self) -> Option<Duration> {
+        let count = u32::try_from(self.recent.len()).ok().filter(|&n| n > 0)?;
+        Some(self.recent.iter().sum::<Duration>() / count)
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.recent.iter().max().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.recent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recent.is_empty()
+    }
+}
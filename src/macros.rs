@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use crate::app::App;
+use crate::keymap::{Action, Key};
+
+/// One thing a macro does when replayed: a bound action, or a key typed into the input.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MacroStep {
+    Action(Action),
+    Text(Key),
+}
+
+/// What the key after `q` or `@` is taken as the register for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MacroPrompt {
+    Record,
+    Replay,
+}
+
+/// Vim-style macros: `q<reg>` records actions until `q` again, `@<reg>` replays them. Registers
+/// last for the session.
+#[derive(Debug, Default)]
+pub struct Macros {
+    registers: HashMap<char, Vec<MacroStep>>,
+    /// The register being recorded into, whether to append to it, and the steps so far.
+    recording: Option<(char, bool, Vec<MacroStep>)>,
+    /// `q` or `@` was pressed and the next key names the register.
+    pub awaiting: Option<MacroPrompt>,
+    /// Replayed by `@@`.
+    last: Option<char>,
+}
+
+impl Macros {
+    /// The register being recorded into, for the status bar.
+    pub fn recording(&self) -> Option<char> {
+        self.recording.as_ref().map(|(register, _, _)| *register)
+    }
+
+    /// Adds a step to the macro being recorded. Starting, stopping, and replaying macros are
+    /// not steps themselves; a replay records the steps it runs.
+    pub fn record(&mut self, step: MacroStep) {
+        if matches!(step, MacroStep::Action(Action::RecordMacro | Action::ReplayMacro)) {
+            return;
+        }
+        if let Some((_, _, steps)) = self.recording.as_mut() {
+            steps.push(step);
+        }
+    }
+
+    /// The steps in `register`, with `@` meaning the register replayed last.
+    pub fn steps(&mut self, register: char) -> Option<(char, Vec<MacroStep>)> {
+        let register = if register == '@' { self.last? } else { register };
+        let steps = self.registers.get(&register)?.clone();
+        self.last = Some(register);
+        Some((register, steps))
+    }
+}
+
+impl App {
+    /// `q`: stops the recording in progress, or asks for the register to record into.
+    pub fn toggle_macro_recording(&mut self) {
+        match self.macros.recording.take() {
+            Some((register, append, steps)) => {
+                let count = steps.len();
+                let stored = self.macros.registers.entry(register).or_default();
+                if !append {
+                    stored.clear();
+                }
+                stored.extend(steps);
+                self.status_message = format!("Recorded @{} ({} step(s))", register, count);
+            }
+            None => {
+                self.macros.awaiting = Some(MacroPrompt::Record);
+                self.status_message = "Record a macro into register: a-z or 0-9 (A-Z appends)".to_string();
+            }
+        }
+    }
+
+    /// Starts recording into `register`; an uppercase letter appends to the lowercase one, as in vim.
+    pub fn start_macro_recording(&mut self, register: char) {
+        if !register.is_ascii_alphanumeric() {
+            self.set_error(format!("'{}' is not a macro register; use a-z or 0-9", register));
+            return;
+        }
+        let append = register.is_ascii_uppercase();
+        let register = register.to_ascii_lowercase();
+        self.macros.recording = Some((register, append, Vec::new()));
+        self.status_message = format!("Recording @{}; press q to stop", register);
+    }
+}
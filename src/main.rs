@@ -1,31 +1,112 @@
 use anyhow::Result;
-use crossterm::{execute, terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen}};
+use crossterm::{event::EnableFocusChange, execute, terminal::{enable_raw_mode, EnterAlternateScreen}};
 use ratatui::{backend::CrosstermBackend, Terminal};
-use std::io;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::io::Write;
+use std::process::ExitCode;
 
-use ollama_testing::app::App;
-use ollama_testing::run_app;
+use llama_term::App;
+use llama_term::cli::{self, Args, UsageError, USAGE};
+use llama_term::config;
+use llama_term::images::detect_picker;
+use llama_term::logging;
+use llama_term::run_app;
+use llama_term::terminal::{self, TerminalGuard};
+use llama_term::theme::detect_background;
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
+    let result = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => run(args).await,
+        Err(e) => Err(e.into()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) if e.is::<UsageError>() => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            ExitCode::from(2)
+        }
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(args: Args) -> Result<()> {
+    if args.help {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    if args.version {
+        println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
+    // Not fatal: the app runs the same without a log file
+    let log_guard = logging::init(&config::base_dir()).ok();
+    tracing::info!(version = env!("CARGO_PKG_VERSION"), "starting");
+    if let Some(path) = &args.batch {
+        return cli::batch(&App::new(), path, &args.models, args.output.as_deref()).await;
+    }
+    // Piped input has to be read before raw mode takes over the terminal
+    let piped = cli::piped_stdin()?;
+    let message = cli::initial_message(args.prompt.as_deref(), piped.as_deref());
+    if let Some(setting) = args.sweep {
+        let prompt = message.ok_or_else(|| UsageError("Nothing to sweep: give a prompt or pipe input on stdin".to_string()))?;
+        return cli::sweep(&App::new(), &prompt, args.model.as_deref(), setting, &args.sweep_values, args.output.as_deref()).await;
+    }
+    if args.headless() {
+        let message = message.ok_or_else(|| UsageError("Nothing to ask: give a question or pipe input on stdin".to_string()))?;
+        let mut app = App::new();
+        if let Some(model) = args.model {
+            app.conversation_mut().model = model;
+        }
+        return cli::oneshot(&app, message).await;
+    }
+
+    terminal::install_panic_hook();
     enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
+    // From here on the terminal is put back however this function ends
+    let _guard = TerminalGuard::enter();
+    let background = detect_background();
+    let mut screen = terminal::screen();
+    execute!(screen, EnterAlternateScreen)?;
+    let picker = detect_picker();
+    let backend = CrosstermBackend::new(screen);
     let mut terminal = Terminal::new(backend)?;
+    // After the terminal queries above, so focus reports can't get mixed into their answers
+    execute!(terminal.backend_mut(), EnableFocusChange)?;
+    let keyboard_enhanced = terminal::enable_keyboard_enhancement();
+    // Save the terminal's title so the one set while running can be undone on exit
+    write!(terminal.backend_mut(), "\x1b[22;0t")?;
 
     let mut app = App::new();
-    let _ = app.fetch_models().await; // non-fatal
-
-    let app_arc = Arc::new(Mutex::new(app));
-    let res = run_app(&mut terminal, app_arc).await;
+    if let Some(model) = args.model.clone() {
+        app.conversation_mut().model = model;
+    }
+    app.set_background(background);
+    app.keyboard_enhanced = keyboard_enhanced;
+    app.print_on_exit = args.print_on_exit;
+    app.images.get_mut().set_picker(picker);
+    // Not fatal: the popup offers to retry or pick another host
+    app.fetch_models();
 
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-    terminal.show_cursor()?;
+    // A prompt is sent straight away; piped input alone waits in the input box for a question
+    let send_now = args.prompt.is_some();
+    if let Some(message) = message {
+        app.conversation_mut().input = message;
+    }
 
-    if let Err(err) = res { eprintln!("Error: {:?}", err); }
+    if send_now {
+        app.start_message_stream();
+    }
+    // Streams are stopped and everything is saved by the time this returns
+    let res = run_app(&mut terminal, app).await;
+    // Write out queued log lines before the terminal changes back
+    drop(log_guard);
+    terminal::restore()?;
+    // On the normal screen, or down the pipe stdout was redirected to
+    if let Some(transcript) = res? {
+        print!("{}", transcript);
+    }
     Ok(())
 }
@@ -0,0 +1,279 @@
+use anyhow::{anyhow, bail, Context, Result};
+use reqwest::header::{HeaderValue, ACCEPT, CONTENT_TYPE};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+
+use crate::config::McpServerConfig;
+use crate::tools::{Tool, ToolSpec};
+
+const PROTOCOL_VERSION: &str = "2025-03-26";
+const SESSION_HEADER: &str = "mcp-session-id";
+/// Resources named in the description of a server's `read_resource` tool.
+const MAX_LISTED_RESOURCES: usize = 50;
+/// How long a server has to answer a request before the call fails.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Where a configured server stands, shown in the MCP server list.
+#[derive(Debug, Clone, PartialEq)]
+pub enum McpStatus {
+    Connecting,
+    Connected { tools: usize, resources: usize },
+    Failed(String),
+}
+
+enum Transport {
+    /// `lines` carries what the server prints, read on a thread of its own so a wait for it
+    /// can time out.
+    Stdio { child: Child, stdin: ChildStdin, lines: Receiver<String> },
+    Http { client: reqwest::Client, url: String, session: Option<HeaderValue>, runtime: Handle },
+}
+
+impl Drop for Transport {
+    fn drop(&mut self) {
+        if let Transport::Stdio { child, .. } = self {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// A live connection to one MCP server. Calls block, so make them off the UI thread.
+pub struct McpServer {
+    name: String,
+    transport: Mutex<Transport>,
+    next_id: Mutex<u64>,
+}
+
+impl McpServer {
+    /// Starts or reaches the server and completes the MCP handshake.
+    pub fn connect(config: &McpServerConfig, runtime: Handle) -> Result<Self> {
+        let transport = match (&config.command, &config.url) {
+            (Some(command), _) => {
+                let mut child = Command::new(command)
+                    .args(&config.args)
+                    .envs(&config.env)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    // Anything the server logs would land on top of the TUI
+                    .stderr(Stdio::null())
+                    .spawn()
+                    .with_context(|| format!("cannot start {}", command))?;
+                let stdin = child.stdin.take().context("no stdin")?;
+                let stdout = BufReader::new(child.stdout.take().context("no stdout")?);
+                let (sender, lines) = mpsc::channel();
+                thread::spawn(move || {
+                    for line in stdout.lines().map_while(Result::ok) {
+                        if sender.send(line).is_err() {
+                            break;
+                        }
+                    }
+                });
+                Transport::Stdio { child, stdin, lines }
+            }
+            (None, Some(url)) => Transport::Http { client: reqwest::Client::builder().timeout(REPLY_TIMEOUT).build()?, url: url.clone(), session: None, runtime },
+            (None, None) => bail!("set either command or url"),
+        };
+        let server = Self { name: config.name.clone(), transport: Mutex::new(transport), next_id: Mutex::new(1) };
+        server.request("initialize", json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": {},
+            "clientInfo": { "name": "ollama-tui", "version": env!("CARGO_PKG_VERSION") },
+        }))?;
+        server.send(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }), None)?;
+        Ok(server)
+    }
+
+    fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let id = {
+            let mut next = self.next_id.lock().unwrap();
+            *next += 1;
+            *next
+        };
+        let response = self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }), Some(id))?.context("no response")?;
+        if let Some(error) = response.get("error") {
+            bail!("{} failed: {}", method, error.get("message").and_then(Value::as_str).unwrap_or("unknown error"));
+        }
+        Ok(response.get("result").cloned().unwrap_or(Value::Null))
+    }
+
+    /// Sends a message and, when `id` is set, waits for the response with that id. Requests
+    /// and notifications from the server in between are skipped.
+    fn send(&self, message: Value, id: Option<u64>) -> Result<Option<Value>> {
+        let mut transport = self.transport.lock().unwrap();
+        match &mut *transport {
+            Transport::Stdio { child, stdin, lines } => {
+                writeln!(stdin, "{}", message)?;
+                stdin.flush()?;
+                let Some(id) = id else { return Ok(None) };
+                let deadline = Instant::now() + REPLY_TIMEOUT;
+                loop {
+                    let line = match lines.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                        Ok(line) => line,
+                        Err(RecvTimeoutError::Disconnected) => bail!("server exited"),
+                        Err(RecvTimeoutError::Timeout) => {
+                            // A server that stopped answering would hold up every later call
+                            let _ = child.kill();
+                            let _ = child.wait();
+                            bail!("no answer within {}s, so the server was stopped", REPLY_TIMEOUT.as_secs());
+                        }
+                    };
+                    if let Ok(reply) = serde_json::from_str::<Value>(&line) {
+                        if reply.get("id").and_then(Value::as_u64) == Some(id) {
+                            return Ok(Some(reply));
+                        }
+                    }
+                }
+            }
+            Transport::Http { client, url, session, runtime } => {
+                let mut request = client
+                    .post(url.as_str())
+                    .header(CONTENT_TYPE, "application/json")
+                    .header(ACCEPT, "application/json, text/event-stream")
+                    .body(message.to_string());
+                if let Some(session) = session.as_ref() {
+                    request = request.header(SESSION_HEADER, session.clone());
+                }
+                let (new_session, content_type, body) = runtime.block_on(async {
+                    let response = request.send().await?.error_for_status()?;
+                    let new_session = response.headers().get(SESSION_HEADER).cloned();
+                    let content_type = response.headers().get(CONTENT_TYPE).and_then(|v| v.to_str().ok()).unwrap_or_default().to_string();
+                    anyhow::Ok((new_session, content_type, response.text().await?))
+                })?;
+                if new_session.is_some() {
+                    *session = new_session;
+                }
+                let Some(id) = id else { return Ok(None) };
+                // The reply is either plain JSON or an event stream carrying it
+                let messages: Vec<Value> = if content_type.starts_with("text/event-stream") {
+                    body.lines().filter_map(|line| serde_json::from_str(line.strip_prefix("data:")?.trim()).ok()).collect()
+                } else {
+                    vec![serde_json::from_str(&body)?]
+                };
+                Ok(messages.into_iter().find(|reply| reply.get("id").and_then(Value::as_u64) == Some(id)))
+            }
+        }
+    }
+
+    /// Every item of a paginated list such as `tools/list`.
+    fn list(&self, method: &str, key: &str) -> Result<Vec<Value>> {
+        let mut items = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let result = self.request(method, params)?;
+            items.extend(result.get(key).and_then(Value::as_array).cloned().unwrap_or_default());
+            cursor = result.get("nextCursor").and_then(Value::as_str).map(String::from);
+            if cursor.is_none() {
+                return Ok(items);
+            }
+        }
+    }
+}
+
+/// The server's tools, plus a `read_resource` tool when it has resources, named
+/// `<server>__<tool>` so servers can't clash. Returns the tools and the resource count.
+pub fn tools(server: McpServer) -> Result<(Vec<Arc<dyn Tool>>, usize)> {
+    let server = Arc::new(server);
+    let mut tools: Vec<Arc<dyn Tool>> = Vec::new();
+    for tool in server.list("tools/list", "tools")? {
+        let name = tool.get("name").and_then(Value::as_str).ok_or_else(|| anyhow!("tool without a name"))?.to_string();
+        tools.push(Arc::new(McpTool {
+            server: server.clone(),
+            spec: ToolSpec {
+                name: format!("{}__{}", server.name, name),
+                description: tool.get("description").and_then(Value::as_str).unwrap_or_default().to_string(),
+                parameters: tool.get("inputSchema").cloned().unwrap_or_else(|| json!({ "type": "object", "properties": {} })),
+            },
+            remote_name: name,
+        }));
+    }
+    // Servers without resources answer with an error, which just means none
+    let resources = server.list("resources/list", "resources").unwrap_or_default();
+    if !resources.is_empty() {
+        let listed: Vec<String> = resources
+            .iter()
+            .take(MAX_LISTED_RESOURCES)
+            .filter_map(|resource| {
+                let uri = resource.get("uri")?.as_str()?;
+                Some(match resource.get("name").and_then(Value::as_str) {
+                    Some(name) => format!("{} ({})", uri, name),
+                    None => uri.to_string(),
+                })
+            })
+            .collect();
+        tools.push(Arc::new(McpResourceReader {
+            server: server.clone(),
+            spec: ToolSpec {
+                name: format!("{}__read_resource", server.name),
+                description: format!("Read a resource from the {} MCP server. Available: {}", server.name, listed.join(", ")),
+                parameters: json!({
+                    "type": "object",
+                    "properties": { "uri": { "type": "string", "description": "URI of the resource" } },
+                    "required": ["uri"],
+                }),
+            },
+        }));
+    }
+    Ok((tools, resources.len()))
+}
+
+struct McpTool {
+    server: Arc<McpServer>,
+    spec: ToolSpec,
+    remote_name: String,
+}
+
+impl Tool for McpTool {
+    fn spec(&self) -> ToolSpec {
+        self.spec.clone()
+    }
+
+    fn run(&self, arguments: &Value) -> Result<String> {
+        let arguments = if arguments.is_null() { json!({}) } else { arguments.clone() };
+        let result = self.server.request("tools/call", json!({ "name": self.remote_name, "arguments": arguments }))?;
+        let text = content_text(result.get("content"));
+        if result.get("isError").and_then(Value::as_bool).unwrap_or(false) {
+            bail!("{}", text);
+        }
+        Ok(text)
+    }
+}
+
+struct McpResourceReader {
+    server: Arc<McpServer>,
+    spec: ToolSpec,
+}
+
+impl Tool for McpResourceReader {
+    fn spec(&self) -> ToolSpec {
+        self.spec.clone()
+    }
+
+    fn run(&self, arguments: &Value) -> Result<String> {
+        let uri = arguments.get("uri").and_then(Value::as_str).ok_or_else(|| anyhow!("missing string argument 'uri'"))?;
+        let result = self.server.request("resources/read", json!({ "uri": uri }))?;
+        Ok(content_text(result.get("contents")))
+    }
+}
+
+/// The text parts of a tool result or resource, joined; binary parts are only named.
+fn content_text(content: Option<&Value>) -> String {
+    let parts = content.and_then(Value::as_array).cloned().unwrap_or_default();
+    parts
+        .iter()
+        .map(|part| match part.get("text").and_then(Value::as_str) {
+            Some(text) => text.to_string(),
+            None => format!("[{} content omitted]", part.get("type").or_else(|| part.get("mimeType")).and_then(Value::as_str).unwrap_or("binary")),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,238 @@
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::app::{App, PendingAction};
+use crate::chat::{Message, ViewRow};
+use crate::text;
+use crate::toast::Severity;
+use crate::ui::ChatLayout;
+
+/// What the quick-actions menu can do with the selected message.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MessageAction {
+    /// Copy the text without markdown markup.
+    Copy,
+    CopyMarkdown,
+    /// Add the message to the input as a `>` quote.
+    Quote,
+    Delete,
+    /// Drop the message and everything after it, and send its prompt again.
+    Regenerate,
+    /// Write each code block to a file in the current directory.
+    SaveCodeBlocks,
+}
+
+impl MessageAction {
+    pub const ALL: [MessageAction; 6] = [
+        MessageAction::Copy,
+        MessageAction::CopyMarkdown,
+        MessageAction::Quote,
+        MessageAction::Delete,
+        MessageAction::Regenerate,
+        MessageAction::SaveCodeBlocks,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MessageAction::Copy => "Copy",
+            MessageAction::CopyMarkdown => "Copy as markdown",
+            MessageAction::Quote => "Quote into input",
+            MessageAction::Delete => "Delete",
+            MessageAction::Regenerate => "Regenerate from here",
+            MessageAction::SaveCodeBlocks => "Save code blocks",
+        }
+    }
+}
+
+impl App {
+    /// Highlights message `index` of the active conversation and scrolls to it; its text is
+    /// what `Ctrl+Y` copies.
+    pub fn select_message(&mut self, index: usize) {
+        if self.highlight_message(index) {
+            self.conversation_mut().jump_to_message(index + 1);
+        }
+    }
+
+    /// Selects the message at the top, middle, or bottom of the view, leaving the view as it is.
+    pub fn select_message_in_view(&mut self, row: ViewRow) {
+        if let Some(index) = self.conversation().message_in_view(row) {
+            self.highlight_message(index);
+        }
+    }
+
+    fn highlight_message(&mut self, index: usize) -> bool {
+        let conversation = self.conversation_mut();
+        let Some(message) = conversation.messages.get(index) else { return false };
+        let content = message.content.clone();
+        conversation.selected_message = Some(index);
+        self.selected_text = Some(content);
+        self.status_message = format!("Message {} selected. Ctrl+Y copies it, Alt+M opens its actions", index + 1);
+        true
+    }
+
+    /// Moves the selection to the message before or after it, starting from the last one.
+    pub fn move_message_selection(&mut self, down: bool) {
+        let conversation = self.conversation();
+        let Some(last) = conversation.messages.len().checked_sub(1) else { return };
+        let index = match conversation.selected_message {
+            Some(selected) if down => (selected + 1).min(last),
+            Some(selected) => selected.saturating_sub(1),
+            None => last,
+        };
+        self.select_message(index);
+    }
+
+    /// Opens the quick-actions menu on the selected message, selecting the last one if none is.
+    pub fn open_message_menu(&mut self) {
+        if self.conversation().selected_message.is_none() {
+            self.move_message_selection(true);
+        }
+        if self.conversation().selected_message.is_some() {
+            self.message_menu = Some(0);
+        }
+    }
+
+    /// Moves the highlight in the open quick-actions menu.
+    pub fn move_menu_selection(&mut self, down: bool) {
+        if let Some(item) = self.message_menu.as_mut() {
+            *item = if down { (*item + 1).min(MessageAction::ALL.len() - 1) } else { item.saturating_sub(1) };
+        }
+    }
+
+    /// Runs the highlighted menu entry on the selected message and closes the menu.
+    pub fn run_message_menu(&mut self) {
+        let Some(item) = self.message_menu.take() else { return };
+        let Some(index) = self.conversation().selected_message else { return };
+        self.run_message_action(index, MessageAction::ALL[item]);
+    }
+
+    pub fn run_message_action(&mut self, index: usize, action: MessageAction) {
+        let Some(message) = self.conversation().messages.get(index).cloned() else { return };
+        match action {
+            MessageAction::Copy => {
+                self.selected_text = Some(text::plain(&message.content));
+                self.copy_to_clipboard();
+            }
+            MessageAction::CopyMarkdown => {
+                self.selected_text = Some(message.content);
+                self.copy_to_clipboard();
+            }
+            MessageAction::Quote => self.quote_message(index),
+            MessageAction::Delete => self.delete_message(index),
+            MessageAction::Regenerate => {
+                let conversation = self.conversation();
+                let (conversation_id, count, thinking) = (conversation.id, conversation.messages.len(), conversation.is_thinking);
+                match conversation.messages[..=index].iter().rposition(Message::is_user) {
+                    _ if thinking => self.set_error("Wait for the reply to finish first".to_string()),
+                    None => self.set_error("There is no prompt before this message to send again".to_string()),
+                    // Only asks when more than the reply itself would be lost
+                    Some(prompt) if count - prompt > 2 => {
+                        let question = format!("Send message {} again? The {} messages after it are removed.", prompt + 1, count - prompt - 1);
+                        self.confirm(question, PendingAction::Regenerate { conversation_id, index: prompt });
+                    }
+                    Some(prompt) => self.regenerate_from(conversation_id, prompt),
+                }
+            }
+            MessageAction::SaveCodeBlocks => match save_code_blocks(&message.content, index + 1, Path::new(".")) {
+                Ok(paths) if paths.is_empty() => self.set_error("This message has no code blocks".to_string()),
+                Ok(paths) => {
+                    let names: Vec<String> = paths.iter().map(|path| path.display().to_string()).collect();
+                    self.notify(Severity::Success, format!("Saved {}", names.join(", ")));
+                }
+                Err(e) => self.set_error(format!("Cannot save code blocks: {:#}", e)),
+            },
+        }
+    }
+
+    /// Quotes the selected message, or the last reply, into the input with `>` before each line,
+    /// so a follow-up says what it refers to even once the message has left the context.
+    pub fn quote_selected_message(&mut self) {
+        let conversation = self.conversation();
+        let index = conversation.selected_message.or_else(|| conversation.messages.iter().rposition(|m| m.role == "assistant"));
+        match index {
+            Some(index) => self.quote_message(index),
+            None => self.set_error("No message to quote".to_string()),
+        }
+    }
+
+    /// Adds message `index` to the input as a quote headed by who wrote it, and switches to
+    /// insert mode for the follow-up.
+    pub fn quote_message(&mut self, index: usize) {
+        let Some(message) = self.conversation().messages.get(index) else { return };
+        let mut quote = vec![format!("> {} wrote:", self.message_label(message))];
+        quote.extend(message.content.trim_end().lines().map(|line| format!("> {}", line).trim_end().to_string()));
+        let input = &mut self.conversation_mut().input;
+        if !input.is_empty() && !input.ends_with('\n') {
+            input.push('\n');
+        }
+        input.push_str(&quote.join("\n"));
+        input.push_str("\n\n");
+        self.vim_insert = true;
+        self.status_message = format!("Quoted message {}", index + 1);
+    }
+
+    /// Removes message `index` from the active conversation; not while a reply is streaming,
+    /// which writes to its message by position.
+    pub fn delete_message(&mut self, index: usize) {
+        let conversation = self.conversation_mut();
+        if conversation.is_thinking {
+            self.set_error("Wait for the reply to finish first".to_string());
+            return;
+        }
+        if index >= conversation.messages.len() {
+            return;
+        }
+        conversation.messages.remove(index);
+        conversation.selected_message = None;
+        conversation.saved_messages = conversation.saved_messages.min(index);
+        *conversation.layout.borrow_mut() = ChatLayout::default();
+        self.selected_text = None;
+        self.status_message = format!("Deleted message {}", index + 1);
+    }
+
+    /// Drops prompt `index` of a conversation and everything after it, then sends the prompt
+    /// again; the reply it replaces is kept as an earlier attempt.
+    pub(crate) fn regenerate_from(&mut self, conversation_id: usize, index: usize) {
+        let Some(position) = self.conversations.iter().position(|c| c.id == conversation_id) else { return };
+        self.active_conversation = position;
+        self.selected_text = None;
+        self.resend_prompt(position, index);
+    }
+}
+
+/// Writes the code blocks of message `number` to `dir` as `message-<number>-<block>.<ext>`,
+/// never over an existing file.
+fn save_code_blocks(content: &str, number: usize, dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for (i, block) in text::code_blocks(content).into_iter().enumerate() {
+        let stem = format!("message-{}-{}", number, i + 1);
+        let extension = extension(&block.language);
+        let mut path = dir.join(format!("{}.{}", stem, extension));
+        let mut copy = 1;
+        while path.exists() {
+            copy += 1;
+            path = dir.join(format!("{}-{}.{}", stem, copy, extension));
+        }
+        fs::write(&path, format!("{}\n", block.code))?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// File extension for a code block's language.
+fn extension(language: &str) -> &str {
+    match language {
+        "rust" => "rs",
+        "python" | "python3" => "py",
+        "javascript" => "js",
+        "typescript" => "ts",
+        "shell" | "bash" | "zsh" | "console" => "sh",
+        "yml" => "yaml",
+        "c++" => "cpp",
+        "markdown" => "md",
+        "" | "text" | "plaintext" => "txt",
+        other if other.chars().all(|c| c.is_ascii_alphanumeric()) => other,
+        _ => "txt",
+    }
+}
@@ -0,0 +1,74 @@
+use anyhow::{bail, Context, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A file format with a `version` field. Files written before formats had one are version 0.
+/// A format that contains another versioned one moves to a new version whenever the inner one
+/// does, and upgrades it as part of that step.
+pub trait Versioned: Serialize + DeserializeOwned {
+    /// The version this build writes.
+    const VERSION: u32;
+
+    /// Changes `value`, as written by `version`, into what `version + 1` writes. The `version`
+    /// field itself is updated by the caller.
+    fn upgrade(version: u32, value: &mut Value) -> Result<()>;
+}
+
+/// Brings `value` up to `T::VERSION` one step at a time; returns whether it changed.
+pub fn upgrade<T: Versioned>(value: &mut Value) -> Result<bool> {
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(0) as u32;
+    if version > T::VERSION {
+        bail!("written by a newer version of the app (format {}, this one reads up to {})", version, T::VERSION);
+    }
+    for from in version..T::VERSION {
+        T::upgrade(from, value).with_context(|| format!("Cannot upgrade from format {}", from))?;
+    }
+    if let Some(object) = value.as_object_mut() {
+        object.insert("version".to_string(), T::VERSION.into());
+    }
+    Ok(version < T::VERSION)
+}
+
+/// Parses `json` as `T`, upgrading it first if an older version wrote it. Also returns the
+/// upgraded JSON when it changed.
+pub fn parse<T: Versioned>(json: &str) -> Result<(T, Option<Value>)> {
+    let mut value: Value = serde_json::from_str(json)?;
+    let upgraded = upgrade::<T>(&mut value)?;
+    let parsed = T::deserialize(&value)?;
+    Ok((parsed, upgraded.then_some(value)))
+}
+
+/// Reads `path` as `T`. A file an older version wrote is rewritten in place in the current
+/// format, once it has been read successfully; if that fails the file is left as it was.
+pub fn load<T: Versioned>(path: &Path) -> Result<T> {
+    let json = fs::read_to_string(path)?;
+    let (parsed, upgraded) = parse::<T>(&json)?;
+    if let Some(value) = upgraded {
+        match write_atomically(path, &serde_json::to_string_pretty(&value)?) {
+            Ok(()) => tracing::info!(path = %path.display(), version = T::VERSION, "upgraded file format"),
+            Err(e) => tracing::warn!(path = %path.display(), "cannot rewrite upgraded file: {:#}", e),
+        }
+    }
+    Ok(parsed)
+}
+
+/// Copies a file that could not be read to `<name>.bak`, before defaults replace it.
+pub fn back_up(path: &Path) -> Result<PathBuf> {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    let backup = PathBuf::from(backup);
+    fs::copy(path, &backup)?;
+    Ok(backup)
+}
+
+/// Writes next to `path` and renames over it, so a crash midway never leaves half a file.
+fn write_atomically(path: &Path, contents: &str) -> Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    fs::write(&temp, contents)?;
+    fs::rename(&temp, path)?;
+    Ok(())
+}
@@ -0,0 +1,427 @@
+use anyhow::Result;
+use ollama_rs::models::ModelOptions;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+
+use crate::app::App;
+use crate::config::MODEL_CONFIG_FILE;
+use crate::error::{AppError, Retry};
+use crate::migrate::{self, Versioned};
+use crate::toast::Severity;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigField {
+    Preset,
+    Temperature,
+    TopP,
+    TopK,
+    RepeatPenalty,
+    ContextWindow,
+    SystemPrompt,
+    MinP,
+    TfsZ,
+    FrequencyPenalty,
+    PresencePenalty,
+    Mirostat,
+    MirostatEta,
+    MirostatTau,
+}
+
+/// How a config field is edited: numeric fields get a slider and step keys, text fields are typed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldKind {
+    Float { min: f64, max: f64, step: f64 },
+    Integer { min: f64, max: f64, step: f64 },
+    Text,
+}
+
+impl ConfigField {
+    /// Fields in the order they appear on the config screen: basic first, then advanced.
+    pub const ALL: [ConfigField; 14] = [
+        ConfigField::Preset,
+        ConfigField::Temperature,
+        ConfigField::TopP,
+        ConfigField::TopK,
+        ConfigField::RepeatPenalty,
+        ConfigField::ContextWindow,
+        ConfigField::SystemPrompt,
+        ConfigField::MinP,
+        ConfigField::TfsZ,
+        ConfigField::FrequencyPenalty,
+        ConfigField::PresencePenalty,
+        ConfigField::Mirostat,
+        ConfigField::MirostatEta,
+        ConfigField::MirostatTau,
+    ];
+
+    pub fn is_advanced(self) -> bool {
+        !matches!(
+            self,
+            ConfigField::Preset | ConfigField::Temperature | ConfigField::TopP | ConfigField::TopK | ConfigField::RepeatPenalty | ConfigField::ContextWindow | ConfigField::SystemPrompt
+        )
+    }
+
+    pub fn kind(self) -> FieldKind {
+        match self {
+            ConfigField::Preset | ConfigField::SystemPrompt => FieldKind::Text,
+            ConfigField::Temperature => FieldKind::Float { min: 0.0, max: 2.0, step: 0.05 },
+            ConfigField::TopP => FieldKind::Float { min: 0.0, max: 1.0, step: 0.05 },
+            ConfigField::TopK => FieldKind::Integer { min: 1.0, max: 1000.0, step: 5.0 },
+            ConfigField::RepeatPenalty => FieldKind::Float { min: 0.0, max: 2.0, step: 0.05 },
+            ConfigField::ContextWindow => FieldKind::Integer { min: 512.0, max: 32768.0, step: 512.0 },
+            ConfigField::MinP => FieldKind::Float { min: 0.0, max: 1.0, step: 0.01 },
+            ConfigField::TfsZ => FieldKind::Float { min: 1.0, max: 10.0, step: 0.1 },
+            ConfigField::FrequencyPenalty | ConfigField::PresencePenalty => FieldKind::Float { min: -2.0, max: 2.0, step: 0.1 },
+            ConfigField::Mirostat => FieldKind::Integer { min: 0.0, max: 2.0, step: 1.0 },
+            ConfigField::MirostatEta => FieldKind::Float { min: 0.0, max: 1.0, step: 0.01 },
+            ConfigField::MirostatTau => FieldKind::Float { min: 0.0, max: 10.0, step: 0.5 },
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ConfigField::Preset => "Preset",
+            ConfigField::Temperature => "Temperature",
+            ConfigField::TopP => "Top P",
+            ConfigField::TopK => "Top K",
+            ConfigField::RepeatPenalty => "Repeat Penalty",
+            ConfigField::ContextWindow => "Context Window",
+            ConfigField::SystemPrompt => "System Prompt",
+            ConfigField::MinP => "Min P",
+            ConfigField::TfsZ => "Tail Free Sampling (tfs_z)",
+            ConfigField::FrequencyPenalty => "Frequency Penalty",
+            ConfigField::PresencePenalty => "Presence Penalty",
+            ConfigField::Mirostat => "Mirostat",
+            ConfigField::MirostatEta => "Mirostat Eta",
+            ConfigField::MirostatTau => "Mirostat Tau",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            ConfigField::Preset => "Named parameter set. Type a preset name & Enter to apply",
+            ConfigField::Temperature => "Controls randomness. Lower = more focused, Higher = more creative",
+            ConfigField::TopP => "Nucleus sampling. Controls diversity of responses",
+            ConfigField::TopK => "Limits token selection to top K options",
+            ConfigField::RepeatPenalty => "Penalizes repetition. Higher = less repetition",
+            ConfigField::ContextWindow => "Number of tokens in context window",
+            ConfigField::SystemPrompt => "System instructions for the model",
+            ConfigField::MinP => "Drops tokens less likely than min_p x the top token",
+            ConfigField::TfsZ => "Reduces the impact of unlikely tokens. 1.0 = disabled",
+            ConfigField::FrequencyPenalty => "Penalizes tokens by how often they already appeared",
+            ConfigField::PresencePenalty => "Penalizes tokens that appeared at all",
+            ConfigField::Mirostat => "Perplexity control. 0 = off, 1 = Mirostat, 2 = Mirostat 2.0",
+            ConfigField::MirostatEta => "Mirostat learning rate",
+            ConfigField::MirostatTau => "Mirostat target. Lower = more coherent",
+        }
+    }
+
+    pub fn range_hint(self) -> &'static str {
+        match self {
+            ConfigField::Preset => "",
+            ConfigField::Temperature => "Range: 0.0 - 2.0, Default: 0.8",
+            ConfigField::TopP => "Range: 0.0 - 1.0, Default: 0.9",
+            ConfigField::TopK => "Range: 1 - 1000, Default: 40",
+            ConfigField::RepeatPenalty => "Range: 0.0 - 2.0, Default: 1.1",
+            ConfigField::ContextWindow => "Range: 512 - 32768, Default: 2048",
+            ConfigField::SystemPrompt => "",
+            ConfigField::MinP => "Range: 0.0 - 1.0, Default: 0.0",
+            ConfigField::TfsZ => "Range: 1.0 - 10.0, Default: 1.0",
+            ConfigField::FrequencyPenalty => "Range: -2.0 - 2.0, Default: 0.0",
+            ConfigField::PresencePenalty => "Range: -2.0 - 2.0, Default: 0.0",
+            ConfigField::Mirostat => "Values: 0, 1, 2, Default: 0",
+            ConfigField::MirostatEta => "Range: 0.0 - 1.0, Default: 0.1",
+            ConfigField::MirostatTau => "Range: 0.0 - 10.0, Default: 5.0",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ModelConfig {
+    /// Format version; see `migrate`.
+    pub version: u32,
+    pub temperature: f32,
+    pub top_p: f32,
+    pub top_k: u32,
+    pub repeat_penalty: f32,
+    pub num_ctx: u64,
+    pub system_prompt: String,
+    pub min_p: f32,
+    pub tfs_z: f32,
+    pub frequency_penalty: f32,
+    pub presence_penalty: f32,
+    pub mirostat: u8,
+    pub mirostat_eta: f32,
+    pub mirostat_tau: f32,
+    /// The preset the sampling parameters came from, cleared once any of them is edited by hand.
+    pub preset: Option<String>,
+}
+
+impl Default for ModelConfig {
+    fn default() -> Self {
+        Self {
+            version: Self::VERSION,
+            temperature: 0.8,
+            top_p: 0.9,
+            top_k: 40,
+            repeat_penalty: 1.1,
+            num_ctx: 2048,
+            system_prompt: String::from("You are a helpful AI assistant."),
+            min_p: 0.0,
+            tfs_z: 1.0,
+            frequency_penalty: 0.0,
+            presence_penalty: 0.0,
+            mirostat: 0,
+            mirostat_eta: 0.1,
+            mirostat_tau: 5.0,
+            preset: None,
+        }
+    }
+}
+
+impl Versioned for ModelConfig {
+    const VERSION: u32 = 1;
+
+    fn upgrade(_version: u32, _value: &mut Value) -> Result<()> {
+        // Version 1 only added the version field
+        Ok(())
+    }
+}
+
+impl ModelConfig {
+    /// The saved config, or the defaults if there is none yet.
+    pub fn load(config_dir: &Path) -> Result<Self> {
+        let path = config_dir.join(MODEL_CONFIG_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        migrate::load(&path)
+    }
+
+    pub fn numeric(&self, field: ConfigField) -> Option<f64> {
+        Some(match field {
+            ConfigField::Temperature => self.temperature as f64,
+            ConfigField::TopP => self.top_p as f64,
+            ConfigField::TopK => self.top_k as f64,
+            ConfigField::RepeatPenalty => self.repeat_penalty as f64,
+            ConfigField::ContextWindow => self.num_ctx as f64,
+            ConfigField::MinP => self.min_p as f64,
+            ConfigField::TfsZ => self.tfs_z as f64,
+            ConfigField::FrequencyPenalty => self.frequency_penalty as f64,
+            ConfigField::PresencePenalty => self.presence_penalty as f64,
+            ConfigField::Mirostat => self.mirostat as f64,
+            ConfigField::MirostatEta => self.mirostat_eta as f64,
+            ConfigField::MirostatTau => self.mirostat_tau as f64,
+            ConfigField::Preset | ConfigField::SystemPrompt => return None,
+        })
+    }
+
+    pub fn set_numeric(&mut self, field: ConfigField, value: f64) {
+        match field {
+            ConfigField::Temperature => self.temperature = value as f32,
+            ConfigField::TopP => self.top_p = value as f32,
+            ConfigField::TopK => self.top_k = value as u32,
+            ConfigField::RepeatPenalty => self.repeat_penalty = value as f32,
+            ConfigField::ContextWindow => self.num_ctx = value as u64,
+            ConfigField::MinP => self.min_p = value as f32,
+            ConfigField::TfsZ => self.tfs_z = value as f32,
+            ConfigField::FrequencyPenalty => self.frequency_penalty = value as f32,
+            ConfigField::PresencePenalty => self.presence_penalty = value as f32,
+            ConfigField::Mirostat => self.mirostat = value as u8,
+            ConfigField::MirostatEta => self.mirostat_eta = value as f32,
+            ConfigField::MirostatTau => self.mirostat_tau = value as f32,
+            ConfigField::Preset | ConfigField::SystemPrompt => {}
+        }
+    }
+
+    pub fn to_options(&self) -> ModelOptions {
+        ModelOptions::default()
+            .temperature(self.temperature)
+            .top_p(self.top_p)
+            .top_k(self.top_k)
+            .repeat_penalty(self.repeat_penalty)
+            .num_ctx(self.num_ctx)
+            .min_p(self.min_p)
+            .tfs_z(self.tfs_z)
+            .mirostat(self.mirostat)
+            .mirostat_eta(self.mirostat_eta)
+            .mirostat_tau(self.mirostat_tau)
+            .extra("frequency_penalty", self.frequency_penalty)
+            .extra("presence_penalty", self.presence_penalty)
+    }
+}
+
+impl App {
+    /// Switches the current conversation to `model` and remembers it for the next start.
+    pub fn select_model(&mut self, model: String) {
+        self.status_message = format!("Model changed to: {}", model);
+        self.conversation_mut().model = model.clone();
+        self.app_config.model = Some(model);
+        self.save_app_config();
+    }
+
+    /// Lists the backend's models in the background, reporting a failure with an offer to retry.
+    pub fn fetch_models(&mut self) {
+        self.fetch_models_then(|app, result| {
+            if let Err(e) = result {
+                app.fail("Cannot list models", e, Some(Retry::ListModels));
+            }
+        });
+    }
+
+    /// Lists the backend's models in the background, then hands the outcome to `done`. A list
+    /// from a host switched away from in the meantime is dropped.
+    pub fn fetch_models_then(&mut self, done: impl FnOnce(&mut App, Result<(), AppError>) + Send + 'static) {
+        tracing::debug!(host = %self.app_config.active().url, "listing models");
+        self.listing_models = true;
+        let (backend, events) = (self.backend.clone(), self.events.clone());
+        let (host, model) = (self.app_config.active_host.clone(), self.conversation().model.clone());
+        tokio::spawn(async move {
+            let result = backend.list_models().await;
+            events.update(move |app| {
+                app.listing_models = false;
+                if app.app_config.active_host != host {
+                    return;
+                }
+                let result = result.map(|models| app.available_models = models).map_err(|e| AppError::from_backend(e, &model));
+                done(app, result);
+            });
+        });
+    }
+
+    /// Pulls `model_name` in the background, then lists the models again and runs `then`, which
+    /// failed for want of the model.
+    pub fn download_model(&mut self, model_name: String, then: Option<Retry>) {
+        self.status_message = format!("Downloading model: {}", model_name);
+        tracing::info!(model = %model_name, host = %self.app_config.active().url, "downloading model");
+        self.downloading = Some(model_name.clone());
+        let (backend, events) = (self.backend.clone(), self.events.clone());
+        tokio::spawn(async move {
+            let result = backend.pull_model(model_name.clone()).await;
+            events.update(move |app| {
+                app.downloading = None;
+                if let Err(e) = result {
+                    return app.fail("Download failed", AppError::from_backend(e, &model_name), None);
+                }
+                app.status_message = "Ready".to_string();
+                app.notify(Severity::Success, format!("Model {} downloaded successfully", model_name));
+                app.fetch_models_then(move |app, result| match result {
+                    Ok(()) => {
+                        if let Some(retry) = then {
+                            app.retry(retry);
+                        }
+                    }
+                    Err(e) => app.fail("Cannot list models", e, Some(Retry::ListModels)),
+                });
+            });
+        });
+    }
+
+    /// Writes the model config in the background.
+    pub fn save_config(&mut self) {
+        let json = match serde_json::to_string_pretty(&self.model_config) {
+            Ok(json) => json,
+            Err(e) => return self.set_error(format!("Cannot save configuration: {}", e)),
+        };
+        self.disk.write(self.config_dir.join(MODEL_CONFIG_FILE), json, |app, result| match result {
+            Ok(()) => app.notify(Severity::Success, "Configuration saved".to_string()),
+            Err(e) => app.set_error(format!("Cannot save configuration: {}", e)),
+        });
+    }
+
+    /// Validates and applies a typed value to the selected field. Rejected input is kept in
+    /// `config_error` for the editor to show; returns whether the value was applied.
+    pub fn update_config_field(&mut self, value: String) -> bool {
+        match self.try_update_config_field(value.trim()) {
+            Ok(()) => {
+                self.config_error = None;
+                true
+            }
+            Err(e) => {
+                self.config_error = Some(e.to_string());
+                false
+            }
+        }
+    }
+
+    fn try_update_config_field(&mut self, value: &str) -> Result<()> {
+        let field = self.config_field;
+        match field.kind() {
+            FieldKind::Text => match field {
+                ConfigField::Preset => self.apply_preset(value)?,
+                _ => self.model_config.system_prompt = value.to_string(),
+            },
+            FieldKind::Float { min, max, .. } | FieldKind::Integer { min, max, .. } => {
+                let parsed = if matches!(field.kind(), FieldKind::Integer { .. }) {
+                    value.parse::<u64>().map(|v| v as f64).map_err(|_| anyhow::anyhow!("'{}' is not a whole number", value))?
+                } else {
+                    value.parse::<f64>().ok().filter(|v| v.is_finite()).ok_or_else(|| anyhow::anyhow!("'{}' is not a number", value))?
+                };
+                if parsed < min || parsed > max {
+                    anyhow::bail!("{} must be between {} and {}", field.label(), min, max);
+                }
+                self.set_config_numeric(field, parsed);
+            }
+        }
+        Ok(())
+    }
+
+    /// Steps the selected numeric field up or down by its increment, clamped to its range.
+    pub fn adjust_config_field(&mut self, direction: f64) {
+        let field = self.config_field;
+        let (FieldKind::Float { min, max, step } | FieldKind::Integer { min, max, step }) = field.kind() else { return };
+        let Some(current) = self.model_config.numeric(field) else { return };
+        // Round to the step grid so repeated float increments don't accumulate noise
+        let value = (((current + direction * step) / step).round() * step).clamp(min, max);
+        self.set_config_numeric(field, value);
+        self.config_input = self.get_current_config_value();
+        self.config_error = None;
+    }
+
+    fn set_config_numeric(&mut self, field: ConfigField, value: f64) {
+        if field != ConfigField::ContextWindow {
+            self.model_config.preset = None;
+        }
+        self.model_config.set_numeric(field, value);
+    }
+
+    pub fn next_config_field(&mut self) {
+        let index = ConfigField::ALL.iter().position(|f| *f == self.config_field).unwrap_or(0);
+        self.config_field = ConfigField::ALL[(index + 1) % ConfigField::ALL.len()];
+    }
+
+    pub fn prev_config_field(&mut self) {
+        let index = ConfigField::ALL.iter().position(|f| *f == self.config_field).unwrap_or(0);
+        self.config_field = ConfigField::ALL[(index + ConfigField::ALL.len() - 1) % ConfigField::ALL.len()];
+    }
+
+    pub fn get_current_config_value(&self) -> String {
+        self.config_value(self.config_field)
+    }
+
+    pub fn config_value(&self, field: ConfigField) -> String {
+        match field {
+            ConfigField::Preset => self.model_config.preset.clone().unwrap_or_else(|| "custom".to_string()),
+            ConfigField::Temperature => self.model_config.temperature.to_string(),
+            ConfigField::TopP => self.model_config.top_p.to_string(),
+            ConfigField::TopK => self.model_config.top_k.to_string(),
+            ConfigField::RepeatPenalty => self.model_config.repeat_penalty.to_string(),
+            ConfigField::ContextWindow => self.model_config.num_ctx.to_string(),
+            ConfigField::SystemPrompt => self.model_config.system_prompt.clone(),
+            ConfigField::MinP => self.model_config.min_p.to_string(),
+            ConfigField::TfsZ => self.model_config.tfs_z.to_string(),
+            ConfigField::FrequencyPenalty => self.model_config.frequency_penalty.to_string(),
+            ConfigField::PresencePenalty => self.model_config.presence_penalty.to_string(),
+            ConfigField::Mirostat => self.model_config.mirostat.to_string(),
+            ConfigField::MirostatEta => self.model_config.mirostat_eta.to_string(),
+            ConfigField::MirostatTau => self.model_config.mirostat_tau.to_string(),
+        }
+    }
+
+    /// The global model config with this session's overrides applied.
+    pub fn effective_config(&self) -> ModelConfig {
+        self.conversation().overrides.apply(&self.model_config)
+    }
+}
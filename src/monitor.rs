@@ -0,0 +1,395 @@
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use sysinfo::{Networks, Pid, ProcessRefreshKind, ProcessesToUpdate, Signal, System};
+
+use crate::app::{App, AppMode, PendingAction};
+use crate::config::HostConfig;
+use crate::gpu::{self, GpuProvider, GpuStats};
+
+/// How often the sampler thread reads the system while the monitor is open.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+/// Process names that belong to Ollama: the server, and the runners it starts per model.
+const OLLAMA_PROCESSES: [&str; 2] = ["ollama", "llama-server"];
+
+/// Samples kept for each trend line, one per `HISTORY_INTERVAL`.
+const HISTORY_LEN: usize = 120;
+const HISTORY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Bytes per second moving through the disks and network interfaces.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct IoRates {
+    pub disk_read: f64,
+    pub disk_write: f64,
+    pub net_rx: f64,
+    pub net_tx: f64,
+}
+
+impl IoRates {
+    fn max(self, other: IoRates) -> IoRates {
+        IoRates {
+            disk_read: self.disk_read.max(other.disk_read),
+            disk_write: self.disk_write.max(other.disk_write),
+            net_rx: self.net_rx.max(other.net_rx),
+            net_tx: self.net_tx.max(other.net_tx),
+        }
+    }
+}
+
+/// Disk and network throughput, worked out from the byte counts since the previous sample.
+pub struct IoMonitor {
+    networks: Networks,
+    last_sample: Option<Instant>,
+    pub current: IoRates,
+    /// Highest rates seen since the app started.
+    pub peak: IoRates,
+}
+
+impl Default for IoMonitor {
+    fn default() -> Self {
+        Self { networks: Networks::new_with_refreshed_list(), last_sample: None, current: IoRates::default(), peak: IoRates::default() }
+    }
+}
+
+impl IoMonitor {
+    /// Takes a sample; `sys` must have had its processes refreshed just before. Disk traffic is
+    /// the sum over processes, which is what sysinfo can report on every platform.
+    pub fn sample(&mut self, sys: &System) {
+        self.networks.refresh();
+        let now = Instant::now();
+        let Some(last) = self.last_sample.replace(now) else { return };
+        let seconds = now.duration_since(last).as_secs_f64().max(0.001);
+        let (read, written) = sys.processes().values().map(|p| p.disk_usage()).fold((0, 0), |(r, w), usage| (r + usage.read_bytes, w + usage.written_bytes));
+        let (rx, tx) = self.networks.list().values().fold((0, 0), |(rx, tx), data| (rx + data.received(), tx + data.transmitted()));
+        self.current = IoRates {
+            disk_read: read as f64 / seconds,
+            disk_write: written as f64 / seconds,
+            net_rx: rx as f64 / seconds,
+            net_tx: tx as f64 / seconds,
+        };
+        self.peak = self.peak.max(self.current);
+    }
+}
+
+/// Rolling utilization percentages for the monitor's sparklines, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    last_sample: Option<Instant>,
+    pub cpu: VecDeque<u64>,
+    pub memory: VecDeque<u64>,
+    pub gpu: VecDeque<u64>,
+}
+
+impl History {
+    /// Records one sample per interval however often it is called, so the window covers a
+    /// fixed stretch of time regardless of the tick rate.
+    pub fn record(&mut self, cpu: f32, memory: f32, gpu: Option<f32>) {
+        if self.last_sample.is_some_and(|last| last.elapsed() < HISTORY_INTERVAL) {
+            return;
+        }
+        self.last_sample = Some(Instant::now());
+        push_bounded(&mut self.cpu, cpu);
+        push_bounded(&mut self.memory, memory);
+        if let Some(gpu) = gpu {
+            push_bounded(&mut self.gpu, gpu);
+        }
+    }
+}
+
+fn push_bounded(samples: &mut VecDeque<u64>, percent: f32) {
+    if samples.len() == HISTORY_LEN {
+        samples.pop_front();
+    }
+    samples.push_back(percent.clamp(0.0, 100.0).round() as u64);
+}
+
+/// One row of the process table.
+#[derive(Debug, Clone)]
+pub struct ProcessInfo {
+    pub pid: Pid,
+    pub parent: Option<Pid>,
+    pub name: String,
+    /// Percent of one core, so it can pass 100 on multi-core machines.
+    pub cpu: f32,
+    /// Resident memory in bytes.
+    pub memory: u64,
+    /// Seconds since the epoch; with the pid, tells the process from a later one given the same pid.
+    pub started: u64,
+}
+
+/// Everything the system monitor draws, as of the last sample.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    pub cpu_usage: f32,
+    /// Utilization of each logical core, in the order the OS numbers them.
+    pub cpu_cores: Vec<f32>,
+    /// Bytes of RAM in use and in total.
+    pub memory_used: u64,
+    pub memory_total: u64,
+    pub gpus: Vec<GpuStats>,
+    pub io: IoRates,
+    pub io_peak: IoRates,
+    pub history: History,
+    pub processes: Vec<ProcessInfo>,
+}
+
+impl Metrics {
+    pub fn memory_percent(&self) -> f32 {
+        if self.memory_total > 0 { self.memory_used as f32 * 100.0 / self.memory_total as f32 } else { 0.0 }
+    }
+}
+
+/// Reads the system on a background thread so `nvidia-smi` and the process scan never stall
+/// drawing. It only samples while active, i.e. while the monitor is on screen.
+pub struct Sampler {
+    active: bool,
+    control: Sender<bool>,
+    snapshots: Receiver<Metrics>,
+}
+
+impl Sampler {
+    pub fn spawn() -> Self {
+        let (control, commands) = mpsc::channel();
+        let (results, snapshots) = mpsc::channel();
+        thread::spawn(move || {
+            let mut collector = Collector::new();
+            let mut active = false;
+            loop {
+                // Idle until activated; once active, wake every interval or on a state change
+                let message = if active { commands.recv_timeout(SAMPLE_INTERVAL) } else { commands.recv().map_err(|_| RecvTimeoutError::Disconnected) };
+                match message {
+                    Ok(state) => active = state,
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+                if active && results.send(collector.sample()).is_err() {
+                    return;
+                }
+            }
+        });
+        Self { active: false, control, snapshots }
+    }
+
+    /// Starts or pauses sampling. Starting takes a sample straight away.
+    pub fn set_active(&mut self, active: bool) {
+        if active != self.active {
+            self.active = active;
+            let _ = self.control.send(active);
+        }
+    }
+
+    /// The newest snapshot taken since the last call, if any.
+    pub fn latest(&self) -> Option<Metrics> {
+        self.snapshots.try_iter().last()
+    }
+}
+
+/// The sampler thread's state: sysinfo needs the previous reading to work out CPU usage.
+struct Collector {
+    sys: System,
+    io: IoMonitor,
+    history: History,
+    gpus: Vec<Box<dyn GpuProvider>>,
+}
+
+impl Collector {
+    fn new() -> Self {
+        let mut collector = Self { sys: System::new(), io: IoMonitor::default(), history: History::default(), gpus: gpu::providers() };
+        // CPU usage is the change between two refreshes, so take a baseline up front
+        collector.refresh();
+        collector
+    }
+
+    /// Refreshes only what the monitor shows: CPU, memory, and per-process usage.
+    fn refresh(&mut self) {
+        self.sys.refresh_cpu_usage();
+        self.sys.refresh_memory();
+        self.sys.refresh_processes_specifics(ProcessesToUpdate::All, ProcessRefreshKind::new().with_cpu().with_memory().with_disk_usage());
+    }
+
+    fn sample(&mut self) -> Metrics {
+        self.refresh();
+        self.io.sample(&self.sys);
+
+        let cpu_cores: Vec<f32> = self.sys.cpus().iter().map(|cpu| cpu.cpu_usage()).collect();
+        let cpu_usage = if cpu_cores.is_empty() { 0.0 } else { cpu_cores.iter().sum::<f32>() / cpu_cores.len() as f32 };
+        let gpus: Vec<GpuStats> = self.gpus.iter().flat_map(|provider| provider.sample()).collect();
+        // Linux lists threads alongside processes; their usage is already in the parent's
+        let processes = self
+            .sys
+            .processes()
+            .values()
+            .filter(|p| p.thread_kind().is_none())
+            .map(|p| ProcessInfo {
+                pid: p.pid(),
+                parent: p.parent(),
+                name: p.name().to_string_lossy().to_string(),
+                cpu: p.cpu_usage(),
+                memory: p.memory(),
+                started: p.start_time(),
+            })
+            .collect();
+        let mut metrics = Metrics {
+            cpu_usage,
+            cpu_cores,
+            memory_used: self.sys.used_memory(),
+            memory_total: self.sys.total_memory(),
+            gpus,
+            io: self.io.current,
+            io_peak: self.io.peak,
+            history: History::default(),
+            processes,
+        };
+        self.history.record(cpu_usage, metrics.memory_percent(), metrics.gpus.first().and_then(|gpu| gpu.utilization));
+        metrics.history = self.history.clone();
+        metrics
+    }
+}
+
+/// The Ollama server, its runners, and anything they started, found by walking each process's
+/// parents (bounded, in case of a pid loop).
+pub fn ollama_processes(processes: &[ProcessInfo]) -> Vec<&ProcessInfo> {
+    let by_pid: HashMap<Pid, &ProcessInfo> = processes.iter().map(|p| (p.pid, p)).collect();
+    let is_ollama = |p: &ProcessInfo| {
+        let name = p.name.to_lowercase();
+        OLLAMA_PROCESSES.iter().any(|n| name.contains(n))
+    };
+    let under_ollama = |p: &ProcessInfo| {
+        let mut current = Some(p);
+        for _ in 0..64 {
+            let Some(process) = current else { return false };
+            if is_ollama(process) {
+                return true;
+            }
+            current = process.parent.and_then(|pid| by_pid.get(&pid).copied());
+        }
+        false
+    };
+    processes.iter().filter(|p| under_ollama(p)).collect()
+}
+
+/// A model the server holds in memory, as listed by `api/ps`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LoadedModel {
+    pub name: String,
+    /// Bytes in memory, and how many of them are on the GPU.
+    pub size: u64,
+    #[serde(default)]
+    pub size_vram: u64,
+    /// RFC 3339 time the model unloads if left idle.
+    #[serde(default)]
+    pub expires_at: Option<String>,
+}
+
+/// Models `host` has loaded. ollama-rs has no call for this, so the endpoint is read directly.
+pub async fn loaded_models(host: &HostConfig) -> Result<Vec<LoadedModel>> {
+    #[derive(Deserialize)]
+    struct Running {
+        models: Vec<LoadedModel>,
+    }
+    let body = host.get("api/ps")?.send().await?.error_for_status()?.bytes().await?;
+    Ok(serde_json::from_slice::<Running>(&body)?.models)
+}
+
+/// A size such as `4.7 GB`, in powers of 1024.
+pub fn format_size(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 { format!("{:.0} {}", value, UNITS[0]) } else { format!("{:.1} {}", value, UNITS[unit]) }
+}
+
+/// A rate such as `12.3 MB/s`, in powers of 1024.
+pub fn format_rate(bytes_per_second: f64) -> String {
+    format!("{}/s", format_size(bytes_per_second))
+}
+
+/// How often the monitor asks the host for its loaded models.
+const LOADED_MODELS_INTERVAL: Duration = Duration::from_secs(2);
+
+impl App {
+    /// Samples the system only while the monitor is open, and picks up the newest reading.
+    /// Returns whether there was one.
+    pub fn update_system_info(&mut self) -> bool {
+        self.sampler.set_active(self.mode == AppMode::SystemMonitor);
+        match self.sampler.latest() {
+            Some(metrics) => {
+                self.metrics = metrics;
+                let last = self.visible_processes().len().saturating_sub(1);
+                self.process_selected = self.process_selected.min(last);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Processes for the monitor table, busiest first, narrowed by the filter and the
+    /// Ollama-only toggle.
+    pub fn visible_processes(&self) -> Vec<&ProcessInfo> {
+        let filter = self.process_filter.to_lowercase();
+        let mut list: Vec<&ProcessInfo> = if self.ollama_only { ollama_processes(&self.metrics.processes) } else { self.metrics.processes.iter().collect() };
+        list.retain(|p| filter.is_empty() || p.name.to_lowercase().contains(&filter));
+        list.sort_by(|a, b| b.cpu.total_cmp(&a.cpu));
+        list
+    }
+
+    /// The highlighted process in the system monitor.
+    pub fn selected_process(&self) -> Option<&ProcessInfo> {
+        self.visible_processes().get(self.process_selected).copied()
+    }
+
+    /// Asks before ending the highlighted process.
+    pub fn end_selected_process(&mut self, force: bool) {
+        let Some(process) = self.selected_process() else {
+            self.status_message = "No process selected".to_string();
+            return;
+        };
+        let (pid, name, started) = (process.pid, process.name.clone(), process.started);
+        let verb = if force { "Kill" } else { "Terminate" };
+        self.confirm(format!("{} {} (pid {})?", verb, name, pid), PendingAction::SignalProcess { pid, name, started, force });
+    }
+
+    pub(crate) fn signal_process(&mut self, pid: Pid, name: &str, started: u64, force: bool) -> Result<String> {
+        let (signal, label) = if force { (Signal::Kill, "SIGKILL") } else { (Signal::Term, "SIGTERM") };
+        // The sampler owns the monitor's System, so look the process up afresh
+        let mut sys = System::new();
+        sys.refresh_processes(ProcessesToUpdate::Some(&[pid]));
+        let process = sys.process(pid).ok_or_else(|| anyhow!("{} (pid {}) has already exited", name, pid))?;
+        // It may have exited while the question was open and its pid gone to another process
+        if process.name().to_string_lossy() != name || process.start_time() != started {
+            bail!("{} (pid {}) has already exited; that pid now belongs to another process", name, pid);
+        }
+        match process.kill_with(signal) {
+            Some(true) => Ok(format!("Sent {} to {} (pid {})", label, name, pid)),
+            Some(false) => Err(anyhow!("Could not signal {} (pid {}); it may belong to another user", name, pid)),
+            None => Err(anyhow!("{} is not supported on this platform", label)),
+        }
+    }
+
+    pub fn toggle_ollama_only(&mut self) {
+        self.ollama_only = !self.ollama_only;
+        self.process_selected = 0;
+        self.status_message = if self.ollama_only { "Showing Ollama processes only".to_string() } else { "Showing all processes".to_string() };
+    }
+
+    /// Asks the active host which models it has loaded, every few seconds while the monitor
+    /// is open.
+    pub fn refresh_loaded_models(&mut self) {
+        if self.mode != AppMode::SystemMonitor || self.loaded_models_checked.is_some_and(|checked| checked.elapsed() < LOADED_MODELS_INTERVAL) {
+            return;
+        }
+        self.loaded_models_checked = Some(Instant::now());
+        let (host, events) = (self.app_config.active().clone(), self.events.clone());
+        tokio::spawn(async move {
+            let models = loaded_models(&host).await.ok();
+            events.update(move |app| app.loaded_models = models);
+        });
+    }
+}
@@ -0,0 +1,98 @@
+use anyhow::{bail, Result};
+
+use crate::app::App;
+use crate::chat::Message;
+use crate::config::AppConfig;
+use crate::text;
+
+/// The template raw mode starts with: the prompt as typed, for a base model to continue.
+pub const DEFAULT_TEMPLATE: &str = "{prompt}{response}";
+
+/// The template a conversation in raw mode is sent through.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RawTemplate {
+    /// The one in the config for the conversation's model at the time.
+    Configured,
+    /// One typed after `/raw` for this conversation.
+    Custom(String),
+}
+
+impl RawTemplate {
+    pub fn text<'a>(&'a self, config: &'a AppConfig, model: &str) -> &'a str {
+        match self {
+            RawTemplate::Configured => config.raw_template_for(model),
+            RawTemplate::Custom(template) => template,
+        }
+    }
+}
+
+/// The text sent in raw mode: `template` filled in once per exchange, with each earlier
+/// prompt from `history` and its reply, then the new `prompt` up to where its reply goes.
+/// `{system}` is only filled in the first time.
+pub fn render(template: &str, system: &str, history: &[Message], prompt: &str) -> String {
+    let mut exchanges: Vec<(&str, &str)> = Vec::new();
+    for message in history {
+        match exchanges.last_mut() {
+            Some((_, reply)) if !message.is_user() && reply.is_empty() => *reply = &message.content,
+            _ if message.is_user() => exchanges.push((&message.content, "")),
+            _ => {}
+        }
+    }
+    // The reply to the new prompt is what the model writes, so the template stops there
+    let open = template.split("{response}").next().unwrap_or(template);
+    let mut out = String::new();
+    let turns = exchanges.iter().map(|&(prompt, reply)| (template, prompt, reply)).chain([(open, prompt, "")]);
+    for (i, (template, prompt, reply)) in turns.enumerate() {
+        out.push_str(&text::fill_template(template, |name| match name {
+            "system" => Some(if i == 0 { system.to_string() } else { String::new() }),
+            "prompt" => Some(prompt.to_string()),
+            "response" => Some(reply.to_string()),
+            _ => None,
+        }));
+    }
+    out
+}
+
+/// `template` as typed after `/raw`, with `\n` standing for a line break; it has to say where
+/// the prompt goes.
+pub fn parse_template(template: &str) -> Result<String> {
+    let template = template.replace("\\n", "\n");
+    if !template.contains("{prompt}") {
+        bail!("A raw template needs a {{prompt}} placeholder, e.g. {}", DEFAULT_TEMPLATE);
+    }
+    Ok(template)
+}
+
+impl App {
+    /// Turns raw mode on or off for the current conversation.
+    pub fn toggle_raw_mode(&mut self) {
+        let enable = self.conversation().raw_template.is_none();
+        self.set_raw_mode(enable);
+    }
+
+    /// Raw mode with the template the config has for the conversation's model.
+    pub fn set_raw_mode(&mut self, enable: bool) {
+        match enable {
+            true => self.set_raw_template(RawTemplate::Configured),
+            false => {
+                self.conversation_mut().raw_template = None;
+                self.status_message = "Raw mode off: prompts go through the model's template again".to_string();
+            }
+        }
+    }
+
+    /// Sends this conversation's prompts filled into `template`, as they are, instead of
+    /// through the model's own template.
+    pub fn set_raw_template(&mut self, template: RawTemplate) {
+        let model = &self.conversation().model;
+        let shown = template.text(&self.app_config, model).replace('\n', "\\n");
+        let source = match template {
+            RawTemplate::Configured if self.app_config.model_template(model).is_some() => format!(" (the template for {})", model),
+            _ => String::new(),
+        };
+        self.status_message = format!("Raw mode on: prompts are sent as {}{}", shown, source);
+        let conversation = self.conversation_mut();
+        conversation.raw_template = Some(template);
+        conversation.fim = false;
+    }
+}
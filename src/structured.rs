@@ -0,0 +1,190 @@
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+use crate::app::App;
+use crate::backend::ReplyFormat;
+use crate::toast::Severity;
+
+/// The JSON in a reply, without the code fence some models put around it anyway.
+pub fn reply_json(content: &str) -> &str {
+    let content = content.trim();
+    content
+        .strip_prefix("```json")
+        .or_else(|| content.strip_prefix("```"))
+        .and_then(|rest| rest.strip_suffix("```"))
+        .map_or(content, str::trim)
+}
+
+/// What is wrong with a JSON reply: that it doesn't parse, or where it breaks `schema`.
+pub fn check_reply(content: &str, schema: Option<&Value>) -> Vec<String> {
+    match serde_json::from_str::<Value>(reply_json(content)) {
+        Ok(value) => schema.map(|schema| validate(&value, schema)).unwrap_or_default(),
+        Err(e) => vec![format!("Not valid JSON: {}", e)],
+    }
+}
+
+/// Where `value` breaks `schema`, each as `path: problem`. Covers the keywords schemas for
+/// model output use: `type`, `enum`, `const`, `properties`, `required`,
+/// `additionalProperties`, `items`, length and range bounds, and `allOf`/`anyOf`/`oneOf`.
+/// Other keywords are ignored.
+pub fn validate(value: &Value, schema: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    check(value, schema, "$", &mut errors);
+    errors
+}
+
+fn check(value: &Value, schema: &Value, path: &str, errors: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else {
+        // `true` accepts anything and `false` nothing
+        if schema == &Value::Bool(false) {
+            errors.push(format!("{}: not allowed here", path));
+        }
+        return;
+    };
+    let types: Vec<&str> = match schema.get("type") {
+        Some(Value::String(name)) => vec![name.as_str()],
+        Some(Value::Array(names)) => names.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    if !types.is_empty() && !types.iter().any(|name| has_type(value, name)) {
+        errors.push(format!("{}: expected {}, got {}", path, types.join(" or "), type_name(value)));
+        return;
+    }
+    if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+        if !options.contains(value) {
+            let options: Vec<String> = options.iter().map(Value::to_string).collect();
+            errors.push(format!("{}: {} is not one of {}", path, value, options.join(", ")));
+        }
+    }
+    if let Some(expected) = schema.get("const").filter(|expected| *expected != value) {
+        errors.push(format!("{}: expected {}, got {}", path, expected, value));
+    }
+
+    match value {
+        Value::Object(object) => {
+            for name in schema.get("required").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str) {
+                if !object.contains_key(name) {
+                    errors.push(format!("{}: missing required property '{}'", path, name));
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (name, item) in object {
+                let item_path = format!("{}.{}", path, name);
+                match (properties.and_then(|properties| properties.get(name)), schema.get("additionalProperties")) {
+                    (Some(property), _) => check(item, property, &item_path, errors),
+                    (None, Some(Value::Bool(false))) => errors.push(format!("{}: unexpected property", item_path)),
+                    (None, Some(extra)) => check(item, extra, &item_path, errors),
+                    (None, None) => {}
+                }
+            }
+        }
+        Value::Array(items) => {
+            bounds(items.len(), schema, ("minItems", "maxItems"), "items", path, errors);
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    check(item, item_schema, &format!("{}[{}]", path, i), errors);
+                }
+            }
+        }
+        Value::String(text) => bounds(text.chars().count(), schema, ("minLength", "maxLength"), "characters", path, errors),
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or_default();
+            if let Some(minimum) = schema.get("minimum").filter(|minimum| minimum.as_f64().is_some_and(|minimum| number < minimum)) {
+                errors.push(format!("{}: {} is less than the minimum of {}", path, value, minimum));
+            }
+            if let Some(maximum) = schema.get("maximum").filter(|maximum| maximum.as_f64().is_some_and(|maximum| number > maximum)) {
+                errors.push(format!("{}: {} is more than the maximum of {}", path, value, maximum));
+            }
+        }
+        _ => {}
+    }
+
+    for part in schema.get("allOf").and_then(Value::as_array).into_iter().flatten() {
+        check(value, part, path, errors);
+    }
+    if let Some(options) = schema.get("anyOf").and_then(Value::as_array) {
+        if !options.iter().any(|option| validate(value, option).is_empty()) {
+            errors.push(format!("{}: matches none of the anyOf schemas", path));
+        }
+    }
+    if let Some(options) = schema.get("oneOf").and_then(Value::as_array) {
+        match options.iter().filter(|option| validate(value, option).is_empty()).count() {
+            0 => errors.push(format!("{}: matches none of the oneOf schemas", path)),
+            1 => {}
+            matching => errors.push(format!("{}: matches {} of the oneOf schemas instead of one", path, matching)),
+        }
+    }
+}
+
+fn bounds(len: usize, schema: &serde_json::Map<String, Value>, (min, max): (&str, &str), unit: &str, path: &str, errors: &mut Vec<String>) {
+    if let Some(min) = schema.get(min).and_then(Value::as_u64).filter(|&min| (len as u64) < min) {
+        errors.push(format!("{}: {} {} where at least {} are needed", path, len, unit, min));
+    }
+    if let Some(max) = schema.get(max).and_then(Value::as_u64).filter(|&max| len as u64 > max) {
+        errors.push(format!("{}: {} {} where at most {} are allowed", path, len, unit, max));
+    }
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "integer" => value.as_f64().is_some_and(|number| number.fract() == 0.0),
+        name => type_name(value) == name || (name == "number" && value.is_number()),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+impl App {
+    /// Turns JSON replies on or off for the current conversation.
+    pub fn toggle_json_mode(&mut self) {
+        let enable = self.conversation().reply_format.is_none();
+        self.set_json_mode(enable);
+    }
+
+    pub fn set_json_mode(&mut self, enable: bool) {
+        self.conversation_mut().reply_format = enable.then_some(ReplyFormat::Json);
+        self.status_message = match enable {
+            true => "JSON mode on: replies in this conversation are asked for as JSON".to_string(),
+            false => "JSON mode off".to_string(),
+        };
+    }
+
+    /// Asks for replies following the JSON schema in `path`, which are checked against it.
+    pub fn load_json_schema(&mut self, path: &Path) -> Result<()> {
+        let text = fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+        let schema: Value = serde_json::from_str(&text).with_context(|| format!("{} is not JSON", path.display()))?;
+        if !schema.is_object() {
+            bail!("{} is not a JSON schema object", path.display());
+        }
+        self.conversation_mut().reply_format = Some(ReplyFormat::Schema(schema));
+        self.status_message = format!("JSON mode on: replies follow the schema in {}", path.display());
+        Ok(())
+    }
+
+    /// Notes on a finished JSON reply whether it parses and follows the conversation's schema,
+    /// so problems show under it.
+    pub(crate) fn check_json_reply(&mut self, conversation_id: usize, message_index: usize) {
+        let Some(conversation) = self.conversation_by_id(conversation_id) else { return };
+        let schema = match &conversation.reply_format {
+            Some(ReplyFormat::Schema(schema)) => Some(schema.clone()),
+            _ => None,
+        };
+        let Some(message) = conversation.messages.get_mut(message_index).filter(|message| message.json) else { return };
+        message.json_errors = check_reply(&message.content, schema.as_ref());
+        if !message.json_errors.is_empty() {
+            let count = message.json_errors.len();
+            self.notify(Severity::Warning, format!("The JSON reply has {} problem(s), shown under it", count));
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+use crate::backend::ChatBackend;
+use crate::batch::{self, BatchResult};
+use crate::models::{ConfigField, FieldKind, ModelConfig};
+use crate::text;
+
+/// Characters of each reply shown in the comparison grid.
+const PREVIEW_WIDTH: usize = 60;
+
+/// The reply to the prompt at one value of the setting swept.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct SweepResult {
+    pub value: f64,
+    #[serde(flatten)]
+    pub result: BatchResult,
+}
+
+/// One prompt sent to one model with a sampling setting at several values, as written by
+/// `llama-term sweep`.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct SweepReport {
+    /// When the run started, local time.
+    pub started: String,
+    pub model: String,
+    pub prompt: String,
+    /// The setting that was varied, e.g. `Temperature`.
+    pub setting: String,
+    /// In the order the values were given.
+    pub results: Vec<SweepResult>,
+}
+
+impl SweepReport {
+    pub fn failed(&self) -> usize {
+        self.results.iter().filter(|sweep| sweep.result.error.is_some()).count()
+    }
+
+    /// Writes the report as Markdown for a `.md` path and as JSON otherwise.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let markdown = path.extension().is_some_and(|ext| ext == "md" || ext == "markdown");
+        let contents = if markdown { self.to_markdown() } else { serde_json::to_string_pretty(self)? };
+        fs::write(path, contents).with_context(|| format!("Cannot write {}", path.display()))
+    }
+
+    /// A grid with a row per value: its timing, speed, and the start of the reply. Then each
+    /// reply in full.
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("# {} sweep, {}\n\n{}, {} value(s) of {}\n\n", self.setting, self.started, self.model, self.results.len(), self.setting);
+        for line in self.prompt.lines() {
+            out.push_str(format!("> {}", line).trim_end());
+            out.push('\n');
+        }
+        out.push_str(&format!("\n| {} | Time | Reply tokens | Tokens/s | Reply |\n|---:|---:|---:|---:|---|\n", self.setting));
+        for SweepResult { value, result } in &self.results {
+            let preview = match &result.error {
+                Some(error) => format!("**Failed:** {}", error),
+                None => text::truncate(&result.reply.split_whitespace().collect::<Vec<_>>().join(" "), PREVIEW_WIDTH),
+            };
+            out.push_str(&format!(
+                "| {} | {:.1}s | {} | {:.1} | {} |\n",
+                value,
+                result.seconds,
+                result.reply_tokens,
+                result.tokens_per_second(),
+                preview.replace('|', "\\|")
+            ));
+        }
+        for SweepResult { value, result } in &self.results {
+            out.push_str(&format!("\n## {} {} ({:.1}s, {} tokens, {:.1} tokens/s)\n\n", self.setting, value, result.seconds, result.reply_tokens, result.tokens_per_second()));
+            if let Some(error) = &result.error {
+                out.push_str(&format!("**Failed:** {}\n\n", error));
+            }
+            if !result.reply.trim().is_empty() {
+                out.push_str(result.reply.trim());
+                out.push('\n');
+            }
+        }
+        out
+    }
+}
+
+/// The values in `list`, separated by commas, each checked against the range of `setting`.
+pub fn parse_values(setting: ConfigField, list: &str) -> Result<Vec<f64>> {
+    let FieldKind::Float { min, max, .. } = setting.kind() else { bail!("{} cannot be swept", setting.label()) };
+    let mut values = Vec::new();
+    for value in list.split(',').map(str::trim).filter(|value| !value.is_empty()) {
+        match value.parse::<f64>() {
+            Ok(number) if (min..=max).contains(&number) => values.push(number),
+            _ => bail!("{} values are numbers from {} to {}, not '{}'", setting.label(), min, max, value),
+        }
+    }
+    if values.is_empty() {
+        bail!("No {} values to try", setting.label());
+    }
+    Ok(values)
+}
+
+/// Sends `prompt` to `model` once for each of `values` of `setting`, the rest of `config` as
+/// it is, one request at a time so the timings compare. Calls `progress` after every reply.
+pub async fn run(
+    backend: &dyn ChatBackend,
+    model: &str,
+    prompt: &str,
+    config: &ModelConfig,
+    setting: ConfigField,
+    values: &[f64],
+    mut progress: impl FnMut(&SweepResult),
+) -> SweepReport {
+    let mut report = SweepReport {
+        started: Local::now().format("%Y-%m-%d %H:%M").to_string(),
+        model: model.to_string(),
+        prompt: prompt.to_string(),
+        setting: setting.label().to_string(),
+        results: Vec::new(),
+    };
+    for &value in values {
+        let mut config = config.clone();
+        config.set_numeric(setting, value);
+        let result = SweepResult { value, result: batch::ask(backend, batch::request(model, prompt, config)).await };
+        progress(&result);
+        report.results.push(result);
+    }
+    report
+}
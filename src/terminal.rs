@@ -0,0 +1,90 @@
+use crossterm::{
+    cursor::Show,
+    event::{DisableFocusChange, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags},
+    execute,
+    terminal::{disable_raw_mode, supports_keyboard_enhancement, LeaveAlternateScreen},
+};
+use std::fs::OpenOptions;
+use std::io::{self, IsTerminal, Write};
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+/// Whether the terminal is in raw mode for the interface and still needs restoring.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+/// Whether the kitty keyboard protocol was turned on and has to be turned off again.
+static ENHANCED: AtomicBool = AtomicBool::new(false);
+
+/// Restores the terminal when dropped, however the interface ends: an error returned early or
+/// a panic unwinding through it.
+pub struct TerminalGuard(());
+
+impl TerminalGuard {
+    /// Call right after `enable_raw_mode`.
+    pub fn enter() -> Self {
+        ACTIVE.store(true, Ordering::SeqCst);
+        Self(())
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = restore();
+    }
+}
+
+/// Where the interface is drawn: stdout, or the terminal itself when stdout is redirected (as
+/// with `--print-on-exit > chat.md`), so that only the transcript goes to the file or pipe.
+pub fn screen() -> Box<dyn Write + Send> {
+    if !io::stdout().is_terminal() {
+        if let Ok(tty) = OpenOptions::new().write(true).open("/dev/tty") {
+            return Box::new(tty);
+        }
+    }
+    Box::new(io::stdout())
+}
+
+/// Turns on the kitty keyboard protocol if the terminal has it, so combinations such as
+/// `Shift+Enter` and `Ctrl+Enter` arrive as themselves and key releases are reported (and
+/// ignored) rather than lost. Returns whether it did; other terminals keep sending legacy keys.
+/// Call in raw mode, before anything else reads input. Skipped when stdout is redirected, as
+/// the query for it is written there.
+pub fn enable_keyboard_enhancement() -> bool {
+    if !io::stdout().is_terminal() || !supports_keyboard_enhancement().unwrap_or(false) {
+        return false;
+    }
+    let flags = KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES | KeyboardEnhancementFlags::REPORT_EVENT_TYPES;
+    let enabled = execute!(screen(), PushKeyboardEnhancementFlags(flags)).is_ok();
+    ENHANCED.store(enabled, Ordering::SeqCst);
+    enabled
+}
+
+/// Leaves raw mode and the alternate screen, stops focus reports and the kitty keyboard protocol, shows the cursor, and puts
+/// back the window title saved at startup. Only the first call does anything.
+pub fn restore() -> io::Result<()> {
+    if !ACTIVE.swap(false, Ordering::SeqCst) {
+        return Ok(());
+    }
+    let raw = disable_raw_mode();
+    let mut screen = screen();
+    write!(screen, "\x1b[23;0t")?;
+    if ENHANCED.swap(false, Ordering::SeqCst) {
+        execute!(screen, PopKeyboardEnhancementFlags)?;
+    }
+    execute!(screen, DisableFocusChange, LeaveAlternateScreen, Show)?;
+    raw
+}
+
+/// Restores the terminal before a panic on the main thread is printed, so the message lands on
+/// the normal screen instead of being wiped with the interface. A panic in a background task
+/// leaves the interface running, so it goes to the log rather than over the screen.
+pub fn install_panic_hook() {
+    let default = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        tracing::error!("{}", info);
+        if !ACTIVE.load(Ordering::SeqCst) || thread::current().name() == Some("main") {
+            let _ = restore();
+            default(info);
+        }
+    }));
+}
@@ -0,0 +1,215 @@
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Columns a tab expands to; terminals disagree, so it is fixed here.
+pub const TAB_WIDTH: usize = 4;
+
+/// Opens a placeholder for a command's output in a prompt: `{{$cmd git diff}}`.
+const COMMAND_OPEN: &str = "{{$cmd ";
+
+/// Display width of `text` in terminal columns. Emoji and CJK take two.
+pub fn width(text: &str) -> usize {
+    text.width()
+}
+
+/// Cuts `text` to at most `max` columns, ending with `…` when anything was dropped.
+pub fn truncate(text: &str, max: usize) -> String {
+    if width(text) <= max {
+        return text.to_string();
+    }
+    let mut out = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w + 1 > max {
+            break;
+        }
+        out.push(c);
+        used += w;
+    }
+    out.push('…');
+    out
+}
+
+/// Word-wraps `text` to `max` columns, breaking inside words only when one is wider than a line.
+/// Every `\n` starts a new line, and blank lines are kept.
+pub fn wrap(text: &str, max: usize) -> Vec<String> {
+    let max = max.max(1);
+    let mut lines = Vec::new();
+    for source in text.split('\n') {
+        let source = source.trim_end_matches('\r').replace('\t', &" ".repeat(TAB_WIDTH));
+        let mut line = String::new();
+        let mut used = 0;
+        // Indentation sticks to the first word so it survives wrapping
+        let rest = source.trim_start_matches(' ');
+        let indent = &source[..source.len() - rest.len()];
+        for (i, word) in rest.split_inclusive(' ').enumerate() {
+            let word = if i == 0 { format!("{}{}", indent, word) } else { word.to_string() };
+            let word = word.as_str();
+            let word_width = width(word);
+            let visible = width(word.trim_end());
+            if used + visible > max && used > 0 {
+                lines.push(line.trim_end().to_string());
+                line.clear();
+                used = 0;
+            }
+            // A trailing space may hang past the edge; it is trimmed when the line breaks
+            if visible <= max.saturating_sub(used) {
+                line.push_str(word);
+                used += word_width;
+                continue;
+            }
+            // Too long for any line: hard-break it by character
+            for c in word.chars() {
+                let w = c.width().unwrap_or(0);
+                if used + w > max && used > 0 {
+                    lines.push(line.trim_end().to_string());
+                    line.clear();
+                    used = 0;
+                }
+                if c == ' ' && used == 0 {
+                    continue;
+                }
+                line.push(c);
+                used += w;
+            }
+        }
+        lines.push(line.trim_end().to_string());
+    }
+    lines
+}
+
+/// `markdown` as plain text: code fences, heading marks, and emphasis markers removed. Code
+/// is kept as it is.
+pub fn plain(markdown: &str) -> String {
+    let mut lines = Vec::new();
+    let mut in_code = false;
+    for line in markdown.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            lines.push(line.to_string());
+            continue;
+        }
+        let trimmed = line.trim_start_matches('#');
+        let line = if trimmed.len() < line.len() && trimmed.starts_with(' ') { &trimmed[1..] } else { line };
+        lines.push(line.replace("**", "").replace("__", "").replace('`', ""));
+    }
+    lines.join("\n")
+}
+
+/// The commands in `{{$cmd ...}}` placeholders in `text`, in order.
+pub fn command_placeholders(text: &str) -> Vec<&str> {
+    let mut commands = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(COMMAND_OPEN) {
+        let after = &rest[start + COMMAND_OPEN.len()..];
+        let Some(end) = after.find("}}") else { break };
+        commands.push(after[..end].trim());
+        rest = &after[end + 2..];
+    }
+    commands
+}
+
+/// `text` with the placeholder for `command` replaced by `output`, or with `output` added at the
+/// end when it has no such placeholder. Output of several lines goes in a code block.
+pub fn insert_command_output(text: &str, command: &str, output: &str) -> String {
+    let output = if output.contains('\n') { format!("\n```\n{}\n```\n", output) } else { output.to_string() };
+    let mut rest = text;
+    let mut offset = 0;
+    while let Some(start) = rest.find(COMMAND_OPEN) {
+        let after = &rest[start + COMMAND_OPEN.len()..];
+        let Some(end) = after.find("}}") else { break };
+        if after[..end].trim() == command {
+            let (from, to) = (offset + start, offset + start + COMMAND_OPEN.len() + end + 2);
+            return format!("{}{}{}", &text[..from], output, &text[to..]);
+        }
+        offset += start + COMMAND_OPEN.len() + end + 2;
+        rest = &after[end + 2..];
+    }
+    match text.is_empty() || text.ends_with('\n') {
+        true => format!("{}{}", text, output.trim_start_matches('\n')),
+        false => format!("{}\n{}", text, output.trim_start_matches('\n')),
+    }
+}
+
+/// How long a piece of markdown is once its markup is dropped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Length {
+    pub words: usize,
+    pub characters: usize,
+}
+
+impl Length {
+    /// Words an adult reads in a minute, for the reading time.
+    const WORDS_PER_MINUTE: usize = 230;
+
+    pub fn of(markdown: &str) -> Self {
+        let text = plain(markdown);
+        Self { words: text.split_whitespace().count(), characters: text.chars().filter(|c| !c.is_whitespace()).count() }
+    }
+
+    /// Whole minutes to read it, rounded up; never less than one.
+    pub fn reading_minutes(&self) -> usize {
+        self.words.div_ceil(Self::WORDS_PER_MINUTE).max(1)
+    }
+}
+
+impl std::fmt::Display for Length {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} words, {} characters, {} min read", self.words, self.characters, self.reading_minutes())
+    }
+}
+
+/// Replaces each `{name}` in `template` with `value(name)`. Unknown names and unmatched braces
+/// are left as they are.
+pub fn fill_template(template: &str, value: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('}').and_then(|end| Some((end, value(&after[..end])?))) {
+            Some((end, filled)) => {
+                out.push_str(&filled);
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// A fenced code block from a reply; `language` is the lowercased first word after the fence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeBlock {
+    pub language: String,
+    pub code: String,
+}
+
+/// The ```-fenced code blocks in `text`, in order. A block still open at the end counts too.
+pub fn code_blocks(text: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut open: Option<(String, Vec<&str>)> = None;
+    for line in text.lines() {
+        let fence = line.trim_start().strip_prefix("```");
+        match (&mut open, fence) {
+            (None, Some(info)) => open = Some((info.split_whitespace().next().unwrap_or("").to_lowercase(), Vec::new())),
+            (Some(_), Some(rest)) if rest.trim().is_empty() => {
+                let (language, lines) = open.take().unwrap_or_default();
+                blocks.push(CodeBlock { language, code: lines.join("\n") });
+            }
+            (Some((_, lines)), _) => lines.push(line),
+            (None, None) => {}
+        }
+    }
+    if let Some((language, lines)) = open {
+        blocks.push(CodeBlock { language, code: lines.join("\n") });
+    }
+    blocks
+}
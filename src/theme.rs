@@ -0,0 +1,206 @@
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+
+/// Which palette to use; `Auto` follows the detected terminal background.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ThemeName {
+    #[default]
+    Auto,
+    Dark,
+    Light,
+    /// Bright colors on the terminal background, for low vision or washed-out screens.
+    HighContrast,
+    /// Blue/orange instead of green/red, safe for deuteranopia and protanopia.
+    Colorblind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Background {
+    Dark,
+    Light,
+}
+
+/// Semantic colors used by every view, so nothing in the UI names a raw color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    /// Title bar, input borders, field labels
+    pub primary: Color,
+    pub user: Color,
+    pub assistant: Color,
+    pub text: Color,
+    /// Secondary labels
+    pub secondary: Color,
+    /// Disabled or unavailable items
+    pub muted: Color,
+    /// Status bar, spinner, the selected config value
+    pub warning: Color,
+    pub accent: Color,
+    pub success: Color,
+    pub danger: Color,
+    pub highlight_bg: Color,
+    pub gauge_bg: Color,
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            primary: Color::Cyan,
+            user: Color::Green,
+            assistant: Color::Blue,
+            text: Color::White,
+            secondary: Color::Gray,
+            muted: Color::DarkGray,
+            warning: Color::Yellow,
+            accent: Color::Magenta,
+            success: Color::Green,
+            danger: Color::Red,
+            highlight_bg: Color::DarkGray,
+            gauge_bg: Color::Black,
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            primary: Color::Rgb(0, 95, 135),
+            user: Color::Rgb(0, 110, 40),
+            assistant: Color::Rgb(20, 60, 170),
+            text: Color::Black,
+            secondary: Color::Rgb(90, 90, 90),
+            muted: Color::Rgb(140, 140, 140),
+            warning: Color::Rgb(150, 90, 0),
+            accent: Color::Rgb(130, 30, 130),
+            success: Color::Rgb(0, 110, 40),
+            danger: Color::Rgb(180, 0, 0),
+            highlight_bg: Color::Rgb(210, 210, 225),
+            gauge_bg: Color::Rgb(230, 230, 230),
+        }
+    }
+
+    pub fn high_contrast() -> Self {
+        Self {
+            primary: Color::LightCyan,
+            user: Color::LightYellow,
+            assistant: Color::White,
+            text: Color::White,
+            secondary: Color::White,
+            muted: Color::Gray,
+            warning: Color::LightYellow,
+            accent: Color::LightMagenta,
+            success: Color::LightCyan,
+            danger: Color::LightRed,
+            highlight_bg: Color::Blue,
+            gauge_bg: Color::Black,
+        }
+    }
+
+    /// Okabe–Ito colors: success and danger differ in hue and brightness, not just red vs green.
+    pub fn colorblind() -> Self {
+        Self {
+            primary: Color::Rgb(86, 180, 233),
+            user: Color::Rgb(240, 228, 66),
+            assistant: Color::Rgb(86, 180, 233),
+            text: Color::White,
+            secondary: Color::Gray,
+            muted: Color::DarkGray,
+            warning: Color::Rgb(230, 159, 0),
+            accent: Color::Rgb(204, 121, 167),
+            success: Color::Rgb(0, 114, 178),
+            danger: Color::Rgb(213, 94, 0),
+            highlight_bg: Color::DarkGray,
+            gauge_bg: Color::Black,
+        }
+    }
+
+    pub fn resolve(name: ThemeName, background: Option<Background>) -> Self {
+        match (name, background) {
+            (ThemeName::HighContrast, _) => Theme::high_contrast(),
+            (ThemeName::Colorblind, _) => Theme::colorblind(),
+            (ThemeName::Light, _) | (ThemeName::Auto, Some(Background::Light)) => Theme::light(),
+            _ => Theme::dark(),
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
+
+/// Works out whether the terminal background is light or dark, from `COLORFGBG` or an OSC 11
+/// query. Must be called after raw mode is enabled so the reply isn't echoed.
+pub fn detect_background() -> Option<Background> {
+    if let Some(background) = std::env::var("COLORFGBG").ok().as_deref().and_then(parse_colorfgbg) {
+        return Some(background);
+    }
+    query_osc11().as_deref().and_then(parse_osc11)
+}
+
+/// `COLORFGBG` is `fg;bg` (sometimes `fg;default;bg`) using the 16 ANSI color indexes.
+fn parse_colorfgbg(value: &str) -> Option<Background> {
+    let bg: u8 = value.rsplit(';').next()?.parse().ok()?;
+    Some(if bg == 7 || bg >= 9 { Background::Light } else { Background::Dark })
+}
+
+/// Parses a reply such as `\x1b]11;rgb:ffff/ffff/dddd\x07` by its relative luminance.
+fn parse_osc11(reply: &str) -> Option<Background> {
+    let rgb = reply.split("rgb:").nth(1)?;
+    let channel = |s: &str| -> Option<f64> {
+        let hex: String = s.chars().take_while(|c| c.is_ascii_hexdigit()).take(4).collect();
+        if hex.is_empty() {
+            return None;
+        }
+        let max = 16f64.powi(hex.len() as i32) - 1.0;
+        Some(u32::from_str_radix(&hex, 16).ok()? as f64 / max)
+    };
+    let mut parts = rgb.split('/');
+    let (r, g, b) = (channel(parts.next()?)?, channel(parts.next()?)?, channel(parts.next()?)?);
+    let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    Some(if luminance > 0.5 { Background::Light } else { Background::Dark })
+}
+
+#[cfg(unix)]
+fn query_osc11() -> Option<String> {
+    use std::io::{Read, Write};
+    use std::os::fd::AsRawFd;
+    use std::time::{Duration, Instant};
+
+    let mut tty = std::fs::OpenOptions::new().read(true).write(true).open("/dev/tty").ok()?;
+    // Device attributes (DA1) follows, which every terminal answers, and in order: once that
+    // reply is in, any answer to the colour query is too, however slow the link, and none is
+    // left to turn up later as typed keys
+    tty.write_all(b"\x1b]11;?\x1b\\\x1b[c").ok()?;
+    tty.flush().ok()?;
+
+    // Only a terminal that answers neither query runs into the deadline
+    let deadline = Instant::now() + Duration::from_secs(2);
+    let mut reply = Vec::new();
+    let mut buf = [0u8; 64];
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        let mut fds = libc::pollfd { fd: tty.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+        // SAFETY: `fds` is a valid pollfd for the duration of the call
+        let ready = unsafe { libc::poll(&mut fds, 1, remaining.as_millis() as libc::c_int) };
+        if ready <= 0 {
+            break;
+        }
+        let n = tty.read(&mut buf).ok()?;
+        if n == 0 {
+            break;
+        }
+        reply.extend_from_slice(&buf[..n]);
+        // The DA1 reply is `ESC [ ? ... c`
+        if let Some(start) = reply.windows(3).position(|w| w == b"\x1b[?") {
+            if reply[start..].contains(&b'c') {
+                reply.truncate(start);
+                break;
+            }
+        }
+    }
+    String::from_utf8(reply).ok()
+}
+
+#[cfg(not(unix))]
+fn query_osc11() -> Option<String> {
+    None
+}
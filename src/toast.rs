@@ -0,0 +1,99 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Most toasts kept at once; older ones are dropped first.
+const MAX_TOASTS: usize = 5;
+/// Entries kept in the event log before the oldest are discarded.
+const MAX_LOG_ENTRIES: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// Errors stay up longest so there is time to read them.
+    pub fn timeout(self) -> Duration {
+        match self {
+            Severity::Info | Severity::Success => Duration::from_secs(3),
+            Severity::Warning => Duration::from_secs(5),
+            Severity::Error => Duration::from_secs(8),
+        }
+    }
+}
+
+/// A transient notification drawn in the corner of the screen.
+#[derive(Debug, Clone)]
+pub struct Toast {
+    pub severity: Severity,
+    pub message: String,
+    pub created: Instant,
+}
+
+#[derive(Debug, Default)]
+pub struct Toasts {
+    queue: VecDeque<Toast>,
+}
+
+impl Toasts {
+    pub fn push(&mut self, severity: Severity, message: String) {
+        if self.queue.len() == MAX_TOASTS {
+            self.queue.pop_front();
+        }
+        self.queue.push_back(Toast { severity, message, created: Instant::now() });
+    }
+
+    /// Drops toasts whose timeout has passed. Returns whether any were removed.
+    pub fn expire(&mut self) -> bool {
+        let before = self.queue.len();
+        self.queue.retain(|toast| toast.created.elapsed() < toast.severity.timeout());
+        self.queue.len() != before
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Toast> {
+        self.queue.iter()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub time: String,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Bounded history of notifications and notable events, browsable after the toasts are gone.
+#[derive(Debug, Default)]
+pub struct EventLog {
+    entries: VecDeque<LogEntry>,
+}
+
+impl EventLog {
+    pub fn push(&mut self, severity: Severity, message: String) {
+        match severity {
+            Severity::Info | Severity::Success => tracing::info!("{}", message),
+            Severity::Warning => tracing::warn!("{}", message),
+            Severity::Error => tracing::error!("{}", message),
+        }
+        if self.entries.len() == MAX_LOG_ENTRIES {
+            self.entries.pop_front();
+        }
+        let time = chrono::Local::now().format("%H:%M:%S").to_string();
+        self.entries.push_back(LogEntry { time, severity, message });
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &LogEntry> {
+        self.entries.iter()
+    }
+}
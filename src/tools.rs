@@ -0,0 +1,244 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::io;
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::config::expand_path;
+use crate::text;
+
+/// Longest tool output sent back to the model, in characters; the rest is cut off.
+const MAX_OUTPUT: usize = 16 * 1024;
+
+/// A tool as the model sees it: a name, what it does, and a JSON schema for its arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolSpec {
+    pub name: String,
+    pub description: String,
+    pub parameters: Value,
+}
+
+/// A call the model asked for.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ToolCall {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: Value,
+}
+
+impl ToolCall {
+    /// `name(arguments)`, for the chat pane and the approval prompt.
+    pub fn summary(&self) -> String {
+        match &self.arguments {
+            Value::Null => format!("{}()", self.name),
+            Value::Object(map) if map.is_empty() => format!("{}()", self.name),
+            arguments => format!("{}({})", self.name, arguments),
+        }
+    }
+}
+
+/// Something a model can ask the app to do on this machine.
+pub trait Tool: Send + Sync {
+    fn spec(&self) -> ToolSpec;
+
+    /// Whether each call waits for the user to approve it; only harmless tools skip this.
+    fn needs_approval(&self) -> bool {
+        true
+    }
+
+    fn run(&self, arguments: &Value) -> Result<String>;
+}
+
+/// The tools offered to models that support tool calling.
+pub struct ToolRegistry {
+    tools: Vec<Arc<dyn Tool>>,
+    /// Tools from each connected MCP server, by server name.
+    servers: BTreeMap<String, Vec<Arc<dyn Tool>>>,
+}
+
+impl Default for ToolRegistry {
+    fn default() -> Self {
+        Self { tools: vec![Arc::new(GetTime), Arc::new(ReadFile), Arc::new(RunCommand)], servers: BTreeMap::new() }
+    }
+}
+
+impl ToolRegistry {
+    fn all(&self) -> impl Iterator<Item = &Arc<dyn Tool>> {
+        self.tools.iter().chain(self.servers.values().flatten())
+    }
+
+    pub fn specs(&self) -> Vec<ToolSpec> {
+        self.all().map(|tool| tool.spec()).collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
+        self.all().find(|tool| tool.spec().name == name).cloned()
+    }
+
+    pub fn set_server_tools(&mut self, server: &str, tools: Vec<Arc<dyn Tool>>) {
+        self.servers.insert(server.to_string(), tools);
+    }
+
+    /// Drops a server's tools; the connection closes once no running call holds one.
+    pub fn remove_server(&mut self, server: &str) {
+        self.servers.remove(server);
+    }
+}
+
+/// Runs a call and returns what goes back to the model. Failures are reported to the model
+/// too, so it can correct itself.
+pub fn run(tool: &dyn Tool, arguments: &Value) -> String {
+    report(tool.run(arguments))
+}
+
+/// Runs a command the user typed after `!`, reporting it the same way as a tool call.
+pub fn run_shell_command(command: &str) -> String {
+    report(shell(command))
+}
+
+/// What a command prints, stdout then stderr, for putting into a prompt: no exit status, and
+/// cut short like any other output.
+pub fn command_output(command: &str) -> String {
+    report(run_in_shell(command).context("cannot start the shell").map(|output| {
+        let mut printed = String::from_utf8_lossy(&output.stdout).into_owned();
+        printed.push_str(&String::from_utf8_lossy(&output.stderr));
+        printed.trim_end().to_string()
+    }))
+}
+
+/// Runs a code block from a reply with the interpreter for its language, reporting it the
+/// same way as a tool call.
+pub fn run_code(language: &str, code: &str) -> String {
+    report(script(language, code))
+}
+
+/// The program, its arguments, and the file extension used to run a code block in
+/// `language`; `None` for languages that can't be run.
+fn interpreter(language: &str) -> Option<(&'static str, &'static [&'static str], &'static str)> {
+    match language {
+        "sh" | "shell" => Some(("sh", &[], "sh")),
+        "bash" => Some(("bash", &[], "sh")),
+        "zsh" => Some(("zsh", &[], "sh")),
+        "python" | "python3" | "py" => Some(("python3", &[], "py")),
+        // Single-file Rust runs as a cargo script, which still needs the nightly toolchain
+        "rust" | "rs" => Some(("cargo", &["+nightly", "-Zscript", "--quiet"], "rs")),
+        _ => None,
+    }
+}
+
+pub fn can_run(language: &str) -> bool {
+    interpreter(language).is_some()
+}
+
+fn report(result: Result<String>) -> String {
+    match result {
+        Ok(output) if output.chars().count() > MAX_OUTPUT => format!("{}\n[output truncated]", text::truncate(&output, MAX_OUTPUT)),
+        Ok(output) => output,
+        Err(e) => format!("Error: {:#}", e),
+    }
+}
+
+fn string_argument<'a>(arguments: &'a Value, name: &str) -> Result<&'a str> {
+    arguments.get(name).and_then(Value::as_str).ok_or_else(|| anyhow!("missing string argument '{}'", name))
+}
+
+struct GetTime;
+struct ReadFile;
+struct RunCommand;
+
+impl Tool for GetTime {
+    fn spec(&self) -> ToolSpec {
+        ToolSpec {
+            name: "get_time".to_string(),
+            description: "Current local date and time, with the UTC offset".to_string(),
+            parameters: json!({ "type": "object", "properties": {} }),
+        }
+    }
+
+    fn needs_approval(&self) -> bool {
+        false
+    }
+
+    fn run(&self, _arguments: &Value) -> Result<String> {
+        Ok(chrono::Local::now().to_rfc3339())
+    }
+}
+
+impl Tool for ReadFile {
+    fn spec(&self) -> ToolSpec {
+        ToolSpec {
+            name: "read_file".to_string(),
+            description: "Read a UTF-8 text file from the user's machine".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": { "path": { "type": "string", "description": "Absolute path, or relative to the working directory; ~ is expanded" } },
+                "required": ["path"],
+            }),
+        }
+    }
+
+    fn run(&self, arguments: &Value) -> Result<String> {
+        let path = expand_path(string_argument(arguments, "path")?);
+        std::fs::read_to_string(&path).with_context(|| format!("cannot read {}", path.display()))
+    }
+}
+
+impl Tool for RunCommand {
+    fn spec(&self) -> ToolSpec {
+        ToolSpec {
+            name: "run_command".to_string(),
+            description: "Run a shell command on the user's machine and return its exit status and output".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": { "command": { "type": "string", "description": "Command line for the shell" } },
+                "required": ["command"],
+            }),
+        }
+    }
+
+    fn run(&self, arguments: &Value) -> Result<String> {
+        shell(string_argument(arguments, "command")?)
+    }
+}
+
+/// Runs `command` with the system shell and reports its exit status, stdout, and stderr.
+fn shell(command: &str) -> Result<String> {
+    Ok(describe(run_in_shell(command).context("cannot start the shell")?))
+}
+
+/// Runs `command` with `sh -c`, or `cmd /C` on Windows, and waits for it.
+fn run_in_shell(command: &str) -> io::Result<Output> {
+    #[cfg(unix)]
+    return Command::new("sh").args(["-c", command]).output();
+    #[cfg(windows)]
+    return Command::new("cmd").args(["/C", command]).output();
+}
+
+/// Writes `code` to a temporary file and runs it, since interpreters differ in how they take
+/// a program on the command line.
+fn script(language: &str, code: &str) -> Result<String> {
+    static RUNS: AtomicUsize = AtomicUsize::new(0);
+    let (program, args, extension) = interpreter(language).ok_or_else(|| anyhow!("can't run {} code", language))?;
+    let run = RUNS.fetch_add(1, Ordering::Relaxed);
+    let path = std::env::temp_dir().join(format!("ollama_tui_block_{}_{}.{}", std::process::id(), run, extension));
+    std::fs::write(&path, format!("{}\n", code)).with_context(|| format!("cannot write {}", path.display()))?;
+    let output = Command::new(program).args(args).arg(&path).output().with_context(|| format!("cannot start {}", program));
+    let _ = std::fs::remove_file(&path);
+    Ok(describe(output?))
+}
+
+/// A finished process as shown to the model and in the chat: exit status, stdout, then stderr.
+fn describe(output: Output) -> String {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let mut result = format!("exit status: {}\n", output.status.code().map_or("killed".to_string(), |code| code.to_string()));
+    result.push_str(&stdout);
+    if !stderr.is_empty() {
+        result.push_str("\nstderr:\n");
+        result.push_str(&stderr);
+    }
+    result
+}
@@ -1,331 +1,1896 @@
 use ratatui::{
     Frame,
-    layout::{Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
+    symbols::border,
     text::{Line, Span},
-    widgets::{Block, Borders, BorderType, Gauge, List, ListItem, Paragraph, Row, Table, Wrap, ListState},
+    widgets::{Block, Borders, BorderType, Clear, Gauge, LineGauge, List, ListItem, ListState, Paragraph, Row, Sparkline, Table, TableState, Wrap},
 };
 
-use crate::app::{App, AppMode, ConfigField};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::time::Duration;
+
+use crate::app::{App, AppMode, Failure};
+use crate::attempts::{self, AttemptDiff, Change};
+use crate::backend::ReplyFormat;
+use crate::chat::{Conversation, Message};
+use crate::error::{AppError, Recovery};
+use crate::fim::Fill;
+use crate::config::{Density, StatusSegment};
+use crate::images::{InlineImages, IMAGE_COLS};
+use crate::keymap::{format_sequence, KeyContext};
+use crate::logging;
+use crate::mcp::McpStatus;
+use crate::message_menu::MessageAction;
+use crate::models::{ConfigField, FieldKind};
+use crate::monitor::{self, format_rate, format_size};
+use crate::structured;
+use crate::text;
+use crate::theme::Theme;
+use crate::toast::Severity;
+use crate::usage::{self, UsageTotals};
+
+const SIDEBAR_WIDTH: u16 = 30;
+/// Loaded models listed in the monitor's Ollama panel before the rest are cut off.
+const MAX_LOADED_MODEL_ROWS: usize = 4;
+const CONTEXT_GAUGE_WIDTH: u16 = 36;
+const TOAST_WIDTH: u16 = 44;
+/// Lines of command and tool output shown until it's expanded.
+const COLLAPSED_OUTPUT_LINES: usize = 4;
+/// Queued prompts listed above the input before the rest are only counted.
+const MAX_QUEUE_ROWS: usize = 3;
+/// Context usage at which the gauge warns that old turns are about to be dropped.
+const CONTEXT_WARN_RATIO: f64 = 0.9;
 
 pub fn ui(f: &mut Frame, app: &App) {
+    let t = &app.theme;
+    let layout = &app.app_config;
+    let input_height = if layout.show_input && app.mode == AppMode::Chat { layout.input_height } else { 0 };
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Length(3),
+            Constraint::Length(if layout.show_title_bar { 3 } else { 0 }),
             Constraint::Min(0),
-            Constraint::Length(3),
-            Constraint::Length(1),
+            Constraint::Length(input_height),
+            Constraint::Length(if layout.show_status_bar || app.command_line.is_some() { 1 } else { 0 }),
         ])
         .split(f.area());
 
     // Title bar
-    let title = Paragraph::new(format!(
-        "Ollama TUI Chat - Model: {} | Mode: {:?}",
-        app.current_model, app.mode
-    ))
-    .style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
-    .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).border_style(Style::default().fg(Color::Cyan)));
-    f.render_widget(title, chunks[0]);
+    let conversation = app.conversation();
+    let title_text = match &layout.title_template {
+        Some(template) => text::fill_template(template, |name| title_field(app, name)),
+        None => default_title(app),
+    };
+    let title_block = panel(app).border_style(Style::default().fg(t.primary));
+    let title = Paragraph::new(title_text)
+    .style(Style::default().fg(t.primary).add_modifier(Modifier::BOLD));
+    if layout.show_title_bar {
+        let inner = title_block.inner(chunks[0]);
+        f.render_widget(title_block, chunks[0]);
+        let parts = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(0), Constraint::Length(CONTEXT_GAUGE_WIDTH.min(inner.width / 3))])
+            .split(inner);
+        f.render_widget(title, parts[0]);
+        render_context_gauge(f, app, parts[1]);
+    }
 
     match app.mode {
-        AppMode::Chat => { render_chat(f, app, chunks[1]); render_input(f, app, chunks[2]); }
+        AppMode::Chat => {
+            let (mut chat_area, mut input_area) = (chunks[1], chunks[2]);
+            if layout.show_sidebar {
+                let body = Rect { height: chunks[1].height + chunks[2].height, ..chunks[1] };
+                let columns = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Length(SIDEBAR_WIDTH.min(body.width / 2)), Constraint::Min(0)])
+                    .split(body);
+                render_sidebar(f, app, columns[0]);
+                let rows = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(0), Constraint::Length(input_height)])
+                    .split(columns[1]);
+                (chat_area, input_area) = (rows[0], rows[1]);
+            }
+            let queued = &conversation.queued;
+            if input_height > 0 && !queued.is_empty() {
+                let queue_height = queued.len().min(MAX_QUEUE_ROWS + 1) as u16;
+                let rows = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(0), Constraint::Length(queue_height)])
+                    .split(chat_area);
+                chat_area = rows[0];
+                render_queue(f, app, conversation, chat_column(rows[1], layout.chat_width));
+            }
+            match app.split_index() {
+                Some(other) => {
+                    let panes = Layout::default()
+                        .direction(Direction::Horizontal)
+                        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                        .split(chat_area);
+                    // Panes keep tab order so they don't swap sides when focus moves
+                    let (left, right) = if other < app.active_conversation { (other, app.active_conversation) } else { (app.active_conversation, other) };
+                    render_chat(f, app, &app.conversations[left], chat_column(panes[0], layout.chat_width));
+                    render_chat(f, app, &app.conversations[right], chat_column(panes[1], layout.chat_width));
+                }
+                None => render_chat(f, app, conversation, chat_column(chat_area, layout.chat_width)),
+            }
+            if input_height > 0 {
+                render_input(f, app, conversation, chat_column(input_area, layout.chat_width));
+            }
+        }
         AppMode::ModelSelection => { render_model_selection(f, app, chunks[1]); }
         AppMode::ModelDownload => { render_model_download(f, app, chunks[1]); }
         AppMode::SystemMonitor => { render_system_monitor(f, app, chunks[1]); }
         AppMode::ChatHistory => { render_chat_history(f, app, chunks[1]); }
         AppMode::ModelConfig => { render_model_config(f, app, chunks[1]); }
+        AppMode::HostSelection => { render_host_selection(f, app, chunks[1]); }
+        AppMode::PromptLibrary => { render_prompt_library(f, app, chunks[1]); }
+        AppMode::EventLog => { render_event_log(f, app, chunks[1]); }
+        AppMode::McpServers => { render_mcp_servers(f, app, chunks[1]); }
+        AppMode::Stats => { render_stats(f, app, chunks[1]); }
+    }
+
+    // The `:` command line takes over the status bar while it is open
+    if let Some(command) = &app.command_line {
+        f.render_widget(Paragraph::new(format!(":{}", command)).style(Style::default().fg(t.text)), chunks[3]);
+    } else if layout.show_status_bar {
+        f.render_widget(Paragraph::new(status_line(app)), chunks[3]);
     }
 
-    let status = Paragraph::new(app.status_message.as_str()).style(Style::default().fg(Color::Yellow));
-    f.render_widget(status, chunks[3]);
+    if app.show_help {
+        render_help(f, app);
+    }
+
+    if app.show_session_stats {
+        render_session_stats(f, app);
+    }
+
+    if let Some(item) = app.message_menu {
+        render_message_menu(f, app, item);
+    }
+    if let Some(item) = app.template_picker {
+        render_template_picker(f, app, item);
+    }
+    if let Some(diff) = &app.attempt_diff {
+        render_attempt_diff(f, app, diff);
+    }
+
+    if app.show_debug {
+        render_debug(f, app);
+    }
+
+    // Screen-reader mode announces notifications in the status line instead
+    if !app.app_config.screen_reader {
+        render_toasts(f, app);
+    }
+
+    if let Some(failure) = &app.failure {
+        render_failure(f, app, failure);
+    }
+
+    if let Some(confirmation) = &app.confirmation {
+        render_confirmation(f, app, &confirmation.message);
+    }
 }
 
-fn render_chat(f: &mut Frame, app: &App, area: Rect) {
-    let mut text = Vec::new();
+fn default_title(app: &App) -> String {
+    let conversation = app.conversation();
+    let mut title_text = format!(
+        "Ollama TUI Chat - Model: {} @ {} | Mode: {:?}",
+        conversation.model, app.app_config.active_host, app.mode
+    );
+    if let Some(temperature) = conversation.overrides.temperature {
+        title_text.push_str(&format!(" | temp {}", temperature));
+    }
+    if let Some(name) = &conversation.overrides.persona {
+        title_text.push_str(&format!(" | persona: {}", name));
+    } else if let Some(name) = &conversation.overrides.system_prompt_name {
+        title_text.push_str(&format!(" | prompt: {}", name));
+    } else if conversation.overrides.system_prompt.is_some() {
+        title_text.push_str(" | session system prompt");
+    }
+    match conversation.reply_format {
+        Some(ReplyFormat::Json) => title_text.push_str(" | JSON"),
+        Some(ReplyFormat::Schema(_)) => title_text.push_str(" | JSON schema"),
+        None => {}
+    }
+    if conversation.raw_template.is_some() {
+        title_text.push_str(" | raw");
+    }
+    if conversation.fim {
+        title_text.push_str(" | fill-in-the-middle");
+    }
+    title_text
+}
 
-    for (i, (role, content)) in app.messages.iter().enumerate() {
-        let style = if role == "user" {
-            Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)
-        } else {
-            Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD)
-        };
+/// Value of a `{placeholder}` in `title_template`; `None` leaves it as typed.
+fn title_field(app: &App, name: &str) -> Option<String> {
+    let conversation = app.conversation();
+    let (used, limit) = app.context_usage();
+    Some(match name {
+        "model" => conversation.model.clone(),
+        "host" => app.app_config.active_host.clone(),
+        "mode" => format!("{:?}", app.mode),
+        "session_title" => conversation.title(),
+        "ctx_used" => used.to_string(),
+        "ctx_max" => limit.to_string(),
+        "ctx_percent" => format!("{:.0}", used as f64 * 100.0 / limit.max(1) as f64),
+        "tokens" => conversation.tokens.to_string(),
+        "temperature" => app.effective_config().temperature.to_string(),
+        "prompt" => conversation.overrides.system_prompt_name.clone().unwrap_or_default(),
+        "persona" => conversation.overrides.persona.clone().unwrap_or_default(),
+        "tab" => (app.active_conversation + 1).to_string(),
+        "tabs" => app.conversations.len().to_string(),
+        "time" => chrono::Local::now().format("%H:%M").to_string(),
+        _ => return None,
+    })
+}
 
-        // Check if this is the last message and we're thinking
-        let is_last = i == app.messages.len() - 1;
-        let is_thinking_message = is_last && app.is_thinking && content.is_empty();
+/// Borders drawn as blanks, keeping the layout without box-drawing characters.
+const BLANK_BORDER: border::Set = border::Set {
+    top_left: " ",
+    top_right: " ",
+    bottom_left: " ",
+    bottom_right: " ",
+    vertical_left: " ",
+    vertical_right: " ",
+    horizontal_top: " ",
+    horizontal_bottom: " ",
+};
+
+/// The box around every pane; plain blanks in screen-reader mode.
+fn panel(app: &App) -> Block<'static> {
+    let block = Block::default().borders(Borders::ALL).border_type(BorderType::Rounded);
+    if app.app_config.screen_reader { block.border_set(BLANK_BORDER) } else { block }
+}
 
-        if is_thinking_message {
+/// A pane title, framed with rules unless screen-reader mode is on.
+fn heading(app: &App, name: &str) -> String {
+    if app.app_config.screen_reader { name.to_string() } else { format!("━━━ {} ━━━", name) }
+}
+
+/// Stacks live toasts in the top-right corner, newest at the bottom.
+fn render_toasts(f: &mut Frame, app: &App) {
+    let t = &app.theme;
+    let area = f.area();
+    let width = TOAST_WIDTH.min(area.width);
+    let mut y = area.y + 1;
+    for toast in app.toasts.iter() {
+        let (color, title) = match toast.severity {
+            Severity::Info => (t.primary, "Info"),
+            Severity::Success => (t.success, "Done"),
+            Severity::Warning => (t.warning, "Warning"),
+            Severity::Error => (t.danger, "Error"),
+        };
+        let paragraph = Paragraph::new(toast.message.as_str()).wrap(Wrap { trim: true });
+        let height = (paragraph.line_count(width.saturating_sub(2)) as u16 + 2).min(6);
+        if y + height > area.bottom() {
+            break;
+        }
+        let rect = Rect { x: area.right() - width, y, width, height };
+        let paragraph = paragraph
+            .style(Style::default().fg(t.text))
+            .block(panel(app).border_style(Style::default().fg(color)).title(title));
+        f.render_widget(Clear, rect);
+        f.render_widget(paragraph, rect);
+        y += height;
+    }
+}
+
+/// Every binding in the active keymap, grouped by the mode it applies to.
+fn render_help(f: &mut Frame, app: &App) {
+    let t = &app.theme;
+    let area = centered_rect(70, 80, f.area());
+    let mut text = Vec::new();
+    for context in KeyContext::ALL {
+        let mut bindings = app.keymap.bindings_for(context).peekable();
+        if bindings.peek().is_none() {
+            continue;
+        }
+        if !text.is_empty() {
+            text.push(Line::from(""));
+        }
+        text.push(Line::from(Span::styled(context.title(), Style::default().fg(t.primary).add_modifier(Modifier::BOLD))));
+        for binding in bindings {
             text.push(Line::from(vec![
-                Span::styled(format!("{}: ", role), style),
-                Span::styled(
-                    format!("{} Thinking...", app.get_thinking_spinner()),
-                    Style::default().fg(Color::Yellow).add_modifier(Modifier::ITALIC),
-                ),
+                Span::styled(format!("  {:<14}", format_sequence(&binding.keys)), Style::default().fg(t.warning)),
+                Span::styled(binding.action.description(), Style::default().fg(t.text)),
             ]));
+        }
+    }
+
+    let popup = Paragraph::new(text)
+        .scroll((app.help_scroll, 0))
+        .block(panel(app).border_style(Style::default().fg(t.primary)).title("Help (Esc to close, j/k to scroll)"));
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+/// What the active conversation took since it was opened, for comparing ways of working.
+fn render_session_stats(f: &mut Frame, app: &App) {
+    let t = &app.theme;
+    let conversation = app.conversation();
+    let usage = &conversation.usage;
+    let prompts = conversation.messages.iter().filter(|m| m.is_user()).count();
+    let replies = conversation.messages.iter().filter(|m| m.role == "assistant").count();
+    let mut models: Vec<(&str, usize)> = Vec::new();
+    for model in conversation.messages.iter().filter_map(|m| m.model.as_deref()) {
+        match models.iter_mut().find(|(name, _)| *name == model) {
+            Some((_, count)) => *count += 1,
+            None => models.push((model, 1)),
+        }
+    }
+    let models = if models.is_empty() {
+        "none yet".to_string()
+    } else {
+        models.iter().map(|(model, count)| format!("{} ({})", model, count)).collect::<Vec<_>>().join(", ")
+    };
+    let speed = if usage.seconds > 0.0 && usage.reply_tokens > 0 { format!("{:.1} tok/s", usage.reply_tokens as f64 / usage.seconds) } else { "-".to_string() };
+    let duration = conversation.started.map_or("-".to_string(), |started| usage::format_duration(started.elapsed().as_secs_f64()));
+
+    let row = |label: &str, value: String| {
+        Line::from(vec![Span::styled(format!("  {:<20}", label), Style::default().fg(t.secondary)), Span::styled(value, Style::default().fg(t.text))])
+    };
+    let text = vec![
+        Line::from(""),
+        row("Messages", format!("{} · prompts {} · replies {}", conversation.messages.len(), prompts, replies)),
+        row("Tokens in / out", format!("{} / {}", usage.prompt_tokens, usage.reply_tokens)),
+        row("Average speed", speed),
+        row("Models", models),
+        row("Since first prompt", duration),
+        row("Generating", usage::format_duration(usage.seconds)),
+        Line::from(""),
+        Line::styled("  Tokens and times count since the chat was opened.", Style::default().fg(t.muted)),
+    ];
+    let area = centered_rect(70, 60, f.area());
+    let popup = Paragraph::new(text)
+        .wrap(Wrap { trim: false })
+        .block(panel(app).border_style(Style::default().fg(t.primary)).title("Conversation Statistics (Esc to close)"));
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+/// The quick actions for the selected message, with entry `item` highlighted.
+fn render_template_picker(f: &mut Frame, app: &App, item: usize) {
+    let t = &app.theme;
+    let rows: Vec<(&str, &str)> = std::iter::once(("Blank conversation", "Start with an empty chat"))
+        .chain(app.app_config.templates.iter().map(|template| (template.name.as_str(), template.description.as_str())))
+        .collect();
+    let items: Vec<ListItem> = rows
+        .iter()
+        .map(|(name, description)| {
+            ListItem::new(Line::from(vec![
+                Span::styled(*name, Style::default().fg(t.text)),
+                Span::styled(format!("  {}", description), Style::default().fg(t.muted)),
+            ]))
+        })
+        .collect();
+    let screen = f.area();
+    let (width, height) = (60.min(screen.width), (rows.len() as u16 + 2).min(screen.height));
+    let area = Rect::new(screen.x + (screen.width - width) / 2, screen.y + (screen.height - height) / 2, width, height);
+    let list = List::new(items)
+        .block(panel(app).border_style(Style::default().fg(t.primary)).title("Start from a template (Enter, Esc)"))
+        .highlight_style(Style::default().bg(t.highlight_bg).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+    let mut state = ListState::default().with_selected(Some(item));
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+/// Two attempts at a reply, the words taken out struck through in red and the words put in
+/// underlined in green. Screen-reader mode marks them `[-like this-]` and `{+like this+}`.
+fn render_attempt_diff(f: &mut Frame, app: &App, diff: &AttemptDiff) {
+    let t = &app.theme;
+    let versions = app.reply_versions(diff.message);
+    let (Some(old), Some(new)) = (versions.get(diff.older), versions.get(diff.older + 1)) else { return };
+    let changes = attempts::word_diff(&old.content, &new.content);
+    let count = |kind: Change| changes.iter().filter(|(change, _)| *change == kind).map(|(_, text)| text.split_whitespace().count()).sum::<usize>();
+    let name = |number: usize, model: &Option<String>| match model {
+        Some(model) => format!("Attempt {} ({})", number, model),
+        None => format!("Attempt {}", number),
+    };
+
+    let mut text = vec![
+        Line::from(vec![
+            Span::styled(name(diff.older + 1, &old.model), Style::default().fg(t.danger)),
+            Span::styled(" → ", Style::default().fg(t.muted)),
+            Span::styled(name(diff.older + 2, &new.model), Style::default().fg(t.success)),
+        ]),
+        Line::styled(format!("{} word(s) removed, {} added", count(Change::Removed), count(Change::Added)), Style::default().fg(t.secondary)),
+        Line::from(""),
+        Line::from(""),
+    ];
+    for (change, part) in changes {
+        let (style, open, close) = match change {
+            Change::Same => (Style::default().fg(t.text), "", ""),
+            Change::Removed => (Style::default().fg(t.danger).add_modifier(Modifier::CROSSED_OUT), "[-", "-]"),
+            Change::Added => (Style::default().fg(t.success).add_modifier(Modifier::UNDERLINED), "{+", "+}"),
+        };
+        let part = if app.app_config.screen_reader { format!("{}{}{}", open, part, close) } else { part };
+        for (i, piece) in part.split('\n').enumerate() {
+            if i > 0 {
+                text.push(Line::from(""));
+            }
+            if let (false, Some(line)) = (piece.is_empty(), text.last_mut()) {
+                line.spans.push(Span::styled(piece.to_string(), style));
+            }
+        }
+    }
+
+    let area = centered_rect(80, 80, f.area());
+    let title = format!("Attempts {} → {} of {} (h/l switch, j/k scroll, Esc)", diff.older + 1, diff.older + 2, versions.len());
+    let popup = Paragraph::new(text)
+        .wrap(Wrap { trim: false })
+        .scroll((diff.scroll, 0))
+        .block(panel(app).border_style(Style::default().fg(t.primary)).title(title));
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+fn render_message_menu(f: &mut Frame, app: &App, item: usize) {
+    let t = &app.theme;
+    let conversation = app.conversation();
+    let number = conversation.selected_message.map_or(0, |index| index + 1);
+    let length = conversation.selected_message.and_then(|index| conversation.messages.get(index)).map(|message| text::Length::of(&message.content));
+    let items: Vec<ListItem> = MessageAction::ALL.iter().map(|action| ListItem::new(action.label()).style(Style::default().fg(t.text))).collect();
+    let screen = f.area();
+    let (width, height) = (44.min(screen.width), (MessageAction::ALL.len() as u16 + 2).min(screen.height));
+    let area = Rect::new(screen.x + (screen.width - width) / 2, screen.y + (screen.height - height) / 2, width, height);
+    let mut block = panel(app).border_style(Style::default().fg(t.primary)).title(format!("Message {} (Enter, Esc)", number));
+    if let Some(length) = length {
+        block = block.title_bottom(Line::styled(format!(" {} ", length), Style::default().fg(t.muted)));
+    }
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().bg(t.highlight_bg).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+    let mut state = ListState::default().with_selected(Some(item));
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+/// Draw times of recent frames over the newest log lines, across the bottom of the screen.
+fn render_debug(f: &mut Frame, app: &App) {
+    let t = &app.theme;
+    let screen = f.area();
+    let height = (screen.height * 2 / 5).max(5).min(screen.height);
+    let area = Rect { y: screen.y + screen.height - height, height, ..screen };
+    let ms = |d: Option<Duration>| d.map_or("-".to_string(), |d| format!("{:.1} ms", d.as_secs_f64() * 1000.0));
+    let frames = &app.frame_times;
+    let keys = if app.keyboard_enhanced { "kitty protocol" } else { "legacy" };
+    let mut text = vec![Line::styled(
+        format!("Frame: last {}   average {}   slowest {}   (over {} frames)   Keys: {}", ms(frames.last()), ms(frames.average()), ms(frames.max()), frames.len(), keys),
+        Style::default().fg(t.primary).add_modifier(Modifier::BOLD),
+    )];
+    let room = usize::from(height.saturating_sub(3));
+    let lines = logging::recent_lines();
+    if lines.is_empty() {
+        text.push(Line::styled(format!("No log lines yet (set {} to log more)", logging::LOG_ENV), Style::default().fg(t.muted)));
+    }
+    for line in &lines[lines.len().saturating_sub(room)..] {
+        let color = if line.contains(" ERROR ") {
+            t.danger
+        } else if line.contains(" WARN ") {
+            t.warning
         } else {
-            text.push(Line::from(vec![Span::styled(format!("{}: ", role), style)]));
-            if !content.is_empty() { text.push(Line::from(content.clone())); }
+            t.text
+        };
+        text.push(Line::styled(line.clone(), Style::default().fg(color)));
+    }
+    let popup = Paragraph::new(text).block(panel(app).border_style(Style::default().fg(t.muted)).title("Debug (Ctrl+g to close)"));
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+/// Color for a usage ratio past the `(warn, danger)` thresholds, plus a text marker so the level
+/// never relies on color alone.
+fn usage_level(t: &Theme, ratio: f64, (warn, danger): (f64, f64), normal: Color, danger_mark: &str) -> (Color, String) {
+    if ratio >= danger {
+        (t.danger, format!(" {}", danger_mark))
+    } else if ratio >= warn {
+        (t.warning, " high".to_string())
+    } else {
+        (normal, String::new())
+    }
+}
+
+/// How full the model's context window is for the active conversation.
+fn render_context_gauge(f: &mut Frame, app: &App, area: Rect) {
+    let t = &app.theme;
+    let (used, limit) = app.context_usage();
+    let ratio = (used as f64 / limit as f64).min(1.0);
+    let (color, mark) = usage_level(t, ratio, (0.7, CONTEXT_WARN_RATIO), t.success, "full!");
+    let label = format!("ctx {}/{}{}", used, limit, mark);
+    if app.app_config.screen_reader {
+        f.render_widget(Paragraph::new(Span::styled(label, Style::default().fg(color))).alignment(Alignment::Right), area);
+        return;
+    }
+    let gauge = LineGauge::default()
+        .filled_style(Style::default().fg(color))
+        .unfilled_style(Style::default().fg(t.muted))
+        .label(Span::styled(label, Style::default().fg(color)))
+        .ratio(ratio);
+    f.render_widget(gauge, area);
+}
+
+/// The bottom bar, built from the segments listed in `status_line`; empty segments are skipped.
+fn status_line(app: &App) -> Line<'static> {
+    let t = &app.theme;
+    let conversation = app.conversation();
+    let mut spans = Vec::new();
+    if let Some(register) = app.macros.recording() {
+        spans.push(Span::styled(format!("recording @{}", register), Style::default().fg(t.danger).add_modifier(Modifier::BOLD)));
+    }
+    for segment in &app.app_config.status_line {
+        let (text, color) = match segment {
+            StatusSegment::Message => (app.status_message.clone(), t.warning),
+            StatusSegment::Model => (conversation.model.clone(), t.primary),
+            StatusSegment::Host => (app.app_config.active_host.clone(), t.primary),
+            StatusSegment::Mode => (format!("{:?}", app.mode), t.secondary),
+            StatusSegment::Tokens if conversation.tokens > 0 => (format!("{} tokens", conversation.tokens), t.secondary),
+            StatusSegment::Context => {
+                let (used, limit) = app.context_usage();
+                let color = if used as f64 >= limit as f64 * CONTEXT_WARN_RATIO { t.danger } else { t.secondary };
+                (format!("ctx {}/{} ({}%)", used, limit, used * 100 / limit), color)
+            }
+            StatusSegment::Clock => (chrono::Local::now().format("%H:%M").to_string(), t.secondary),
+            StatusSegment::LastError => match &app.last_error {
+                // Already on screen as the status message
+                Some(error) if *error != app.status_message => (format!("last error: {}", error), t.danger),
+                _ => continue,
+            },
+            _ => continue,
+        };
+        if !spans.is_empty() {
+            let separator = if app.app_config.screen_reader { " | " } else { " │ " };
+            spans.push(Span::styled(separator, Style::default().fg(t.muted)));
         }
+        spans.push(Span::styled(text, Style::default().fg(color)));
+    }
+    Line::from(spans)
+}
+
+/// Narrows `area` to at most `width` characters of content (plus borders), centered horizontally.
+fn chat_column(area: Rect, width: Option<u16>) -> Rect {
+    match width {
+        Some(width) if area.width > width + 2 => {
+            let width = width + 2;
+            Rect { x: area.x + (area.width - width) / 2, width, ..area }
+        }
+        _ => area,
+    }
+}
+
+/// A rectangle of the given percentage size centered in `area`.
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage((100 - percent_y) / 2), Constraint::Percentage(percent_y), Constraint::Percentage((100 - percent_y) / 2)])
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage((100 - percent_x) / 2), Constraint::Percentage(percent_x), Constraint::Percentage((100 - percent_x) / 2)])
+        .split(vertical[1])[1]
+}
+
+/// The question is the first line of `message`; any lines after it, such as code about to
+/// run, are shown below it as they are.
+fn render_confirmation(f: &mut Frame, app: &App, message: &str) {
+    let t = &app.theme;
+    let mut lines = message.lines();
+    let question = lines.next().unwrap_or("");
+    let detail: Vec<Line> = lines.map(|line| Line::styled(line.to_string(), Style::default().fg(t.muted)).alignment(Alignment::Left)).collect();
+    let area = centered_rect(60, if detail.is_empty() { 25 } else { 50 }, f.area());
+    let mut text = vec![Line::from(""), Line::from(question.to_string())];
+    if !detail.is_empty() {
         text.push(Line::from(""));
+        text.extend(detail);
     }
+    text.push(Line::from(""));
+    text.push(Line::from(Span::styled("y: confirm   n/Esc: cancel", Style::default().fg(t.success).add_modifier(Modifier::BOLD))));
+    let popup = Paragraph::new(text)
+        .wrap(Wrap { trim: false })
+        .alignment(Alignment::Center)
+        .block(panel(app).border_style(Style::default().fg(t.danger)).title("Confirm"));
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
 
-    let messages_widget = Paragraph::new(text)
-        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).border_style(Style::default().fg(Color::Blue)).title("Chat"))
-        .wrap(Wrap { trim: true })
-        .scroll((app.scroll_offset as u16, 0));
+/// The error popup: what failed and the keys for each way out of it.
+fn render_failure(f: &mut Frame, app: &App, failure: &Failure) {
+    let t = &app.theme;
+    let area = centered_rect(60, 35, f.area());
+    let mut text = vec![Line::from(""), Line::from(failure.message.clone())];
+    if let AppError::Unreachable(_) = failure.error {
+        let host = app.app_config.active();
+        text.push(Line::from(""));
+        text.push(Line::styled(format!("Host: {} ({})", host.name, host.url), Style::default().fg(t.muted)));
+    }
+    let mut hints: Vec<&str> = failure.recoveries.iter().map(|&recovery| recovery_hint(recovery)).collect();
+    hints.push("Esc: dismiss");
+    text.push(Line::from(""));
+    text.push(Line::from(Span::styled(hints.join("   "), Style::default().fg(t.success).add_modifier(Modifier::BOLD))));
+    let popup = Paragraph::new(text)
+        .wrap(Wrap { trim: false })
+        .alignment(Alignment::Center)
+        .block(panel(app).border_style(Style::default().fg(t.danger)).title("Error"));
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+fn recovery_hint(recovery: Recovery) -> &'static str {
+    match recovery {
+        Recovery::Retry => "r: retry",
+        Recovery::OpenHosts => "h: switch host",
+        Recovery::OpenModels => "m: choose a model",
+        Recovery::PullModel => "p: download it",
+    }
+}
+
+/// Chat block title: just "Chat", or a tab strip once more than one conversation is open.
+/// In split view each pane is titled with its own tab instead.
+fn tab_strip(app: &App, conversation: &Conversation) -> Line<'static> {
+    let t = &app.theme;
+    if app.conversations.len() == 1 {
+        return Line::from("Chat");
+    }
+    if app.split_index().is_some() {
+        let index = app.conversations.iter().position(|c| c.id == conversation.id).unwrap_or(0);
+        let style = if index == app.active_conversation { Style::default().fg(t.primary).add_modifier(Modifier::BOLD) } else { Style::default().fg(t.secondary) };
+        return Line::from(Span::styled(format!(" {}: {} ", index + 1, conversation.title()), style));
+    }
+    let mut spans = Vec::new();
+    for (i, conversation) in app.conversations.iter().enumerate() {
+        let busy = if conversation.is_thinking { " …" } else { "" };
+        let label = format!(" {}: {}{} ", i + 1, conversation.title(), busy);
+        spans.push(if i == app.active_conversation {
+            Span::styled(label, Style::default().fg(t.primary).bg(t.highlight_bg).add_modifier(Modifier::BOLD))
+        } else {
+            Span::styled(label, Style::default().fg(t.secondary))
+        });
+    }
+    Line::from(spans)
+}
+
+/// Settings that shape every message's lines; a change to any of them drops the whole layout.
+#[derive(Clone, Copy, PartialEq)]
+struct LayoutKey {
+    width: usize,
+    compact: bool,
+    expand_output: bool,
+    collapse_reply_lines: usize,
+    screen_reader: bool,
+    image_rows: u16,
+    theme: Theme,
+}
+
+/// What a message's lines were built from. Messages only grow while streaming, so lengths
+/// are enough to tell when one changed.
+#[derive(PartialEq)]
+struct Fingerprint {
+    label: String,
+    content: usize,
+    tool_calls: usize,
+    sources: usize,
+    images: usize,
+    json_errors: usize,
+    expanded: bool,
+    folded: Vec<usize>,
+}
+
+struct MessageLayout {
+    fingerprint: Fingerprint,
+    lines: Vec<Line<'static>>,
+    /// Offset into `lines` of each of the message's images.
+    images: Vec<usize>,
+}
+
+/// Wrapped, styled lines of each message from earlier frames, so a long transcript is only
+/// laid out again when the pane is resized or a setting changes. Kept on the conversation.
+#[derive(Default)]
+pub struct ChatLayout {
+    key: Option<LayoutKey>,
+    messages: Vec<Option<MessageLayout>>,
+}
+
+/// How the messages of a conversation are drawn this frame.
+struct MessageStyle<'a> {
+    app: &'a App,
+    width: usize,
+    content_width: usize,
+    indent: &'static str,
+    compact: bool,
+    expand_output: bool,
+    image_width: u16,
+    image_rows: u16,
+}
+
+impl MessageStyle<'_> {
+    /// The lines for one message, and where its images go among them. `stats` is the live
+    /// line of a reply still streaming in.
+    fn lines(&self, message: &Message, label: &str, stats: Option<Span<'static>>, images: &mut InlineImages) -> (Vec<Line<'static>>, Vec<usize>) {
+        let (app, t, indent) = (self.app, &self.app.theme, self.indent);
+        let mut text = Vec::new();
+        let mut image_lines = Vec::new();
+        // A finished JSON reply is pretty-printed; one that doesn't parse is shown as it came
+        let streaming = stats.is_some();
+        let json = message.json && !streaming;
+        let pretty = json
+            .then(|| serde_json::from_str::<serde_json::Value>(structured::reply_json(&message.content)).ok())
+            .flatten()
+            .and_then(|value| serde_json::to_string_pretty(&value).ok());
+        let highlight = pretty.is_some();
+        let content = match (pretty, self.expand_output) {
+            _ if message.fill.is_some() => Cow::Owned(message.fill.as_ref().map(|fill| fill.stitch(&message.content)).unwrap_or_default()),
+            (Some(pretty), _) => Cow::Owned(pretty),
+            (None, true) => Cow::Borrowed(message.content.as_str()),
+            (None, false) => collapsed_output(message).map_or(Cow::Borrowed(message.content.as_str()), Cow::Owned),
+        };
+        let style = if message.is_user() {
+            Style::default().fg(t.user).add_modifier(Modifier::BOLD)
+        } else if message.is_output() {
+            Style::default().fg(t.secondary).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(t.assistant).add_modifier(Modifier::BOLD)
+        };
+        // Tool output is set apart from what people and models wrote
+        let body = if message.is_output() { Style::default().fg(t.muted) } else { Style::default() };
+        // Markdown is styled line by line, so a reply is styled as it streams in
+        let mut markdown = MarkdownLines::new(&content, &message.folded, app.app_config.screen_reader);
+        let mut fill = message.fill.as_ref().map(|fill| FillLines::new(fill, &message.content));
+        let mut content_line = |indent: &str, line: &str| {
+            if let Some(fill) = fill.as_mut() {
+                Some(fill.line(indent, line, t))
+            } else if highlight {
+                Some(json_line(indent, line, t))
+            } else if message.is_output() || message.json {
+                Some(Line::styled(format!("{}{}", indent, line), body))
+            } else {
+                markdown.line(indent, line, t)
+            }
+        };
+
+        let mut content_start = 0;
+        if self.compact {
+            // Label inline with the first line of the message; live stats get a line of their own
+            let head = format!("{}:", label);
+            let mut lines = text::wrap(&format!("{} {}", head, content), self.width).into_iter();
+            let first = lines.next().unwrap_or_default();
+            match stats {
+                Some(stats) if content.is_empty() => {
+                    text.push(Line::from(vec![Span::styled(head, style), Span::raw(" "), stats]));
+                }
+                stats => {
+                    let first_line = match first.strip_prefix(head.as_str()) {
+                        Some(rest) => {
+                            let mut line = content_line("", rest).unwrap_or_default();
+                            line.spans.insert(0, Span::styled(head.clone(), style));
+                            line
+                        }
+                        None => content_line("", &first).unwrap_or_default(),
+                    };
+                    text.push(first_line);
+                    text.extend(lines.filter_map(|line| content_line("", &line)));
+                    text.extend(stats.map(Line::from));
+                }
+            }
+        } else {
+            let mut header = vec![Span::styled(format!("{}: ", label), style)];
+            header.extend(stats);
+            text.push(Line::from(header));
+            content_start = text.len();
+            if !content.is_empty() {
+                text.extend(text::wrap(&content, self.content_width).into_iter().filter_map(|line| content_line(indent, &line)));
+            }
+        }
+        // A long reply shows its start until expanded; one still streaming in is shown whole, as
+        // is filled-in code, whose gap may be anywhere in it
+        let limit = app.app_config.collapse_reply_lines;
+        let shown = text.len() - content_start;
+        if message.role == "assistant" && !message.expanded && !streaming && message.fill.is_none() && limit > 0 && shown > limit + 1 {
+            text.truncate(content_start + limit);
+            let footer = format!("{}… (z a to expand, {} more lines)", indent, shown - limit);
+            text.push(Line::styled(footer, Style::default().fg(t.muted).add_modifier(Modifier::ITALIC)));
+        }
+        for call in &message.tool_calls {
+            let marker = if app.app_config.screen_reader { "Tool call:" } else { "⚙" };
+            let call_style = Style::default().fg(t.warning).add_modifier(Modifier::ITALIC);
+            text.extend(text::wrap(&format!("{} {}", marker, call.summary()), self.content_width).into_iter().map(|line| Line::styled(format!("{}{}", indent, line), call_style)));
+        }
+        if !message.json_errors.is_empty() {
+            let marker = if app.app_config.screen_reader { "JSON problem:" } else { "✗" };
+            let error_style = Style::default().fg(t.danger);
+            for error in &message.json_errors {
+                text.extend(text::wrap(&format!("{} {}", marker, error), self.content_width).into_iter().map(|line| Line::styled(format!("{}{}", indent, line), error_style)));
+            }
+        }
+        if !message.sources.is_empty() {
+            let sources: Vec<String> = message.sources.iter().enumerate().map(|(i, source)| format!("[{}] {}", i + 1, source)).collect();
+            let source_style = Style::default().fg(t.muted).add_modifier(Modifier::ITALIC);
+            text.extend(text::wrap(&format!("Sources: {}", sources.join("  ")), self.content_width).into_iter().map(|line| Line::styled(format!("{}{}", indent, line), source_style)));
+        }
+        for data in &message.images {
+            image_lines.push(text.len());
+            text.extend(image_placeholder(images.label(data), self.image_width as usize, self.image_rows as usize, t).into_iter().map(|mut line| {
+                line.spans.insert(0, Span::raw(indent));
+                line
+            }));
+        }
+        if !self.compact {
+            text.push(Line::from(""));
+        }
+        (text, image_lines)
+    }
+}
+
+/// Styles the wrapped lines of a fill-in-the-middle reply: the code around the gap muted and
+/// what the model wrote in it as code. Each line is looked for in the stitched code after the
+/// one before, which tells which part it came from.
+struct FillLines {
+    code: String,
+    middle: Range<usize>,
+    cursor: usize,
+}
+
+impl FillLines {
+    fn new(fill: &Fill, middle: &str) -> Self {
+        // As `text::wrap` has it, so the wrapped lines are found as they are
+        let clean = |part: &str| part.replace('\r', "").replace('\t', &" ".repeat(text::TAB_WIDTH));
+        let (prefix, middle) = (clean(&fill.prefix), clean(middle));
+        let gap = prefix.len()..prefix.len() + middle.len();
+        Self { code: prefix + &middle + &clean(&fill.suffix), middle: gap, cursor: 0 }
+    }
+
+    fn line(&mut self, indent: &str, line: &str, t: &Theme) -> Line<'static> {
+        let code = line.trim_start();
+        let lead = &line[..line.len() - code.len()];
+        let Some(start) = self.code[self.cursor..].find(code).map(|i| self.cursor + i) else {
+            return Line::styled(format!("{}{}", indent, line), Style::default().fg(t.muted));
+        };
+        let end = start + code.len();
+        self.cursor = end;
+        let (gap_start, gap_end) = (self.middle.start.clamp(start, end), self.middle.end.clamp(start, end));
+        let mut spans = vec![Span::raw(format!("{}{}", indent, lead))];
+        for (part, color) in [(start..gap_start, t.muted), (gap_start..gap_end, t.accent), (gap_end..end, t.muted)] {
+            if !part.is_empty() {
+                spans.push(Span::styled(self.code[part].to_string(), Style::default().fg(color)));
+            }
+        }
+        Line::from(spans)
+    }
+}
+
+/// Styles the wrapped lines of a message as Markdown, one at a time, carrying whether they are
+/// inside a code fence. A fence that hasn't closed yet runs to the last line, so code reads as
+/// code while it streams in.
+struct MarkdownLines<'a> {
+    in_code: bool,
+    /// Code blocks started so far.
+    blocks: usize,
+    /// Blocks shown as a one-line summary, counting from 0, and their code for the summary.
+    folded: &'a [usize],
+    code: Vec<text::CodeBlock>,
+    /// Inside a folded block, whose lines are left out.
+    hiding: bool,
+    screen_reader: bool,
+}
+
+impl<'a> MarkdownLines<'a> {
+    fn new(content: &str, folded: &'a [usize], screen_reader: bool) -> Self {
+        let code = if folded.is_empty() { Vec::new() } else { text::code_blocks(content) };
+        Self { in_code: false, blocks: 0, folded, code, hiding: false, screen_reader }
+    }
+
+    /// The styled line, or nothing for a line of a folded code block.
+    fn line(&mut self, indent: &str, line: &str, t: &Theme) -> Option<Line<'static>> {
+        let trimmed = line.trim_start();
+        let fence = trimmed.strip_prefix("```");
+        if self.in_code {
+            // Only a bare fence closes a block, as in `text::code_blocks`
+            if fence.is_some_and(|rest| rest.trim().is_empty()) {
+                self.in_code = false;
+                return (!std::mem::take(&mut self.hiding)).then(|| Line::styled(format!("{}{}", indent, line), Style::default().fg(t.muted)));
+            }
+            return (!self.hiding).then(|| Line::styled(format!("{}{}", indent, line), Style::default().fg(t.accent)));
+        }
+        if line.is_empty() {
+            return Some(Line::from(""));
+        }
+        if let Some(info) = fence {
+            self.in_code = true;
+            self.blocks += 1;
+            if self.folded.contains(&(self.blocks - 1)) {
+                self.hiding = true;
+                let lines = self.code.get(self.blocks - 1).map_or(0, |block| block.code.lines().count());
+                let language = info.split_whitespace().next().unwrap_or("code");
+                let marker = if self.screen_reader { "Folded:" } else { "▸" };
+                let unit = if lines == 1 { "line" } else { "lines" };
+                let summary = format!("{}{} {}, {} {} (z f to unfold)", indent, marker, language, lines, unit);
+                return Some(Line::styled(summary, Style::default().fg(t.muted).add_modifier(Modifier::ITALIC)));
+            }
+            return Some(Line::styled(format!("{}{}", indent, line), Style::default().fg(t.muted)));
+        }
+        let leading = &line[..line.len() - trimmed.len()];
+        let mut spans = vec![Span::raw(format!("{}{}", indent, leading))];
+        let hashes = trimmed.chars().take_while(|&c| c == '#').count();
+        if (1..=6).contains(&hashes) && trimmed[hashes..].starts_with(' ') {
+            spans.push(Span::styled(trimmed[..=hashes].to_string(), Style::default().fg(t.muted)));
+            spans.extend(inline_spans(&trimmed[hashes + 1..], Style::default().fg(t.primary).add_modifier(Modifier::BOLD), t));
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            spans.push(Span::styled(">", Style::default().fg(t.muted)));
+            spans.extend(inline_spans(quote, Style::default().fg(t.secondary).add_modifier(Modifier::ITALIC), t));
+        } else if let Some(marker) = list_marker(trimmed) {
+            spans.push(Span::styled(marker.to_string(), Style::default().fg(t.primary)));
+            spans.extend(inline_spans(&trimmed[marker.len()..], Style::default(), t));
+        } else {
+            spans.extend(inline_spans(trimmed, Style::default(), t));
+        }
+        Some(Line::from(spans))
+    }
+}
+
+/// The bullet or number starting a list item, with the space after it.
+fn list_marker(line: &str) -> Option<&str> {
+    if ["- ", "* ", "+ "].iter().any(|bullet| line.starts_with(bullet)) {
+        return Some(&line[..2]);
+    }
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    let rest = &line[digits..];
+    (digits > 0 && (rest.starts_with(". ") || rest.starts_with(") "))).then(|| &line[..digits + 2])
+}
+
+/// `text` with code spans coloured and `**bold**` made bold, the markers dropped. A marker
+/// whose partner hasn't arrived yet is shown as typed.
+fn inline_spans(text: &str, base: Style, t: &Theme) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut plain = String::new();
+    let mut bold = false;
+    let mut rest = text;
+    let style = |bold: bool| if bold { base.add_modifier(Modifier::BOLD) } else { base };
+    while let Some(c) = rest.chars().next() {
+        let code_end = (c == '`').then(|| rest[1..].find('`')).flatten();
+        let strong = rest.starts_with("**") && (bold || rest[2..].contains("**"));
+        if code_end.is_none() && !strong {
+            plain.push(c);
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+        if !plain.is_empty() {
+            spans.push(Span::styled(std::mem::take(&mut plain), style(bold)));
+        }
+        match code_end {
+            Some(end) => {
+                spans.push(Span::styled(rest[1..=end].to_string(), style(bold).fg(t.accent)));
+                rest = &rest[end + 2..];
+            }
+            None => {
+                bold = !bold;
+                rest = &rest[2..];
+            }
+        }
+    }
+    if !plain.is_empty() {
+        spans.push(Span::styled(plain, style(bold)));
+    }
+    spans
+}
+
+/// A line of pretty-printed JSON coloured by token: keys, strings, numbers, and the literals
+/// `true`, `false`, and `null`, with punctuation muted.
+fn json_line(indent: &str, line: &str, t: &Theme) -> Line<'static> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut spans = vec![Span::raw(indent.to_string())];
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        let style = match chars[i] {
+            '"' => {
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    i += if chars[i] == '\\' { 2 } else { 1 };
+                }
+                i = (i + 1).min(chars.len());
+                // A string followed by a colon is a key
+                match chars[i..].iter().find(|c| !c.is_whitespace()) {
+                    Some(':') => Style::default().fg(t.primary),
+                    _ => Style::default().fg(t.success),
+                }
+            }
+            c if c == '-' || c.is_ascii_digit() => {
+                while i < chars.len() && (chars[i].is_ascii_digit() || "-+.eE".contains(chars[i])) {
+                    i += 1;
+                }
+                Style::default().fg(t.warning)
+            }
+            c if c.is_ascii_alphabetic() => {
+                while i < chars.len() && chars[i].is_ascii_alphabetic() {
+                    i += 1;
+                }
+                Style::default().fg(t.secondary)
+            }
+            _ => {
+                while i < chars.len() && !(chars[i] == '"' || chars[i] == '-' || chars[i].is_ascii_alphanumeric()) {
+                    i += 1;
+                }
+                Style::default().fg(t.muted)
+            }
+        };
+        spans.push(Span::styled(chars[start..i].iter().collect::<String>(), style));
+    }
+    Line::from(spans)
+}
+
+fn render_chat(f: &mut Frame, app: &App, conversation: &Conversation, area: Rect) {
+    let t = &app.theme;
+    // Lines are wrapped here, by display width, so the line count used for scrolling is exact
+    let width = area.width.saturating_sub(2) as usize;
+    let compact = app.app_config.density == Density::Compact;
+    let indent = if compact { "" } else { "  " };
+    let content_width = width.saturating_sub(indent.len());
+    let mut images = app.images.borrow_mut();
+    let image_width = IMAGE_COLS.min(content_width as u16);
+    // Screen-reader mode names images on one line instead of drawing them
+    let image_rows = if app.app_config.screen_reader { 1 } else { images.rows() };
+    let style = MessageStyle { app, width, content_width, indent, compact, expand_output: conversation.expand_output, image_width, image_rows };
+    let mut message_lines = Vec::with_capacity(conversation.messages.len());
+    let mut line_count = 0;
+    let mut layout = conversation.layout.borrow_mut();
+    let key = LayoutKey { width, compact, expand_output: conversation.expand_output, collapse_reply_lines: app.app_config.collapse_reply_lines, screen_reader: app.app_config.screen_reader, image_rows, theme: *t };
+    if layout.key != Some(key) {
+        *layout = ChatLayout { key: Some(key), messages: Vec::new() };
+    }
+    layout.messages.resize_with(conversation.messages.len(), || None);
+    let mut streaming = None;
+
+    // Lay out what changed and count lines; only the lines in view are copied out below
+    for (i, message) in conversation.messages.iter().enumerate() {
+        message_lines.push(line_count);
+        let mut label = match app.app_config.number_messages {
+            true => format!("#{} {}", i + 1, app.message_label(message)),
+            false => app.message_label(message),
+        };
+        if !message.attempts.is_empty() {
+            label = format!("{} ({}/{})", label, attempts::shown_version(message) + 1, message.attempts.len() + 1);
+        }
+        if conversation.selected_message == Some(i) {
+            label = format!("{} {}", if app.app_config.screen_reader { "Selected:" } else { "▶" }, label);
+        }
+
+        // The last message is the reply still streaming in while we're thinking; it changes
+        // every frame, so it is laid out afresh and never kept
+        let is_last = i == conversation.messages.len() - 1;
+        if is_last && conversation.is_thinking {
+            let stats = conversation.stream_stats().map(|stats| {
+                Span::styled(
+                    if app.app_config.screen_reader { "generating".to_string() } else { format!("{} {}", app.get_thinking_spinner(), stats) },
+                    Style::default().fg(t.warning).add_modifier(Modifier::ITALIC),
+                )
+            });
+            let (lines, offsets) = style.lines(message, &label, stats, &mut images);
+            line_count += lines.len();
+            streaming = Some((lines, offsets));
+            layout.messages[i] = None;
+            continue;
+        }
+
+        let fingerprint = Fingerprint { label, content: message.content.len(), tool_calls: message.tool_calls.len(), sources: message.sources.len(), images: message.images.len(), json_errors: message.json_errors.len(), expanded: message.expanded, folded: message.folded.clone() };
+        let cached = &mut layout.messages[i];
+        if cached.as_ref().is_none_or(|cached| cached.fingerprint != fingerprint) {
+            let (lines, images) = style.lines(message, &fingerprint.label, None, &mut images);
+            *cached = Some(MessageLayout { fingerprint, lines, images });
+        }
+        line_count += cached.as_ref().map_or(0, |cached| cached.lines.len());
+    }
 
-    f.render_widget(messages_widget, area);
+    let focused_pane = app.split_index().is_some() && conversation.id == app.conversation().id;
+    let border = if focused_pane { t.primary } else { t.assistant };
+    let mut block = panel(app).border_style(Style::default().fg(border)).title(tab_strip(app, conversation));
+    if !conversation.follow_output && conversation.unseen_output {
+        block = block.title_bottom(Line::from(Span::styled(" new output ↓ (G to follow) ", Style::default().fg(t.warning).add_modifier(Modifier::BOLD))).right_aligned());
+    }
+    // The system prompt stays above the messages rather than scrolling with them
+    let header = system_prompt_header(app, conversation, width, area.height.saturating_sub(2) as usize / 2);
+    // Work out where the bottom is so streaming output can stay in view
+    let viewport = (area.height.saturating_sub(2) as usize).saturating_sub(header.len());
+    let max_scroll = line_count.saturating_sub(viewport);
+    let scroll = if conversation.follow_output { max_scroll } else { conversation.scroll_offset.min(max_scroll) };
+    let shown = scroll..(scroll + viewport).min(line_count);
+
+    // The exact line counts mean the view needs no margin: copy out just the lines in it
+    let header_rows = header.len();
+    let mut text = header;
+    text.reserve(shown.len());
+    // First line of each image's reserved rows, for drawing the pictures over them afterwards
+    let mut image_lines = Vec::new();
+    let first = message_lines.partition_point(|&start| start <= shown.start).saturating_sub(1);
+    for (i, message) in conversation.messages.iter().enumerate().skip(first) {
+        let start = message_lines[i];
+        if start >= shown.end {
+            break;
+        }
+        let (lines, offsets) = match &layout.messages[i] {
+            Some(cached) => (&cached.lines[..], &cached.images[..]),
+            None => streaming.as_ref().map(|(lines, offsets)| (&lines[..], &offsets[..])).unwrap_or_default(),
+        };
+        let from = shown.start.saturating_sub(start).min(lines.len());
+        let to = (shown.end - start).min(lines.len());
+        text.extend(lines[from..to].iter().cloned());
+        image_lines.extend(offsets.iter().zip(&message.images).map(|(offset, data)| (start + offset, data.as_str())));
+    }
+    drop(layout);
+    conversation.max_scroll.set(max_scroll);
+    conversation.viewport_height.set(viewport);
+    conversation.message_lines.replace(message_lines);
+
+    let inner = block.inner(area);
+    f.render_widget(Paragraph::new(text).block(block), area);
+
+    // Images are only drawn when wholly in view; a partly scrolled-off one keeps its placeholder
+    for (line, data) in image_lines {
+        if app.app_config.screen_reader || line < scroll || line + image_rows as usize > scroll + viewport {
+            continue;
+        }
+        if let Some(protocol) = images.protocol(data, image_width) {
+            let image_area = Rect::new(inner.x + indent.len() as u16, inner.y + (header_rows + line - scroll) as u16, image_width, image_rows);
+            f.render_widget(Clear, image_area);
+            f.render_widget(ratatui_image::Image::new(protocol), image_area);
+        }
+    }
+}
+
+/// The system prompt the conversation sends, over a rule: its first line, or as much of it as
+/// fits in `max_rows` when expanded. Nothing when there is no system prompt.
+fn system_prompt_header(app: &App, conversation: &Conversation, width: usize, max_rows: usize) -> Vec<Line<'static>> {
+    let t = &app.theme;
+    let overrides = &conversation.overrides;
+    let prompt = overrides.system_prompt.as_deref().unwrap_or(&app.model_config.system_prompt).trim();
+    if prompt.is_empty() || max_rows < 3 {
+        return Vec::new();
+    }
+    let source = match (overrides.persona.as_ref().or(overrides.system_prompt_name.as_ref()), &overrides.system_prompt) {
+        (Some(name), _) => format!("System ({}): ", name),
+        (None, Some(_)) => "System (this chat): ".to_string(),
+        (None, None) => "System: ".to_string(),
+    };
+    let label = Style::default().fg(t.secondary).add_modifier(Modifier::BOLD);
+    let body = Style::default().fg(t.muted).add_modifier(Modifier::ITALIC);
+    let mut lines = Vec::new();
+    if app.app_config.expand_system_prompt {
+        lines.push(Line::styled(source.trim_end().to_string(), label));
+        let mut wrapped = text::wrap(prompt, width.saturating_sub(2));
+        // Leave room for the rule and, when cut short, the line saying so
+        let room = max_rows - 2;
+        if wrapped.len() > room {
+            let hidden = wrapped.len() - (room - 1);
+            wrapped.truncate(room - 1);
+            wrapped.push(format!("… {} more lines (/system edit shows them all)", hidden));
+        }
+        lines.extend(wrapped.into_iter().map(|line| Line::styled(format!("  {}", line), body)));
+    } else {
+        let first = prompt.lines().next().unwrap_or("");
+        let shown = if prompt.lines().nth(1).is_some() { format!("{} …", first) } else { first.to_string() };
+        let room = width.saturating_sub(text::width(&source));
+        lines.push(Line::from(vec![Span::styled(source, label), Span::styled(text::truncate(&shown, room), body)]));
+    }
+    let rule = if app.app_config.screen_reader { String::new() } else { "─".repeat(width) };
+    lines.push(Line::styled(rule, Style::default().fg(t.muted)));
+    lines
+}
+
+/// The first lines of long command or tool output, with a count of the rest.
+fn collapsed_output(message: &Message) -> Option<String> {
+    if !message.is_output() {
+        return None;
+    }
+    let lines: Vec<&str> = message.content.lines().collect();
+    if lines.len() <= COLLAPSED_OUTPUT_LINES + 1 {
+        return None;
+    }
+    let hidden = lines.len() - COLLAPSED_OUTPUT_LINES;
+    Some(format!("{}\n… {} more lines (z o to expand)", lines[..COLLAPSED_OUTPUT_LINES].join("\n"), hidden))
+}
+
+/// A box standing in for an image, the size of the space it will be drawn in.
+fn image_placeholder(label: &str, width: usize, rows: usize, t: &Theme) -> Vec<Line<'static>> {
+    let style = Style::default().fg(t.muted);
+    if rows < 3 {
+        return vec![Line::styled(format!("[{}]", label), style)];
+    }
+    let inner = width.saturating_sub(2);
+    let label = text::truncate(label, inner.saturating_sub(2));
+    let mut lines = vec![Line::styled(format!("┌{}┐", "─".repeat(inner)), style)];
+    for row in 0..rows.saturating_sub(2) {
+        let content = if row == 0 { format!(" {}", label) } else { String::new() };
+        let pad = inner.saturating_sub(text::width(&content));
+        lines.push(Line::styled(format!("│{}{}│", content, " ".repeat(pad)), style));
+    }
+    lines.push(Line::styled(format!("└{}┘", "─".repeat(inner)), style));
+    lines
+}
+
+/// The prompts waiting for the current reply to finish, first to be sent at the top.
+fn render_queue(f: &mut Frame, app: &App, conversation: &Conversation, area: Rect) {
+    let t = &app.theme;
+    let queued = &conversation.queued;
+    // A last row that would only name one more prompt shows it instead
+    let shown = if queued.len() > MAX_QUEUE_ROWS + 1 { MAX_QUEUE_ROWS } else { queued.len() };
+    let mut lines: Vec<Line> = queued
+        .iter()
+        .take(shown)
+        .enumerate()
+        .map(|(i, prompt)| {
+            let mut first = prompt.trim().lines().next().unwrap_or_default().to_string();
+            if prompt.trim().lines().nth(1).is_some() {
+                first.push_str(" …");
+            }
+            Line::from(vec![
+                Span::styled(format!(" Queued {}: ", i + 1), Style::default().fg(t.secondary).add_modifier(Modifier::BOLD)),
+                Span::styled(first, Style::default().fg(t.muted)),
+            ])
+        })
+        .collect();
+    if queued.len() > shown {
+        lines.push(Line::styled(format!(" … {} more queued (/queue clear drops them all)", queued.len() - shown), Style::default().fg(t.muted)));
+    }
+    f.render_widget(Paragraph::new(lines), area);
 }
 
-fn render_input(f: &mut Frame, app: &App, area: Rect) {
-    let input = Paragraph::new(app.input.as_str())
-        .style(Style::default().fg(Color::White))
-        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).border_style(Style::default().fg(Color::Cyan)).title("Input (Press Enter to send)"));
+fn render_input(f: &mut Frame, app: &App, conversation: &Conversation, area: Rect) {
+    let t = &app.theme;
+    let mut title = if conversation.is_thinking {
+        "Input (Press Enter to queue until the reply finishes)".to_string()
+    } else {
+        "Input (Press Enter to send)".to_string()
+    };
+    if !conversation.pending_images.is_empty() {
+        title.push_str(&format!(" · {} image(s) attached", conversation.pending_images.len()));
+    }
+    if !conversation.pending_output.is_empty() {
+        title.push_str(&format!(" · {} command output(s) attached", conversation.pending_output.len()));
+    }
+    let mut title = vec![Span::raw(title)];
+    if conversation.draft_tokens() > 0 {
+        let (tokens, window) = app.draft_usage();
+        title.push(if tokens > window {
+            Span::styled(format!(" · ~{} tokens, more than the {}-token context", tokens, window), Style::default().fg(t.danger).add_modifier(Modifier::BOLD))
+        } else {
+            Span::raw(format!(" · ~{} tokens", tokens))
+        });
+    }
+    let input = Paragraph::new(conversation.input.as_str())
+        .style(Style::default().fg(t.text))
+        .wrap(Wrap { trim: false })
+        .block(panel(app).border_style(Style::default().fg(t.primary)).title(Line::from(title)));
     f.render_widget(input, area);
 }
 
 fn render_model_selection(f: &mut Frame, app: &App, area: Rect) {
+    let t = &app.theme;
     let items: Vec<ListItem> = app
         .available_models
         .iter()
         .map(|model| {
-            let style = if model == &app.current_model {
-                Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)
+            let style = if model == &app.conversation().model {
+                Style::default().fg(t.success).add_modifier(Modifier::BOLD)
             } else { Style::default() };
             ListItem::new(model.as_str()).style(style)
         })
         .collect();
 
+    let title = if app.listing_models { "Select Model (loading...)" } else { "Select Model (Enter to select, Esc to cancel)" };
     let list = List::new(items)
-        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).border_style(Style::default().fg(Color::Green)).title("Select Model (Enter to select, Esc to cancel)"))
-        .highlight_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD))
+        .block(panel(app).border_style(Style::default().fg(t.success)).title(title))
+        .highlight_style(Style::default().bg(t.highlight_bg).add_modifier(Modifier::BOLD))
         .highlight_symbol(">> ");
 
     let mut state = app.model_list_state.clone();
     f.render_stateful_widget(list, area, &mut state);
 }
 
+fn render_host_selection(f: &mut Frame, app: &App, area: Rect) {
+    let t = &app.theme;
+    let items: Vec<ListItem> = app
+        .app_config
+        .hosts
+        .iter()
+        .map(|host| {
+            let style = if host.name == app.app_config.active_host {
+                Style::default().fg(t.success).add_modifier(Modifier::BOLD)
+            } else { Style::default() };
+            ListItem::new(format!("{} - {}", host.name, host.url)).style(style)
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(panel(app).border_style(Style::default().fg(t.success)).title("Select Host (Enter to connect, Esc to cancel)"))
+        .highlight_style(Style::default().bg(t.highlight_bg).add_modifier(Modifier::BOLD))
+        .highlight_symbol(">> ");
+
+    let mut state = app.host_list_state.clone();
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn render_mcp_servers(f: &mut Frame, app: &App, area: Rect) {
+    let t = &app.theme;
+    let items: Vec<ListItem> = app
+        .app_config
+        .mcp_servers
+        .iter()
+        .map(|server| {
+            let (status, style) = match app.mcp_connections.get(&server.name).map(|(_, status)| status) {
+                _ if !server.enabled => ("disabled".to_string(), Style::default().fg(t.muted)),
+                Some(McpStatus::Connected { tools, resources }) => {
+                    (format!("{} tools, {} resources", tools, resources), Style::default().fg(t.success))
+                }
+                Some(McpStatus::Failed(error)) => (format!("failed: {}", error), Style::default().fg(t.danger)),
+                Some(McpStatus::Connecting) | None => ("connecting...".to_string(), Style::default()),
+            };
+            let mark = if server.enabled { "[x]" } else { "[ ]" };
+            ListItem::new(format!("{} {} - {} ({})", mark, server.name, server.describe(), status)).style(style)
+        })
+        .collect();
+
+    let title = if app.app_config.mcp_servers.is_empty() {
+        "MCP Servers (none yet; add them under mcp_servers in config.json, Esc to go back)"
+    } else {
+        "MCP Servers (Enter to enable/disable, Esc to go back)"
+    };
+    let list = List::new(items)
+        .block(panel(app).border_style(Style::default().fg(t.success)).title(title))
+        .highlight_style(Style::default().bg(t.highlight_bg).add_modifier(Modifier::BOLD))
+        .highlight_symbol(">> ");
+
+    let mut state = app.mcp_list_state.clone();
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn render_prompt_library(f: &mut Frame, app: &App, area: Rect) {
+    let t = &app.theme;
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(7), Constraint::Length(3)])
+        .split(area);
+
+    let items: Vec<ListItem> = app
+        .app_config
+        .system_prompts
+        .iter()
+        .map(|entry| {
+            let style = if app.conversation().overrides.system_prompt_name.as_deref() == Some(entry.name.as_str()) {
+                Style::default().fg(t.success).add_modifier(Modifier::BOLD)
+            } else { Style::default() };
+            ListItem::new(entry.name.as_str()).style(style)
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(panel(app).border_style(Style::default().fg(t.success)).title("System Prompts (Enter use for session, n new, e edit, d delete, Esc back)"))
+        .highlight_style(Style::default().bg(t.highlight_bg).add_modifier(Modifier::BOLD))
+        .highlight_symbol(">> ");
+    let mut state = app.prompt_list_state.clone();
+    f.render_stateful_widget(list, chunks[0], &mut state);
+
+    let preview = app.prompt_list_state.selected().and_then(|i| app.app_config.system_prompts.get(i)).map(|e| e.prompt.as_str()).unwrap_or("");
+    let preview_widget = Paragraph::new(preview)
+        .wrap(Wrap { trim: true })
+        .block(panel(app).border_style(Style::default().fg(t.assistant)).title("Preview"));
+    f.render_widget(preview_widget, chunks[1]);
+
+    let (input, title) = match &app.prompt_draft {
+        Some(draft) if draft.index.is_some() => (draft.input.as_str(), "Edit prompt (Name: prompt text, Enter to save, Esc to cancel)"),
+        Some(draft) => (draft.input.as_str(), "New prompt (Name: prompt text, Enter to save, Esc to cancel)"),
+        None => ("", "Press n to add a prompt"),
+    };
+    let input_widget = Paragraph::new(input)
+        .style(Style::default().fg(t.text))
+        .block(panel(app).border_style(Style::default().fg(t.primary)).title(title));
+    f.render_widget(input_widget, chunks[2]);
+}
+
 fn render_model_download(f: &mut Frame, app: &App, area: Rect) {
+    let t = &app.theme;
     let download = Paragraph::new(app.download_input.as_str())
-        .style(Style::default().fg(Color::White))
-        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).border_style(Style::default().fg(Color::Magenta)).title("Download Model (Enter model name, e.g., 'llama2:latest')"));
+        .style(Style::default().fg(t.text))
+        .block(panel(app).border_style(Style::default().fg(t.accent)).title("Download Model (Enter model name, e.g., 'llama2:latest')"));
     f.render_widget(download, area);
 }
 
 fn render_system_monitor(f: &mut Frame, app: &App, area: Rect) {
+    let t = &app.theme;
+    let inner_width = area.width.saturating_sub(2);
+    let core_rows = core_grid_rows(app, inner_width - trend_width(app, inner_width));
+    let model_rows = app.loaded_models.as_ref().map_or(1, |models| models.len().clamp(1, MAX_LOADED_MODEL_ROWS)) as u16;
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Length(4),
+            Constraint::Length(3 + model_rows),
+            Constraint::Length(3 + core_rows),
             Constraint::Length(4),
             Constraint::Length(5),
+            Constraint::Length(4),
             Constraint::Min(0),
         ])
         .split(area);
 
+    render_ollama_panel(f, app, chunks[0]);
+
     // CPU
-    let cpu_percent = app.cpu_usage.min(100.0);
-    let cpu_color = if cpu_percent > 80.0 { Color::Red } else if cpu_percent > 50.0 { Color::Yellow } else { Color::Cyan };
-    let cpu_gauge = Gauge::default()
-        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title(Span::styled("━━━ CPU ━━━", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))).border_style(Style::default().fg(Color::Cyan)))
-        .gauge_style(Style::default().fg(cpu_color).bg(Color::Black).add_modifier(Modifier::BOLD))
-        .percent(cpu_percent as u16)
-        .label(Span::styled(format!("{:.1}%", cpu_percent), Style::default().fg(Color::White).add_modifier(Modifier::BOLD)));
-    f.render_widget(cpu_gauge, chunks[0]);
+    let cpu_percent = app.metrics.cpu_usage.min(100.0);
+    let (cpu_color, cpu_mark) = usage_level(t, cpu_percent as f64 / 100.0, (0.5, 0.8), t.primary, "critical!");
+    let cpu_block = panel(app).title(Span::styled(heading(app, "CPU"), Style::default().fg(t.primary).add_modifier(Modifier::BOLD))).border_style(Style::default().fg(t.primary));
+    let cpu_inner = cpu_block.inner(chunks[1]);
+    f.render_widget(cpu_block, chunks[1]);
+    let cpu_inner = render_trend(f, app, cpu_inner, &app.metrics.history.cpu, cpu_color);
+    let cpu_rows = Layout::default().direction(Direction::Vertical).constraints([Constraint::Length(1), Constraint::Min(0)]).split(cpu_inner);
+    render_gauge(f, app, cpu_rows[0], Block::default(), cpu_percent as u16, cpu_color, format!("{:.1}%{}", cpu_percent, cpu_mark));
+    f.render_widget(Paragraph::new(core_grid(app)).wrap(Wrap { trim: false }), cpu_rows[1]);
 
     // Memory
-    let memory_percent = if app.memory_total > 0 { ((app.memory_usage as f64 / app.memory_total as f64) * 100.0) as u16 } else { 0 };
-    let memory_gb_used = app.memory_usage as f64 / 1024.0 / 1024.0 / 1024.0;
-    let memory_gb_total = app.memory_total as f64 / 1024.0 / 1024.0 / 1024.0;
-    let mem_color = if memory_percent > 80 { Color::Red } else if memory_percent > 50 { Color::Yellow } else { Color::Magenta };
-    let memory_gauge = Gauge::default()
-        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title(Span::styled("━━━ MEMORY ━━━", Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD))).border_style(Style::default().fg(Color::Magenta)))
-        .gauge_style(Style::default().fg(mem_color).bg(Color::Black).add_modifier(Modifier::BOLD))
-        .percent(memory_percent)
-        .label(Span::styled(format!("{:.1} GB / {:.1} GB", memory_gb_used, memory_gb_total), Style::default().fg(Color::White).add_modifier(Modifier::BOLD)));
-    f.render_widget(memory_gauge, chunks[1]);
+    let memory_percent = if app.metrics.memory_total > 0 { ((app.metrics.memory_used as f64 / app.metrics.memory_total as f64) * 100.0) as u16 } else { 0 };
+    let memory_gb_used = app.metrics.memory_used as f64 / 1024.0 / 1024.0 / 1024.0;
+    let memory_gb_total = app.metrics.memory_total as f64 / 1024.0 / 1024.0 / 1024.0;
+    let (mem_color, mem_mark) = usage_level(t, memory_percent as f64 / 100.0, (0.5, 0.8), t.accent, "critical!");
+    let memory_block = panel(app).title(Span::styled(heading(app, "MEMORY"), Style::default().fg(t.accent).add_modifier(Modifier::BOLD))).border_style(Style::default().fg(t.accent));
+    let memory_inner = memory_block.inner(chunks[2]);
+    f.render_widget(memory_block, chunks[2]);
+    let memory_inner = render_trend(f, app, memory_inner, &app.metrics.history.memory, mem_color);
+    render_gauge(f, app, memory_inner, Block::default(), memory_percent, mem_color, format!("{:.1} GB / {:.1} GB{}", memory_gb_used, memory_gb_total, mem_mark));
 
     // GPU
-    let gpu_lines = if let Some(ref gpu_info) = app.gpu_info {
-        let parts: Vec<&str> = gpu_info.trim().split(',').collect();
-        if parts.len() >= 4 {
-            let gpu_util = parts[0].trim();
-            let mem_used = parts[1].trim();
-            let mem_total = parts[2].trim();
-            let temp = parts[3].trim();
+    let gpu = app.metrics.gpus.first();
+    let gpu_lines = match gpu {
+        Some(gpu) => {
+            let unknown = || "n/a".to_string();
+            let memory = match (gpu.memory_used, gpu.memory_total) {
+                (Some(used), Some(total)) => format!("{} / {} MB", used, total),
+                _ => unknown(),
+            };
             vec![
-                Line::from(vec![Span::styled("  Utilization: ", Style::default().fg(Color::Gray)), Span::styled(format!("{}%", gpu_util), Style::default().fg(Color::Green).add_modifier(Modifier::BOLD))]),
-                Line::from(vec![Span::styled("  VRAM: ", Style::default().fg(Color::Gray)), Span::styled(format!("{} / {} MB", mem_used, mem_total), Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))]),
-                Line::from(vec![Span::styled("  Temperature: ", Style::default().fg(Color::Gray)), Span::styled(format!("{}°C", temp), Style::default().fg(Color::Red).add_modifier(Modifier::BOLD))]),
+                Line::from(vec![Span::styled("  Utilization: ", Style::default().fg(t.secondary)), Span::styled(gpu.utilization.map_or_else(unknown, |u| format!("{:.0}%", u)), Style::default().fg(t.success).add_modifier(Modifier::BOLD))]),
+                Line::from(vec![Span::styled(if gpu.memory_pressure.is_some() { "  Unified memory: " } else { "  VRAM: " }, Style::default().fg(t.secondary)), Span::styled(memory, Style::default().fg(t.warning).add_modifier(Modifier::BOLD))]),
+                match gpu.memory_pressure {
+                    Some(pressure) => Line::from(vec![Span::styled("  Memory pressure: ", Style::default().fg(t.secondary)), Span::styled(format!("{:.0}%", pressure), Style::default().fg(t.danger).add_modifier(Modifier::BOLD))]),
+                    None => Line::from(vec![Span::styled("  Temperature: ", Style::default().fg(t.secondary)), Span::styled(gpu.temperature.map_or_else(unknown, |c| format!("{:.0}°C", c)), Style::default().fg(t.danger).add_modifier(Modifier::BOLD))]),
+                },
             ]
-        } else { vec![Line::from("GPU detected")] }
-    } else { vec![Line::from(Span::styled("  No GPU detected", Style::default().fg(Color::DarkGray)))] };
-
-    let gpu_widget = Paragraph::new(gpu_lines)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .border_type(BorderType::Rounded)
-                .title(Span::styled("━━━ GPU ━━━", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)))
-                .border_style(Style::default().fg(Color::Green)),
-        );
-    f.render_widget(gpu_widget, chunks[2]);
+        }
+        None => vec![Line::from(Span::styled("  No GPU detected", Style::default().fg(t.muted)))],
+    };
+    let gpu_title = match (gpu, app.metrics.gpus.len()) {
+        (Some(gpu), 1) => format!("GPU: {}", gpu.name),
+        (Some(gpu), n) => format!("GPU: {} (+{} more)", gpu.name, n - 1),
+        (None, _) => "GPU".to_string(),
+    };
+
+    let gpu_block = panel(app)
+        .title(Span::styled(heading(app, &gpu_title), Style::default().fg(t.success).add_modifier(Modifier::BOLD)))
+        .border_style(Style::default().fg(t.success));
+    let gpu_inner = gpu_block.inner(chunks[3]);
+    f.render_widget(gpu_block, chunks[3]);
+    let gpu_inner = if app.metrics.history.gpu.is_empty() { gpu_inner } else { render_trend(f, app, gpu_inner, &app.metrics.history.gpu, t.success) };
+    f.render_widget(Paragraph::new(gpu_lines), gpu_inner);
+
+    // Disk and network
+    let io_columns = Layout::default().direction(Direction::Horizontal).constraints([Constraint::Percentage(50), Constraint::Percentage(50)]).split(chunks[4]);
+    let (current, peak) = (app.metrics.io, app.metrics.io_peak);
+    let rate_line = |label: &str, now: f64, max: f64| {
+        Line::from(vec![
+            Span::styled(format!("  {:<7}", label), Style::default().fg(t.secondary)),
+            Span::styled(format!("{:>11}", format_rate(now)), Style::default().fg(t.text).add_modifier(Modifier::BOLD)),
+            Span::styled(format!("  peak {}", format_rate(max)), Style::default().fg(t.muted)),
+        ])
+    };
+    let disk = Paragraph::new(vec![rate_line("Read", current.disk_read, peak.disk_read), rate_line("Write", current.disk_write, peak.disk_write)])
+        .block(panel(app).title(Span::styled(heading(app, "DISK"), Style::default().fg(t.primary).add_modifier(Modifier::BOLD))).border_style(Style::default().fg(t.primary)));
+    f.render_widget(disk, io_columns[0]);
+    let network = Paragraph::new(vec![rate_line("Down", current.net_rx, peak.net_rx), rate_line("Up", current.net_tx, peak.net_tx)])
+        .block(panel(app).title(Span::styled(heading(app, "NETWORK"), Style::default().fg(t.accent).add_modifier(Modifier::BOLD))).border_style(Style::default().fg(t.accent)));
+    f.render_widget(network, io_columns[1]);
 
     // Top Processes
-    let mut processes: Vec<_> = app.sys_info.processes().values().collect();
-    processes.sort_by(|a, b| b.cpu_usage().partial_cmp(&a.cpu_usage()).unwrap());
+    let processes = app.visible_processes();
 
     let process_rows: Vec<Row> = processes
         .iter()
-        .skip(app.process_scroll)
-        .take(15)
         .map(|p| {
-            let cpu = format!("{:.1}%", p.cpu_usage());
-            let mem = format!("{:.0} MB", p.memory() as f64 / 1024.0 / 1024.0);
-            let name = p.name().to_string_lossy();
-            Row::new(vec![name.to_string(), cpu, mem]).style(Style::default().fg(Color::White))
+            let cpu = format!("{:.1}%", p.cpu);
+            let mem = format!("{:.0} MB", p.memory as f64 / 1024.0 / 1024.0);
+            Row::new(vec![p.pid.to_string(), p.name.clone(), cpu, mem]).style(Style::default().fg(t.text))
         })
         .collect();
 
     let process_table = Table::new(
         process_rows,
-        [Constraint::Percentage(60), Constraint::Percentage(20), Constraint::Percentage(20)],
+        [Constraint::Length(8), Constraint::Percentage(55), Constraint::Percentage(20), Constraint::Percentage(20)],
     )
     .header(
-        Row::new(vec!["Process", "CPU", "Memory"]).style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)).bottom_margin(1),
+        Row::new(vec!["PID", "Process", "CPU", "Memory"]).style(Style::default().fg(t.warning).add_modifier(Modifier::BOLD)).bottom_margin(1),
     )
+    .highlight_style(Style::default().bg(t.highlight_bg).add_modifier(Modifier::BOLD))
     .block(
-        Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title(Span::styled("━━━ TOP PROCESSES ━━━", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))).border_style(Style::default().fg(Color::Yellow)),
+        panel(app)
+            .title(Span::styled(heading(app, if app.ollama_only { "OLLAMA PROCESSES" } else { "TOP PROCESSES" }), Style::default().fg(t.warning).add_modifier(Modifier::BOLD)))
+            .title(process_filter_title(app))
+            .border_style(Style::default().fg(t.warning)),
     )
     .column_spacing(2);
 
-    f.render_widget(process_table, chunks[3]);
+    let mut state = TableState::default().with_selected(Some(app.process_selected));
+    f.render_stateful_widget(process_table, chunks[5], &mut state);
+}
+
+/// Ollama's combined footprint on this machine, and the models its host has loaded.
+fn render_ollama_panel(f: &mut Frame, app: &App, area: Rect) {
+    let t = &app.theme;
+    let ollama = monitor::ollama_processes(&app.metrics.processes);
+    let label = |text: &str| Span::styled(text.to_string(), Style::default().fg(t.secondary));
+    let value = |text: String| Span::styled(text, Style::default().fg(t.text).add_modifier(Modifier::BOLD));
+    let summary = if ollama.is_empty() {
+        Line::from(Span::styled("  No Ollama processes on this machine", Style::default().fg(t.muted)))
+    } else {
+        let cpu: f32 = ollama.iter().map(|p| p.cpu).sum();
+        let memory: u64 = ollama.iter().map(|p| p.memory).sum();
+        let vram = app.loaded_models.as_ref().map_or_else(|| "n/a".to_string(), |models| format_size(models.iter().map(|m| m.size_vram).sum::<u64>() as f64));
+        Line::from(vec![
+            label("  CPU "),
+            value(format!("{:.1}%", cpu)),
+            label("   RSS "),
+            value(format_size(memory as f64)),
+            label("   VRAM "),
+            value(vram),
+            Span::styled(format!("   {} process{}", ollama.len(), if ollama.len() == 1 { "" } else { "es" }), Style::default().fg(t.muted)),
+        ])
+    };
+    let mut lines = vec![summary];
+    match &app.loaded_models {
+        None => lines.push(Line::from(Span::styled(format!("  Cannot list loaded models on {}", app.app_config.active().name), Style::default().fg(t.muted)))),
+        Some(models) if models.is_empty() => lines.push(Line::from(Span::styled("  No models loaded", Style::default().fg(t.muted)))),
+        Some(models) => lines.extend(models.iter().take(MAX_LOADED_MODEL_ROWS).map(|model| {
+            Line::from(vec![
+                Span::styled(format!("  {:<28}", text::truncate(&model.name, 28)), Style::default().fg(t.success).add_modifier(Modifier::BOLD)),
+                Span::styled(format!("{:>9}", format_size(model.size as f64)), Style::default().fg(t.text)),
+                Span::styled(format!("  {:<16}", processor_split(model.size, model.size_vram)), Style::default().fg(t.secondary)),
+                Span::styled(unload_time(model.expires_at.as_deref()), Style::default().fg(t.muted)),
+            ])
+        })),
+    }
+    let block = panel(app).title(Span::styled(heading(app, "OLLAMA"), Style::default().fg(t.secondary).add_modifier(Modifier::BOLD))).border_style(Style::default().fg(t.secondary));
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Where a model sits, worded like `ollama ps`: `100% GPU` or `25%/75% CPU/GPU`.
+fn processor_split(size: u64, vram: u64) -> String {
+    let gpu = if size == 0 { 0 } else { (vram as f64 * 100.0 / size as f64).round() as u64 };
+    match gpu {
+        0 => "100% CPU".to_string(),
+        100.. => "100% GPU".to_string(),
+        gpu => format!("{}%/{}% CPU/GPU", 100 - gpu, gpu),
+    }
+}
+
+/// When an idle model is unloaded, from its `expires_at` time.
+fn unload_time(expires_at: Option<&str>) -> String {
+    let Some(expires) = expires_at.and_then(|time| chrono::DateTime::parse_from_rfc3339(time).ok()) else { return String::new() };
+    let left = expires.signed_duration_since(chrono::Local::now());
+    match left.num_minutes() {
+        // A negative keep_alive keeps the model loaded; the server reports a far-future time
+        m if m > 60 * 24 * 365 => "kept loaded".to_string(),
+        m if m >= 1 => format!("unloads in {}m", m),
+        _ => format!("unloads in {}s", left.num_seconds().max(0)),
+    }
+}
+
+/// The filter being typed, or a hint on how to start one.
+fn process_filter_title(app: &App) -> Line<'static> {
+    let t = &app.theme;
+    let text = if app.process_filter.is_empty() {
+        format!(" type to filter · Tab: {} · Del: terminate ", if app.ollama_only { "all processes" } else { "Ollama only" })
+    } else {
+        format!(" filter: {}_ (Esc clears) ", app.process_filter)
+    };
+    Line::from(Span::styled(text, Style::default().fg(t.secondary))).right_aligned()
 }
 
 fn render_chat_history(f: &mut Frame, app: &App, area: Rect) {
+    let t = &app.theme;
     let items: Vec<ListItem> = app
         .chat_history
         .iter()
         .map(|session| {
             let msg_count = session.messages.len();
-            let preview = if let Some((_, content)) = session.messages.first() {
-                let preview_text = content.chars().take(50).collect::<String>();
+            let preview = if let Some(message) = session.messages.first() {
+                let preview_text = text::truncate(&message.content, 50);
                 format!("{} - {} msgs - {}", session.timestamp, msg_count, preview_text)
             } else { format!("{} - {} msgs", session.timestamp, msg_count) };
-            ListItem::new(preview).style(Style::default().fg(Color::White))
+            ListItem::new(preview).style(Style::default().fg(t.text))
         })
         .collect();
 
     let list = List::new(items)
-        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).border_style(Style::default().fg(Color::Blue)).title("Chat History (Enter to load, Esc to cancel)"))
-        .highlight_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD))
+        .block(panel(app).border_style(Style::default().fg(t.assistant)).title("Chat History (Enter to load, Esc to cancel)"))
+        .highlight_style(Style::default().bg(t.highlight_bg).add_modifier(Modifier::BOLD))
         .highlight_symbol(">> ");
 
     let mut state = app.history_list_state.clone();
     f.render_stateful_widget(list, area, &mut state);
 }
 
+fn render_event_log(f: &mut Frame, app: &App, area: Rect) {
+    let t = &app.theme;
+    let items: Vec<ListItem> = app
+        .event_log
+        .iter()
+        .map(|entry| {
+            let (color, level) = match entry.severity {
+                Severity::Info => (t.secondary, "INFO "),
+                Severity::Success => (t.success, "OK   "),
+                Severity::Warning => (t.warning, "WARN "),
+                Severity::Error => (t.danger, "ERROR"),
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{} ", entry.time), Style::default().fg(t.muted)),
+                Span::styled(format!("{} ", level), Style::default().fg(color).add_modifier(Modifier::BOLD)),
+                Span::styled(entry.message.clone(), Style::default().fg(t.text)),
+            ]))
+        })
+        .collect();
+
+    let title = if app.event_log.is_empty() { "Event Log (nothing yet, Esc to go back)" } else { "Event Log (j/k to scroll, Esc to go back)" };
+    let list = List::new(items)
+        .block(panel(app).border_style(Style::default().fg(t.primary)).title(title))
+        .highlight_style(Style::default().bg(t.highlight_bg));
+
+    let mut state = app.log_list_state.clone();
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn render_stats(f: &mut Frame, app: &App, area: Rect) {
+    let t = &app.theme;
+    let total = app.usage.total();
+    let daily = app.usage.daily(chrono::Local::now().date_naive(), usage::CHART_DAYS);
+    let [summary, chart, models] = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(4), Constraint::Length(8), Constraint::Min(0)])
+        .areas(area);
+
+    let mut recent = UsageTotals::default();
+    for (_, totals) in &daily {
+        recent.add(totals);
+    }
+    let describe = |totals: &UsageTotals| {
+        format!(
+            "{} {} · {} prompt tokens · {} reply tokens · {} waiting",
+            totals.messages,
+            if totals.messages == 1 { "reply" } else { "replies" },
+            totals.prompt_tokens,
+            totals.reply_tokens,
+            usage::format_duration(totals.seconds)
+        )
+    };
+    let lines = vec![
+        Line::from(vec![Span::styled("All time: ", Style::default().fg(t.secondary)), Span::styled(describe(&total), Style::default().fg(t.text))]),
+        Line::from(vec![Span::styled(format!("Last {} days: ", usage::CHART_DAYS), Style::default().fg(t.secondary)), Span::styled(describe(&recent), Style::default().fg(t.text))]),
+    ];
+    f.render_widget(Paragraph::new(lines).block(panel(app).border_style(Style::default().fg(t.primary)).title("Usage Statistics (Esc to go back)")), summary);
+
+    let chart_block = panel(app).border_style(Style::default().fg(t.success)).title(format!("Replies per day, last {} days", usage::CHART_DAYS));
+    if app.app_config.screen_reader {
+        let busiest = daily.iter().max_by_key(|(_, totals)| totals.messages).filter(|(_, totals)| totals.messages > 0);
+        let today = daily.last().map_or(0, |(_, totals)| totals.messages);
+        let text = match busiest {
+            Some((day, totals)) => format!("Today: {} replies. Busiest day: {} with {}.", today, day.format("%Y-%m-%d"), totals.messages),
+            None => "No replies in this period.".to_string(),
+        };
+        f.render_widget(Paragraph::new(text).block(chart_block), chart);
+    } else {
+        // One bar per day, stretched to fill the width, today at the right edge
+        let inner = chart_block.inner(chart);
+        let bar = (inner.width as usize / daily.len().max(1)).max(1);
+        let mut data = vec![0; (inner.width as usize).saturating_sub(bar * daily.len())];
+        data.extend(daily.iter().flat_map(|(_, totals)| std::iter::repeat_n(totals.messages, bar)));
+        f.render_widget(chart_block, chart);
+        f.render_widget(Sparkline::default().data(&data).style(Style::default().fg(t.success)), inner);
+    }
+
+    let rows: Vec<Row> = app
+        .usage
+        .by_model()
+        .into_iter()
+        .map(|(model, totals)| {
+            let speed = if totals.seconds > 0.0 { format!("{:.0}", totals.reply_tokens as f64 / totals.seconds) } else { "-".to_string() };
+            let share = if total.messages > 0 { format!("{:.0}%", totals.messages as f64 * 100.0 / total.messages as f64) } else { "-".to_string() };
+            Row::new(vec![
+                model,
+                totals.messages.to_string(),
+                share,
+                totals.prompt_tokens.to_string(),
+                totals.reply_tokens.to_string(),
+                usage::format_duration(totals.seconds),
+                speed,
+            ])
+            .style(Style::default().fg(t.text))
+        })
+        .collect();
+    let title = if rows.is_empty() { "By model (no replies recorded yet)" } else { "By model, most used first" };
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Min(16),
+            Constraint::Length(8),
+            Constraint::Length(6),
+            Constraint::Length(14),
+            Constraint::Length(13),
+            Constraint::Length(8),
+            Constraint::Length(6),
+        ],
+    )
+    .header(
+        Row::new(vec!["Model", "Replies", "Share", "Prompt tokens", "Reply tokens", "Time", "tok/s"])
+            .style(Style::default().fg(t.warning).add_modifier(Modifier::BOLD))
+            .bottom_margin(1),
+    )
+    .block(panel(app).border_style(Style::default().fg(t.warning)).title(title))
+    .column_spacing(1);
+    f.render_widget(table, models);
+}
+
+fn render_sidebar(f: &mut Frame, app: &App, area: Rect) {
+    let t = &app.theme;
+    let items: Vec<ListItem> = app
+        .chat_history
+        .iter()
+        .map(|session| {
+            let preview = session.messages.first().map(|m| m.content.lines().next().unwrap_or("").to_string()).unwrap_or_default();
+            ListItem::new(vec![
+                Line::from(Span::styled(session.timestamp.clone(), Style::default().fg(t.secondary))),
+                Line::from(Span::styled(preview, Style::default().fg(t.text))),
+            ])
+        })
+        .collect();
+
+    let border = if app.sidebar_focus { t.primary } else { t.muted };
+    let list = List::new(items)
+        .block(panel(app).border_style(Style::default().fg(border)).title("Sessions"))
+        .highlight_style(Style::default().bg(t.highlight_bg).add_modifier(Modifier::BOLD));
+
+    let mut state = app.history_list_state.clone();
+    f.render_stateful_widget(list, area, &mut state);
+}
+
 fn render_model_config(f: &mut Frame, app: &App, area: Rect) {
+    let t = &app.theme;
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([Constraint::Min(0), Constraint::Length(3)])
         .split(area);
 
-    // Fields
-    let config_items = vec![
-        // Temperature
-        Line::from(vec![
-            Span::styled("  Temperature ", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
-            Span::styled(
-                format!("[{}]", app.model_config.temperature),
-                if matches!(app.config_field, ConfigField::Temperature) { Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD) } else { Style::default().fg(Color::White) },
-            ),
-        ]),
-        Line::from("    Controls randomness. Lower = more focused, Higher = more creative"),
-        Line::from("    Range: 0.0 - 2.0, Default: 0.8"),
-        Line::from(""),
-        // Top P
-        Line::from(vec![
-            Span::styled("  Top P ", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
-            Span::styled(
-                format!("[{}]", app.model_config.top_p),
-                if matches!(app.config_field, ConfigField::TopP) { Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD) } else { Style::default().fg(Color::White) },
-            ),
-        ]),
-        Line::from("    Nucleus sampling. Controls diversity of responses"),
-        Line::from("    Range: 0.0 - 1.0, Default: 0.9"),
-        Line::from(""),
-        // Top K
-        Line::from(vec![
-            Span::styled("  Top K ", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
-            Span::styled(
-                format!("[{}]", app.model_config.top_k),
-                if matches!(app.config_field, ConfigField::TopK) { Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD) } else { Style::default().fg(Color::White) },
-            ),
-        ]),
-        Line::from("    Limits token selection to top K options"),
-        Line::from("    Range: 1+, Default: 40"),
-        Line::from(""),
-        // Repeat Penalty
-        Line::from(vec![
-            Span::styled("  Repeat Penalty ", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
-            Span::styled(
-                format!("[{}]", app.model_config.repeat_penalty),
-                if matches!(app.config_field, ConfigField::RepeatPenalty) { Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD) } else { Style::default().fg(Color::White) },
-            ),
-        ]),
-        Line::from("    Penalizes repetition. Higher = less repetition"),
-        Line::from("    Range: 0.0 - 2.0, Default: 1.1"),
-        Line::from(""),
-        // Context Window
-        Line::from(vec![
-            Span::styled("  Context Window ", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
-            Span::styled(
-                format!("[{}]", app.model_config.num_ctx),
-                if matches!(app.config_field, ConfigField::ContextWindow) { Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD) } else { Style::default().fg(Color::White) },
-            ),
-        ]),
-        Line::from("    Number of tokens in context window"),
-        Line::from("    Range: 512 - 32768, Default: 2048"),
-        Line::from(""),
-        // System Prompt
-        Line::from(vec![
-            Span::styled("  System Prompt ", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+    // Fields, grouped into basic and advanced sections
+    let mut config_items = Vec::new();
+    let mut selected_line = 0;
+    for (i, field) in ConfigField::ALL.iter().enumerate() {
+        if i == 0 || field.is_advanced() != ConfigField::ALL[i - 1].is_advanced() {
+            let section = if field.is_advanced() { "━━ Advanced ━━" } else { "━━ Basic ━━" };
+            config_items.push(Line::from(Span::styled(section, Style::default().fg(t.accent).add_modifier(Modifier::BOLD))));
+        }
+        let value = text::truncate(&app.config_value(*field), 30);
+        let selected = app.config_field == *field;
+        if selected { selected_line = config_items.len(); }
+        let mut spans = vec![
+            Span::styled(format!("  {} ", field.label()), Style::default().fg(t.primary).add_modifier(Modifier::BOLD)),
             Span::styled(
-                format!(
-                    "[{}]",
-                    if app.model_config.system_prompt.len() > 30 { format!("{}...", &app.model_config.system_prompt[..30]) } else { app.model_config.system_prompt.clone() }
-                ),
-                if matches!(app.config_field, ConfigField::SystemPrompt) { Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD) } else { Style::default().fg(Color::White) },
+                format!("[{}]", value),
+                if selected { Style::default().fg(t.warning).add_modifier(Modifier::BOLD) } else { Style::default().fg(t.text) },
             ),
-        ]),
-        Line::from("    System instructions for the model"),
-        Line::from(""),
-        Line::from(""),
-        Line::from(Span::styled(
-            "Navigation: Up/Down or Tab | Edit: Type value & Enter | Save: Auto | Esc: Back",
-            Style::default().fg(Color::Green),
-        )),
-    ];
+        ];
+        if let (FieldKind::Float { min, max, .. } | FieldKind::Integer { min, max, .. }, Some(current)) = (field.kind(), app.model_config.numeric(*field)) {
+            spans.push(Span::styled(format!(" {}", slider(current, min, max, 20)), Style::default().fg(if selected { t.warning } else { t.muted })));
+        }
+        config_items.push(Line::from(spans));
+        let hint = field.range_hint();
+        if *field == ConfigField::Preset {
+            let names: Vec<&str> = app.app_config.presets.iter().map(|p| p.name.as_str()).collect();
+            config_items.push(Line::from(format!("    {} ({})", field.description(), names.join(", "))));
+        } else if hint.is_empty() {
+            config_items.push(Line::from(format!("    {}", field.description())));
+        } else {
+            config_items.push(Line::from(format!("    {} ({})", field.description(), hint)));
+        }
+    }
+    config_items.push(Line::from(""));
+    config_items.push(Line::from(Span::styled(
+        "Navigation: Up/Down or Tab | Adjust: Left/Right | Edit: Type value & Enter | Save: Auto | Esc: Back",
+        Style::default().fg(t.success),
+    )));
+
+    // Keep the selected field in view on short terminals
+    let visible = chunks[0].height.saturating_sub(2) as usize;
+    let scroll = (selected_line + 2).saturating_sub(visible);
 
     let config_widget = Paragraph::new(config_items)
-        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title(Span::styled("━━━ MODEL CONFIGURATION ━━━", Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD))).border_style(Style::default().fg(Color::Magenta)))
-        .wrap(Wrap { trim: false });
+        .block(panel(app).title(Span::styled(heading(app, "MODEL CONFIGURATION"), Style::default().fg(t.accent).add_modifier(Modifier::BOLD))).border_style(Style::default().fg(t.accent)))
+        .scroll((scroll as u16, 0));
 
     f.render_widget(config_widget, chunks[0]);
 
-    let field_name = match app.config_field {
-        ConfigField::Temperature => "Temperature",
-        ConfigField::TopP => "Top P",
-        ConfigField::TopK => "Top K",
-        ConfigField::RepeatPenalty => "Repeat Penalty",
-        ConfigField::ContextWindow => "Context Window",
-        ConfigField::SystemPrompt => "System Prompt",
-    };
-
-    let input = Paragraph::new(app.config_input.as_str())
-        .style(Style::default().fg(Color::White))
-        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title(format!("Editing: {} (Press Enter to save)", field_name)).border_style(Style::default().fg(Color::Yellow)));
+    // Rejected input stays in the box with the reason shown alongside it in red
+    let mut input_line = vec![Span::styled(app.config_input.as_str(), Style::default().fg(t.text))];
+    let border_color = if let Some(error) = &app.config_error {
+        input_line.push(Span::styled(format!("  ✗ {}", error), Style::default().fg(t.danger).add_modifier(Modifier::BOLD)));
+        t.danger
+    } else { t.warning };
+    let input = Paragraph::new(Line::from(input_line))
+        .block(panel(app).title(format!("Editing: {} (Press Enter to save)", app.config_field.label())).border_style(Style::default().fg(border_color)));
     f.render_widget(input, chunks[1]);
 }
+
+/// A text slider such as `━━━━━━●─────────` showing where `value` sits in `min..=max`.
+/// Width of one core's cell in the per-core grid: a bar and a space, or ` 99% ` as text.
+fn core_cell_width(app: &App) -> u16 {
+    if app.app_config.screen_reader { 5 } else { 2 }
+}
+
+/// Rows the per-core grid needs at this width.
+fn core_grid_rows(app: &App, width: u16) -> u16 {
+    let per_row = (width / core_cell_width(app)).max(1) as usize;
+    app.metrics.cpu_cores.len().div_ceil(per_row) as u16
+}
+
+/// One cell per logical core: a bar whose height is its load, or the percentage as text.
+fn core_grid(app: &App) -> Line<'static> {
+    const BARS: [&str; 8] = ["▁", "▂", "▃", "▄", "▅", "▆", "▇", "█"];
+    let t = &app.theme;
+    let spans = app.metrics.cpu_cores.iter().map(|&usage| {
+        let usage = usage.clamp(0.0, 100.0);
+        let (color, _) = usage_level(t, usage as f64 / 100.0, (0.5, 0.8), t.primary, "");
+        let cell = if app.app_config.screen_reader {
+            format!("{:>3.0}% ", usage)
+        } else {
+            format!("{} ", BARS[((usage / 100.0) * (BARS.len() - 1) as f32).round() as usize])
+        };
+        Span::styled(cell, Style::default().fg(color))
+    });
+    Line::from(spans.collect::<Vec<_>>())
+}
+
+/// Columns given to a trend sparkline on the right of a monitor panel; none in screen-reader mode.
+fn trend_width(app: &App, width: u16) -> u16 {
+    if app.app_config.screen_reader { 0 } else { width / 3 }
+}
+
+/// Draws recent samples (0–100) as a sparkline on the right of `area`, newest at the right
+/// edge, and returns the space left of it.
+fn render_trend(f: &mut Frame, app: &App, area: Rect, samples: &VecDeque<u64>, color: Color) -> Rect {
+    let width = trend_width(app, area.width);
+    if width == 0 {
+        return area;
+    }
+    let [rest, trend] = Layout::default().direction(Direction::Horizontal).constraints([Constraint::Min(0), Constraint::Length(width)]).areas(area);
+    let recent: Vec<u64> = samples.iter().skip(samples.len().saturating_sub(width as usize)).copied().collect();
+    f.render_widget(Sparkline::default().data(&recent).max(100).style(Style::default().fg(color)), trend);
+    rest
+}
+
+/// A percentage bar, or just its label in screen-reader mode.
+fn render_gauge(f: &mut Frame, app: &App, area: Rect, block: Block, percent: u16, color: Color, label: String) {
+    let t = &app.theme;
+    let label = Span::styled(label, Style::default().fg(t.text).add_modifier(Modifier::BOLD));
+    if app.app_config.screen_reader {
+        f.render_widget(Paragraph::new(label).block(block), area);
+        return;
+    }
+    let gauge = Gauge::default()
+        .block(block)
+        .gauge_style(Style::default().fg(color).bg(t.gauge_bg).add_modifier(Modifier::BOLD))
+        .percent(percent)
+        .label(label);
+    f.render_widget(gauge, area);
+}
+
+fn slider(value: f64, min: f64, max: f64, width: usize) -> String {
+    let ratio = if max > min { ((value - min) / (max - min)).clamp(0.0, 1.0) } else { 0.0 };
+    let pos = (ratio * (width - 1) as f64).round() as usize;
+    (0..width).map(|i| if i == pos { '●' } else if i < pos { '━' } else { '─' }).collect()
+}
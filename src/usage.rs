@@ -0,0 +1,144 @@
+use anyhow::Result;
+use chrono::{Days, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::app::App;
+use crate::backend::Usage;
+use crate::migrate::{self, Versioned};
+
+pub const USAGE_FILE: &str = "usage.json";
+/// Days of history the statistics screen charts.
+pub const CHART_DAYS: usize = 30;
+
+/// What was asked of one model on one day.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct UsageTotals {
+    /// Replies received.
+    pub messages: u64,
+    pub prompt_tokens: u64,
+    pub reply_tokens: u64,
+    /// Time spent waiting for replies.
+    pub seconds: f64,
+}
+
+impl UsageTotals {
+    pub fn add(&mut self, other: &UsageTotals) {
+        self.messages += other.messages;
+        self.prompt_tokens += other.prompt_tokens;
+        self.reply_tokens += other.reply_tokens;
+        self.seconds += other.seconds;
+    }
+}
+
+/// Totals per day and model, kept across sessions in `usage.json`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct UsageStats {
+    /// Format version; see `migrate`.
+    #[serde(default)]
+    pub version: u32,
+    /// Day (`YYYY-MM-DD`, local time), then model.
+    pub days: BTreeMap<String, BTreeMap<String, UsageTotals>>,
+}
+
+impl Versioned for UsageStats {
+    const VERSION: u32 = 1;
+
+    fn upgrade(_version: u32, _value: &mut Value) -> Result<()> {
+        // Written with a version from the start
+        Ok(())
+    }
+}
+
+impl UsageStats {
+    pub fn load(config_dir: &Path) -> Result<Self> {
+        let path = config_dir.join(USAGE_FILE);
+        if !path.exists() {
+            return Ok(Self { version: Self::VERSION, ..Self::default() });
+        }
+        migrate::load(&path)
+    }
+
+    /// Today's totals for `model`.
+    pub fn today(&mut self, model: &str) -> &mut UsageTotals {
+        let day = Local::now().format("%Y-%m-%d").to_string();
+        self.days.entry(day).or_default().entry(model.to_string()).or_default()
+    }
+
+    /// Totals per model over every day, most used first.
+    pub fn by_model(&self) -> Vec<(String, UsageTotals)> {
+        let mut models: BTreeMap<&str, UsageTotals> = BTreeMap::new();
+        for (model, totals) in self.days.values().flatten() {
+            models.entry(model).or_default().add(totals);
+        }
+        let mut models: Vec<(String, UsageTotals)> = models.into_iter().map(|(model, totals)| (model.to_string(), totals)).collect();
+        models.sort_by_key(|(_, totals)| std::cmp::Reverse(totals.messages));
+        models
+    }
+
+    /// Totals for each of the `days` days up to and including `today`, oldest first; days
+    /// without replies are zero.
+    pub fn daily(&self, today: NaiveDate, days: usize) -> Vec<(NaiveDate, UsageTotals)> {
+        (0..days as u64)
+            .rev()
+            .map(|ago| {
+                let day = today - Days::new(ago);
+                let mut totals = UsageTotals::default();
+                for day_totals in self.days.get(&day.format("%Y-%m-%d").to_string()).into_iter().flat_map(BTreeMap::values) {
+                    totals.add(day_totals);
+                }
+                (day, totals)
+            })
+            .collect()
+    }
+
+    pub fn total(&self) -> UsageTotals {
+        let mut total = UsageTotals::default();
+        for totals in self.days.values().flat_map(BTreeMap::values) {
+            total.add(totals);
+        }
+        total
+    }
+}
+
+impl App {
+    /// Counts the tokens Ollama reports for a reply, including rounds that only called tools.
+    pub(crate) fn record_tokens(&mut self, conversation_id: usize, usage: Usage) {
+        self.record(conversation_id, UsageTotals { prompt_tokens: usage.prompt_tokens, reply_tokens: usage.reply_tokens, ..UsageTotals::default() });
+    }
+
+    /// Counts a finished reply and the time it took, and saves the totals.
+    pub(crate) fn record_reply(&mut self, conversation_id: usize, elapsed: Duration) {
+        self.record(conversation_id, UsageTotals { messages: 1, seconds: elapsed.as_secs_f64(), ..UsageTotals::default() });
+        let json = match serde_json::to_string_pretty(&self.usage) {
+            Ok(json) => json,
+            Err(e) => return tracing::warn!("cannot save usage statistics: {}", e),
+        };
+        self.disk.write(self.config_dir.join(USAGE_FILE), json, |_, result| {
+            if let Err(e) = result {
+                tracing::warn!("cannot save usage statistics: {}", e);
+            }
+        });
+    }
+
+    /// Adds `totals` to the conversation's and to today's for its model.
+    fn record(&mut self, conversation_id: usize, totals: UsageTotals) {
+        let Some(conversation) = self.conversation_by_id(conversation_id) else { return };
+        conversation.usage.add(&totals);
+        let model = conversation.model.clone();
+        self.usage.today(&model).add(&totals);
+    }
+}
+
+/// `42s`, `3m 05s`, or `2h 14m`.
+pub fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m {:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
@@ -0,0 +1,227 @@
+use anyhow::{bail, Result};
+use reqwest::header::CONTENT_TYPE;
+use std::time::Duration;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+/// Pages bigger than this are cut off; the rest is never downloaded.
+const MAX_PAGE_BYTES: usize = 4 * 1024 * 1024;
+/// Elements whose content is never shown to a reader.
+const HIDDEN_ELEMENTS: [&str; 7] = ["script", "style", "noscript", "head", "svg", "template", "iframe"];
+/// Elements that start a new line of text.
+const BLOCK_ELEMENTS: [&str; 27] = [
+    "p", "div", "br", "li", "ul", "ol", "tr", "table", "section", "article", "header", "footer", "nav", "aside", "main", "h1", "h2",
+    "h3", "h4", "h5", "h6", "pre", "blockquote", "hr", "dt", "dd", "figcaption",
+];
+
+/// A fetched page reduced to readable text.
+#[derive(Debug, Clone)]
+pub struct Page {
+    pub title: Option<String>,
+    pub text: String,
+}
+
+/// Downloads `url`; HTML is converted to text, plain text passes through, and anything
+/// else is refused.
+pub async fn fetch(url: &str) -> Result<Page> {
+    let client = reqwest::Client::builder().timeout(FETCH_TIMEOUT).user_agent(concat!("ollama-tui/", env!("CARGO_PKG_VERSION"))).build()?;
+    let mut response = client.get(url).send().await?.error_for_status()?;
+    let content_type = response.headers().get(CONTENT_TYPE).and_then(|v| v.to_str().ok()).unwrap_or("text/html").to_lowercase();
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        bytes.extend_from_slice(&chunk);
+        if bytes.len() >= MAX_PAGE_BYTES {
+            bytes.truncate(MAX_PAGE_BYTES);
+            break;
+        }
+    }
+    let body = String::from_utf8_lossy(&bytes).to_string();
+    if content_type.contains("html") {
+        Ok(Page { title: html_title(&body), text: html_to_text(&body) })
+    } else if content_type.starts_with("text/") || content_type.contains("json") || content_type.contains("xml") {
+        Ok(Page { title: None, text: body })
+    } else {
+        bail!("{} is {}, not a web page", url, content_type)
+    }
+}
+
+/// Text of the `<title>` element.
+fn html_title(html: &str) -> Option<String> {
+    // ASCII-only lowercasing keeps every byte offset the same as in `html`
+    let lower = html.to_ascii_lowercase();
+    let open = lower.find("<title")?;
+    let start = open + lower[open..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+    let title = decode_entities(html[start..end].trim());
+    (!title.is_empty()).then_some(title)
+}
+
+/// The visible text of an HTML document: tags dropped, hidden elements skipped, block
+/// elements on their own lines, list items bulleted, and whitespace collapsed.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut hidden: Option<String> = None;
+    let mut in_pre = false;
+    let mut rest = html;
+    while let Some(open) = rest.find('<') {
+        push_text(&mut text, &rest[..open], hidden.is_some(), in_pre);
+        rest = &rest[open..];
+        // Comments may contain `>`, so they end at `-->`
+        if rest.starts_with("<!--") {
+            rest = rest.find("-->").map_or("", |end| &rest[end + 3..]);
+            continue;
+        }
+        let Some(close) = rest.find('>') else { break };
+        let tag = &rest[1..close];
+        rest = &rest[close + 1..];
+        let closing = tag.starts_with('/');
+        let name: String = tag.trim_start_matches('/').chars().take_while(|c| c.is_ascii_alphanumeric()).collect::<String>().to_lowercase();
+        if let Some(element) = &hidden {
+            if closing && *element == name {
+                hidden = None;
+            }
+            continue;
+        }
+        if !closing && HIDDEN_ELEMENTS.contains(&name.as_str()) && !tag.ends_with('/') {
+            hidden = Some(name);
+            continue;
+        }
+        if name == "pre" {
+            in_pre = !closing;
+        }
+        if BLOCK_ELEMENTS.contains(&name.as_str()) {
+            if !text.ends_with('\n') {
+                text.push('\n');
+            }
+            if !closing && name == "li" {
+                text.push_str("- ");
+            }
+        }
+    }
+    push_text(&mut text, rest, hidden.is_some(), in_pre);
+
+    // Keep at most one blank line between blocks
+    let mut result = String::new();
+    let mut blank = true;
+    for line in text.lines().map(str::trim_end) {
+        if line.trim().is_empty() {
+            if !blank {
+                result.push('\n');
+            }
+            blank = true;
+        } else {
+            result.push_str(line);
+            result.push('\n');
+            blank = false;
+        }
+    }
+    result.trim().to_string()
+}
+
+fn push_text(text: &mut String, raw: &str, hidden: bool, in_pre: bool) {
+    if hidden || raw.is_empty() {
+        return;
+    }
+    let decoded = decode_entities(raw);
+    if in_pre {
+        text.push_str(&decoded);
+        return;
+    }
+    for word in decoded.split_whitespace() {
+        if !text.is_empty() && !text.ends_with(['\n', ' ']) {
+            text.push(' ');
+        }
+        text.push_str(word);
+    }
+    // Words split across inline tags stay apart, as a browser would show them
+    if decoded.ends_with(char::is_whitespace) && !text.ends_with(['\n', ' ']) {
+        text.push(' ');
+    }
+}
+
+/// Replaces the common named entities and all numeric ones.
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').filter(|&end| end <= 10).and_then(|end| {
+            let entity = &rest[1..end];
+            let c = match entity {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                "nbsp" => ' ',
+                "mdash" => '—',
+                "ndash" => '–',
+                "hellip" => '…',
+                "rsquo" => '’',
+                "lsquo" => '‘',
+                "rdquo" => '”',
+                "ldquo" => '“',
+                _ => {
+                    let number = entity.strip_prefix('#')?;
+                    let code = match number.strip_prefix(['x', 'X']) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => number.parse().ok()?,
+                    };
+                    char::from_u32(code)?
+                }
+            };
+            Some((c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                result.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Splits text into pieces of at most `max_chars`, breaking between lines, and mid-line only
+/// when a single line is too long.
+pub fn chunks(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut chunks = Vec::new();
+    let (mut current, mut current_len) = (String::new(), 0);
+    for line in text.lines() {
+        let mut line = line;
+        let mut line_len = line.chars().count();
+        while line_len > max_chars {
+            let split = line.char_indices().nth(max_chars).map_or(line.len(), |(i, _)| i);
+            if current_len > 0 {
+                chunks.push(std::mem::take(&mut current));
+                current_len = 0;
+            }
+            chunks.push(line[..split].to_string());
+            line = &line[split..];
+            line_len -= max_chars;
+        }
+        if current_len > 0 && current_len + line_len + 1 > max_chars {
+            chunks.push(std::mem::take(&mut current));
+            current_len = 0;
+        }
+        if current_len > 0 {
+            current.push('\n');
+            current_len += 1;
+        }
+        current.push_str(line);
+        current_len += line_len;
+    }
+    if !current.trim().is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
@@ -0,0 +1,736 @@
+//! Drives the app against a scripted backend: sending, failing, recovering, and quitting.
+
+mod common;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::sync::Arc;
+
+use common::Harness;
+use llama_term::app::PendingAction;
+use llama_term::attempts::{word_diff, Change};
+use llama_term::backend::{MockBackend, ReplyFormat};
+use llama_term::error::{AppError, Recovery, Retry};
+use llama_term::message_menu::MessageAction;
+use llama_term::{input, App};
+
+#[tokio::test]
+async fn reply_streams_into_the_conversation() {
+    let backend = Arc::new(MockBackend::new(&["llama3.2:latest"]).reply(&["Hello", ", ", "world"]));
+    let mut harness = Harness::new(backend.clone());
+    harness.send("Hi").await;
+
+    let conversation = harness.app.conversation();
+    assert_eq!(conversation.messages.len(), 2);
+    assert_eq!(conversation.messages[1].content, "Hello, world");
+    assert_eq!(conversation.stream_tokens, 3);
+    assert!(conversation.input.is_empty());
+    assert_eq!(harness.app.status_message, "Ready");
+    assert_eq!(backend.requests()[0].prompt, "Hi");
+}
+
+#[tokio::test]
+async fn lists_the_backends_models() {
+    let mut harness = Harness::new(Arc::new(MockBackend::new(&["llama3.2:latest", "mistral:7b"])));
+    harness.app.fetch_models();
+    harness.settle().await;
+    assert_eq!(harness.app.available_models, ["llama3.2:latest", "mistral:7b"]);
+}
+
+#[tokio::test]
+async fn failed_reply_can_be_retried() {
+    let backend = Arc::new(MockBackend::new(&[]).fail("server exploded").reply(&["Recovered"]));
+    let mut harness = Harness::new(backend.clone());
+    harness.send("Hi").await;
+
+    let failure = harness.app.failure.take().expect("the error popup is open");
+    assert_eq!(failure.recoveries, [Recovery::Retry]);
+    assert_eq!(harness.app.conversation().messages.len(), 1);
+
+    harness.app.retry(failure.retry.expect("the prompt can be resent"));
+    harness.settle().await;
+    let messages = &harness.app.conversation().messages;
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].content, "Hi");
+    assert_eq!(messages[1].content, "Recovered");
+    assert_eq!(backend.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn failure_arriving_after_the_reply_was_stopped_keeps_the_prompt() {
+    let backend = Arc::new(MockBackend::new(&[]).fail("server exploded"));
+    let mut harness = Harness::new(backend);
+    harness.app.conversation_mut().input = "Hi".to_string();
+    harness.app.start_message_stream();
+    let failure = harness.inbox.recv().await.expect("the failure is queued");
+    harness.app.conversation_mut().stop_reply();
+    harness.app.handle_event(failure);
+    harness.settle().await;
+
+    assert!(harness.app.failure.is_none());
+    let messages = &harness.app.conversation().messages;
+    assert_eq!((messages.len(), messages[0].content.as_str()), (1, "Hi"));
+}
+
+#[tokio::test]
+async fn missing_model_offers_a_download() {
+    let backend = Arc::new(MockBackend::new(&[]).fail(r#"model "llama2:latest" not found, try pulling it first"#).reply(&["Hi!"]));
+    let mut harness = Harness::new(backend.clone());
+    harness.send("Hi").await;
+
+    let failure = harness.app.failure.take().expect("the error popup is open");
+    assert!(matches!(failure.error, AppError::ModelMissing(ref model) if model == "llama2:latest"));
+    assert_eq!(failure.recoveries, [Recovery::PullModel, Recovery::OpenModels]);
+
+    harness.app.download_model("llama2:latest".to_string(), Some(Retry::Reply(harness.app.conversation().id)));
+    assert_eq!(harness.app.status_message, "Downloading model: llama2:latest");
+    harness.settle().await;
+    assert_eq!(backend.pulled(), ["llama2:latest"]);
+    assert!(harness.app.available_models.contains(&"llama2:latest".to_string()));
+    assert_eq!(harness.app.conversation().messages[1].content, "Hi!");
+}
+
+#[tokio::test]
+async fn quitting_saves_unsaved_chats() {
+    let mut harness = Harness::new(Arc::new(MockBackend::new(&[]).reply(&["Saved reply"])));
+    harness.send("Hi").await;
+    harness.app.new_conversation();
+
+    harness.app.shutdown().await;
+    harness.settle().await;
+    // The empty second tab has nothing to save
+    let chats = harness.saved_chats();
+    assert_eq!(chats.len(), 1);
+    assert!(std::fs::read_to_string(&chats[0]).unwrap().contains("Saved reply"));
+    assert!(!harness.app.conversations[0].has_unsaved_messages());
+}
+
+#[tokio::test]
+async fn shift_enter_starts_a_new_line_instead_of_sending() {
+    let backend = Arc::new(MockBackend::new(&[]).reply(&["Two lines"]));
+    let mut harness = Harness::new(backend.clone());
+    harness.app.conversation_mut().input = "First".to_string();
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Enter, KeyModifiers::SHIFT)).await;
+    harness.app.conversation_mut().input.push_str("Second");
+    assert_eq!(harness.app.conversation().input, "First\nSecond");
+    assert!(backend.requests().is_empty());
+
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)).await;
+    harness.settle().await;
+    assert_eq!(backend.requests()[0].prompt, "First\nSecond");
+}
+
+#[tokio::test]
+async fn oldest_messages_are_summarized_near_the_context_limit() {
+    let reply = "An answer that is exactly forty chars ..";
+    let backend = Arc::new(MockBackend::new(&[]).reply(&[reply]).reply(&[reply]).reply(&[reply]).reply(&["Earlier: asked about A."]).reply(&["Done"]));
+    let mut harness = Harness::new(backend.clone());
+    harness.app.model_config.system_prompt.clear();
+    // Each exchange takes about 20 of the 70 tokens; the third passes 80%
+    harness.app.model_config.num_ctx = 70;
+    for question in ["Question A, padded out to forty chars ..", "Question B, padded out to forty chars ..", "Question C, padded out to forty chars .."] {
+        harness.send(question).await;
+    }
+
+    let requests = backend.requests();
+    assert_eq!(requests.len(), 4);
+    assert!(requests[3].prompt.contains("User: Question A"), "{}", requests[3].prompt);
+    assert!(!requests[3].prompt.contains("Question B"));
+    assert_eq!(requests[1].history.len(), 2);
+    let messages = &harness.app.conversation().messages;
+    assert_eq!(messages.len(), 5);
+    assert!(messages[0].is_summary());
+    assert_eq!(messages[0].content, "Earlier: asked about A.");
+    assert!(messages[1].content.starts_with("Question B"));
+
+    harness.send("Next").await;
+    let history = &backend.requests()[4].history;
+    assert!(history[0].content.starts_with("Summary of our conversation so far:\nEarlier: asked about A."));
+    assert_eq!(history.len(), 5);
+}
+
+#[tokio::test]
+async fn replies_are_counted_in_the_usage_statistics() {
+    let mut harness = Harness::new(Arc::new(MockBackend::new(&[]).reply(&["One"]).reply(&["Two"])));
+    harness.send("Hi").await;
+    harness.send("Again").await;
+    // Usage is written in the background
+    harness.app.disk.flush().await;
+
+    let reopened = App::with_backend(harness.dir.path(), Arc::new(MockBackend::new(&[])));
+    let models = reopened.usage.by_model();
+    assert_eq!(models.len(), 1);
+    assert_eq!(models[0].0, harness.app.conversation().model);
+    assert_eq!(models[0].1.messages, 2);
+}
+
+#[tokio::test]
+async fn stats_command_opens_a_summary_of_the_conversation() {
+    let backend = MockBackend::new(&[]).reply(&["One"]).usage(12, 3).reply(&["Two"]).usage(20, 5);
+    let mut harness = Harness::new(Arc::new(backend));
+    harness.send("Hi").await;
+    harness.send("Again").await;
+
+    harness.app.command_line = Some("stats".to_string());
+    harness.app.run_command_line();
+    assert!(harness.app.show_session_stats);
+    let usage = harness.app.conversation().usage;
+    assert_eq!((usage.messages, usage.prompt_tokens, usage.reply_tokens), (2, 32, 8));
+
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Char('q'), KeyModifiers::NONE)).await;
+    assert!(!harness.app.show_session_stats);
+    assert!(harness.app.conversation().input.is_empty());
+}
+
+#[tokio::test]
+async fn message_menu_regenerates_and_quotes() {
+    let backend = Arc::new(MockBackend::new(&[]).reply(&["First"]).reply(&["Second"]).reply(&["Third"]).reply(&["Fourth"]));
+    let mut harness = Harness::new(backend.clone());
+    harness.send("Hi").await;
+    harness.send("Again").await;
+
+    // The last reply: regenerating it replaces just that reply, without asking
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Up, KeyModifiers::CONTROL)).await;
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Char('m'), KeyModifiers::ALT)).await;
+    for _ in 0..4 {
+        input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Down, KeyModifiers::NONE)).await;
+    }
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)).await;
+    harness.settle().await;
+    let contents: Vec<&str> = harness.app.conversation().messages.iter().map(|m| m.content.as_str()).collect();
+    assert_eq!(contents, ["Hi", "First", "Again", "Third"]);
+
+    // An earlier reply drops the exchanges after it, once confirmed
+    harness.app.run_message_action(1, MessageAction::Regenerate);
+    assert!(harness.app.confirmation.is_some());
+    harness.app.answer_confirmation(true);
+    harness.settle().await;
+    let contents: Vec<&str> = harness.app.conversation().messages.iter().map(|m| m.content.as_str()).collect();
+    assert_eq!(contents, ["Hi", "Fourth"]);
+    assert_eq!(backend.requests()[3].prompt, "Hi");
+
+    harness.app.run_message_action(1, MessageAction::Quote);
+    assert_eq!(harness.app.conversation().input, "> llama2 wrote:\n> Fourth\n\n");
+}
+
+#[tokio::test]
+async fn quoting_marks_each_line_and_who_wrote_it() {
+    let backend = Arc::new(MockBackend::new(&[]).reply(&["Use a map.\n\nOr a set."]));
+    let mut harness = Harness::new(backend);
+    harness.send("How do I dedupe?").await;
+    harness.app.conversation_mut().input = "About this:".to_string();
+
+    // Nothing selected quotes the last reply
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Char('q'), KeyModifiers::ALT)).await;
+    assert_eq!(harness.app.conversation().input, "About this:\n> llama2 wrote:\n> Use a map.\n>\n> Or a set.\n\n");
+
+    harness.app.select_message(0);
+    harness.app.conversation_mut().input.clear();
+    harness.app.quote_selected_message();
+    assert!(harness.app.conversation().input.starts_with("> You wrote:\n> How do I dedupe?"), "{}", harness.app.conversation().input);
+}
+
+#[tokio::test]
+async fn system_edit_puts_the_prompt_in_force_into_the_input() {
+    let backend = Arc::new(MockBackend::new(&[]).reply(&["Ok"]));
+    let mut harness = Harness::new(backend.clone());
+    harness.app.model_config.system_prompt = "Be brief.".to_string();
+    harness.app.conversation_mut().input = "/system edit".to_string();
+    harness.app.run_slash_command();
+    assert_eq!(harness.app.conversation().input, "/system Be brief.");
+
+    harness.app.conversation_mut().input.push_str("\nAnswer in French.");
+    harness.app.run_slash_command();
+    assert_eq!(harness.app.effective_config().system_prompt, "Be brief.\nAnswer in French.");
+    assert_eq!(harness.app.model_config.system_prompt, "Be brief.");
+    harness.send("Hi").await;
+    assert_eq!(backend.requests()[0].config.system_prompt, "Be brief.\nAnswer in French.");
+}
+
+#[tokio::test]
+async fn persona_sets_the_model_prompt_and_sampling_for_one_conversation() {
+    let backend = Arc::new(MockBackend::new(&[]).reply(&["Looks fine"]).reply(&["Hi"]));
+    let mut harness = Harness::new(backend.clone());
+    harness.app.app_config.personas[0].model = Some("qwen2.5-coder".to_string());
+    harness.app.app_config.personas[0].temperature = Some(0.3);
+    harness.app.conversation_mut().input = "/persona rust reviewer".to_string();
+    harness.app.run_slash_command();
+    harness.send("Review this").await;
+
+    let request = &backend.requests()[0];
+    assert_eq!(request.model, "qwen2.5-coder");
+    assert!(request.config.system_prompt.starts_with("You are a senior Rust engineer"));
+    assert_eq!((request.config.temperature, request.config.top_k), (0.3, 40));
+    assert_eq!(request.config.preset.as_deref(), Some("coding"));
+    assert_eq!(harness.app.model_config.temperature, llama_term::models::ModelConfig::default().temperature);
+
+    // Cycling past the last persona drops it, keeping the model
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Char('r'), KeyModifiers::ALT)).await;
+    assert_eq!(harness.app.conversation().overrides.persona.as_deref(), Some("Creative writer"));
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Char('r'), KeyModifiers::ALT)).await;
+    assert!(harness.app.conversation().overrides.is_empty());
+    harness.send("Hello").await;
+    assert_eq!(backend.requests()[1].model, "qwen2.5-coder");
+    assert_eq!(backend.requests()[1].config.system_prompt, harness.app.model_config.system_prompt);
+}
+
+#[tokio::test]
+async fn a_template_chosen_on_a_new_tab_sets_the_prompt_sampling_and_first_message() {
+    let backend = Arc::new(MockBackend::new(&[]).reply(&["Try clearing the cache"]));
+    let mut harness = Harness::new(backend.clone());
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Char('t'), KeyModifiers::CONTROL)).await;
+    assert_eq!(harness.app.template_picker, Some(0));
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE)).await;
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)).await;
+    assert_eq!(harness.app.template_picker, None);
+    assert!(harness.app.conversation().input.starts_with("What I expected:"));
+
+    harness.send("The build fails").await;
+    let request = &backend.requests()[0];
+    assert!(request.config.system_prompt.starts_with("You are a patient debugging partner"));
+    assert_eq!(request.config.preset.as_deref(), Some("precise"));
+    assert_eq!(harness.app.conversation().overrides.system_prompt_name.as_deref(), Some("Debugging"));
+
+    // Escape opens a blank chat instead
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Char('t'), KeyModifiers::CONTROL)).await;
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE)).await;
+    assert_eq!(harness.app.template_picker, None);
+    assert!(harness.app.conversation().overrides.is_empty());
+    assert!(harness.app.conversation().input.is_empty());
+}
+
+#[tokio::test]
+async fn vim_macros_record_actions_and_typed_text_and_replay_them() {
+    let mut harness = Harness::new(Arc::new(MockBackend::new(&[])));
+    let press = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+    harness.app.vim_insert = false;
+    for key in "qaihi".chars().map(press).chain([KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE), press('q')]) {
+        input::handle_key(&mut harness.app, key).await;
+    }
+    assert_eq!(harness.app.conversation().input, "hi");
+    assert_eq!(harness.app.macros.recording(), None);
+
+    input::handle_key(&mut harness.app, press('@')).await;
+    input::handle_key(&mut harness.app, press('a')).await;
+    assert_eq!(harness.app.conversation().input, "hihi");
+    assert!(!harness.app.vim_insert);
+    input::handle_key(&mut harness.app, press('@')).await;
+    input::handle_key(&mut harness.app, press('@')).await;
+    assert_eq!(harness.app.conversation().input, "hihihi");
+
+    // An empty register replays nothing
+    input::handle_key(&mut harness.app, press('@')).await;
+    input::handle_key(&mut harness.app, press('b')).await;
+    assert_eq!(harness.app.conversation().input, "hihihi");
+    assert_eq!(harness.app.last_error.as_deref(), Some("Register @b holds no macro"));
+}
+
+#[tokio::test]
+async fn brace_motions_move_between_messages_and_hml_select_in_view() {
+    let reply = "line\n".repeat(8);
+    let backend = Arc::new(MockBackend::new(&[]).reply(&[&reply]).reply(&[&reply]));
+    let mut harness = Harness::new(backend);
+    harness.send("First").await;
+    harness.send("Second").await;
+    harness.render(80, 24);
+    harness.app.vim_insert = false;
+    let press = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+    let starts = harness.app.conversation().message_lines.borrow().clone();
+
+    for key in ['g', 'g', '}'] {
+        input::handle_key(&mut harness.app, press(key)).await;
+    }
+    assert_eq!(harness.app.conversation().top_line(), starts[1]);
+    input::handle_key(&mut harness.app, press('}')).await;
+    assert_eq!(harness.app.conversation().top_line(), starts[2]);
+    input::handle_key(&mut harness.app, press('{')).await;
+    assert_eq!(harness.app.conversation().top_line(), starts[1]);
+
+    // The view stays put while H, M, and L pick what it shows
+    input::handle_key(&mut harness.app, press('H')).await;
+    assert_eq!(harness.app.conversation().selected_message, Some(1));
+    input::handle_key(&mut harness.app, press('L')).await;
+    assert_eq!(harness.app.conversation().selected_message, Some(2));
+    assert_eq!(harness.app.conversation().top_line(), starts[1]);
+
+    input::handle_key(&mut harness.app, press('{')).await;
+    input::handle_key(&mut harness.app, press('{')).await;
+    assert_eq!(harness.app.conversation().top_line(), 0);
+}
+
+#[tokio::test]
+async fn a_long_reply_reports_its_length_when_it_finishes() {
+    let long = "word ".repeat(460);
+    let backend = Arc::new(MockBackend::new(&[]).reply(&["Short **answer**"]).reply(&[&long]));
+    let mut harness = Harness::new(backend);
+    harness.send("Briefly").await;
+    assert_eq!(harness.app.status_message, "Ready");
+    harness.send("At length").await;
+    assert_eq!(harness.app.status_message, "Ready. The reply is 460 words, 1840 characters, 2 min read");
+}
+
+#[test]
+fn auto_copy_takes_the_reply_or_its_last_code_block() {
+    use llama_term::config::AutoCopy;
+    let reply = "Try this:\n```sh\nls\n```\nor this:\n```rust\nfn main() {}\n```\n";
+    assert_eq!(AutoCopy::Off.text(reply), None);
+    assert_eq!(AutoCopy::Reply.text(reply).as_deref(), Some(reply.trim()));
+    assert_eq!(AutoCopy::LastCodeBlock.text(reply).as_deref(), Some("fn main() {}"));
+    assert_eq!(AutoCopy::LastCodeBlock.text("No code here"), None);
+}
+
+#[tokio::test]
+async fn conversation_exports_to_a_standalone_html_page() {
+    let reply = "## Fix\n\nUse **`Option`** here:\n\n```rust\nfn main() { let x = \"<b>\"; } // done\n```\n\n- one\n- two\n\n[docs](https://doc.rust-lang.org) [bad](javascript:alert(1))";
+    let backend = Arc::new(MockBackend::new(&[]).reply(&[reply]));
+    let mut harness = Harness::new(backend);
+    harness.send("Why <does> this fail?").await;
+    let path = harness.dir.path().join("chat.html");
+    harness.app.conversation_mut().input = format!("/export {}", path.display());
+    harness.app.run_slash_command();
+    assert_eq!(harness.app.last_error, None);
+
+    let html = std::fs::read_to_string(&path).unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>") && html.contains("<style>"));
+    assert!(html.contains("<section class=\"user\">\n<h2>You</h2>\n<p>Why &lt;does&gt; this fail?</p>"));
+    assert!(html.contains("<h4>Fix</h4>"));
+    assert!(html.contains("<p>Use <strong><code>Option</code></strong> here:</p>"));
+    assert!(html.contains("<pre><code class=\"language-rust\"><span class=\"kw\">fn</span> main() { <span class=\"kw\">let</span> x = <span class=\"str\">&quot;&lt;b&gt;&quot;</span>; } <span class=\"com\">// done</span></code></pre>"));
+    assert!(html.contains("<ul>\n<li>one</li>\n<li>two</li>\n</ul>"));
+    assert!(html.contains("<a href=\"https://doc.rust-lang.org\">docs</a> [bad](javascript:alert(1))"));
+
+    // Exporting over an existing file asks first
+    harness.app.conversation_mut().input = format!("/export {}", path.display());
+    harness.app.run_slash_command();
+    assert!(harness.app.confirmation.is_some());
+}
+
+#[tokio::test]
+async fn hardcopy_prints_the_conversation_as_markdown_on_exit() {
+    let backend = Arc::new(MockBackend::new(&[]).reply(&["Paris."]));
+    let mut harness = Harness::new(backend);
+    assert!(!llama_term::cli::Args::parse(["--prompt".to_string(), "Hi".to_string()]).unwrap().print_on_exit);
+    assert!(llama_term::cli::Args::parse(["--print-on-exit".to_string()]).unwrap().print_on_exit);
+    harness.send("Capital of France?").await;
+    harness.app.conversation_mut().input = "/hardcopy".to_string();
+    harness.app.run_slash_command();
+    assert!(harness.app.print_on_exit);
+    assert_eq!(harness.app.transcript(), "## You\n\nCapital of France?\n\n## llama2\n\nParis.\n\n");
+}
+
+#[tokio::test]
+async fn command_placeholders_are_replaced_by_their_output_once_confirmed() {
+    let backend = Arc::new(MockBackend::new(&[]));
+    let mut harness = Harness::new(backend.clone());
+    harness.app.conversation_mut().input = "explain: {{$cmd echo one}} and {{$cmd printf 'a\\nb'}}".to_string();
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)).await;
+    assert!(backend.requests().is_empty());
+    assert_eq!(harness.app.confirmation.as_ref().unwrap().message, "Run `echo one`, `printf 'a\\nb'` and put their output in the prompt?");
+
+    harness.app.answer_confirmation(true);
+    let event = tokio::time::timeout(std::time::Duration::from_secs(5), harness.inbox.recv()).await.unwrap().unwrap();
+    harness.app.handle_event(event);
+    assert_eq!(harness.app.conversation().input, "explain: one and \n```\na\nb\n```\n");
+
+    // /run-insert adds the output after what was typed
+    harness.app.conversation_mut().input = "/run-insert echo two".to_string();
+    harness.app.run_slash_command();
+    harness.app.answer_confirmation(true);
+    let event = tokio::time::timeout(std::time::Duration::from_secs(5), harness.inbox.recv()).await.unwrap().unwrap();
+    harness.app.handle_event(event);
+    assert_eq!(harness.app.conversation().input, "two");
+}
+
+#[tokio::test]
+async fn prompts_sent_during_a_reply_are_queued_and_sent_in_order() {
+    let backend = Arc::new(MockBackend::new(&[]).reply(&["One"]).reply(&["Two"]).reply(&["Three"]));
+    let mut harness = Harness::new(backend.clone());
+    for prompt in ["First", "Second", "Third"] {
+        harness.app.conversation_mut().input = prompt.to_string();
+        input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)).await;
+    }
+    assert_eq!(harness.app.conversation().queued, ["Second", "Third"]);
+    assert!(harness.app.conversation().input.is_empty());
+
+    harness.settle().await;
+    let prompts: Vec<String> = backend.requests().iter().map(|r| r.prompt.clone()).collect();
+    assert_eq!(prompts, ["First", "Second", "Third"]);
+    let contents: Vec<&str> = harness.app.conversation().messages.iter().map(|m| m.content.as_str()).collect();
+    assert_eq!(contents, ["First", "One", "Second", "Two", "Third", "Three"]);
+    assert!(harness.app.conversation().queued.is_empty());
+}
+
+#[tokio::test]
+async fn json_mode_asks_for_json_and_checks_it_against_the_schema() {
+    let backend = Arc::new(MockBackend::new(&[]).reply(&[r#"{"name": "Ada", "#, r#""age": "36"}"#]).reply(&["Not JSON"]).reply(&["Plain"]));
+    let mut harness = Harness::new(backend.clone());
+    let schema = harness.dir.path().join("person.json");
+    let schema_json = serde_json::json!({
+        "type": "object",
+        "properties": { "name": { "type": "string" }, "age": { "type": "integer" }, "email": { "type": "string" } },
+        "required": ["name", "age", "email"],
+    });
+    std::fs::write(&schema, schema_json.to_string()).unwrap();
+    harness.app.conversation_mut().input = format!("/json schema {}", schema.display());
+    harness.app.run_slash_command();
+    harness.send("Describe Ada Lovelace").await;
+
+    assert_eq!(backend.requests()[0].format, Some(ReplyFormat::Schema(schema_json)));
+    let reply = &harness.app.conversation().messages[1];
+    assert!(reply.json);
+    assert_eq!(reply.json_errors, ["$: missing required property 'email'", "$.age: expected integer, got string"]);
+
+    harness.app.conversation_mut().input = "/json on".to_string();
+    harness.app.run_slash_command();
+    harness.send("Again").await;
+    assert!(harness.app.conversation().messages[3].json_errors[0].starts_with("Not valid JSON"));
+
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Char('j'), KeyModifiers::ALT)).await;
+    harness.send("Now in prose").await;
+    assert_eq!(backend.requests()[2].format, None);
+    assert!(!harness.app.conversation().messages[5].json);
+}
+
+#[tokio::test]
+async fn long_replies_are_cut_short_until_expanded() {
+    let reply: Vec<String> = (1..=60).map(|n| format!("{}line {}", if n == 1 { "" } else { "\n" }, n)).collect();
+    let reply: Vec<&str> = reply.iter().map(String::as_str).collect();
+    let mut harness = Harness::new(Arc::new(MockBackend::new(&[]).reply(&reply)));
+    harness.send("Count to sixty").await;
+
+    let screen = harness.render(80, 24);
+    assert!(screen.contains("line 40"));
+    assert!(screen.contains("… (z a to expand, 20 more lines)"));
+    assert!(!screen.contains("line 41"));
+
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Char('e'), KeyModifiers::ALT)).await;
+    let screen = harness.render(80, 24);
+    assert!(screen.contains("line 60"));
+    assert!(!screen.contains("more lines"));
+    assert_eq!(harness.app.status_message, "Reply expanded");
+}
+
+#[tokio::test]
+async fn code_blocks_fold_into_a_summary_line() {
+    let reply = "Two ways:\n\n```rust\nfn one() {}\nfn two() {}\n```\n\nor\n\n```python\nprint('three')\n```";
+    let mut harness = Harness::new(Arc::new(MockBackend::new(&[]).reply(&[reply])));
+    harness.send("Show me").await;
+
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Char('f'), KeyModifiers::ALT)).await;
+    assert_eq!(harness.app.status_message, "Folded 2 code block(s)");
+    let screen = harness.render(80, 24);
+    assert!(screen.contains("▸ rust, 2 lines (z f to unfold)"));
+    assert!(screen.contains("▸ python, 1 line (z f to unfold)"));
+    assert!(!screen.contains("fn one"));
+
+    harness.app.command_line = Some("fold 2".to_string());
+    harness.app.run_command_line();
+    let screen = harness.render(80, 24);
+    assert!(screen.contains("▸ rust, 2 lines"));
+    assert!(screen.contains("print('three')"));
+    // Folding is for display only
+    assert_eq!(harness.app.conversation().messages[1].content, reply);
+}
+
+#[tokio::test]
+async fn regenerating_keeps_the_earlier_attempt_to_compare() {
+    let backend = MockBackend::new(&[]).reply(&["The quick brown fox\njumps."]).reply(&["The slow brown fox\njumps high."]);
+    let mut harness = Harness::new(Arc::new(backend));
+    harness.send("Describe a fox").await;
+    harness.app.run_message_action(1, MessageAction::Regenerate);
+    harness.settle().await;
+
+    let reply = &harness.app.conversation().messages[1];
+    assert_eq!(reply.content, "The slow brown fox\njumps high.");
+    assert_eq!(reply.attempts.len(), 1);
+    assert_eq!(reply.attempts[0].content, "The quick brown fox\njumps.");
+
+    let changes = word_diff(&reply.attempts[0].content, &reply.content);
+    let side = |skip: Change| changes.iter().filter(|(change, _)| *change != skip).map(|(_, text)| text.as_str()).collect::<String>();
+    assert_eq!(side(Change::Added), reply.attempts[0].content);
+    assert_eq!(side(Change::Removed), reply.content);
+    assert!(changes.contains(&(Change::Removed, "quick".to_string())));
+    assert!(changes.contains(&(Change::Added, "slow".to_string())));
+
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Char('c'), KeyModifiers::ALT)).await;
+    assert!(harness.app.attempt_diff.is_some());
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE)).await;
+    assert!(harness.app.attempt_diff.is_none());
+}
+
+#[tokio::test]
+async fn sampled_replies_can_be_browsed_before_continuing() {
+    let backend = Arc::new(MockBackend::new(&[]).reply(&["One"]).reply(&["Two"]).reply(&["Three"]).reply(&["Sure"]));
+    let mut harness = Harness::new(backend.clone());
+    harness.send("Pick a number").await;
+    harness.app.conversation_mut().input = "half-written draft".to_string();
+    harness.app.command_line = Some("sample 2".to_string());
+    harness.app.run_command_line();
+    harness.settle().await;
+
+    let conversation = harness.app.conversation();
+    assert_eq!(conversation.messages.len(), 2);
+    assert_eq!(conversation.messages[1].content, "Three");
+    assert_eq!(conversation.input, "half-written draft");
+    let screen = harness.render(80, 24);
+    assert!(screen.contains("(3/3)"));
+
+    harness.app.vim_mode = true;
+    harness.app.vim_insert = false;
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Char('<'), KeyModifiers::NONE)).await;
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Char('<'), KeyModifiers::NONE)).await;
+    assert_eq!(harness.app.conversation().messages[1].content, "One");
+    assert_eq!(harness.app.status_message, "Showing version 1 of 3; the conversation continues from it");
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Char('<'), KeyModifiers::NONE)).await;
+    assert_eq!(harness.app.status_message, "This is the first version");
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Char('>'), KeyModifiers::NONE)).await;
+    assert_eq!(harness.app.conversation().messages[1].content, "Two");
+    assert!(harness.render(80, 24).contains("(2/3)"));
+
+    // The version shown is the one the next prompt follows
+    harness.send("Why that one?").await;
+    let history = &backend.requests()[3].history;
+    assert_eq!(history.last().map(|message| message.content.as_str()), Some("Two"));
+    let versions: Vec<String> = harness.app.reply_versions(1).into_iter().map(|attempt| attempt.content).collect();
+    assert_eq!(versions, ["One", "Two", "Three"]);
+}
+
+#[tokio::test]
+async fn raw_mode_sends_the_whole_transcript_through_the_template() {
+    let backend = Arc::new(MockBackend::new(&[]).reply(&["Hello"]).reply(&["Bye"]).reply(&["Plain"]));
+    let mut harness = Harness::new(backend.clone());
+    harness.app.conversation_mut().input = "/system Be brief.".to_string();
+    harness.app.run_slash_command();
+    harness.app.conversation_mut().input = "/raw Respond".to_string();
+    harness.app.run_slash_command();
+    assert!(harness.app.last_error.as_deref().is_some_and(|error| error.contains("{prompt}")));
+
+    harness.app.conversation_mut().input = r"/raw {system}\n### User: {prompt}\n### Assistant: {response}\n\n".to_string();
+    harness.app.run_slash_command();
+    assert_eq!(harness.app.status_message, r"Raw mode on: prompts are sent as {system}\n### User: {prompt}\n### Assistant: {response}\n\n");
+    harness.send("Hi").await;
+    harness.send("Again").await;
+
+    let requests = backend.requests();
+    assert!(requests[0].raw);
+    assert_eq!(requests[0].prompt, "Be brief.\n### User: Hi\n### Assistant: ");
+    assert_eq!(requests[1].prompt, "Be brief.\n### User: Hi\n### Assistant: Hello\n\n\n### User: Again\n### Assistant: ");
+    assert!(requests[1].history.is_empty());
+
+    harness.app.conversation_mut().input = "/raw off".to_string();
+    harness.app.run_slash_command();
+    harness.send("Normal now").await;
+    let request = &backend.requests()[2];
+    assert!(!request.raw);
+    assert_eq!((request.prompt.as_str(), request.history.len()), ("Normal now", 4));
+}
+
+#[tokio::test]
+async fn raw_mode_uses_the_template_configured_for_the_model() {
+    let backend = Arc::new(MockBackend::new(&[]).reply(&["Hello"]).reply(&[" there"]));
+    let mut harness = Harness::new(backend.clone());
+    harness.app.app_config.model_templates.insert("llama2".to_string(), "[INST] {prompt} [/INST]{response}</s>".to_string());
+    harness.app.command_line = Some("raw".to_string());
+    harness.app.run_command_line();
+    assert_eq!(harness.app.status_message, "Raw mode on: prompts are sent as [INST] {prompt} [/INST]{response}</s> (the template for llama2:latest)");
+    harness.send("Hi").await;
+    assert_eq!(backend.requests()[0].prompt, "[INST] Hi [/INST]");
+
+    // Other models get the general template
+    harness.app.conversation_mut().model = "base-model:7b".to_string();
+    harness.send("Well,").await;
+    assert_eq!(backend.requests()[1].prompt, "HiHelloWell,");
+
+    // A registry port is not a tag
+    let config = &mut harness.app.app_config;
+    config.model_templates.insert("localhost:5000/mistral".to_string(), "<s>{prompt}{response}".to_string());
+    assert_eq!(config.model_template("localhost:5000/mistral:7b"), Some("<s>{prompt}{response}"));
+    assert_eq!(config.model_template("localhost:5000/llama2"), None);
+
+    config.model_templates.insert("broken".to_string(), "[INST] [/INST]".to_string());
+    assert_eq!(config.raw_template_for("broken:7b"), "{prompt}{response}");
+}
+
+#[tokio::test]
+async fn fim_fills_the_gap_in_a_file_region() {
+    let backend = Arc::new(MockBackend::new(&[]).reply(&["    b = 2\n"]).reply(&["Plain"]));
+    let mut harness = Harness::new(backend.clone());
+    let file = harness.dir.path().join("sum.py");
+    std::fs::write(&file, "def sum():\n    a = 1\n    b = 0\n    return a + b\n").unwrap();
+    harness.app.conversation_mut().input = format!("/fim {}:3-9", file.display());
+    harness.app.run_slash_command();
+    assert!(harness.app.last_error.as_deref().is_some_and(|error| error.ends_with("has 4 lines, so lines 3-9 cannot be filled in")));
+
+    harness.app.conversation_mut().input = format!("/fim {}:3-3", file.display());
+    harness.app.run_slash_command();
+    assert!(harness.app.conversation().fim);
+    assert_eq!(harness.app.conversation().input, "def sum():\n    a = 1\n{{fill}}    return a + b\n");
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)).await;
+    harness.settle().await;
+
+    let request = &backend.requests()[0];
+    assert_eq!(request.prompt, "def sum():\n    a = 1\n");
+    assert_eq!(request.suffix.as_deref(), Some("    return a + b\n"));
+    let reply = &harness.app.conversation().messages[1];
+    assert_eq!(reply.content, "    b = 2\n");
+    assert_eq!(reply.fill.as_ref().map(|fill| fill.stitch(&reply.content)).as_deref(), Some("def sum():\n    a = 1\n    b = 2\n    return a + b\n"));
+
+    // The prompts after it are sent as usual once it is off
+    harness.app.command_line = Some("fim".to_string());
+    harness.app.run_command_line();
+    harness.send("Thanks").await;
+    let request = &backend.requests()[1];
+    assert_eq!((request.suffix.as_ref(), request.history.len()), (None, 2));
+}
+
+#[tokio::test]
+async fn closing_a_tab_mid_reply_asks_first_and_stops_the_reply() {
+    let backend = Arc::new(MockBackend::new(&[]).reply(&["Too", " late"]));
+    let mut harness = Harness::new(backend);
+    harness.app.new_conversation();
+    harness.app.conversation_mut().input = "Hi".to_string();
+    harness.app.start_message_stream();
+    assert!(harness.app.conversation().is_thinking);
+
+    input::handle_key(&mut harness.app, KeyEvent::new(KeyCode::Char('w'), KeyModifiers::CONTROL)).await;
+    assert!(harness.app.confirmation.is_some());
+    assert_eq!(harness.app.conversations.len(), 2);
+    harness.app.answer_confirmation(true);
+    assert_eq!(harness.app.conversations.len(), 1);
+
+    // The last tab is cleared instead, leaving nothing streaming into it
+    harness.app.conversation_mut().input = "Again".to_string();
+    harness.app.start_message_stream();
+    harness.app.clear_chat();
+    harness.settle().await;
+    let conversation = harness.app.conversation();
+    assert!(!conversation.is_thinking);
+    assert!(conversation.messages.is_empty());
+}
+
+#[tokio::test]
+async fn a_process_is_not_signalled_once_its_pid_belongs_to_another() {
+    let mut harness = Harness::new(Arc::new(MockBackend::new(&[])));
+    let pid = sysinfo::Pid::from_u32(std::process::id());
+    let mut sys = sysinfo::System::new();
+    sys.refresh_processes(sysinfo::ProcessesToUpdate::Some(&[pid]));
+    let name = sys.process(pid).expect("this test's own process").name().to_string_lossy().to_string();
+    // Started at another time than this process, as one the pid was reused from would have
+    harness.app.confirm("Terminate?".to_string(), PendingAction::SignalProcess { pid, name, started: 1, force: false });
+    harness.app.answer_confirmation(true);
+    assert!(harness.app.last_error.as_deref().is_some_and(|error| error.ends_with("that pid now belongs to another process")));
+}
+
+#[tokio::test]
+async fn a_question_raised_while_another_is_open_waits_its_turn() {
+    let mut harness = Harness::new(Arc::new(MockBackend::new(&[])));
+    let (first, second) = (harness.dir.path().join("first.json"), harness.dir.path().join("second.json"));
+    harness.app.confirm("Export to first.json?".to_string(), PendingAction::ExportConfig(first.clone()));
+    harness.app.confirm("Export to second.json?".to_string(), PendingAction::ExportConfig(second.clone()));
+
+    harness.app.answer_confirmation(true);
+    assert!(first.exists());
+    assert_eq!(harness.app.confirmation.as_ref().map(|c| c.message.as_str()), Some("Export to second.json?"));
+    harness.app.answer_confirmation(true);
+    assert!(second.exists());
+    assert!(harness.app.confirmation.is_none());
+}
@@ -0,0 +1,64 @@
+//! Runs a file of prompts against several models, and a prompt at several temperatures,
+//! and checks the reports.
+
+use std::fs;
+
+use llama_term::backend::MockBackend;
+use llama_term::batch;
+use llama_term::cli::Args;
+use llama_term::models::ConfigField;
+use llama_term::sweep;
+use llama_term::ModelConfig;
+
+#[tokio::test]
+async fn every_model_answers_every_prompt() {
+    let dir = tempfile::tempdir().unwrap();
+    let prompts_file = dir.path().join("prompts.txt");
+    fs::write(&prompts_file, "# capitals\nCapital of France?\n\nCapital of Peru?\n").unwrap();
+    let args = Args::parse(["batch", "-m", "llama3.2,mistral", prompts_file.to_str().unwrap(), "-o", "report.md"].map(String::from)).unwrap();
+    assert_eq!(args.models, ["llama3.2", "mistral"]);
+    assert_eq!(args.batch.as_deref(), Some(prompts_file.as_path()));
+
+    let backend = MockBackend::new(&[]).reply(&["Paris"]).usage(10, 2).reply(&["Lima"]).reply(&["Paris."]).fail("out of memory");
+    let prompts = batch::load_prompts(&prompts_file).unwrap();
+    let report = batch::run(&backend, &args.models, &prompts, &ModelConfig::default(), |_| {}).await;
+
+    let answers: Vec<(&str, &str)> = report.results.iter().map(|r| (r.model.as_str(), r.reply.as_str())).collect();
+    assert_eq!(answers, [("llama3.2", "Paris"), ("llama3.2", "Lima"), ("mistral", "Paris."), ("mistral", "")]);
+    assert_eq!((report.results[0].prompt_tokens, report.results[0].reply_tokens), (10, 2));
+    assert_eq!(report.results[1].reply_tokens, 1);
+    assert_eq!(report.results[3].error.as_deref(), Some("out of memory"));
+    assert_eq!(report.failed(), 1);
+
+    let markdown = report.to_markdown();
+    assert!(markdown.contains("## Prompt 2\n\n> Capital of Peru?\n\n### llama3.2"), "{}", markdown);
+    assert!(markdown.contains("**Failed:** out of memory"));
+    let json = dir.path().join("report.json");
+    report.save(&json).unwrap();
+    let saved: serde_json::Value = serde_json::from_str(&fs::read_to_string(json).unwrap()).unwrap();
+    assert_eq!(saved["results"][2]["model"], "mistral");
+}
+
+#[tokio::test]
+async fn a_sweep_tries_each_temperature_in_turn() {
+    let args = Args::parse(["sweep", "-m", "llama3.2", "--temperature", "0.2, 1.2", "Name", "a", "colour"].map(String::from)).unwrap();
+    assert_eq!((args.sweep, args.sweep_values.as_slice()), (Some(ConfigField::Temperature), [0.2, 1.2].as_slice()));
+    assert_eq!(args.prompt.as_deref(), Some("Name a colour"));
+    assert!(Args::parse(["sweep", "--top-p", "1.5", "Hi"].map(String::from)).is_err());
+    assert!(Args::parse(["sweep", "--top-p", "0.5", "-t", "0.5", "Hi"].map(String::from)).is_err());
+
+    let backend = MockBackend::new(&[]).reply(&["Blue | green"]).usage(5, 3).fail("timed out");
+    let report = sweep::run(&backend, "llama3.2", "Name a colour", &ModelConfig::default(), ConfigField::Temperature, &args.sweep_values, |_| {}).await;
+    let temperatures: Vec<f32> = backend.requests().iter().map(|request| request.config.temperature).collect();
+    assert_eq!(temperatures, [0.2, 1.2]);
+    assert_eq!(report.failed(), 1);
+
+    let markdown = report.to_markdown();
+    assert!(markdown.contains("| Temperature | Time | Reply tokens | Tokens/s | Reply |"), "{}", markdown);
+    let rows: Vec<&str> = markdown.lines().filter(|line| line.starts_with("| 0.2 |") || line.starts_with("| 1.2 |")).collect();
+    assert_eq!(rows.len(), 2, "{}", markdown);
+    // Time and speed vary; the token count and the reply, its `|` escaped, don't
+    assert!(rows[0].contains(" | 3 | ") && rows[0].ends_with(" | Blue \\| green |"), "{}", rows[0]);
+    assert!(rows[1].ends_with("**Failed:** timed out |"), "{}", rows[1]);
+    assert!(markdown.contains("## Temperature 1.2"));
+}
@@ -0,0 +1,99 @@
+//! Helpers shared by the integration tests: an app wired to a scripted backend in a
+//! temporary directory, and plain-text renders of its screen.
+#![allow(dead_code)]
+
+use ratatui::{backend::TestBackend, Terminal};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::sync::mpsc::UnboundedReceiver;
+use unicode_width::UnicodeWidthStr;
+
+use llama_term::App;
+use llama_term::backend::MockBackend;
+use llama_term::config::StatusSegment;
+use llama_term::events::AppEvent;
+use llama_term::ui::ui;
+
+/// An app talking to `backend`, its events, and the directory holding its files, which is
+/// removed when dropped.
+pub struct Harness {
+    pub app: App,
+    pub inbox: UnboundedReceiver<AppEvent>,
+    pub dir: TempDir,
+}
+
+impl Harness {
+    pub fn new(backend: Arc<MockBackend>) -> Self {
+        let dir = tempfile::tempdir().expect("temporary directory");
+        let mut app = App::with_backend(dir.path(), backend);
+        // The clock would make every render different
+        app.app_config.status_line.retain(|segment| *segment != StatusSegment::Clock);
+        let inbox = app.take_inbox().expect("a new app has its inbox");
+        Self { app, inbox, dir }
+    }
+
+    /// Handles events until no reply is streaming, summary pending, or model list or download
+    /// running, and nothing else is queued.
+    pub async fn settle(&mut self) {
+        while self.app.conversations.iter().any(|c| c.is_thinking || c.compressing) || self.app.listing_models || self.app.downloading.is_some() {
+            let event = tokio::time::timeout(Duration::from_secs(5), self.inbox.recv()).await.expect("the reply finishes within 5s");
+            self.app.handle_event(event.expect("the app still holds its events"));
+        }
+        while let Ok(event) = self.inbox.try_recv() {
+            self.app.handle_event(event);
+        }
+    }
+
+    /// Types `message` into the input and sends it, then waits for the reply.
+    pub async fn send(&mut self, message: &str) {
+        self.app.conversation_mut().input = message.to_string();
+        self.app.start_message_stream();
+        self.settle().await;
+    }
+
+    /// Files saved to the chat history.
+    pub fn saved_chats(&self) -> Vec<PathBuf> {
+        let chats = self.dir.path().join("chats");
+        std::fs::read_dir(chats).map(|entries| entries.flatten().map(|entry| entry.path()).collect()).unwrap_or_default()
+    }
+
+    /// The screen as text, one line per row with trailing spaces trimmed.
+    pub fn render(&self, width: u16, height: u16) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).expect("test terminal");
+        terminal.draw(|f| ui(f, &self.app)).expect("draw");
+        let buffer = terminal.backend().buffer();
+        let mut rows = Vec::new();
+        for y in 0..height {
+            let mut row = String::new();
+            let mut skip = 0;
+            for x in 0..width {
+                // The cells covered by a wide character hold blanks that aren't on screen
+                if skip > 0 {
+                    skip -= 1;
+                    continue;
+                }
+                let symbol = buffer[(x, y)].symbol();
+                skip = symbol.width().saturating_sub(1);
+                row.push_str(symbol);
+            }
+            rows.push(row.trim_end().to_string());
+        }
+        rows.join("\n") + "\n"
+    }
+}
+
+/// Compares `actual` with `tests/snapshots/<name>.txt`. With `UPDATE_SNAPSHOTS` set, every
+/// snapshot is written instead, new ones included; review the files before committing them.
+pub fn assert_snapshot(name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("snapshots").join(format!("{}.txt", name));
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    assert!(path.exists(), "missing snapshot {}, rerun with UPDATE_SNAPSHOTS=1\n--- actual\n{}", name, actual);
+    let expected = std::fs::read_to_string(&path).unwrap();
+    assert!(expected == actual, "{} changed (rerun with UPDATE_SNAPSHOTS=1 to accept)\n--- expected\n{}\n--- actual\n{}", name, expected, actual);
+}
@@ -0,0 +1,73 @@
+//! Files written by older versions are upgraded in place; ones that can't be read are kept.
+
+use std::fs;
+use std::sync::Arc;
+
+use llama_term::{App, ChatSession};
+use llama_term::backend::MockBackend;
+use llama_term::config::{AppConfig, HostConfig, MODEL_CONFIG_FILE};
+use llama_term::toast::Severity;
+use llama_term::migrate::{self, Versioned};
+use serde_json::{json, Value};
+
+#[test]
+fn old_chat_is_upgraded_and_rewritten() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("chat.json");
+    let old = json!({
+        "timestamp": "2024-05-01 09:30:00",
+        "model": "llama3.2:latest",
+        "messages": [["user", "What is Rust?"], ["assistant", "A language."]],
+    });
+    fs::write(&path, old.to_string()).unwrap();
+
+    let session: ChatSession = migrate::load(&path).unwrap();
+    assert_eq!(session.version, ChatSession::VERSION);
+    assert_eq!(session.messages[0].content, "What is Rust?");
+    assert_eq!(session.messages[1].role, "assistant");
+    assert_eq!(session.messages[1].model.as_deref(), Some("llama3.2:latest"));
+
+    let rewritten: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(rewritten["version"], ChatSession::VERSION);
+    assert_eq!(rewritten["messages"][0]["role"], "user");
+}
+
+#[test]
+fn newer_file_is_refused_and_left_alone() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("chat.json");
+    let newer = json!({ "version": ChatSession::VERSION + 1, "timestamp": "", "model": "", "messages": [] }).to_string();
+    fs::write(&path, &newer).unwrap();
+
+    let error = migrate::load::<ChatSession>(&path).err().expect("a newer format is refused");
+    assert!(error.to_string().contains("newer version"));
+    assert_eq!(fs::read_to_string(&path).unwrap(), newer);
+}
+
+#[tokio::test]
+async fn unreadable_settings_are_backed_up_and_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join(MODEL_CONFIG_FILE), "{ not json").unwrap();
+
+    let app = App::with_backend(dir.path(), Arc::new(MockBackend::new(&[])));
+    let error = app.last_error.expect("the broken file is reported");
+    assert!(error.contains(MODEL_CONFIG_FILE), "{}", error);
+    let backup = dir.path().join(format!("{}.bak", MODEL_CONFIG_FILE));
+    assert_eq!(fs::read_to_string(backup).unwrap(), "{ not json");
+}
+
+#[tokio::test]
+async fn a_template_without_a_prompt_is_skipped_and_the_rest_kept() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = AppConfig::default();
+    config.hosts.push(HostConfig { name: "gpu-box".to_string(), url: "http://10.0.0.5:11434".to_string(), ..config.hosts[0].clone() });
+    config.model_templates.insert("mistral".to_string(), "[INST] [/INST]".to_string());
+    config.save(dir.path()).unwrap();
+
+    let app = App::with_backend(dir.path(), Arc::new(MockBackend::new(&[])));
+    assert_eq!(app.last_error, None);
+    assert_eq!(app.app_config.hosts, config.hosts);
+    assert_eq!(app.app_config.raw_template_for("mistral:7b"), "{prompt}{response}");
+    let warning = app.toasts.iter().find(|toast| toast.severity == Severity::Warning).expect("the template is reported");
+    assert_eq!(warning.message, "Ignoring the template for mistral in model_templates, which has no {prompt} placeholder");
+}
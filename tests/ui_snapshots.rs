@@ -0,0 +1,198 @@
+//! Renders each screen against `TestBackend` and compares it with `tests/snapshots/`.
+
+mod common;
+
+use std::sync::Arc;
+
+use common::{assert_snapshot, Harness};
+use llama_term::{AppMode, ChatSession, Message};
+use llama_term::backend::MockBackend;
+use llama_term::message_menu::MessageAction;
+use llama_term::migrate::Versioned;
+
+const WIDTH: u16 = 80;
+const HEIGHT: u16 = 24;
+
+fn harness() -> Harness {
+    Harness::new(Arc::new(MockBackend::new(&["llama3.2:latest", "mistral:7b"]).reply(&["Hello", "! How can I", " help?"])))
+}
+
+async fn snapshot(mode: AppMode, name: &str) {
+    let mut harness = harness();
+    harness.app.fetch_models();
+    harness.settle().await;
+    harness.app.switch_mode(mode);
+    assert_snapshot(name, &harness.render(WIDTH, HEIGHT));
+}
+
+#[tokio::test]
+async fn chat_empty() {
+    snapshot(AppMode::Chat, "chat_empty").await;
+}
+
+#[tokio::test]
+async fn chat_with_reply() {
+    let mut harness = harness();
+    harness.send("Hi there").await;
+    assert_snapshot("chat_with_reply", &harness.render(WIDTH, HEIGHT));
+}
+
+#[tokio::test]
+async fn chat_with_oversized_draft() {
+    let mut harness = harness();
+    harness.app.model_config.num_ctx = 256;
+    harness.app.conversation_mut().input = "Summarize this log: ".to_string() + &"error at line 12; ".repeat(60);
+    assert_snapshot("chat_with_oversized_draft", &harness.render(WIDTH, HEIGHT));
+}
+
+#[tokio::test]
+async fn chat_with_error_popup() {
+    let mut harness = Harness::new(Arc::new(MockBackend::new(&[]).fail(r#"model "llama2:latest" not found, try pulling it first"#)));
+    harness.send("Hi there").await;
+    assert_snapshot("chat_with_error_popup", &harness.render(WIDTH, HEIGHT));
+}
+
+#[tokio::test]
+async fn model_selection() {
+    snapshot(AppMode::ModelSelection, "model_selection").await;
+}
+
+#[tokio::test]
+async fn model_download() {
+    snapshot(AppMode::ModelDownload, "model_download").await;
+}
+
+#[tokio::test]
+async fn system_monitor() {
+    snapshot(AppMode::SystemMonitor, "system_monitor").await;
+}
+
+#[tokio::test]
+async fn chat_history() {
+    let mut harness = harness();
+    harness.app.chat_history = vec![ChatSession {
+        version: ChatSession::VERSION,
+        timestamp: "2024-05-01 09:30:00".to_string(),
+        model: "llama3.2:latest".to_string(),
+        messages: vec![Message::user("What is Rust?".to_string(), Vec::new())],
+        overrides: Default::default(),
+    }];
+    harness.app.history_list_state.select(Some(0));
+    harness.app.switch_mode(AppMode::ChatHistory);
+    assert_snapshot("chat_history", &harness.render(WIDTH, HEIGHT));
+}
+
+#[tokio::test]
+async fn model_config() {
+    snapshot(AppMode::ModelConfig, "model_config").await;
+}
+
+#[tokio::test]
+async fn host_selection() {
+    snapshot(AppMode::HostSelection, "host_selection").await;
+}
+
+#[tokio::test]
+async fn prompt_library() {
+    snapshot(AppMode::PromptLibrary, "prompt_library").await;
+}
+
+#[tokio::test]
+async fn event_log() {
+    snapshot(AppMode::EventLog, "event_log").await;
+}
+
+#[tokio::test]
+async fn mcp_servers() {
+    snapshot(AppMode::McpServers, "mcp_servers").await;
+}
+
+#[tokio::test]
+async fn usage_stats() {
+    let mut harness = harness();
+    harness.send("Hi there").await;
+    harness.app.switch_mode(AppMode::Stats);
+    assert_snapshot("usage_stats", &harness.render(WIDTH, HEIGHT));
+}
+
+#[tokio::test]
+async fn session_stats() {
+    let mut harness = harness();
+    harness.send("Hi there").await;
+    harness.app.show_session_stats = true;
+    assert_snapshot("session_stats", &harness.render(WIDTH, HEIGHT));
+}
+
+#[tokio::test]
+async fn template_picker() {
+    let mut harness = harness();
+    harness.app.open_template_picker();
+    harness.app.move_template_selection(true);
+    assert_snapshot("template_picker", &harness.render(WIDTH, HEIGHT));
+}
+
+#[tokio::test]
+async fn message_menu() {
+    let mut harness = harness();
+    harness.send("Hi there").await;
+    harness.app.open_message_menu();
+    assert_snapshot("message_menu", &harness.render(WIDTH, HEIGHT));
+}
+
+#[tokio::test]
+async fn chat_with_expanded_system_prompt() {
+    let mut harness = harness();
+    harness.app.conversation_mut().overrides.system_prompt = Some("You review Rust code.\nPoint out bugs first, then style.".to_string());
+    harness.app.toggle_system_prompt();
+    assert_snapshot("chat_with_expanded_system_prompt", &harness.render(WIDTH, HEIGHT));
+}
+
+#[tokio::test]
+async fn chat_with_queued_prompts() {
+    let mut harness = harness();
+    harness.send("Hi there").await;
+    let queued = ["Now in French", "And in German,\nformally", "Then Spanish", "Then Italian", "Then Dutch"];
+    harness.app.conversation_mut().queued.extend(queued.map(String::from));
+    assert_snapshot("chat_with_queued_prompts", &harness.render(WIDTH, HEIGHT));
+}
+
+#[tokio::test]
+async fn chat_with_json_reply() {
+    let mut harness = Harness::new(Arc::new(MockBackend::new(&[]).reply(&[r#"{"name":"Ada","languages":["English","French"],"age":"36"}"#])));
+    harness.app.set_json_mode(true);
+    harness.send("Describe Ada Lovelace").await;
+    harness.app.conversation_mut().messages[1].json_errors = vec!["$.age: expected integer, got string".to_string()];
+    assert_snapshot("chat_with_json_reply", &harness.render(WIDTH, HEIGHT));
+}
+
+#[tokio::test]
+async fn chat_with_streaming_code_block() {
+    let mut harness = harness();
+    let conversation = harness.app.conversation_mut();
+    conversation.messages.push(Message::user("How do I print in Rust?".to_string(), Vec::new()));
+    let mut reply = Message::assistant("llama3.2:latest");
+    reply.content = "## Printing\n\nUse the **`println!`** macro:\n\n```rust\nfn main() {\n    println!(\"Hello, {}!\", name);".to_string();
+    conversation.messages.push(reply);
+    conversation.is_thinking = true;
+    assert_snapshot("chat_with_streaming_code_block", &harness.render(WIDTH, HEIGHT));
+}
+
+#[tokio::test]
+async fn attempt_diff() {
+    let backend = MockBackend::new(&[]).reply(&["Rust is a fast, memory-safe systems language."]).reply(&["Rust is a fast and memory-safe language\nfor systems programming."]);
+    let mut harness = Harness::new(Arc::new(backend));
+    harness.send("What is Rust?").await;
+    harness.app.run_message_action(1, MessageAction::Regenerate);
+    harness.settle().await;
+    harness.app.open_attempt_diff();
+    assert_snapshot("attempt_diff", &harness.render(WIDTH, HEIGHT));
+}
+
+#[tokio::test]
+async fn fill_in_the_middle() {
+    let backend = MockBackend::new(&[]).reply(&["    let total: u32 = ", "values.iter().sum();\n"]);
+    let mut harness = Harness::new(Arc::new(backend));
+    harness.app.set_fim_mode(true);
+    harness.send("fn average(values: &[u32]) -> u32 {\n{{fill}}    total / values.len() as u32\n}\n").await;
+    assert_snapshot("fill_in_the_middle", &harness.render(WIDTH, HEIGHT));
+}